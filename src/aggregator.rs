@@ -0,0 +1,383 @@
+use bson::{Bson, Document};
+use bson::oid::ObjectId;
+use bson::ordered::OrderedDocument;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use analyzer::get_value;
+use error::TipupError;
+
+use std::collections::{HashMap, HashSet};
+
+struct AggregationPolicy {
+    window: i64,
+    grace: i64,
+    fields: Vec<Vec<String>>,
+}
+
+struct FieldStatistics {
+    minimum: f64,
+    maximum: f64,
+    sum: f64,
+    count: usize,
+}
+
+struct Bucket {
+    template: OrderedDocument,
+    window_start: i64,
+    ids: HashSet<ObjectId>,
+    count: usize,
+    errors: usize,
+    error_message: Option<String>,
+    error_category: Option<String>,
+    statistics: Vec<Option<FieldStatistics>>,
+}
+
+//results buffered in open buckets are lost on restart, so the watermark persisted for a host's
+//measurement class is held before its oldest open bucket while fetches continue from the latest
+//fetched result, and buckets skip results they already hold when a failed fetch is retried
+pub struct Aggregator {
+    policies: HashMap<String, AggregationPolicy>,
+    buckets: HashMap<(String, String, String, i64), Bucket>,
+    fetched: HashMap<(String, String), i64>,
+}
+
+impl Aggregator {
+    pub fn new() -> Aggregator {
+        Aggregator {
+            policies: HashMap::new(),
+            buckets: HashMap::new(),
+            fetched: HashMap::new(),
+        }
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //query mongodb for aggregation policies
+        let mut count = 0;
+        let cursor = try!(proddle_db.collection("aggregation_policies").find(None, None));
+        for document in cursor {
+            let document = try!(document);
+
+            let measurement_class = match document.get("measurement_class") {
+                Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+                _ => return Err(TipupError::from("failed to parse aggregation policy measurement_class")),
+            };
+
+            let window = match document.get("window") {
+                Some(&Bson::I32(window)) => window as i64,
+                Some(&Bson::I64(window)) => window,
+                None => 60,
+                _ => return Err(TipupError::from(format!("failed to parse aggregation policy window for '{}'", measurement_class))),
+            };
+
+            if window <= 0 {
+                return Err(TipupError::from(format!("aggregation policy window for '{}' must be positive", measurement_class)));
+            }
+
+            //seconds after a window closes that its buckets wait for late results before the wall
+            //clock flushes them, e.g. for hosts which stopped reporting
+            let grace = match document.get("grace") {
+                Some(&Bson::I32(grace)) if grace >= 0 => grace as i64,
+                Some(&Bson::I64(grace)) if grace >= 0 => grace,
+                None => 300,
+                _ => return Err(TipupError::from(format!("failed to parse aggregation policy grace for '{}' as non-negative seconds", measurement_class))),
+            };
+
+            let mut fields = Vec::new();
+            if let Some(&Bson::Array(ref field_array)) = document.get("fields") {
+                for field in field_array.iter() {
+                    match field {
+                        &Bson::Array(ref field) => fields.push(field.iter().map(|x| x.to_string().replace("\"", "")).collect()),
+                        _ => return Err(TipupError::from(format!("failed to parse aggregation policy fields for '{}'", measurement_class))),
+                    }
+                }
+            }
+
+            self.policies.insert(measurement_class,
+                AggregationPolicy {
+                    window: window,
+                    grace: grace,
+                    fields: fields,
+                }
+            );
+
+            count += 1;
+        }
+
+        if count > 0 {
+            info!("loaded {} aggregation policy(s)", count);
+        }
+
+        Ok(())
+    }
+
+    //returns results of classes without an aggregation policy, buffering the rest into window buckets
+    pub fn aggregate(&mut self, document: OrderedDocument) -> Result<Option<OrderedDocument>, TipupError> {
+        let policy = match document.get("measurement_class") {
+            Some(&Bson::String(ref measurement_class)) => match self.policies.get(measurement_class) {
+                Some(policy) => policy,
+                None => return Ok(Some(document)),
+            },
+            _ => return Ok(Some(document)),
+        };
+
+        let key = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("measurement_class"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::String(ref measurement_class)), Some(&Bson::I64(timestamp))) =>
+                (hostname.to_owned(), domain.to_owned(), measurement_class.to_owned(), timestamp - (timestamp % policy.window)),
+            _ => return Err(TipupError::from("failed to parse result vantage_hostname, measurement_domain, measurement_class, and timestamp")),
+        };
+
+        //update bucket statistics
+        let window_start = key.3;
+        let values: Vec<Option<f64>> = policy.fields.iter().map(|x| get_value(x, &document)).collect();
+        let error_message = match document.get("measurement_error_message") {
+            Some(&Bson::String(ref error_message)) => Some(error_message.to_owned()),
+            Some(_) => Some(String::new()),
+            None => None,
+        };
+
+        let error_category = match document.get("measurement_error_category") {
+            Some(&Bson::String(ref error_category)) => Some(error_category.to_owned()),
+            _ => None,
+        };
+
+        let id = match document.get("_id") {
+            Some(&Bson::ObjectId(ref id)) => id.clone(),
+            _ => return Err(TipupError::from("failed to parse result '_id' as ObjectId")),
+        };
+
+        let bucket = self.buckets.entry(key).or_insert_with(|| {
+            Bucket {
+                template: document.clone(),
+                window_start: window_start,
+                ids: HashSet::new(),
+                count: 0,
+                errors: 0,
+                error_message: None,
+                error_category: None,
+                statistics: values.iter().map(|_| None).collect(),
+            }
+        });
+
+        if !bucket.ids.insert(id) {
+            return Ok(None);
+        }
+
+        bucket.count += 1;
+        if error_message.is_some() {
+            bucket.errors += 1;
+            bucket.error_message = error_message;
+            bucket.error_category = error_category;
+        }
+
+        for (statistics, value) in bucket.statistics.iter_mut().zip(values.iter()) {
+            let value = match *value {
+                Some(value) => value,
+                None => continue,
+            };
+
+            match *statistics {
+                Some(ref mut statistics) => {
+                    statistics.minimum = statistics.minimum.min(value);
+                    statistics.maximum = statistics.maximum.max(value);
+                    statistics.sum += value;
+                    statistics.count += 1;
+                },
+                None => *statistics = Some(FieldStatistics { minimum: value, maximum: value, sum: value, count: 1 }),
+            }
+        }
+
+        Ok(None)
+    }
+
+    //emit summaries for the hostname's measurement class buckets whose window closed at or before timestamp
+    pub fn flush(&mut self, hostname: &str, measurement_class: &str, timestamp: i64) -> Vec<OrderedDocument> {
+        let keys: Vec<(String, String, String, i64)> = self.buckets.keys()
+            .filter(|x| x.0 == hostname && x.2 == measurement_class && self.policies.get(&x.2).map_or(true, |policy| x.3 + policy.window <= timestamp))
+            .cloned()
+            .collect();
+
+        self.summarize(keys)
+    }
+
+    //emit summaries for buckets of any host whose window closed over its grace period before now
+    pub fn flush_expired(&mut self, now: i64) -> Vec<OrderedDocument> {
+        let keys: Vec<(String, String, String, i64)> = self.buckets.keys()
+            .filter(|x| self.policies.get(&x.2).map_or(true, |policy| x.3 + policy.window + policy.grace <= now))
+            .cloned()
+            .collect();
+
+        self.summarize(keys)
+    }
+
+    //records the timestamp of the latest result fetched for the hostname's measurement class
+    pub fn fetched(&mut self, hostname: &str, measurement_class: &str, timestamp: i64) {
+        if self.policies.contains_key(measurement_class) {
+            self.fetched.insert((hostname.to_owned(), measurement_class.to_owned()), timestamp);
+        }
+    }
+
+    pub fn fetched_timestamp(&self, hostname: &str, measurement_class: &str) -> Option<i64> {
+        self.fetched.get(&(hostname.to_owned(), measurement_class.to_owned())).cloned()
+    }
+
+    //the timestamp up to which the hostname's measurement class results are analyzed or summarized,
+    //before the oldest of its open buckets
+    pub fn watermark(&self, hostname: &str, measurement_class: &str, timestamp: i64) -> i64 {
+        self.buckets.keys()
+            .filter(|x| x.0 == hostname && x.2 == measurement_class)
+            .map(|x| x.3 - 1)
+            .fold(timestamp, |x, y| x.min(y))
+    }
+
+    fn summarize(&mut self, keys: Vec<(String, String, String, i64)>) -> Vec<OrderedDocument> {
+        let mut documents = Vec::new();
+        for key in keys {
+            let bucket = self.buckets.remove(&key).unwrap();
+            let policy = match self.policies.get(&key.2) {
+                Some(policy) => policy,
+                None => continue,
+            };
+
+            documents.push(summarize(bucket, &policy.fields, policy.window));
+        }
+
+        documents.sort_by_key(|x| match x.get("timestamp") {
+            Some(&Bson::I64(timestamp)) => timestamp,
+            _ => 0,
+        });
+
+        documents
+    }
+}
+
+fn summarize(bucket: Bucket, fields: &Vec<Vec<String>>, window: i64) -> OrderedDocument {
+    //replace field values with window means and attach min/max/loss summaries
+    let mut document = bucket.template;
+    let (mut minimum_document, mut maximum_document) = (Document::new(), Document::new());
+    for (field, statistics) in fields.iter().zip(bucket.statistics.iter()) {
+        match *statistics {
+            Some(ref statistics) => {
+                set_value(field, &mut document, Bson::FloatingPoint(statistics.sum / statistics.count as f64));
+                set_value(field, &mut minimum_document, Bson::FloatingPoint(statistics.minimum));
+                set_value(field, &mut maximum_document, Bson::FloatingPoint(statistics.maximum));
+            },
+            None => remove_value(field, &mut document),
+        }
+    }
+
+    //only mark the summary as failed when every result in the window failed
+    document.remove("measurement_error_message");
+    document.remove("measurement_error_category");
+    if bucket.errors == bucket.count {
+        if let Some(ref error_message) = bucket.error_message {
+            document.insert("measurement_error_message", error_message.to_owned());
+        }
+
+        if let Some(ref error_category) = bucket.error_category {
+            document.insert("measurement_error_category", error_category.to_owned());
+        }
+    }
+
+    let (count, errors) = (bucket.count as i64, bucket.errors as i64);
+    document.insert("_id", Bson::ObjectId(ObjectId::new().unwrap()));
+    document.insert("timestamp", bucket.window_start);
+    document.insert("aggregate", doc!(
+        "window" => window,
+        "count" => count,
+        "errors" => errors,
+        "loss" => (errors as f64 / count as f64),
+        "minimum" => minimum_document,
+        "maximum" => maximum_document
+    ));
+
+    document
+}
+
+fn set_value(field: &[String], document: &mut OrderedDocument, value: Bson) {
+    if field.len() == 1 {
+        document.insert(field[0].to_owned(), value);
+        return;
+    }
+
+    //create intermediate documents where missing
+    match document.get(&field[0]) {
+        Some(&Bson::Document(_)) => {},
+        _ => { document.insert(field[0].to_owned(), Document::new()); },
+    }
+
+    if let Some(&mut Bson::Document(ref mut document)) = document.get_mut(&field[0]) {
+        set_value(&field[1..], document, value);
+    }
+}
+
+fn remove_value(field: &[String], document: &mut OrderedDocument) {
+    if field.len() == 1 {
+        document.remove(&field[0]);
+    } else if let Some(&mut Bson::Document(ref mut document)) = document.get_mut(&field[0]) {
+        remove_value(&field[1..], document);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::Bson;
+    use bson::oid::ObjectId;
+    use bson::ordered::OrderedDocument;
+
+    use super::{AggregationPolicy, Aggregator};
+
+    fn aggregator() -> Aggregator {
+        let mut aggregator = Aggregator::new();
+        aggregator.policies.insert("ping".to_owned(), AggregationPolicy { window: 60, grace: 300, fields: vec!(vec!("latency".to_owned())) });
+        aggregator
+    }
+
+    fn result(timestamp: i64, latency: f64) -> OrderedDocument {
+        doc!(
+            "_id" => (ObjectId::new().unwrap()),
+            "vantage_hostname" => "vantage",
+            "measurement_domain" => "example.com",
+            "measurement_class" => "ping",
+            "timestamp" => timestamp,
+            "latency" => latency
+        )
+    }
+
+    #[test]
+    fn holds_watermark_before_open_buckets() {
+        let mut aggregator = aggregator();
+        assert!(aggregator.aggregate(result(100, 10.0)).unwrap().is_none());
+        assert!(aggregator.aggregate(result(110, 20.0)).unwrap().is_none());
+        assert_eq!(aggregator.flush("vantage", "ping", 110).len(), 0);
+        assert_eq!(aggregator.watermark("vantage", "ping", 110), 59);
+
+        assert!(aggregator.aggregate(result(125, 30.0)).unwrap().is_none());
+        let summaries = aggregator.flush("vantage", "ping", 125);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].get("latency"), Some(&Bson::FloatingPoint(15.0)));
+        assert_eq!(aggregator.watermark("vantage", "ping", 125), 119);
+    }
+
+    #[test]
+    fn skips_results_already_buffered() {
+        let mut aggregator = aggregator();
+        let document = result(100, 10.0);
+        aggregator.aggregate(document.clone()).unwrap();
+        aggregator.aggregate(document).unwrap();
+
+        let summaries = aggregator.flush("vantage", "ping", 120);
+        match summaries[0].get("aggregate") {
+            Some(&Bson::Document(ref aggregate)) => assert_eq!(aggregate.get("count"), Some(&Bson::I64(1))),
+            _ => panic!("summary without aggregate document"),
+        }
+    }
+
+    #[test]
+    fn flushes_expired_buckets_by_wall_clock() {
+        let mut aggregator = aggregator();
+        aggregator.aggregate(result(100, 10.0)).unwrap();
+        assert_eq!(aggregator.flush_expired(419).len(), 0);
+        assert_eq!(aggregator.flush_expired(420).len(), 1);
+        assert_eq!(aggregator.watermark("vantage", "ping", 100), 100);
+    }
+}
@@ -0,0 +1,245 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std;
+use std::collections::HashMap;
+
+//maximum number of bins the transition cost grows with
+const MAX_TRANSITION_BINS: f64 = 1000.0;
+
+struct BurstState {
+    bin: i64,
+    errors: u64,
+    results: u64,
+    background_errors: u64,
+    background_results: u64,
+    bins: u64,
+    costs: Vec<f64>,
+    level: usize,
+    peak_level: usize,
+}
+
+//kleinberg burst detection over the error rate of fixed width time bins, where each burst level
+//expects a scaled multiple of the background error rate and entering a level carries a cost
+pub struct BurstAnalyzer {
+    name: String,
+    status: String,
+    fields: Vec<String>,
+    bin_seconds: i64,
+    levels: usize,
+    scaling: f64,
+    gamma: f64,
+    states: HashMap<(String, String), BurstState>,
+    flag_tx: Sender<Flag>,
+}
+
+impl BurstAnalyzer {
+    pub fn new(name: &str, status: &str, fields: Vec<String>, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<BurstAnalyzer, TipupError> {
+        //results carrying any of the fields are errors
+        let fields = match fields.len() {
+            0 => vec!("measurement_error_message".to_owned()),
+            _ => fields,
+        };
+
+        let bin_seconds = match parameters.get("bin_seconds") {
+            Some(&Bson::I32(bin_seconds)) if bin_seconds > 0 => bin_seconds as i64,
+            None => 300,
+            _ => return Err(TipupError::from("failed to parse bin_seconds parameter in BurstAnalyzer as positive integer")),
+        };
+
+        let levels = match parameters.get("levels") {
+            Some(&Bson::I32(levels)) if levels > 0 => levels as usize,
+            None => 3,
+            _ => return Err(TipupError::from("failed to parse levels parameter in BurstAnalyzer as positive integer")),
+        };
+
+        let scaling = match parameters.get("scaling") {
+            Some(&Bson::FloatingPoint(scaling)) if scaling > 1.0 => scaling,
+            Some(&Bson::I32(scaling)) if scaling > 1 => scaling as f64,
+            None => 2.0,
+            _ => return Err(TipupError::from("failed to parse scaling parameter in BurstAnalyzer, expected a value greater than 1")),
+        };
+
+        let gamma = match parameters.get("gamma") {
+            Some(&Bson::FloatingPoint(gamma)) if gamma > 0.0 => gamma,
+            Some(&Bson::I32(gamma)) if gamma > 0 => gamma as f64,
+            None => 1.0,
+            _ => return Err(TipupError::from("failed to parse gamma parameter in BurstAnalyzer as positive number")),
+        };
+
+        Ok(
+            BurstAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                fields: fields,
+                bin_seconds: bin_seconds,
+                levels: levels,
+                scaling: scaling,
+                gamma: gamma,
+                states: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for BurstAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let error = self.fields.iter().any(|x| document.contains_key(x));
+        let bin = timestamp / self.bin_seconds;
+        //sequences begin outside a burst
+        let mut costs = vec![std::f64::INFINITY; self.levels + 1];
+        costs[0] = 0.0;
+        let state = self.states.entry((hostname, domain)).or_insert(
+            BurstState {
+                bin: bin,
+                errors: 0,
+                results: 0,
+                background_errors: 0,
+                background_results: 0,
+                bins: 0,
+                costs: costs,
+                level: 0,
+                peak_level: 0,
+            }
+        );
+
+        //late results are counted in the current bin
+        if bin > state.bin {
+            let previous_level = state.level;
+            close_bin(state, self.scaling, self.gamma);
+            state.bin = bin;
+
+            trace.statistic("level", state.level as i64);
+            if previous_level == 0 && state.level > 0 {
+                trace.decision("flag: error burst started");
+                try!(send_flag(document, &self.status, &self.name, "start", state, &self.flag_tx));
+            } else if previous_level > 0 && state.level == 0 {
+                trace.decision("flag: error burst ended");
+                try!(send_flag(document, &self.status, &self.name, "end", state, &self.flag_tx));
+                state.peak_level = 0;
+            } else {
+                trace.decision("no flag: burst level unchanged");
+            }
+        } else {
+            trace.decision("no flag: bin not complete");
+        }
+
+        state.results += 1;
+        if error {
+            state.errors += 1;
+        }
+
+        trace.statistic("bin_errors", state.errors as i64);
+        trace.statistic("bin_results", state.results as i64);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.states.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn close_bin(state: &mut BurstState, scaling: f64, gamma: f64) {
+    //background error rate of bins outside bursts, smoothed so error free history keeps a positive rate
+    let background_rate = (state.background_errors as f64 + 1.0) / (state.background_results as f64 + 2.0);
+    state.bins += 1;
+    let transition_cost = gamma * (state.bins as f64).min(MAX_TRANSITION_BINS).max(2.0).ln();
+
+    //extend the cheapest state sequence by one bin, rising a level costs while falling is free
+    let (errors, results) = (state.errors as f64, state.results as f64);
+    let mut costs = Vec::new();
+    for j in 0..state.costs.len() {
+        let rate = (background_rate * scaling.powi(j as i32)).min(0.9999);
+        let bin_cost = -((errors * rate.ln()) + ((results - errors) * (1.0 - rate).ln()));
+        let path_cost = state.costs.iter().enumerate()
+            .map(|(i, cost)| cost + if j > i { (j - i) as f64 * transition_cost } else { 0.0 })
+            .fold(std::f64::MAX, |minimum, x| minimum.min(x));
+        costs.push(path_cost + bin_cost);
+    }
+
+    //normalize costs so they remain bounded over long runs
+    let minimum = costs.iter().fold(std::f64::MAX, |minimum, x| minimum.min(*x));
+    state.level = costs.iter().position(|x| *x == minimum).unwrap_or(0);
+    state.costs = costs.iter().map(|x| x - minimum).collect();
+    state.peak_level = state.peak_level.max(state.level);
+
+    if state.level == 0 {
+        state.background_errors += state.errors;
+        state.background_results += state.results;
+    }
+
+    state.errors = 0;
+    state.results = 0;
+}
+
+fn send_flag(document: &OrderedDocument, status: &str, name: &str, burst: &str, state: &BurstState, flag_tx: &Sender<Flag>) -> Result<(), TipupError> {
+    let mut flag = try!(Flag::new(document, status, name));
+    let background_rate = (state.background_errors as f64 + 1.0) / (state.background_results as f64 + 2.0);
+    flag.evidence.insert("burst".to_owned(), burst.to_owned());
+    flag.evidence.insert("level".to_owned(), state.level.to_string());
+    flag.evidence.insert("peak_level".to_owned(), state.peak_level.to_string());
+    flag.evidence.insert("background_error_rate".to_owned(), background_rate.to_string());
+    flag_tx.send(flag);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{close_bin, BurstState};
+
+    use std;
+
+    fn state(levels: usize) -> BurstState {
+        let mut costs = vec![std::f64::INFINITY; levels + 1];
+        costs[0] = 0.0;
+        BurstState { bin: 0, errors: 0, results: 0, background_errors: 0, background_results: 0, bins: 0, costs: costs, level: 0, peak_level: 0 }
+    }
+
+    fn close(state: &mut BurstState, errors: u64, results: u64) {
+        state.errors = errors;
+        state.results = results;
+        close_bin(state, 2.0, 1.0);
+    }
+
+    #[test]
+    fn close_bin_matches_hand_computed_costs() {
+        //background rate 0.5 and level rates 0.9999, so ten errors cost 10ln2 at level 0 and
+        //0.001 above it, plus ln2 per level risen
+        let mut state = state(2);
+        close(&mut state, 10, 10);
+        assert_eq!(state.level, 1);
+        let expected = [(10.0 * 2f64.ln()) - 2f64.ln() - 0.001, 0.0, 2f64.ln()];
+        for (cost, expected) in state.costs.iter().zip(expected.iter()) {
+            assert!((cost - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn bursts_start_and_end_with_error_rate() {
+        let mut state = state(3);
+        for _ in 0..20 {
+            close(&mut state, 1, 100);
+            assert_eq!(state.level, 0);
+        }
+
+        close(&mut state, 40, 100);
+        assert!(state.level > 0);
+        let peak_level = state.peak_level;
+
+        close(&mut state, 1, 100);
+        assert_eq!(state.level, 0);
+        assert_eq!(state.peak_level, peak_level);
+        assert_eq!(state.background_results, 2100);
+    }
+}
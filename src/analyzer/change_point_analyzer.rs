@@ -0,0 +1,327 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std;
+use std::collections::{HashMap, VecDeque};
+
+//lanczos approximation coefficients (g = 7) of the log gamma function
+const LANCZOS_COEFFICIENTS: [f64; 9] = [0.99999999999980993, 676.5203681218851, -1259.1392167224028, 771.32342877765313,
+    -176.61502916214059, 12.507343278686905, -0.13857109526572012, 9.9843695780195716e-6, 1.5050632857710404e-7];
+
+//a hypothesis that the current run of results began run_length results ago, with the
+//normal-gamma posterior of the results in that run
+struct RunLength {
+    run_length: usize,
+    log_probability: f64,
+    mean: f64,
+    kappa: f64,
+    alpha: f64,
+    beta: f64,
+}
+
+struct ChangePointState {
+    warmup: Vec<f64>,
+    prior: (f64, f64),
+    run_lengths: Vec<RunLength>,
+    timestamps: VecDeque<i64>,
+    flagged: bool,
+}
+
+//bayesian online changepoint detection (adams and mackay) maintaining a distribution over the
+//number of results since the last distribution shift, flagging when the probability that one
+//occurred within the detection window crosses a threshold and reporting the result it began at
+pub struct ChangePointAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    hazard: f64,
+    threshold: f64,
+    detection_window: usize,
+    warmup: usize,
+    max_run_lengths: usize,
+    states: HashMap<(String, String), ChangePointState>,
+    flag_tx: Sender<Flag>,
+}
+
+impl ChangePointAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<ChangePointAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in ChangePointAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in ChangePointAnalyzer")),
+        };
+
+        //expected number of results between changepoints, the inverse of the hazard rate
+        let hazard = match parameters.get("expected_run_length") {
+            Some(&Bson::I32(expected_run_length)) if expected_run_length > 1 => 1.0 / expected_run_length as f64,
+            None => 1.0 / 250.0,
+            _ => return Err(TipupError::from("failed to parse expected_run_length parameter in ChangePointAnalyzer, expected an integer greater than 1")),
+        };
+
+        let threshold = match parameters.get("threshold") {
+            Some(&Bson::FloatingPoint(threshold)) if threshold > 0.0 && threshold < 1.0 => threshold,
+            None => 0.8,
+            _ => return Err(TipupError::from("failed to parse threshold parameter in ChangePointAnalyzer, expected a value between 0 and 1")),
+        };
+
+        //number of most recent results a changepoint is looked for in
+        let detection_window = try!(parse_size(parameters, "detection_window", 10, "ChangePointAnalyzer"));
+        //results the prior mean and variance of runs are learned from
+        let warmup = try!(parse_size(parameters, "warmup", 30, "ChangePointAnalyzer"));
+        //run length hypotheses retained, the least probable pruned
+        let max_run_lengths = try!(parse_size(parameters, "max_run_lengths", 200, "ChangePointAnalyzer"));
+
+        if warmup < 2 || max_run_lengths <= detection_window {
+            return Err(TipupError::from("ChangePointAnalyzer requires a warmup of at least 2 and max_run_lengths greater than detection_window"));
+        }
+
+        Ok(
+            ChangePointAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                hazard: hazard,
+                threshold: threshold,
+                detection_window: detection_window,
+                warmup: warmup,
+                max_run_lengths: max_run_lengths,
+                states: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for ChangePointAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let state = self.states.entry((hostname, domain)).or_insert(
+            ChangePointState {
+                warmup: Vec::new(),
+                prior: (0.0, 0.0),
+                run_lengths: Vec::new(),
+                timestamps: VecDeque::new(),
+                flagged: false,
+            }
+        );
+
+        state.timestamps.push_back(timestamp);
+        if state.timestamps.len() > self.detection_window + 1 {
+            state.timestamps.pop_front();
+        }
+
+        trace.statistic("value", value);
+        if state.warmup.len() < self.warmup {
+            state.warmup.push(value);
+            if state.warmup.len() == self.warmup {
+                start_run(state);
+            }
+
+            trace.decision("no flag: learning prior");
+            return Ok(());
+        }
+
+        update(state, value, self.hazard, self.max_run_lengths);
+
+        //probability a changepoint occurred within the detection window
+        let detection_window = self.detection_window;
+        let probability: f64 = state.run_lengths.iter()
+            .filter(|x| x.run_length > 0 && x.run_length <= detection_window)
+            .map(|x| x.log_probability.exp())
+            .sum();
+        let most_probable = state.run_lengths.iter()
+            .fold(&state.run_lengths[0], |a, b| if b.log_probability > a.log_probability { b } else { a });
+
+        trace.statistic("changepoint_probability", probability);
+        trace.statistic("most_probable_run_length", most_probable.run_length as i64);
+        if probability < self.threshold {
+            state.flagged = false;
+            trace.decision("no flag: changepoint probability below threshold");
+            return Ok(());
+        }
+
+        if state.flagged {
+            trace.decision("no flag: changepoint already flagged");
+            return Ok(());
+        }
+
+        //the result the current run began with
+        let run_length = most_probable.run_length.max(1).min(state.timestamps.len());
+        let changepoint_timestamp = state.timestamps[state.timestamps.len() - run_length];
+
+        state.flagged = true;
+        trace.decision("flag: changepoint probability exceeds threshold");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("changepoint_probability".to_owned(), probability.to_string());
+        flag.evidence.insert("changepoint_timestamp".to_owned(), changepoint_timestamp.to_string());
+        flag.evidence.insert("run_mean".to_owned(), most_probable.mean.to_string());
+        flag.evidence.insert("prior_mean".to_owned(), state.prior.0.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.states.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn start_run(state: &mut ChangePointState) {
+    //new runs expect the warmup mean and variance, the warmup forming the initial run
+    let count = state.warmup.len() as f64;
+    let mean = state.warmup.iter().sum::<f64>() / count;
+    let variance = (state.warmup.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1.0)).max(std::f64::EPSILON.max(mean.abs() * 1e-6));
+    state.prior = (mean, variance);
+    state.run_lengths = vec!(
+        RunLength {
+            run_length: state.warmup.len(),
+            log_probability: 0.0,
+            mean: mean,
+            kappa: 1.0 + count,
+            alpha: 1.0 + (count / 2.0),
+            beta: variance + (0.5 * (count - 1.0) * variance),
+        }
+    );
+}
+
+fn update(state: &mut ChangePointState, value: f64, hazard: f64, max_run_lengths: usize) {
+    //weight each hypothesis by the student-t predictive probability of the value
+    let mut changepoint_terms = Vec::new();
+    let mut run_lengths = Vec::new();
+    for x in state.run_lengths.iter() {
+        let scale = (x.beta * (x.kappa + 1.0) / (x.alpha * x.kappa)).sqrt();
+        let log_predictive = log_student_t(value, 2.0 * x.alpha, x.mean, scale);
+        changepoint_terms.push(x.log_probability + log_predictive + hazard.ln());
+
+        //the run grows by the value
+        run_lengths.push(
+            RunLength {
+                run_length: x.run_length + 1,
+                log_probability: x.log_probability + log_predictive + (1.0 - hazard).ln(),
+                mean: ((x.kappa * x.mean) + value) / (x.kappa + 1.0),
+                kappa: x.kappa + 1.0,
+                alpha: x.alpha + 0.5,
+                beta: x.beta + (x.kappa * (value - x.mean).powi(2) / (2.0 * (x.kappa + 1.0))),
+            }
+        );
+    }
+
+    //or a new run begins after it
+    let (mean, variance) = state.prior;
+    run_lengths.push(
+        RunLength {
+            run_length: 0,
+            log_probability: log_sum_exp(&changepoint_terms),
+            mean: mean,
+            kappa: 1.0,
+            alpha: 1.0,
+            beta: variance,
+        }
+    );
+
+    if run_lengths.len() > max_run_lengths {
+        run_lengths.sort_by(|a, b| b.log_probability.partial_cmp(&a.log_probability).unwrap_or(std::cmp::Ordering::Equal));
+        run_lengths.truncate(max_run_lengths);
+    }
+
+    let normalizer = log_sum_exp(&run_lengths.iter().map(|x| x.log_probability).collect());
+    for x in run_lengths.iter_mut() {
+        x.log_probability -= normalizer;
+    }
+
+    state.run_lengths = run_lengths;
+}
+
+fn log_student_t(x: f64, degrees_of_freedom: f64, location: f64, scale: f64) -> f64 {
+    let z = (x - location) / scale;
+    ln_gamma((degrees_of_freedom + 1.0) / 2.0) - ln_gamma(degrees_of_freedom / 2.0)
+        - (0.5 * (degrees_of_freedom * std::f64::consts::PI).ln()) - scale.ln()
+        - (((degrees_of_freedom + 1.0) / 2.0) * (1.0 + (z * z / degrees_of_freedom)).ln())
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    //arguments are at least 1 as degrees of freedom are at least 2
+    let x = x - 1.0;
+    let mut sum = LANCZOS_COEFFICIENTS[0];
+    for (i, coefficient) in LANCZOS_COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+
+    let t = x + 7.5;
+    (0.5 * (2.0 * std::f64::consts::PI).ln()) + ((x + 0.5) * t.ln()) - t + sum.ln()
+}
+
+fn log_sum_exp(values: &Vec<f64>) -> f64 {
+    let maximum = values.iter().fold(std::f64::NEG_INFINITY, |maximum, x| maximum.max(*x));
+    if !maximum.is_finite() {
+        return maximum;
+    }
+
+    maximum + values.iter().map(|x| (x - maximum).exp()).sum::<f64>().ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chan;
+
+    use analyzer::{Analyzer, Trace};
+
+    use super::{ln_gamma, log_student_t, log_sum_exp, ChangePointAnalyzer};
+
+    #[test]
+    fn densities_match_reference_values() {
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+        assert!((ln_gamma(1.5) - (-0.1207822376352452)).abs() < 1e-10);
+
+        //the student's t density with 2 degrees of freedom is 1 / (2 * sqrt(2)) at its location
+        assert!((log_student_t(3.0, 2.0, 3.0, 1.0) - (1.0 / (2.0 * 2f64.sqrt())).ln()).abs() < 1e-10);
+        assert!((log_student_t(5.0, 2.0, 3.0, 2.0) - (1.0 / (2.0 * 2f64.sqrt() * 2.0 * 1.5f64.powf(1.5))).ln()).abs() < 1e-10);
+        assert!((log_sum_exp(&vec!(1f64.ln(), 2f64.ln(), 3f64.ln())) - 6f64.ln()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn flags_shift_in_mean_at_its_first_result() {
+        let (flag_tx, flag_rx) = chan::async();
+        let parameters = doc!("variable_name" => ["latency"]);
+        let mut analyzer = ChangePointAnalyzer::new("change_point", "warning", &parameters, flag_tx).unwrap();
+        for timestamp in 0..80 {
+            let value = if timestamp < 60 { 10.0 } else { 20.0 } + (timestamp % 2) as f64;
+
+            let document = doc!("_id" => (ObjectId::new().unwrap()), "vantage_hostname" => "vantage", "measurement_domain" => "example.com",
+                "timestamp" => (timestamp as i64), "latency" => value);
+            analyzer.process_measurement(&document, &mut Trace::new(false)).unwrap();
+        }
+
+        drop(analyzer);
+        let flags: Vec<_> = flag_rx.iter().collect();
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].evidence.get("changepoint_timestamp").map(|x| x.as_str()), Some("60"));
+    }
+}
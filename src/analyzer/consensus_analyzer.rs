@@ -0,0 +1,150 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, HashSet};
+
+struct Observation {
+    error: bool,
+    timestamp: i64,
+}
+
+//flags a target as down only once at least min_failing vantage points report failures for it
+//within the window, so a single vantage point's network hiccup never flags, raising one flag for
+//the target rather than one per vantage point and flagging again only after it recovers
+pub struct ConsensusAnalyzer {
+    name: String,
+    status: String,
+    fields: Vec<String>,
+    min_failing: usize,
+    min_vantage_points: usize,
+    window: i64,
+    targets: HashMap<String, HashMap<String, Observation>>,
+    flagged: HashSet<String>,
+    flag_tx: Sender<Flag>,
+}
+
+impl ConsensusAnalyzer {
+    pub fn new(name: &str, status: &str, fields: Vec<String>, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<ConsensusAnalyzer, TipupError> {
+        //results carrying any of the fields are failures
+        let fields = match fields.len() {
+            0 => vec!("measurement_error_message".to_owned()),
+            _ => fields,
+        };
+
+        //failing vantage points, k of n, required to flag the target
+        let min_failing = match parameters.get("min_failing") {
+            Some(&Bson::I32(min_failing)) if min_failing >= 2 => min_failing as usize,
+            None => 3,
+            _ => return Err(TipupError::from("failed to parse min_failing parameter in ConsensusAnalyzer, expected an integer of at least 2")),
+        };
+
+        //vantage points, n of k of n, which must report within the window for failures to count
+        let min_vantage_points = match parameters.get("min_vantage_points") {
+            Some(&Bson::I32(min_vantage_points)) if min_vantage_points as usize >= min_failing => min_vantage_points as usize,
+            None => min_failing,
+            _ => return Err(TipupError::from("failed to parse min_vantage_points parameter in ConsensusAnalyzer, expected an integer of at least min_failing")),
+        };
+
+        //seconds around a result within which other vantage points' latest results count
+        let window = match parameters.get("window") {
+            Some(&Bson::I32(window)) if window > 0 => window as i64,
+            Some(&Bson::I64(window)) if window > 0 => window,
+            None => 300,
+            _ => return Err(TipupError::from("failed to parse window parameter in ConsensusAnalyzer as positive seconds")),
+        };
+
+        Ok(
+            ConsensusAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                fields: fields,
+                min_failing: min_failing,
+                min_vantage_points: min_vantage_points,
+                window: window,
+                targets: HashMap::new(),
+                flagged: HashSet::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for ConsensusAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let error = self.fields.iter().any(|x| document.contains_key(x));
+        trace.statistic("error", error);
+
+        //results of a vantage point fetched out of order never replace its later ones
+        let window = self.window;
+        let vantage_points = self.targets.entry(domain.clone()).or_insert(HashMap::new());
+        if vantage_points.get(&hostname).map_or(true, |x| x.timestamp <= timestamp) {
+            vantage_points.insert(hostname, Observation { error: error, timestamp: timestamp });
+        }
+
+        let mut reporting = 0;
+        let mut failing = Vec::new();
+        for (hostname, observation) in vantage_points.iter() {
+            if (timestamp - observation.timestamp).abs() > window {
+                continue;
+            }
+
+            reporting += 1;
+            if observation.error {
+                failing.push(hostname.to_owned());
+            }
+        }
+
+        trace.statistic("vantage_points", reporting as i64);
+        trace.statistic("failing", failing.len() as i64);
+        if reporting < self.min_vantage_points {
+            trace.decision("no flag: too few recent vantage points for a consensus");
+            return Ok(());
+        }
+
+        if failing.len() < self.min_failing {
+            self.flagged.remove(&domain);
+            trace.decision("no flag: too few failing vantage points");
+            return Ok(());
+        }
+
+        if !self.flagged.insert(domain.clone()) {
+            trace.decision("no flag: target already flagged down");
+            return Ok(());
+        }
+
+        trace.decision("flag: vantage points agree target is down");
+        failing.sort();
+        let mut flag = Flag::for_domain(&domain, timestamp, &self.status, &self.name);
+        if let Some(&Bson::String(ref measurement_class)) = document.get("measurement_class") {
+            flag.labels.insert("measurement_class".to_owned(), measurement_class.to_owned());
+        }
+
+        flag.evidence.insert("failing".to_owned(), failing.len().to_string());
+        flag.evidence.insert("failing_vantage_points".to_owned(), failing.join(","));
+        flag.evidence.insert("vantage_points".to_owned(), reporting.to_string());
+        flag.evidence.insert("min_failing".to_owned(), self.min_failing.to_string());
+        flag.evidence.insert("window".to_owned(), self.window.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        if let Some(vantage_points) = self.targets.get_mut(domain) {
+            vantage_points.remove(hostname);
+        }
+    }
+
+    fn routes_by_target(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,149 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+pub struct ContentChangeAnalyzer {
+    name: String,
+    status: String,
+    hash_field: Vec<String>,
+    change_windows: Vec<(i64, i64)>,
+    hashes: HashMap<(String, String), String>,
+    flag_tx: Sender<Flag>,
+}
+
+impl ContentChangeAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<ContentChangeAnalyzer, TipupError> {
+        let hash_field = match parameters.get("hash_field") {
+            Some(&Bson::Array(ref hash_field)) => {
+                let mut fields = Vec::new();
+                for x in hash_field {
+                    match x {
+                        &Bson::String(ref y) => fields.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse hash_field as String in ContentChangeAnalyzer")),
+                    }
+                }
+
+                fields
+            },
+            None => vec!("content_hash".to_owned()),
+            _ => return Err(TipupError::from("failed to parse hash_field parameter in ContentChangeAnalyzer")),
+        };
+
+        //daily utc windows, e.g. {"start": "02:00", "end": "04:00"}, in which content may change
+        let mut change_windows = Vec::new();
+        match parameters.get("change_windows") {
+            Some(&Bson::Array(ref windows)) => {
+                for window in windows {
+                    let (start, end) = match window {
+                        &Bson::Document(ref window) => match (window.get("start"), window.get("end")) {
+                            (Some(&Bson::String(ref start)), Some(&Bson::String(ref end))) => (try!(parse_time_of_day(start)), try!(parse_time_of_day(end))),
+                            _ => return Err(TipupError::from("change_windows in ContentChangeAnalyzer require 'start' and 'end' times")),
+                        },
+                        _ => return Err(TipupError::from("failed to parse change window in ContentChangeAnalyzer")),
+                    };
+
+                    change_windows.push((start, end));
+                }
+            },
+            None => {},
+            _ => return Err(TipupError::from("failed to parse change_windows parameter in ContentChangeAnalyzer")),
+        }
+
+        Ok(
+            ContentChangeAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                hash_field: hash_field,
+                change_windows: change_windows,
+                hashes: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+
+    fn in_change_window(&self, timestamp: i64) -> bool {
+        let seconds = timestamp % SECONDS_PER_DAY;
+        self.change_windows.iter().any(|&(start, end)| match start <= end {
+            true => seconds >= start && seconds < end,
+            false => seconds >= start || seconds < end, //window wraps past midnight
+        })
+    }
+}
+
+impl Analyzer for ContentChangeAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let hash = match get_string(&self.hash_field, document) {
+            Some(hash) => hash,
+            None => {
+                trace.decision("skipped: content hash not present");
+                return Ok(());
+            },
+        };
+
+        //the first hash observed for a target establishes its baseline
+        trace.statistic("content_hash", hash.clone());
+        let previous_hash = match self.hashes.insert((hostname, domain), hash.clone()) {
+            Some(previous_hash) => previous_hash,
+            None => {
+                trace.decision("no flag: baseline content hash recorded");
+                return Ok(());
+            },
+        };
+
+        let in_change_window = self.in_change_window(timestamp);
+        trace.statistic("previous_content_hash", previous_hash.clone());
+        trace.statistic("in_change_window", in_change_window);
+        if previous_hash == hash {
+            trace.decision("no flag: content unchanged");
+        } else if in_change_window {
+            trace.decision("no flag: content changed within change window");
+        } else {
+            trace.decision("flag: content changed outside change windows");
+            let mut flag = try!(Flag::new(document, &self.status, &self.name));
+            flag.labels.insert("previous_content_hash".to_owned(), previous_hash);
+            flag.labels.insert("content_hash".to_owned(), hash);
+            self.flag_tx.send(flag);
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.hashes.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn parse_time_of_day(time: &str) -> Result<i64, TipupError> {
+    //parse 'HH:MM' into seconds past midnight
+    let mut fields = time.splitn(2, ':');
+    match (fields.next().map(|x| x.parse::<i64>()), fields.next().map(|x| x.parse::<i64>())) {
+        (Some(Ok(hour)), Some(Ok(minute))) if hour >= 0 && hour < 24 && minute >= 0 && minute < 60 => Ok((hour * 3600) + (minute * 60)),
+        _ => Err(TipupError::from(format!("failed to parse time of day '{}' in ContentChangeAnalyzer, expected 'HH:MM'", time))),
+    }
+}
+
+fn get_string(variable_name: &Vec<String>, document: &OrderedDocument) -> Option<String> {
+    let mut index_document = document;
+    for variable in variable_name {
+        match index_document.get(variable) {
+            Some(&Bson::Document(ref document)) => index_document = document,
+            Some(&Bson::String(ref s)) => return Some(s.to_owned()),
+            _ => return None,
+        }
+    }
+
+    None
+}
@@ -0,0 +1,258 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct Signal {
+    measurement_class: String,
+    variable_name: Vec<String>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    max_score: f64,
+}
+
+impl Signal {
+    fn parse(document: &OrderedDocument) -> Result<Signal, TipupError> {
+        let measurement_class = match document.get("measurement_class") {
+            Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+            _ => return Err(TipupError::from("failed to parse signal measurement_class in CorrelationAnalyzer")),
+        };
+
+        let variable_name = match document.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse signal variable_name as String in CorrelationAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            None => Vec::new(),
+            _ => return Err(TipupError::from("failed to parse signal variable_name in CorrelationAnalyzer")),
+        };
+
+        let (minimum, maximum) = (try!(parse_bound(document, "min")), try!(parse_bound(document, "max")));
+        let max_score = match document.get("max_score") {
+            Some(&Bson::FloatingPoint(max_score)) if max_score > 0.0 => max_score,
+            Some(&Bson::I32(max_score)) if max_score > 0 => max_score as f64,
+            None => 3.0,
+            _ => return Err(TipupError::from("failed to parse signal max_score in CorrelationAnalyzer as positive number")),
+        };
+
+        Ok(
+            Signal {
+                measurement_class: measurement_class,
+                variable_name: variable_name,
+                minimum: minimum,
+                maximum: maximum,
+                max_score: max_score,
+            }
+        )
+    }
+}
+
+struct Anomaly {
+    timestamp: i64,
+    reason: String,
+}
+
+//correlates two measurement classes of a target, e.g. ping and http, flagging only when both show
+//anomalies within a window of each other rather than on blips of a single signal, a result being
+//anomalous if it failed or its variable falls outside fixed bounds or, without bounds, deviates
+//from its recent values by a z-score
+pub struct CorrelationAnalyzer {
+    name: String,
+    status: String,
+    signals: Vec<Signal>,
+    window: i64,
+    window_size: usize,
+    values: HashMap<(String, String, usize), VecDeque<f64>>,
+    anomalies: HashMap<(String, usize), Anomaly>,
+    flagged: HashMap<String, i64>,
+    flag_tx: Sender<Flag>,
+}
+
+impl CorrelationAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<CorrelationAnalyzer, TipupError> {
+        let signals = match parameters.get("signals") {
+            Some(&Bson::Array(ref param_signals)) if param_signals.len() == 2 => {
+                let mut signals = Vec::new();
+                for x in param_signals {
+                    match x {
+                        &Bson::Document(ref y) => signals.push(try!(Signal::parse(y))),
+                        _ => return Err(TipupError::from("failed to parse signal as document in CorrelationAnalyzer")),
+                    }
+                }
+
+                signals
+            },
+            _ => return Err(TipupError::from("failed to parse signals parameter in CorrelationAnalyzer, expected two signal documents")),
+        };
+
+        if signals[0].measurement_class == signals[1].measurement_class {
+            return Err(TipupError::from("CorrelationAnalyzer requires signals of different measurement classes"));
+        }
+
+        //seconds within which anomalies of both signals must occur
+        let window = match parameters.get("window") {
+            Some(&Bson::I32(window)) if window > 0 => window as i64,
+            Some(&Bson::I64(window)) if window > 0 => window,
+            None => 300,
+            _ => return Err(TipupError::from("failed to parse window parameter in CorrelationAnalyzer as positive integer")),
+        };
+
+        //number of recent values z-scores of unbounded signals are computed over
+        let window_size = match parameters.get("window_size") {
+            Some(&Bson::I32(window_size)) if window_size > 1 => window_size as usize,
+            None => 50,
+            _ => return Err(TipupError::from("failed to parse window_size parameter in CorrelationAnalyzer as integer greater than 1")),
+        };
+
+        Ok(
+            CorrelationAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                signals: signals,
+                window: window,
+                window_size: window_size,
+                values: HashMap::new(),
+                anomalies: HashMap::new(),
+                flagged: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+
+    //the reason a result of the signal is anomalous, if it is
+    fn anomaly(&mut self, index: usize, hostname: &str, domain: &str, document: &OrderedDocument, trace: &mut Trace) -> Option<String> {
+        if let Some(&Bson::String(ref error)) = document.get("measurement_error_message") {
+            return Some(format!("error: {}", error));
+        }
+
+        let signal = &self.signals[index];
+        let value = match get_value(&signal.variable_name, document) {
+            Some(value) => value,
+            None => return None,
+        };
+
+        trace.statistic("value", value);
+        if signal.minimum.is_some() || signal.maximum.is_some() {
+            return match (signal.minimum, signal.maximum) {
+                (Some(minimum), _) if value < minimum => Some(format!("{} below {}", value, minimum)),
+                (_, Some(maximum)) if value > maximum => Some(format!("{} above {}", value, maximum)),
+                _ => None,
+            };
+        }
+
+        let values = self.values.entry((hostname.to_owned(), domain.to_owned(), index)).or_insert(VecDeque::new());
+        let score = match values.len() {
+            x if x < self.window_size => None,
+            _ => {
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let std_dev = (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt();
+                match std_dev > 0.0 {
+                    true => Some((value - mean) / std_dev),
+                    false => None,
+                }
+            },
+        };
+
+        values.push_back(value);
+        if values.len() > self.window_size {
+            values.pop_front();
+        }
+
+        match score {
+            Some(score) => {
+                trace.statistic("score", score);
+                match score.abs() > signal.max_score {
+                    true => Some(format!("{} at z-score {:.2}", value, score)),
+                    false => None,
+                }
+            },
+            None => None,
+        }
+    }
+}
+
+impl Analyzer for CorrelationAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let index = match document.get("measurement_class") {
+            Some(&Bson::String(ref measurement_class)) => match self.signals.iter().position(|x| &x.measurement_class == measurement_class) {
+                Some(index) => index,
+                None => {
+                    trace.decision("skipped: measurement class is not a signal");
+                    return Ok(());
+                },
+            },
+            _ => return Ok(()),
+        };
+
+        let reason = match self.anomaly(index, &hostname, &domain, document, trace) {
+            Some(reason) => reason,
+            None => {
+                trace.decision("no flag: signal not anomalous");
+                return Ok(());
+            },
+        };
+
+        self.anomalies.insert((domain.clone(), index), Anomaly { timestamp: timestamp, reason: reason });
+        let other = match self.anomalies.get(&(domain.clone(), 1 - index)) {
+            Some(other) if (timestamp - other.timestamp).abs() <= self.window => other,
+            _ => {
+                trace.decision("no flag: no anomaly of the correlated signal within window");
+                return Ok(());
+            },
+        };
+
+        //both signals remaining anomalous are flagged once per window
+        if self.flagged.get(&domain).map_or(false, |x| timestamp - x <= self.window) {
+            trace.decision("no flag: correlated anomalies already flagged within window");
+            return Ok(());
+        }
+
+        trace.decision("flag: both signals anomalous within window");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("window".to_owned(), self.window.to_string());
+        for (signal_index, anomaly) in [(index, &self.anomalies[&(domain.clone(), index)]), (1 - index, other)].iter() {
+            let measurement_class = &self.signals[*signal_index].measurement_class;
+            flag.evidence.insert(format!("{}_anomaly", measurement_class), anomaly.reason.to_owned());
+            flag.evidence.insert(format!("{}_timestamp", measurement_class), anomaly.timestamp.to_string());
+        }
+
+        self.flagged.insert(domain, timestamp);
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.values.retain(|&(ref x, ref y, _), _| x != hostname || y != domain);
+    }
+
+    fn measurement_classes(&self) -> Vec<String> {
+        self.signals.iter().map(|x| x.measurement_class.to_owned()).collect()
+    }
+}
+
+fn parse_bound(document: &OrderedDocument, key: &str) -> Result<Option<f64>, TipupError> {
+    match document.get(key) {
+        Some(&Bson::FloatingPoint(bound)) => Ok(Some(bound)),
+        Some(&Bson::I32(bound)) => Ok(Some(bound as f64)),
+        Some(&Bson::I64(bound)) => Ok(Some(bound as f64)),
+        None => Ok(None),
+        _ => Err(TipupError::from(format!("failed to parse signal {} in CorrelationAnalyzer as number", key))),
+    }
+}
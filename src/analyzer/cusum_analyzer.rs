@@ -0,0 +1,160 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+//minimum standard deviation as a fraction of the target
+const MINIMUM_RELATIVE_STD_DEV: f64 = 0.01;
+
+struct Cusum {
+    count: usize,
+    mean: f64,
+    m2: f64,
+    upper_sum: f64,
+    lower_sum: f64,
+}
+
+//two-sided tabular cusum accumulating deviations beyond a slack from a target mean learned over a
+//warmup, flagging once either sum crosses the decision threshold, so small but sustained shifts
+//like a persistent 10ms latency increase are caught where single result thresholds miss them
+pub struct CusumAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    warmup: usize,
+    slack: f64,
+    threshold: f64,
+    states: HashMap<(String, String), Cusum>,
+    flag_tx: Sender<Flag>,
+}
+
+impl CusumAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<CusumAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in CusumAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in CusumAnalyzer")),
+        };
+
+        //results the target mean and standard deviation are learned over
+        let warmup = match parameters.get("warmup") {
+            Some(&Bson::I32(warmup)) if warmup > 1 => warmup as usize,
+            None => 30,
+            _ => return Err(TipupError::from("failed to parse warmup parameter in CusumAnalyzer, expected an integer greater than 1")),
+        };
+
+        //deviation from the target, in standard deviations, tolerated before accumulating
+        let slack = match parameters.get("slack") {
+            Some(&Bson::FloatingPoint(slack)) if slack >= 0.0 => slack,
+            Some(&Bson::I32(slack)) if slack >= 0 => slack as f64,
+            None => 0.5,
+            _ => return Err(TipupError::from("failed to parse slack parameter in CusumAnalyzer as non-negative number")),
+        };
+
+        //accumulated deviation, in standard deviations, which signals a shift
+        let threshold = match parameters.get("threshold") {
+            Some(&Bson::FloatingPoint(threshold)) if threshold > 0.0 => threshold,
+            Some(&Bson::I32(threshold)) if threshold > 0 => threshold as f64,
+            None => 5.0,
+            _ => return Err(TipupError::from("failed to parse threshold parameter in CusumAnalyzer as positive number")),
+        };
+
+        Ok(
+            CusumAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                warmup: warmup,
+                slack: slack,
+                threshold: threshold,
+                states: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for CusumAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let state = self.states.entry((hostname, domain)).or_insert(Cusum { count: 0, mean: 0.0, m2: 0.0, upper_sum: 0.0, lower_sum: 0.0 });
+        trace.statistic("value", value);
+        if state.count < self.warmup {
+            state.count += 1;
+            let delta = value - state.mean;
+            state.mean += delta / state.count as f64;
+            state.m2 += delta * (value - state.mean);
+            trace.decision("no flag: learning target");
+            return Ok(());
+        }
+
+        //standardize against the target, the deviation floored so near constant warmups do not
+        //turn the slightest change into a shift
+        let std_dev = (state.m2 / (state.count - 1) as f64).sqrt().max(state.mean.abs() * MINIMUM_RELATIVE_STD_DEV);
+        if std_dev == 0.0 {
+            trace.decision("no flag: target has no deviation");
+            return Ok(());
+        }
+
+        let z = (value - state.mean) / std_dev;
+
+        state.upper_sum = (state.upper_sum + z - self.slack).max(0.0);
+        state.lower_sum = (state.lower_sum - z - self.slack).max(0.0);
+        trace.statistic("target", state.mean);
+        trace.statistic("upper_sum", state.upper_sum);
+        trace.statistic("lower_sum", state.lower_sum);
+
+        let shift = if state.upper_sum > self.threshold {
+            "increase"
+        } else if state.lower_sum > self.threshold {
+            "decrease"
+        } else {
+            trace.decision("no flag: cumulative deviation within threshold");
+            return Ok(());
+        };
+
+        trace.decision(&format!("flag: sustained {} from target", shift));
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("shift".to_owned(), shift.to_owned());
+        flag.evidence.insert("target".to_owned(), state.mean.to_string());
+        flag.evidence.insert("std_dev".to_owned(), std_dev.to_string());
+        flag.evidence.insert("upper_sum".to_owned(), state.upper_sum.to_string());
+        flag.evidence.insert("lower_sum".to_owned(), state.lower_sum.to_string());
+        self.flag_tx.send(flag);
+
+        //relearn the target at the shifted level so a shift is flagged once
+        *state = Cusum { count: 0, mean: 0.0, m2: 0.0, upper_sum: 0.0, lower_sum: 0.0 };
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.states.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
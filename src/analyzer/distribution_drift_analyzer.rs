@@ -0,0 +1,209 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct Distribution {
+    reference: VecDeque<f64>,
+    recent: VecDeque<f64>,
+    results_since_test: usize,
+}
+
+//flags significant drift between a per key reference distribution and the recent window using a
+//two-sample kolmogorov-smirnov test, catching shape changes which leave the mean stable
+pub struct DistributionDriftAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    reference_size: usize,
+    window_size: usize,
+    test_interval: usize,
+    significance: f64,
+    distributions: HashMap<(String, String), Distribution>,
+    flag_tx: Sender<Flag>,
+}
+
+impl DistributionDriftAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<DistributionDriftAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in DistributionDriftAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in DistributionDriftAnalyzer")),
+        };
+
+        let reference_size = try!(parse_size(parameters, "reference_size", 200, "DistributionDriftAnalyzer"));
+        let window_size = try!(parse_size(parameters, "window_size", 50, "DistributionDriftAnalyzer"));
+        let test_interval = try!(parse_size(parameters, "test_interval", 10, "DistributionDriftAnalyzer"));
+
+        //p-value below which the recent window is considered drawn from a different distribution
+        let significance = match parameters.get("significance") {
+            Some(&Bson::FloatingPoint(significance)) if significance > 0.0 && significance < 1.0 => significance,
+            None => 0.01,
+            _ => return Err(TipupError::from("failed to parse significance parameter in DistributionDriftAnalyzer, expected a value between 0 and 1")),
+        };
+
+        Ok(
+            DistributionDriftAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                reference_size: reference_size,
+                window_size: window_size,
+                test_interval: test_interval,
+                significance: significance,
+                distributions: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for DistributionDriftAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        //values age out of the recent window into the reference distribution
+        let distribution = self.distributions.entry((hostname, domain)).or_insert(Distribution { reference: VecDeque::new(), recent: VecDeque::new(), results_since_test: 0 });
+        distribution.recent.push_back(value);
+        if distribution.recent.len() > self.window_size {
+            distribution.reference.push_back(distribution.recent.pop_front().unwrap());
+            if distribution.reference.len() > self.reference_size {
+                distribution.reference.pop_front();
+            }
+        }
+
+        trace.statistic("value", value);
+        trace.statistic("reference_samples", distribution.reference.len() as i64);
+        trace.statistic("recent_samples", distribution.recent.len() as i64);
+        if distribution.reference.len() < self.reference_size {
+            trace.decision("no flag: insufficient reference samples");
+            return Ok(());
+        }
+
+        distribution.results_since_test += 1;
+        if distribution.results_since_test < self.test_interval {
+            trace.decision("no flag: test not due");
+            return Ok(());
+        }
+
+        distribution.results_since_test = 0;
+        let mut reference: Vec<f64> = distribution.reference.iter().cloned().collect();
+        let mut recent: Vec<f64> = distribution.recent.iter().cloned().collect();
+        reference.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        recent.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let statistic = ks_statistic(&reference, &recent);
+        let p_value = ks_p_value(statistic, reference.len(), recent.len());
+        trace.statistic("ks_statistic", statistic);
+        trace.statistic("p_value", p_value);
+        if p_value >= self.significance {
+            trace.decision("no flag: recent distribution consistent with reference");
+            return Ok(());
+        }
+
+        trace.decision("flag: recent distribution drifted from reference");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("ks_statistic".to_owned(), statistic.to_string());
+        flag.evidence.insert("p_value".to_owned(), p_value.to_string());
+        flag.evidence.insert("reference_median".to_owned(), median(&reference).to_string());
+        flag.evidence.insert("recent_median".to_owned(), median(&recent).to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.distributions.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn ks_statistic(a: &Vec<f64>, b: &Vec<f64>) -> f64 {
+    //maximum distance between the empirical distribution functions of two sorted samples
+    let (mut i, mut j, mut statistic) = (0, 0, 0.0f64);
+    while i < a.len() && j < b.len() {
+        let value = a[i].min(b[j]);
+        while i < a.len() && a[i] <= value {
+            i += 1;
+        }
+
+        while j < b.len() && b[j] <= value {
+            j += 1;
+        }
+
+        statistic = statistic.max(((i as f64 / a.len() as f64) - (j as f64 / b.len() as f64)).abs());
+    }
+
+    statistic
+}
+
+fn ks_p_value(statistic: f64, n: usize, m: usize) -> f64 {
+    //asymptotic kolmogorov distribution with the stephens small sample correction
+    let effective_size = ((n * m) as f64 / (n + m) as f64).sqrt();
+    let lambda = (effective_size + 0.12 + (0.11 / effective_size)) * statistic;
+    if lambda < 0.2 {
+        return 1.0;
+    }
+
+    let mut p_value = 0.0;
+    for j in 1..101 {
+        let term = 2.0 * (if j % 2 == 1 { 1.0 } else { -1.0 }) * (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        p_value += term;
+        if term.abs() < 1e-10 {
+            break;
+        }
+    }
+
+    p_value.max(0.0).min(1.0)
+}
+
+fn median(sorted_values: &Vec<f64>) -> f64 {
+    match sorted_values.len() % 2 {
+        0 => (sorted_values[sorted_values.len() / 2 - 1] + sorted_values[sorted_values.len() / 2]) / 2.0,
+        _ => sorted_values[sorted_values.len() / 2],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ks_p_value, ks_statistic};
+
+    #[test]
+    fn ks_statistic_matches_reference() {
+        let a = vec!(1.0, 2.0, 3.0, 4.0, 5.0);
+        assert_eq!(ks_statistic(&a, &a), 0.0);
+        assert_eq!(ks_statistic(&a, &vec!(3.0, 4.0, 5.0, 6.0, 7.0)), 0.4);
+        assert_eq!(ks_statistic(&a, &vec!(6.0, 7.0, 8.0)), 1.0);
+    }
+
+    #[test]
+    fn ks_p_value_matches_kolmogorov_distribution() {
+        //samples of 50 scale the statistic by 5.142, so these are Q(1.0) and Q(1.3581)
+        assert!((ks_p_value(1.0 / 5.142, 50, 50) - 0.2700).abs() < 1e-4);
+        assert!((ks_p_value(1.3581 / 5.142, 50, 50) - 0.0500).abs() < 1e-4);
+        assert_eq!(ks_p_value(0.0, 50, 50), 1.0);
+    }
+}
@@ -0,0 +1,190 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, HashSet};
+
+//scales the median absolute deviation to the standard deviation of normally distributed values
+const MAD_SCALE: f64 = 0.6745;
+
+struct Observation {
+    value: f64,
+    timestamp: i64,
+}
+
+//compares the value a vantage point measures for a target against the consensus of the latest
+//values other vantage points measured for it, flagging vantage points diverging by a modified
+//z-score of the median absolute deviation, e.g. one vantage point's latency to a target tripling
+pub struct DivergenceAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    min_vantage_points: usize,
+    max_age: i64,
+    max_score: f64,
+    min_difference: f64,
+    targets: HashMap<String, HashMap<String, Observation>>,
+    flagged: HashSet<(String, String)>,
+    flag_tx: Sender<Flag>,
+}
+
+impl DivergenceAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<DivergenceAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable_name as String in DivergenceAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable_name parameter in DivergenceAnalyzer")),
+        };
+
+        //vantage points, including the diverging one, required to form a consensus
+        let min_vantage_points = match parameters.get("min_vantage_points") {
+            Some(&Bson::I32(min_vantage_points)) if min_vantage_points >= 3 => min_vantage_points as usize,
+            None => 3,
+            _ => return Err(TipupError::from("failed to parse min_vantage_points parameter in DivergenceAnalyzer, expected an integer of at least 3")),
+        };
+
+        //seconds before a result within which other vantage points' values join its consensus
+        let max_age = match parameters.get("max_age") {
+            Some(&Bson::I32(max_age)) if max_age > 0 => max_age as i64,
+            Some(&Bson::I64(max_age)) if max_age > 0 => max_age,
+            None => 3600,
+            _ => return Err(TipupError::from("failed to parse max_age parameter in DivergenceAnalyzer as positive integer")),
+        };
+
+        let max_score = match parameters.get("max_score") {
+            Some(&Bson::FloatingPoint(max_score)) if max_score > 0.0 => max_score,
+            Some(&Bson::I32(max_score)) if max_score > 0 => max_score as f64,
+            None => 3.5,
+            _ => return Err(TipupError::from("failed to parse max_score parameter in DivergenceAnalyzer as positive number")),
+        };
+
+        //absolute difference from the consensus below which values never diverge, so tightly
+        //agreeing vantage points do not flag negligible differences
+        let min_difference = match parameters.get("min_difference") {
+            Some(&Bson::FloatingPoint(min_difference)) if min_difference >= 0.0 => min_difference,
+            Some(&Bson::I32(min_difference)) if min_difference >= 0 => min_difference as f64,
+            None => 0.0,
+            _ => return Err(TipupError::from("failed to parse min_difference parameter in DivergenceAnalyzer as non-negative number")),
+        };
+
+        Ok(
+            DivergenceAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                min_vantage_points: min_vantage_points,
+                max_age: max_age,
+                max_score: max_score,
+                min_difference: min_difference,
+                targets: HashMap::new(),
+                flagged: HashSet::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for DivergenceAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) => value,
+            None => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("value", value);
+        let max_age = self.max_age;
+        let vantage_points = self.targets.entry(domain.clone()).or_insert(HashMap::new());
+        vantage_points.insert(hostname.clone(), Observation { value: value, timestamp: timestamp });
+
+        //latest values of other vantage points measured near this result, whose results may be
+        //fetched before or after it
+        let mut others: Vec<f64> = vantage_points.iter()
+            .filter(|&(x, y)| x != &hostname && (timestamp - y.timestamp).abs() <= max_age)
+            .map(|(_, y)| y.value)
+            .collect();
+
+        trace.statistic("vantage_points", (others.len() + 1) as i64);
+        if others.len() + 1 < self.min_vantage_points {
+            trace.decision("no flag: too few recent vantage points for a consensus");
+            return Ok(());
+        }
+
+        let consensus = median(&mut others);
+        let mut deviations: Vec<f64> = others.iter().map(|x| (x - consensus).abs()).collect();
+        let mad = median(&mut deviations);
+        let difference = value - consensus;
+        let score = if mad > 0.0 {
+            MAD_SCALE * difference / mad
+        } else if difference.abs() > self.min_difference {
+            difference.signum() * ::std::f64::INFINITY
+        } else {
+            0.0
+        };
+
+        trace.statistic("consensus", consensus);
+        trace.statistic("score", score);
+
+        let key = (hostname, domain);
+        if score.abs() <= self.max_score || difference.abs() <= self.min_difference {
+            self.flagged.remove(&key);
+            trace.decision("no flag: vantage point agrees with consensus");
+            return Ok(());
+        }
+
+        if !self.flagged.insert(key) {
+            trace.decision("no flag: divergence already flagged");
+            return Ok(());
+        }
+
+        trace.decision("flag: vantage point diverges from consensus");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("consensus".to_owned(), consensus.to_string());
+        flag.evidence.insert("score".to_owned(), score.to_string());
+        flag.evidence.insert("max_score".to_owned(), self.max_score.to_string());
+        flag.evidence.insert("vantage_points".to_owned(), (others.len() + 1).to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        if let Some(vantage_points) = self.targets.get_mut(domain) {
+            vantage_points.remove(hostname);
+        }
+
+        self.flagged.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+
+    fn routes_by_target(&self) -> bool {
+        true
+    }
+}
+
+fn median(values: &mut Vec<f64>) -> f64 {
+    values.sort_by(|x, y| x.partial_cmp(y).unwrap_or(::std::cmp::Ordering::Equal));
+    match values.len() % 2 {
+        0 => (values[values.len() / 2 - 1] + values[values.len() / 2]) / 2.0,
+        _ => values[values.len() / 2],
+    }
+}
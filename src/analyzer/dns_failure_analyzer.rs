@@ -0,0 +1,218 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_bson, parse_field, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+//failure kinds reported in flag evidence, in addition to 'other'
+const FAILURE_KINDS: [&'static str; 4] = ["nxdomain", "servfail", "refused", "timeout"];
+
+struct FailureWindow {
+    failures: VecDeque<Option<&'static str>>,
+    baseline_rate: Option<f64>,
+    flagged: bool,
+}
+
+//classifies dns results as nxdomain, servfail, refused, timeout, or other failures and flags when
+//the failure rate over a window spikes above both a maximum and a multiple of its long-run
+//baseline, per domain or per resolver of each vantage point, with the failure breakdown
+pub struct DnsFailureAnalyzer {
+    name: String,
+    status: String,
+    rcode_field: Vec<String>,
+    error_field: Vec<String>,
+    resolver_field: Vec<String>,
+    group_by: String,
+    window_size: usize,
+    baseline_size: usize,
+    max_failure_rate: f64,
+    spike_ratio: f64,
+    windows: HashMap<(String, String), FailureWindow>,
+    flag_tx: Sender<Flag>,
+}
+
+impl DnsFailureAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<DnsFailureAnalyzer, TipupError> {
+        let rcode_field = try!(parse_field(parameters, "rcode_field", &["rcode"], "DnsFailureAnalyzer"));
+        let error_field = try!(parse_field(parameters, "error_field", &["error"], "DnsFailureAnalyzer"));
+        let resolver_field = try!(parse_field(parameters, "resolver_field", &["resolver"], "DnsFailureAnalyzer"));
+
+        let group_by = match parameters.get("group_by") {
+            Some(&Bson::String(ref group_by)) if group_by == "domain" || group_by == "resolver" => group_by.to_owned(),
+            None => "domain".to_owned(),
+            _ => return Err(TipupError::from("failed to parse group_by parameter in DnsFailureAnalyzer, expected 'domain' or 'resolver'")),
+        };
+
+        //number of recent results the failure rate is computed over
+        let window_size = try!(parse_size(parameters, "window_size", 50, "DnsFailureAnalyzer"));
+        //number of results the baseline failure rate is averaged over
+        let baseline_size = try!(parse_size(parameters, "baseline_size", 1000, "DnsFailureAnalyzer"));
+
+        let max_failure_rate = match parameters.get("max_failure_rate") {
+            Some(&Bson::FloatingPoint(max_failure_rate)) if max_failure_rate >= 0.0 && max_failure_rate < 1.0 => max_failure_rate,
+            None => 0.1,
+            _ => return Err(TipupError::from("failed to parse max_failure_rate parameter in DnsFailureAnalyzer, expected a value in [0, 1)")),
+        };
+
+        //multiple of the baseline failure rate a spike must exceed, so persistently failing
+        //targets are not flagged continuously
+        let spike_ratio = match parameters.get("spike_ratio") {
+            Some(&Bson::FloatingPoint(spike_ratio)) if spike_ratio >= 1.0 => spike_ratio,
+            Some(&Bson::I32(spike_ratio)) if spike_ratio >= 1 => spike_ratio as f64,
+            None => 3.0,
+            _ => return Err(TipupError::from("failed to parse spike_ratio parameter in DnsFailureAnalyzer, expected a value of at least 1")),
+        };
+
+        if baseline_size < window_size {
+            return Err(TipupError::from("DnsFailureAnalyzer requires baseline_size no less than window_size"));
+        }
+
+        Ok(
+            DnsFailureAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                rcode_field: rcode_field,
+                error_field: error_field,
+                resolver_field: resolver_field,
+                group_by: group_by,
+                window_size: window_size,
+                baseline_size: baseline_size,
+                max_failure_rate: max_failure_rate,
+                spike_ratio: spike_ratio,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for DnsFailureAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let group = match self.group_by.as_str() {
+            "resolver" => match get_string(&self.resolver_field, document) {
+                Some(resolver) => resolver,
+                None => {
+                    trace.decision("skipped: resolver not present");
+                    return Ok(());
+                },
+            },
+            _ => domain,
+        };
+
+        let failure = match classify(get_bson(&self.rcode_field, document), get_string(&self.error_field, document)) {
+            Ok(failure) => failure,
+            Err(()) => {
+                trace.decision("skipped: neither rcode nor error present");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("failure", failure.unwrap_or("none"));
+        let window = self.windows.entry((hostname, group.clone())).or_insert(
+            FailureWindow {
+                failures: VecDeque::new(),
+                baseline_rate: None,
+                flagged: false,
+            }
+        );
+
+        window.failures.push_back(failure);
+        if window.failures.len() > self.window_size {
+            window.failures.pop_front();
+        }
+
+        let failure_count = window.failures.iter().filter(|x| x.is_some()).count();
+        let failure_rate = failure_count as f64 / window.failures.len() as f64;
+        trace.statistic("failure_rate", failure_rate);
+        if window.failures.len() < self.window_size {
+            trace.decision("no flag: filling window");
+            return Ok(());
+        }
+
+        //the baseline learns from full windows, excluding spikes once flagged
+        let baseline_rate = window.baseline_rate.unwrap_or(failure_rate);
+        trace.statistic("baseline_rate", baseline_rate);
+        if failure_rate <= self.max_failure_rate || (window.baseline_rate.is_some() && failure_rate <= baseline_rate * self.spike_ratio) {
+            let learning_rate = 1.0 / self.baseline_size as f64;
+            window.baseline_rate = Some(baseline_rate + (learning_rate * (failure_rate - baseline_rate)));
+            window.flagged = false;
+            trace.decision("no flag: failure rate within maximum or baseline");
+            return Ok(());
+        }
+
+        if window.flagged {
+            trace.decision("no flag: failure spike already flagged");
+            return Ok(());
+        }
+
+        let mut breakdown = BTreeMap::new();
+        for failure in window.failures.iter().filter_map(|x| *x) {
+            *breakdown.entry(failure).or_insert(0) += 1;
+        }
+
+        window.flagged = true;
+        trace.decision("flag: failure rate spiked");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("failure_rate".to_owned(), failure_rate.to_string());
+        flag.evidence.insert("baseline_rate".to_owned(), baseline_rate.to_string());
+        flag.evidence.insert("max_failure_rate".to_owned(), self.max_failure_rate.to_string());
+        flag.evidence.insert("window_size".to_owned(), self.window_size.to_string());
+        flag.evidence.insert(self.group_by.to_owned(), group);
+        for kind in FAILURE_KINDS.iter().chain(["other"].iter()) {
+            flag.evidence.insert(format!("{}_count", kind), breakdown.get(kind).cloned().unwrap_or(0).to_string());
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        match self.group_by.as_str() {
+            "resolver" => self.windows.retain(|&(ref x, _), _| x != hostname),
+            _ => { self.windows.remove(&(hostname.to_owned(), domain.to_owned())); },
+        }
+    }
+}
+
+//the failure kind of a result, none if it resolved, or an error if it reports neither an rcode
+//nor an error
+fn classify(rcode: Option<&Bson>, error: Option<String>) -> Result<Option<&'static str>, ()> {
+    let rcode = match rcode {
+        Some(&Bson::I32(rcode)) => Some(rcode.to_string()),
+        Some(&Bson::I64(rcode)) => Some(rcode.to_string()),
+        Some(&Bson::String(ref rcode)) => Some(rcode.to_uppercase()),
+        _ => None,
+    };
+
+    match (rcode.as_ref().map(|x| x.as_str()), error) {
+        (Some("0"), _) | (Some("NOERROR"), _) => Ok(None),
+        (Some("2"), _) | (Some("SERVFAIL"), _) => Ok(Some("servfail")),
+        (Some("3"), _) | (Some("NXDOMAIN"), _) => Ok(Some("nxdomain")),
+        (Some("5"), _) | (Some("REFUSED"), _) => Ok(Some("refused")),
+        (Some(_), _) => Ok(Some("other")),
+        (None, Some(error)) => {
+            let error = error.to_lowercase();
+            match error.contains("timeout") || error.contains("timed out") {
+                true => Ok(Some("timeout")),
+                false => Ok(Some("other")),
+            }
+        },
+        (None, None) => Err(()),
+    }
+}
+
+fn get_string(field: &Vec<String>, document: &OrderedDocument) -> Option<String> {
+    match get_bson(field, document) {
+        Some(&Bson::String(ref value)) => Some(value.to_owned()),
+        _ => None,
+    }
+}
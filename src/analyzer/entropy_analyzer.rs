@@ -0,0 +1,204 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_category, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct Window {
+    values: VecDeque<String>,
+    counts: HashMap<String, usize>,
+}
+
+impl Window {
+    fn new() -> Window {
+        Window {
+            values: VecDeque::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, value: String) {
+        *self.counts.entry(value.clone()).or_insert(0) += 1;
+        self.values.push_back(value);
+    }
+
+    fn pop(&mut self) -> Option<String> {
+        let value = match self.values.pop_front() {
+            Some(value) => value,
+            None => return None,
+        };
+
+        let remove = match self.counts.get_mut(&value) {
+            Some(count) => {
+                *count -= 1;
+                *count == 0
+            },
+            None => false,
+        };
+
+        if remove {
+            self.counts.remove(&value);
+        }
+
+        Some(value)
+    }
+
+    //shannon entropy in bits
+    fn entropy(&self) -> f64 {
+        let total = self.values.len() as f64;
+        self.counts.values().map(|x| *x as f64 / total).map(|x| -x * x.log2()).sum()
+    }
+}
+
+struct History {
+    reference: Window,
+    recent: Window,
+}
+
+//tracks the entropy of a categorical field, e.g. error strings, resolved addresses, or server
+//headers, flagging when the recent window collapses onto few values or explodes across many
+//compared to the reference window, as when a resolver is hijacked or a cdn fails over
+pub struct EntropyAnalyzer {
+    name: String,
+    status: String,
+    field: Vec<String>,
+    missing_value: Option<String>,
+    reference_size: usize,
+    window_size: usize,
+    max_decrease: f64,
+    max_increase: f64,
+    histories: HashMap<(String, String), History>,
+    flag_tx: Sender<Flag>,
+}
+
+impl EntropyAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<EntropyAnalyzer, TipupError> {
+        let field = match parameters.get("field") {
+            Some(&Bson::Array(ref param_field)) => {
+                let mut field = Vec::new();
+                for x in param_field {
+                    match x {
+                        &Bson::String(ref y) => field.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse field as String in EntropyAnalyzer")),
+                    }
+                }
+
+                field
+            },
+            _ => return Err(TipupError::from("failed to parse field parameter in EntropyAnalyzer")),
+        };
+
+        //category of results missing the field, e.g. 'none' for error fields only set on failure,
+        //otherwise such results are skipped
+        let missing_value = match parameters.get("missing_value") {
+            Some(&Bson::String(ref missing_value)) => Some(missing_value.to_owned()),
+            None => None,
+            _ => return Err(TipupError::from("failed to parse missing_value parameter in EntropyAnalyzer as String")),
+        };
+
+        let reference_size = try!(parse_size(parameters, "reference_size", 500, "EntropyAnalyzer"));
+        let window_size = try!(parse_size(parameters, "window_size", 50, "EntropyAnalyzer"));
+
+        //change in bits of entropy, 1 bit being e.g. a halving or doubling of equally likely values
+        let max_decrease = try!(parse_bits(parameters, "max_decrease", 1.0));
+        let max_increase = try!(parse_bits(parameters, "max_increase", 1.0));
+
+        Ok(
+            EntropyAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                field: field,
+                missing_value: missing_value,
+                reference_size: reference_size,
+                window_size: window_size,
+                max_decrease: max_decrease,
+                max_increase: max_increase,
+                histories: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for EntropyAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match (get_category(&self.field, document), &self.missing_value) {
+            (Some(value), _) => value,
+            (None, &Some(ref missing_value)) => missing_value.to_owned(),
+            (None, &None) => {
+                trace.decision("skipped: field not present");
+                return Ok(());
+            },
+        };
+
+        //values age out of the recent window into the reference window
+        trace.statistic("value", value.clone());
+        let history = self.histories.entry((hostname, domain)).or_insert(History { reference: Window::new(), recent: Window::new() });
+        history.recent.push(value);
+        if history.recent.values.len() > self.window_size {
+            let value = history.recent.pop().unwrap();
+            history.reference.push(value);
+            if history.reference.values.len() > self.reference_size {
+                history.reference.pop();
+            }
+        }
+
+        trace.statistic("reference_samples", history.reference.values.len() as i64);
+        if history.reference.values.len() < self.reference_size {
+            trace.decision("no flag: insufficient reference samples");
+            return Ok(());
+        }
+
+        let (reference_entropy, recent_entropy) = (history.reference.entropy(), history.recent.entropy());
+        trace.statistic("reference_entropy", reference_entropy);
+        trace.statistic("recent_entropy", recent_entropy);
+
+        let reason = if reference_entropy - recent_entropy > self.max_decrease {
+            "entropy_collapse"
+        } else if recent_entropy - reference_entropy > self.max_increase {
+            "entropy_explosion"
+        } else {
+            trace.decision("no flag: entropy change within threshold");
+            return Ok(());
+        };
+
+        trace.decision(&format!("flag: {}", reason.replace('_', " ")));
+
+        //the most frequent recent values show what the field collapsed onto or spread across
+        let mut recent_values: Vec<(&String, &usize)> = history.recent.counts.iter().collect();
+        recent_values.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let top_values: Vec<String> = recent_values.iter().take(5).map(|&(value, count)| format!("{} ({})", value, count)).collect();
+
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("reason".to_owned(), reason.to_owned());
+        flag.evidence.insert("reference_entropy".to_owned(), reference_entropy.to_string());
+        flag.evidence.insert("recent_entropy".to_owned(), recent_entropy.to_string());
+        flag.evidence.insert("reference_distinct_values".to_owned(), history.reference.counts.len().to_string());
+        flag.evidence.insert("recent_distinct_values".to_owned(), history.recent.counts.len().to_string());
+        flag.evidence.insert("recent_values".to_owned(), top_values.join(", "));
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.histories.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn parse_bits(parameters: &OrderedDocument, key: &str, default: f64) -> Result<f64, TipupError> {
+    match parameters.get(key) {
+        Some(&Bson::FloatingPoint(bits)) if bits > 0.0 => Ok(bits),
+        None => Ok(default),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in EntropyAnalyzer as positive bits", key))),
+    }
+}
@@ -2,7 +2,7 @@ use bson::Bson;
 use bson::ordered::OrderedDocument;
 use chan::Sender;
 
-use analyzer::Analyzer;
+use analyzer::{Analyzer, Trace};
 use error::TipupError;
 use flag_manager::Flag;
 
@@ -15,6 +15,11 @@ pub struct ErrorAnalyzer {
 
 impl ErrorAnalyzer {
     pub fn new(name: &str, status: &str, fields: Vec<String>, flag_tx: Sender<Flag>) -> Result<ErrorAnalyzer, TipupError> {
+        let fields = match fields.len() {
+            0 => vec!("measurement_error_message".to_owned()),
+            _ => fields,
+        };
+
         Ok(
             ErrorAnalyzer {
                 name: name.to_owned(),
@@ -27,16 +32,19 @@ impl ErrorAnalyzer {
 }
 
 impl Analyzer for ErrorAnalyzer {
-    fn process_measurement(&mut self, document: &OrderedDocument) -> Result<(), TipupError> {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
         //check if fields exist
         for field in self.fields.iter() {
             if document.contains_key(field) {
+                trace.statistic("field", field.to_owned());
+                trace.decision("flag: error field present");
                 let flag = try!(Flag::new(document, &self.status, &self.name));
                 self.flag_tx.send(flag);
-                break;
+                return Ok(());
             }
         }
 
+        trace.decision("no flag: no error fields present");
         Ok(())
     }
 }
@@ -0,0 +1,224 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+//flags results which the generalized extreme studentized deviate test finds to be outliers among a
+//short window of recent results, suiting measurements too infrequent for streaming baselines
+pub struct EsdAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    window_size: usize,
+    minimum_samples: usize,
+    max_outliers: usize,
+    alpha: f64,
+    windows: HashMap<(String, String), VecDeque<f64>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl EsdAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<EsdAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in EsdAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in EsdAnalyzer")),
+        };
+
+        let window_size = try!(parse_size(parameters, "window_size", 24, "EsdAnalyzer"));
+        let minimum_samples = try!(parse_size(parameters, "minimum_samples", 8, "EsdAnalyzer"));
+        let max_outliers = try!(parse_size(parameters, "max_outliers", 3, "EsdAnalyzer"));
+
+        let alpha = match parameters.get("alpha") {
+            Some(&Bson::FloatingPoint(alpha)) if alpha > 0.0 && alpha < 1.0 => alpha,
+            None => 0.05,
+            _ => return Err(TipupError::from("failed to parse alpha parameter in EsdAnalyzer, expected a value between 0 and 1")),
+        };
+
+        //each test iteration requires at least three remaining samples
+        if minimum_samples > window_size || max_outliers + 3 > minimum_samples {
+            return Err(TipupError::from("EsdAnalyzer requires minimum_samples no greater than window_size and at least max_outliers plus 3"));
+        }
+
+        Ok(
+            EsdAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                window_size: window_size,
+                minimum_samples: minimum_samples,
+                max_outliers: max_outliers,
+                alpha: alpha,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for EsdAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let window = self.windows.entry((hostname, domain)).or_insert(VecDeque::new());
+        window.push_back(value);
+        if window.len() > self.window_size {
+            window.pop_front();
+        }
+
+        trace.statistic("value", value);
+        trace.statistic("samples", window.len() as i64);
+        if window.len() < self.minimum_samples {
+            trace.decision("no flag: insufficient samples");
+            return Ok(());
+        }
+
+        //the newest result is the last value of the window
+        let values: Vec<f64> = window.iter().cloned().collect();
+        let outliers = generalized_esd(&values, self.max_outliers, self.alpha);
+        trace.statistic("outliers", outliers.len() as i64);
+        let outlier = match outliers.iter().find(|x| x.0 == values.len() - 1) {
+            Some(outlier) => outlier,
+            None => {
+                trace.decision("no flag: value is not an outlier of the window");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("test_statistic", outlier.1);
+        trace.statistic("critical_value", outlier.2);
+        trace.decision("flag: value is an outlier of the window");
+
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("window_mean".to_owned(), mean.to_string());
+        flag.evidence.insert("test_statistic".to_owned(), outlier.1.to_string());
+        flag.evidence.insert("critical_value".to_owned(), outlier.2.to_string());
+        flag.evidence.insert("outliers".to_owned(), outliers.len().to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.windows.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn generalized_esd(values: &Vec<f64>, max_outliers: usize, alpha: f64) -> Vec<(usize, f64, f64)> {
+    //iteratively remove the most extreme value, the outliers being those removed up to the last
+    //iteration whose test statistic exceeded its critical value
+    let n = values.len();
+    let mut remaining: Vec<(usize, f64)> = values.iter().cloned().enumerate().collect();
+    let (mut removed, mut outlier_count) = (Vec::new(), 0);
+    for i in 1..(max_outliers + 1) {
+        let count = remaining.len() as f64;
+        let mean = remaining.iter().map(|x| x.1).sum::<f64>() / count;
+        let std_dev = (remaining.iter().map(|x| (x.1 - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt();
+        if std_dev == 0.0 {
+            break;
+        }
+
+        let (position, deviation) = remaining.iter().enumerate().map(|(j, x)| (j, (x.1 - mean).abs()))
+            .fold((0, -1.0), |maximum, x| if x.1 > maximum.1 { x } else { maximum });
+        let test_statistic = deviation / std_dev;
+
+        let degrees_of_freedom = (n - i - 1) as f64;
+        let t = t_quantile(1.0 - (alpha / (2.0 * (n - i + 1) as f64)), degrees_of_freedom);
+        let critical_value = ((n - i) as f64 * t) / ((degrees_of_freedom + t.powi(2)) * (n - i + 1) as f64).sqrt();
+
+        let (index, _) = remaining.remove(position);
+        removed.push((index, test_statistic, critical_value));
+        if test_statistic > critical_value {
+            outlier_count = i;
+        }
+    }
+
+    removed.truncate(outlier_count);
+    removed
+}
+
+fn t_quantile(p: f64, degrees_of_freedom: f64) -> f64 {
+    //cornish-fisher expansion of the student's t quantile about the normal quantile
+    let z = normal_quantile(p);
+    let (z3, z5, z7, z9) = (z.powi(3), z.powi(5), z.powi(7), z.powi(9));
+    let g1 = (z3 + z) / 4.0;
+    let g2 = ((5.0 * z5) + (16.0 * z3) + (3.0 * z)) / 96.0;
+    let g3 = ((3.0 * z7) + (19.0 * z5) + (17.0 * z3) - (15.0 * z)) / 384.0;
+    let g4 = ((79.0 * z9) + (776.0 * z7) + (1482.0 * z5) - (1920.0 * z3) - (945.0 * z)) / 92160.0;
+    z + (g1 / degrees_of_freedom) + (g2 / degrees_of_freedom.powi(2)) + (g3 / degrees_of_freedom.powi(3)) + (g4 / degrees_of_freedom.powi(4))
+}
+
+fn normal_quantile(p: f64) -> f64 {
+    //acklam's rational approximation of the inverse standard normal distribution
+    let a = [-3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02, 1.383577518672690e+02, -3.066479806614716e+01, 2.506628277459239e+00];
+    let b = [-5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02, 6.680131188771972e+01, -1.328068155288572e+01];
+    let c = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00, -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00];
+    let d = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00, 3.754408661907416e+00];
+
+    let tail = |q: f64| (((((c[0] * q + c[1]) * q + c[2]) * q + c[3]) * q + c[4]) * q + c[5]) / ((((d[0] * q + d[1]) * q + d[2]) * q + d[3]) * q + 1.0);
+    match p {
+        p if p < 0.02425 => tail((-2.0 * p.ln()).sqrt()),
+        p if p > 1.0 - 0.02425 => -tail((-2.0 * (1.0 - p).ln()).sqrt()),
+        p => {
+            let q = p - 0.5;
+            let r = q * q;
+            (((((a[0] * r + a[1]) * r + a[2]) * r + a[3]) * r + a[4]) * r + a[5]) * q / (((((b[0] * r + b[1]) * r + b[2]) * r + b[3]) * r + b[4]) * r + 1.0)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generalized_esd, t_quantile};
+
+    //rosner's example from the nist engineering statistics handbook
+    const ROSNER: [f64; 54] = [-0.25, 0.68, 0.94, 1.15, 1.20, 1.26, 1.26, 1.34, 1.38, 1.43, 1.49, 1.49, 1.55, 1.56,
+        1.58, 1.65, 1.69, 1.70, 1.76, 1.77, 1.81, 1.91, 1.94, 1.96, 1.99, 2.06, 2.09, 2.10, 2.14, 2.15, 2.23, 2.24,
+        2.26, 2.35, 2.37, 2.40, 2.47, 2.54, 2.62, 2.64, 2.90, 2.92, 2.92, 2.93, 3.21, 3.26, 3.30, 3.59, 3.68, 4.30,
+        4.64, 5.34, 5.42, 6.01];
+
+    #[test]
+    fn t_quantile_matches_reference() {
+        assert!((t_quantile(0.975, 10.0) - 2.2281).abs() < 1e-3);
+        assert!((t_quantile(0.995, 30.0) - 2.7500).abs() < 1e-3);
+    }
+
+    #[test]
+    fn generalized_esd_finds_rosner_outliers() {
+        let outliers = generalized_esd(&ROSNER.to_vec(), 10, 0.05);
+        let indices: Vec<usize> = outliers.iter().map(|x| x.0).collect();
+        assert_eq!(indices, vec!(53, 52, 51));
+
+        //test statistics and critical values of the handbook's first three iterations
+        for (outlier, &(statistic, critical_value)) in outliers.iter().zip([(3.118, 3.158), (2.942, 3.151), (3.179, 3.143)].iter()) {
+            assert!((outlier.1 - statistic).abs() < 2e-3);
+            assert!((outlier.2 - critical_value).abs() < 2e-3);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+struct Ewma {
+    mean: f64,
+    variance: f64,
+    samples: usize,
+}
+
+//flags results outside deviation bands around an exponentially weighted moving average, keeping
+//only the weighted mean and variance per key rather than a window of results
+pub struct EwmaAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    alpha: f64,
+    bands: f64,
+    minimum_samples: usize,
+    averages: HashMap<(String, String), Ewma>,
+    flag_tx: Sender<Flag>,
+}
+
+impl EwmaAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<EwmaAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in EwmaAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in EwmaAnalyzer")),
+        };
+
+        //weight of the newest result, higher values track changes faster
+        let alpha = match parameters.get("alpha") {
+            Some(&Bson::FloatingPoint(alpha)) if alpha > 0.0 && alpha < 1.0 => alpha,
+            None => 0.1,
+            _ => return Err(TipupError::from("failed to parse alpha parameter in EwmaAnalyzer, expected a value between 0 and 1")),
+        };
+
+        //width of the deviation bands in weighted standard deviations
+        let bands = match parameters.get("bands") {
+            Some(&Bson::FloatingPoint(bands)) if bands > 0.0 => bands,
+            Some(&Bson::I32(bands)) if bands > 0 => bands as f64,
+            None => 3.0,
+            _ => return Err(TipupError::from("failed to parse bands parameter in EwmaAnalyzer as positive number")),
+        };
+
+        let minimum_samples = match parameters.get("minimum_samples") {
+            Some(&Bson::I32(minimum_samples)) if minimum_samples > 0 => minimum_samples as usize,
+            None => 10,
+            _ => return Err(TipupError::from("failed to parse minimum_samples parameter in EwmaAnalyzer as positive integer")),
+        };
+
+        Ok(
+            EwmaAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                alpha: alpha,
+                bands: bands,
+                minimum_samples: minimum_samples,
+                averages: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for EwmaAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let ewma = match self.averages.get_mut(&(hostname.clone(), domain.clone())) {
+            Some(ewma) => ewma,
+            None => {
+                self.averages.insert((hostname, domain), Ewma { mean: value, variance: 0.0, samples: 1 });
+                trace.decision("no flag: insufficient samples");
+                return Ok(());
+            },
+        };
+
+        //test against the bands before the value updates them
+        let (mean, std_dev) = (ewma.mean, ewma.variance.sqrt());
+        let deviation = value - mean;
+        let samples = ewma.samples;
+        ewma.mean += self.alpha * deviation;
+        ewma.variance = (1.0 - self.alpha) * (ewma.variance + (self.alpha * deviation * deviation));
+        ewma.samples += 1;
+
+        trace.statistic("value", value);
+        trace.statistic("ewma", mean);
+        trace.statistic("std_dev", std_dev);
+        if samples < self.minimum_samples {
+            trace.decision("no flag: insufficient samples");
+            return Ok(());
+        }
+
+        if deviation.abs() <= self.bands * std_dev {
+            trace.decision("no flag: value within deviation bands");
+            return Ok(());
+        }
+
+        trace.decision("flag: value outside deviation bands");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("ewma".to_owned(), mean.to_string());
+        flag.evidence.insert("std_dev".to_owned(), std_dev.to_string());
+        flag.evidence.insert("lower_band".to_owned(), (mean - (self.bands * std_dev)).to_string());
+        flag.evidence.insert("upper_band".to_owned(), (mean + (self.bands * std_dev)).to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.averages.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
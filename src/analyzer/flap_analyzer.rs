@@ -0,0 +1,156 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct FlapState {
+    errors: VecDeque<bool>,
+    flapping: bool,
+}
+
+//nagios style flap detection over the ok/error state of recent results, weighting state changes
+//from 0.8 for the oldest to 1.2 for the newest so recent oscillation counts more, and flagging
+//once when a key starts flapping rather than on each alternation
+pub struct FlapAnalyzer {
+    name: String,
+    status: String,
+    fields: Vec<String>,
+    window_size: usize,
+    high_threshold: f64,
+    low_threshold: f64,
+    states: HashMap<(String, String), FlapState>,
+    flag_tx: Sender<Flag>,
+}
+
+impl FlapAnalyzer {
+    pub fn new(name: &str, status: &str, fields: Vec<String>, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<FlapAnalyzer, TipupError> {
+        //results carrying any of the fields are errors
+        let fields = match fields.len() {
+            0 => vec!("measurement_error_message".to_owned()),
+            _ => fields,
+        };
+
+        //most recent results whose state changes are counted, 21 results holding 20 changes
+        let window_size = match parameters.get("window_size") {
+            Some(&Bson::I32(window_size)) if window_size > 2 => window_size as usize,
+            None => 21,
+            _ => return Err(TipupError::from("failed to parse window_size parameter in FlapAnalyzer, expected an integer over 2")),
+        };
+
+        //percent state change at which a key starts flapping, and below which it stops
+        let high_threshold = try!(parse_percent(parameters, "high_threshold", 50.0));
+        let low_threshold = try!(parse_percent(parameters, "low_threshold", 25.0));
+        if low_threshold > high_threshold {
+            return Err(TipupError::from("low_threshold parameter in FlapAnalyzer must not exceed high_threshold"));
+        }
+
+        Ok(
+            FlapAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                fields: fields,
+                window_size: window_size,
+                high_threshold: high_threshold,
+                low_threshold: low_threshold,
+                states: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for FlapAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let error = self.fields.iter().any(|x| document.contains_key(x));
+        trace.statistic("error", error);
+
+        let state = self.states.entry((hostname, domain)).or_insert(FlapState { errors: VecDeque::new(), flapping: false });
+        let changed = state.errors.back().map_or(false, |x| *x != error);
+        state.errors.push_back(error);
+        if state.errors.len() > self.window_size {
+            state.errors.pop_front();
+        }
+
+        trace.statistic("history", state.errors.len() as i64);
+        if state.errors.len() < self.window_size {
+            trace.decision("no flag: insufficient history");
+            return Ok(());
+        }
+
+        let (percent_state_change, state_changes) = percent_state_change(&state.errors);
+        trace.statistic("percent_state_change", percent_state_change);
+
+        let was_flapping = state.flapping;
+        state.flapping = match state.flapping {
+            true => percent_state_change >= self.low_threshold,
+            false => percent_state_change > self.high_threshold,
+        };
+
+        //repeat the flag on state changes while flapping so it stays open, repeats merging into it
+        match (was_flapping, state.flapping) {
+            (false, true) => trace.decision("flag: started flapping"),
+            (true, true) if changed => trace.decision("flag: state changed while flapping"),
+            (true, true) => {
+                trace.decision("no flag: flapping without state change");
+                return Ok(());
+            },
+            (true, false) => {
+                trace.decision("no flag: stopped flapping");
+                return Ok(());
+            },
+            (false, false) => {
+                trace.decision("no flag: percent state change below threshold");
+                return Ok(());
+            },
+        }
+
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("reason".to_owned(), "flapping".to_owned());
+        flag.evidence.insert("percent_state_change".to_owned(), percent_state_change.to_string());
+        flag.evidence.insert("state_changes".to_owned(), state_changes.to_string());
+        flag.evidence.insert("errors".to_owned(), state.errors.iter().filter(|x| **x).count().to_string());
+        flag.evidence.insert("window_size".to_owned(), self.window_size.to_string());
+        flag.evidence.insert("high_threshold".to_owned(), self.high_threshold.to_string());
+        flag.evidence.insert("low_threshold".to_owned(), self.low_threshold.to_string());
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.states.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+//weighted percentage of possible state changes which occurred, along with their count
+fn percent_state_change(errors: &VecDeque<bool>) -> (f64, usize) {
+    let transitions = errors.len() - 1;
+    let (mut weighted_changes, mut state_changes) = (0.0, 0);
+    for i in 0..transitions {
+        if errors[i] != errors[i + 1] {
+            weighted_changes += 0.8 + (0.4 * i as f64 / (transitions - 1).max(1) as f64);
+            state_changes += 1;
+        }
+    }
+
+    ((weighted_changes / transitions as f64) * 100.0, state_changes)
+}
+
+fn parse_percent(parameters: &OrderedDocument, key: &str, default: f64) -> Result<f64, TipupError> {
+    match parameters.get(key) {
+        Some(&Bson::FloatingPoint(percent)) if percent > 0.0 && percent <= 100.0 => Ok(percent),
+        Some(&Bson::I32(percent)) if percent > 0 && percent <= 100 => Ok(percent as f64),
+        None => Ok(default),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in FlapAnalyzer, expected a percentage between 0 and 100", key))),
+    }
+}
@@ -0,0 +1,211 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+struct Cidr {
+    network: Vec<u8>,
+    prefix_length: usize,
+}
+
+impl Cidr {
+    fn parse(cidr: &str) -> Result<Cidr, TipupError> {
+        let mut fields = cidr.trim().splitn(2, '/');
+        let address = match fields.next().map(|x| x.parse::<IpAddr>()) {
+            Some(Ok(address)) => address,
+            _ => return Err(TipupError::from(format!("failed to parse address of range '{}'", cidr))),
+        };
+
+        let network = octets(&address);
+        let prefix_length = match fields.next().map(|x| x.parse::<usize>()) {
+            Some(Ok(prefix_length)) if prefix_length <= network.len() * 8 => prefix_length,
+            None => network.len() * 8,
+            _ => return Err(TipupError::from(format!("failed to parse prefix length of range '{}'", cidr))),
+        };
+
+        Ok(
+            Cidr {
+                network: network,
+                prefix_length: prefix_length,
+            }
+        )
+    }
+
+    fn contains(&self, address: &IpAddr) -> bool {
+        let address = octets(address);
+        if address.len() != self.network.len() {
+            return false;
+        }
+
+        (0..self.prefix_length).all(|i| {
+            let mask = 0x80 >> (i % 8);
+            (address[i / 8] & mask) == (self.network[i / 8] & mask)
+        })
+    }
+}
+
+pub struct GeoDnsAnalyzer {
+    name: String,
+    status: String,
+    address_field: Vec<String>,
+    ranges: HashMap<String, Vec<Cidr>>,
+    consistent_vantages: HashMap<String, HashMap<String, bool>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl GeoDnsAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<GeoDnsAnalyzer, TipupError> {
+        let address_field = match parameters.get("address_field") {
+            Some(&Bson::Array(ref address_field)) => {
+                let mut fields = Vec::new();
+                for x in address_field {
+                    match x {
+                        &Bson::String(ref y) => fields.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse address_field as String in GeoDnsAnalyzer")),
+                    }
+                }
+
+                fields
+            },
+            None => vec!("resolved_addresses".to_owned()),
+            _ => return Err(TipupError::from("failed to parse address_field parameter in GeoDnsAnalyzer")),
+        };
+
+        Ok(
+            GeoDnsAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                address_field: address_field,
+                ranges: HashMap::new(),
+                consistent_vantages: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for GeoDnsAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        //only domains with known provider ranges are checked
+        let ranges = match self.ranges.get(&domain) {
+            Some(ranges) => ranges,
+            None => {
+                trace.decision("skipped: no provider ranges for domain");
+                return Ok(());
+            },
+        };
+
+        let addresses = get_addresses(&self.address_field, document);
+        if addresses.len() == 0 {
+            trace.decision("skipped: no resolved addresses");
+            return Ok(());
+        }
+
+        let unknown_addresses: Vec<String> = addresses.iter()
+            .filter(|&&(_, ref address)| !ranges.iter().any(|x| x.contains(address)))
+            .map(|&(ref address, _)| address.to_owned()).collect();
+
+        //track which vantages currently resolve within known ranges to distinguish regional from global issues
+        let vantages = self.consistent_vantages.entry(domain).or_insert(HashMap::new());
+        vantages.insert(hostname.clone(), unknown_addresses.len() == 0);
+        trace.statistic("addresses", addresses.len() as i64);
+        trace.statistic("unknown_addresses", unknown_addresses.join(","));
+        if unknown_addresses.len() == 0 {
+            trace.decision("no flag: all addresses within provider ranges");
+            return Ok(());
+        }
+
+        let consistent_count = vantages.iter().filter(|&(vantage, consistent)| *vantage != hostname && *consistent).count();
+        trace.decision("flag: addresses outside provider ranges");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.labels.insert("unknown_addresses".to_owned(), unknown_addresses.join(","));
+        flag.labels.insert("consistent_vantages".to_owned(), consistent_count.to_string());
+        flag.labels.insert("vantages".to_owned(), vantages.len().to_string());
+        self.flag_tx.send(flag);
+
+        Ok(())
+    }
+
+    fn refresh(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //load known cdn and provider address ranges per domain
+        let mut ranges = HashMap::new();
+        for document in try!(proddle_db.collection("provider_ranges").find(None, None)) {
+            let document = try!(document);
+            let domain = match document.get("domain") {
+                Some(&Bson::String(ref domain)) => domain.to_owned(),
+                _ => return Err(TipupError::from("failed to parse provider range domain")),
+            };
+
+            let domain_ranges = ranges.entry(domain).or_insert(Vec::new());
+            match document.get("ranges") {
+                Some(&Bson::Array(ref cidrs)) => {
+                    for cidr in cidrs {
+                        match cidr {
+                            &Bson::String(ref cidr) => domain_ranges.push(try!(Cidr::parse(cidr))),
+                            _ => return Err(TipupError::from("failed to parse provider range as String")),
+                        }
+                    }
+                },
+                _ => return Err(TipupError::from("failed to parse provider ranges")),
+            }
+        }
+
+        self.ranges = ranges;
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        if let Some(vantages) = self.consistent_vantages.get_mut(domain) {
+            vantages.remove(hostname);
+        }
+    }
+}
+
+fn octets(address: &IpAddr) -> Vec<u8> {
+    match *address {
+        IpAddr::V4(ref address) => address.octets().to_vec(),
+        IpAddr::V6(ref address) => address.octets().to_vec(),
+    }
+}
+
+fn get_addresses(variable_name: &Vec<String>, document: &OrderedDocument) -> Vec<(String, IpAddr)> {
+    //resolved addresses may be recorded as a single string or an array of strings
+    let mut index_document = document;
+    let mut values = Vec::new();
+    for variable in variable_name {
+        match index_document.get(variable) {
+            Some(&Bson::Document(ref document)) => index_document = document,
+            Some(&Bson::String(ref address)) => {
+                values.push(address.to_owned());
+                break;
+            },
+            Some(&Bson::Array(ref addresses)) => {
+                for address in addresses {
+                    if let &Bson::String(ref address) = address {
+                        values.push(address.to_owned());
+                    }
+                }
+
+                break;
+            },
+            _ => break,
+        }
+    }
+
+    values.into_iter().filter_map(|x| match x.parse::<IpAddr>() {
+        Ok(address) => Some((x, address)),
+        Err(_) => None,
+    }).collect()
+}
@@ -0,0 +1,237 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+struct HoltWinters {
+    level: f64,
+    trend: f64,
+    seasonal: Vec<Option<f64>>,
+    residual_variance: f64,
+    first_timestamp: i64,
+}
+
+//additive holt-winters smoothing of a level, trend, and seasonal component per key, flagging
+//results whose residual from the seasonal forecast is large relative to past residuals, so daily
+//and weekly latency cycles are not mistaken for anomalies
+pub struct HoltWintersAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    season_length: i64,
+    season_bins: usize,
+    alpha: f64,
+    beta: f64,
+    gamma: f64,
+    bands: f64,
+    minimum_seasons: i64,
+    states: HashMap<(String, String), HoltWinters>,
+    flag_tx: Sender<Flag>,
+}
+
+impl HoltWintersAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<HoltWintersAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in HoltWintersAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in HoltWintersAnalyzer")),
+        };
+
+        //seconds in a season, e.g. 86400 for daily or 604800 for weekly cycles
+        let season_length = match parameters.get("season_length") {
+            Some(&Bson::I32(season_length)) if season_length > 0 => season_length as i64,
+            Some(&Bson::I64(season_length)) if season_length > 0 => season_length,
+            None => 86400,
+            _ => return Err(TipupError::from("failed to parse season_length parameter in HoltWintersAnalyzer as positive seconds")),
+        };
+
+        //number of seasonal components the season is divided into
+        let season_bins = match parameters.get("season_bins") {
+            Some(&Bson::I32(season_bins)) if season_bins > 0 && season_bins as i64 <= season_length => season_bins as usize,
+            None => 24.min(season_length as usize),
+            _ => return Err(TipupError::from("failed to parse season_bins parameter in HoltWintersAnalyzer, expected a positive integer no greater than season_length")),
+        };
+
+        let alpha = try!(parse_smoothing(parameters, "alpha", 0.1));
+        let beta = try!(parse_smoothing(parameters, "beta", 0.01));
+        let gamma = try!(parse_smoothing(parameters, "gamma", 0.1));
+
+        //width of the residual bands in standard deviations of past residuals
+        let bands = match parameters.get("bands") {
+            Some(&Bson::FloatingPoint(bands)) if bands > 0.0 => bands,
+            Some(&Bson::I32(bands)) if bands > 0 => bands as f64,
+            None => 3.0,
+            _ => return Err(TipupError::from("failed to parse bands parameter in HoltWintersAnalyzer as positive number")),
+        };
+
+        //complete seasons observed before flagging
+        let minimum_seasons = match parameters.get("minimum_seasons") {
+            Some(&Bson::I32(minimum_seasons)) if minimum_seasons > 0 => minimum_seasons as i64,
+            None => 1,
+            _ => return Err(TipupError::from("failed to parse minimum_seasons parameter in HoltWintersAnalyzer as positive integer")),
+        };
+
+        Ok(
+            HoltWintersAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                season_length: season_length,
+                season_bins: season_bins,
+                alpha: alpha,
+                beta: beta,
+                gamma: gamma,
+                bands: bands,
+                minimum_seasons: minimum_seasons,
+                states: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for HoltWintersAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let bin = (timestamp.rem_euclid(self.season_length) * self.season_bins as i64 / self.season_length) as usize;
+        let state = match self.states.get_mut(&(hostname.clone(), domain.clone())) {
+            Some(state) => state,
+            None => {
+                let mut seasonal = vec![None; self.season_bins];
+                seasonal[bin] = Some(0.0);
+                self.states.insert((hostname, domain), HoltWinters { level: value, trend: 0.0, seasonal: seasonal, residual_variance: 0.0, first_timestamp: timestamp });
+                trace.decision("no flag: initializing season");
+                return Ok(());
+            },
+        };
+
+        //the first result of each seasonal component initializes it against the current level
+        let seasonal = match state.seasonal[bin] {
+            Some(seasonal) => seasonal,
+            None => {
+                state.seasonal[bin] = Some(value - state.level);
+                trace.decision("no flag: initializing season");
+                return Ok(());
+            },
+        };
+
+        //test the residual before the value updates the components
+        let forecast = state.level + state.trend + seasonal;
+        let residual = value - forecast;
+        let std_dev = state.residual_variance.sqrt();
+
+        let previous_level = state.level;
+        state.level = (self.alpha * (value - seasonal)) + ((1.0 - self.alpha) * (state.level + state.trend));
+        state.trend = (self.beta * (state.level - previous_level)) + ((1.0 - self.beta) * state.trend);
+        state.seasonal[bin] = Some((self.gamma * (value - state.level)) + ((1.0 - self.gamma) * seasonal));
+        state.residual_variance = (1.0 - self.alpha) * (state.residual_variance + (self.alpha * residual * residual));
+
+        trace.statistic("value", value);
+        trace.statistic("forecast", forecast);
+        trace.statistic("residual", residual);
+        trace.statistic("residual_std_dev", std_dev);
+        if timestamp - state.first_timestamp < self.minimum_seasons * self.season_length {
+            trace.decision("no flag: insufficient seasons");
+            return Ok(());
+        }
+
+        if residual.abs() <= self.bands * std_dev {
+            trace.decision("no flag: residual within bands");
+            return Ok(());
+        }
+
+        trace.decision("flag: residual outside bands");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("forecast".to_owned(), forecast.to_string());
+        flag.evidence.insert("residual".to_owned(), residual.to_string());
+        flag.evidence.insert("residual_std_dev".to_owned(), std_dev.to_string());
+        flag.evidence.insert("season_bin".to_owned(), bin.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.states.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn parse_smoothing(parameters: &OrderedDocument, name: &str, default: f64) -> Result<f64, TipupError> {
+    match parameters.get(name) {
+        Some(&Bson::FloatingPoint(x)) if x > 0.0 && x < 1.0 => Ok(x),
+        None => Ok(default),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in HoltWintersAnalyzer, expected a value between 0 and 1", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chan;
+
+    use analyzer::{Analyzer, Trace};
+
+    use super::HoltWintersAnalyzer;
+
+    #[test]
+    fn forecasts_seasonal_cycle_and_flags_departures() {
+        let (flag_tx, flag_rx) = chan::async();
+        let parameters = doc!("variable_name" => ["latency"], "season_length" => 4, "season_bins" => 4);
+        let mut analyzer = HoltWintersAnalyzer::new("holt_winters", "warning", &parameters, flag_tx).unwrap();
+
+        //a repeating cycle is forecast exactly, the level 10 plus each bin's seasonal offset
+        let cycle = [10.0, 20.0, 30.0, 20.0];
+        for timestamp in 0..14 {
+            let value = match timestamp {
+                13 => 100.0,
+                _ => cycle[timestamp as usize % 4],
+            };
+
+            let document = doc!("_id" => (ObjectId::new().unwrap()), "vantage_hostname" => "vantage", "measurement_domain" => "example.com",
+                "timestamp" => (timestamp as i64), "latency" => value);
+            analyzer.process_measurement(&document, &mut Trace::new(false)).unwrap();
+        }
+
+        //the departure moves the level to 0.1 * (100 - 10) + 0.9 * 10 and its seasonal offset to
+        //0.1 * (100 - 18) + 0.9 * 10
+        {
+            let state = analyzer.states.values().next().unwrap();
+            assert!((state.level - 18.0).abs() < 1e-9);
+            assert!((state.trend - 0.08).abs() < 1e-9);
+            assert!((state.seasonal[1].unwrap() - 17.2).abs() < 1e-9);
+        }
+
+        drop(analyzer);
+        let flags: Vec<_> = flag_rx.iter().collect();
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].timestamp, 13);
+        assert_eq!(flags[0].evidence.get("forecast").map(|x| x.as_str()), Some("20"));
+        assert_eq!(flags[0].evidence.get("residual").map(|x| x.as_str()), Some("80"));
+    }
+}
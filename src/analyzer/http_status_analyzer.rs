@@ -0,0 +1,158 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+//flags http results whose status code is not a success (2xx) or redirect (3xx), and results whose
+//status code changed from the previous result of the target, e.g. 200 to 403
+pub struct HttpStatusAnalyzer {
+    name: String,
+    status: String,
+    status_field: Vec<String>,
+    allowed_classes: Vec<i64>,
+    flag_transitions: bool,
+    transition_granularity: String,
+    previous: HashMap<(String, String), i64>,
+    flag_tx: Sender<Flag>,
+}
+
+impl HttpStatusAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<HttpStatusAnalyzer, TipupError> {
+        let status_field = match parameters.get("status_field") {
+            Some(&Bson::Array(ref param_status_field)) => {
+                let mut status_field = Vec::new();
+                for x in param_status_field {
+                    match x {
+                        &Bson::String(ref y) => status_field.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse status_field as String in HttpStatusAnalyzer")),
+                    }
+                }
+
+                status_field
+            },
+            None => vec!("status_code".to_owned()),
+            _ => return Err(TipupError::from("failed to parse status_field parameter in HttpStatusAnalyzer")),
+        };
+
+        //leading digits of status codes which are not flagged, e.g. [2, 3] for 2xx and 3xx
+        let allowed_classes = match parameters.get("allowed_classes") {
+            Some(&Bson::Array(ref param_allowed_classes)) => {
+                let mut allowed_classes = Vec::new();
+                for x in param_allowed_classes {
+                    match x {
+                        &Bson::I32(class) if class >= 1 && class <= 5 => allowed_classes.push(class as i64),
+                        _ => return Err(TipupError::from("failed to parse allowed_classes in HttpStatusAnalyzer, expected integers from 1 to 5")),
+                    }
+                }
+
+                allowed_classes
+            },
+            None => vec!(2, 3),
+            _ => return Err(TipupError::from("failed to parse allowed_classes parameter in HttpStatusAnalyzer")),
+        };
+
+        let flag_transitions = match parameters.get("flag_transitions") {
+            Some(&Bson::Boolean(flag_transitions)) => flag_transitions,
+            None => true,
+            _ => return Err(TipupError::from("failed to parse flag_transitions parameter in HttpStatusAnalyzer as boolean")),
+        };
+
+        //'class' flags transitions between status classes (200 to 403), 'code' any changed code (200 to 204)
+        let transition_granularity = match parameters.get("transition_granularity") {
+            Some(&Bson::String(ref granularity)) if granularity == "class" || granularity == "code" => granularity.to_owned(),
+            None => "class".to_owned(),
+            _ => return Err(TipupError::from("failed to parse transition_granularity parameter in HttpStatusAnalyzer, expected 'class' or 'code'")),
+        };
+
+        Ok(
+            HttpStatusAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                status_field: status_field,
+                allowed_classes: allowed_classes,
+                flag_transitions: flag_transitions,
+                transition_granularity: transition_granularity,
+                previous: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for HttpStatusAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let status_code = match get_status_code(&self.status_field, document) {
+            Some(status_code) if status_code >= 100 && status_code < 600 => status_code,
+            _ => {
+                trace.decision("skipped: status code not present");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("status_code", status_code);
+        let previous_status_code = self.previous.insert((hostname, domain), status_code);
+        let mut reasons = Vec::new();
+        if !self.allowed_classes.contains(&(status_code / 100)) {
+            reasons.push("error_status");
+        }
+
+        if let Some(previous_status_code) = previous_status_code {
+            trace.statistic("previous_status_code", previous_status_code);
+            let changed = match self.transition_granularity.as_str() {
+                "code" => status_code != previous_status_code,
+                _ => status_code / 100 != previous_status_code / 100,
+            };
+
+            if self.flag_transitions && changed {
+                reasons.push("transition");
+            }
+        }
+
+        if reasons.len() == 0 {
+            trace.decision("no flag: allowed status code without transition");
+            return Ok(());
+        }
+
+        trace.decision(&format!("flag: {}", reasons.join(" and ")));
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("reason".to_owned(), reasons.join(","));
+        flag.evidence.insert("status_code".to_owned(), status_code.to_string());
+        if let Some(previous_status_code) = previous_status_code {
+            flag.evidence.insert("previous_status_code".to_owned(), previous_status_code.to_string());
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.previous.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+//status codes may be stored as integers, whole floats, or strings
+fn get_status_code(variable_name: &Vec<String>, document: &OrderedDocument) -> Option<i64> {
+    let mut index_document = document;
+    for variable in variable_name {
+        match index_document.get(variable) {
+            Some(&Bson::Document(ref document)) => index_document = document,
+            Some(&Bson::I32(i)) => return Some(i as i64),
+            Some(&Bson::I64(i)) => return Some(i),
+            Some(&Bson::FloatingPoint(f)) if f.fract() == 0.0 => return Some(f as i64),
+            Some(&Bson::String(ref s)) => return s.trim().parse::<i64>().ok(),
+            _ => return None,
+        }
+    }
+
+    None
+}
@@ -0,0 +1,313 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+use time;
+
+use analyzer::{get_value, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+//euler-mascheroni constant approximating harmonic numbers
+const EULER_GAMMA: f64 = 0.5772156649;
+
+enum Node {
+    Split {
+        variable: usize,
+        value: f64,
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    Leaf {
+        size: usize,
+    },
+}
+
+struct Forest {
+    trees: Vec<Node>,
+    sample_size: usize,
+}
+
+struct History {
+    points: VecDeque<Vec<f64>>,
+    forest: Option<Forest>,
+    results_since_training: usize,
+}
+
+//isolates results by several variables at once, e.g. latency alongside packet loss, with forests
+//of random partitioning trees trained periodically on recent history, outliers being isolated in
+//fewer partitions than typical results and so scoring closer to 1
+pub struct IsolationForestAnalyzer {
+    name: String,
+    status: String,
+    variable_names: Vec<Vec<String>>,
+    history_size: usize,
+    tree_count: usize,
+    sample_size: usize,
+    retrain_interval: usize,
+    max_score: f64,
+    histories: HashMap<(String, String), History>,
+    random_state: u64,
+    flag_tx: Sender<Flag>,
+}
+
+impl IsolationForestAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<IsolationForestAnalyzer, TipupError> {
+        let variable_names = match parameters.get("variable_names") {
+            Some(&Bson::Array(ref param_variable_names)) if param_variable_names.len() > 0 => {
+                let mut variable_names = Vec::new();
+                for x in param_variable_names {
+                    let mut variable_name = Vec::new();
+                    match x {
+                        &Bson::Array(ref y) => for z in y {
+                            match z {
+                                &Bson::String(ref z) => variable_name.push(z.to_owned()),
+                                _ => return Err(TipupError::from("failed to parse variable name as String in IsolationForestAnalyzer")),
+                            }
+                        },
+                        _ => return Err(TipupError::from("failed to parse variable name as Array in IsolationForestAnalyzer")),
+                    }
+
+                    variable_names.push(variable_name);
+                }
+
+                variable_names
+            },
+            _ => return Err(TipupError::from("failed to parse variable_names parameter in IsolationForestAnalyzer, expected an array of variable names")),
+        };
+
+        //most recent results per key the forest is trained on
+        let history_size = try!(parse_size(parameters, "history_size", 256, "IsolationForestAnalyzer"));
+        let tree_count = try!(parse_size(parameters, "tree_count", 100, "IsolationForestAnalyzer"));
+
+        //results each tree is built from, a subsample isolating anomalies better than all history
+        let sample_size = try!(parse_size(parameters, "sample_size", 64, "IsolationForestAnalyzer"));
+        if sample_size < 2 || sample_size > history_size {
+            return Err(TipupError::from("sample_size parameter in IsolationForestAnalyzer must be at least 2 and not exceed history_size"));
+        }
+
+        //results between retraining the forest on the current history
+        let retrain_interval = try!(parse_size(parameters, "retrain_interval", 100, "IsolationForestAnalyzer"));
+
+        let max_score = match parameters.get("max_score") {
+            Some(&Bson::FloatingPoint(max_score)) if max_score > 0.5 && max_score < 1.0 => max_score,
+            None => 0.7,
+            _ => return Err(TipupError::from("failed to parse max_score parameter in IsolationForestAnalyzer, expected a value between 0.5 and 1")),
+        };
+
+        Ok(
+            IsolationForestAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_names: variable_names,
+                history_size: history_size,
+                tree_count: tree_count,
+                sample_size: sample_size,
+                retrain_interval: retrain_interval,
+                max_score: max_score,
+                histories: HashMap::new(),
+                random_state: time::precise_time_ns() | 1,
+                flag_tx: flag_tx,
+            }
+        )
+    }
+
+    fn train(&mut self, points: &VecDeque<Vec<f64>>) -> Forest {
+        let sample_size = self.sample_size.min(points.len());
+        let height_limit = (sample_size as f64).log2().ceil() as usize;
+        let mut trees = Vec::new();
+        for _ in 0..self.tree_count {
+            //sample without replacement through a partial fisher-yates shuffle
+            let mut indices: Vec<usize> = (0..points.len()).collect();
+            for i in 0..sample_size {
+                let j = i + (self.next_random() * (indices.len() - i) as f64) as usize;
+                indices.swap(i, j);
+            }
+
+            let sample: Vec<&Vec<f64>> = indices[..sample_size].iter().map(|x| &points[*x]).collect();
+            trees.push(self.build(&sample, 0, height_limit));
+        }
+
+        Forest {
+            trees: trees,
+            sample_size: sample_size,
+        }
+    }
+
+    fn build(&mut self, sample: &Vec<&Vec<f64>>, depth: usize, height_limit: usize) -> Node {
+        if depth >= height_limit || sample.len() <= 1 {
+            return Node::Leaf { size: sample.len() };
+        }
+
+        //split on a random variable which still varies within the sample
+        let mut variables: Vec<usize> = (0..self.variable_names.len()).collect();
+        while variables.len() > 0 {
+            let index = (self.next_random() * variables.len() as f64) as usize;
+            let variable = variables.swap_remove(index);
+            let minimum = sample.iter().map(|x| x[variable]).fold(::std::f64::INFINITY, f64::min);
+            let maximum = sample.iter().map(|x| x[variable]).fold(::std::f64::NEG_INFINITY, f64::max);
+            if minimum >= maximum {
+                continue;
+            }
+
+            let value = minimum + (self.next_random() * (maximum - minimum));
+            let (left, right): (Vec<&Vec<f64>>, Vec<&Vec<f64>>) = sample.iter().partition(|x| x[variable] < value);
+            return Node::Split {
+                variable: variable,
+                value: value,
+                left: Box::new(self.build(&left, depth + 1, height_limit)),
+                right: Box::new(self.build(&right, depth + 1, height_limit)),
+            };
+        }
+
+        Node::Leaf { size: sample.len() }
+    }
+
+    fn next_random(&mut self) -> f64 {
+        //xorshift64* uniform in [0, 1)
+        self.random_state ^= self.random_state >> 12;
+        self.random_state ^= self.random_state << 25;
+        self.random_state ^= self.random_state >> 27;
+        (self.random_state.wrapping_mul(0x2545f4914f6cdd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Analyzer for IsolationForestAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let mut point = Vec::new();
+        for variable_name in self.variable_names.iter() {
+            match get_value(variable_name, document) {
+                Some(value) if value.is_finite() => point.push(value),
+                _ => {
+                    trace.decision("skipped: variable not present");
+                    return Ok(());
+                },
+            }
+        }
+
+        //retrain on the history preceding this result once enough results were seen since
+        let key = (hostname, domain);
+        let mut history = self.histories.remove(&key).unwrap_or(History { points: VecDeque::new(), forest: None, results_since_training: 0 });
+        history.results_since_training += 1;
+        if history.points.len() >= self.sample_size && (history.forest.is_none() || history.results_since_training >= self.retrain_interval) {
+            history.forest = Some(self.train(&history.points));
+            history.results_since_training = 0;
+            trace.statistic("trained", true);
+        }
+
+        let score = history.forest.as_ref().map(|x| score(x, &point));
+        history.points.push_back(point.clone());
+        if history.points.len() > self.history_size {
+            history.points.pop_front();
+        }
+
+        trace.statistic("history", history.points.len() as i64);
+        self.histories.insert(key, history);
+        let score = match score {
+            Some(score) => score,
+            None => {
+                trace.decision("no flag: insufficient history to train forest");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("score", score);
+        if score <= self.max_score {
+            trace.decision("no flag: anomaly score within threshold");
+            return Ok(());
+        }
+
+        trace.decision("flag: anomaly score exceeds threshold");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("score".to_owned(), score.to_string());
+        flag.evidence.insert("max_score".to_owned(), self.max_score.to_string());
+        for (variable_name, value) in self.variable_names.iter().zip(point.iter()) {
+            flag.evidence.insert(variable_name.join("."), value.to_string());
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.histories.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+//2^(-mean path length / average path length of an unsuccessful binary search tree search)
+fn score(forest: &Forest, point: &Vec<f64>) -> f64 {
+    let mean_path_length = forest.trees.iter().map(|x| path_length(x, point, 0)).sum::<f64>() / forest.trees.len() as f64;
+    2f64.powf(-mean_path_length / average_path_length(forest.sample_size))
+}
+
+fn path_length(node: &Node, point: &Vec<f64>, depth: usize) -> f64 {
+    match node {
+        &Node::Split { variable, value, ref left, ref right } => match point[variable] < value {
+            true => path_length(left, point, depth + 1),
+            false => path_length(right, point, depth + 1),
+        },
+        //leaves cut off by the height limit hold unresolved subtrees of their size
+        &Node::Leaf { size } => depth as f64 + average_path_length(size),
+    }
+}
+
+fn average_path_length(size: usize) -> f64 {
+    match size {
+        0 | 1 => 0.0,
+        2 => 1.0,
+        _ => {
+            let n = size as f64;
+            2.0 * ((n - 1.0).ln() + EULER_GAMMA) - (2.0 * (n - 1.0) / n)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chan;
+
+    use super::{average_path_length, score, Forest, IsolationForestAnalyzer, Node};
+
+    use std::collections::VecDeque;
+
+    #[test]
+    fn average_path_length_matches_reference() {
+        //c(256) from liu, ting, and zhou's isolation forest paper
+        assert!((average_path_length(256) - 10.2448).abs() < 1e-4);
+        assert_eq!(average_path_length(2), 1.0);
+        assert_eq!(average_path_length(1), 0.0);
+    }
+
+    #[test]
+    fn scores_hand_built_tree() {
+        //one split isolating a single point from three, scored 2^(-1 / c(4)) and 2^(-(1 + c(3)) / c(4))
+        let tree = Node::Split { variable: 0, value: 5.0, left: Box::new(Node::Leaf { size: 1 }), right: Box::new(Node::Leaf { size: 3 }) };
+        let forest = Forest { trees: vec!(tree), sample_size: 4 };
+        assert!((score(&forest, &vec!(1.0)) - 0.687744).abs() < 1e-6);
+        assert!((score(&forest, &vec!(9.0)) - 0.437660).abs() < 1e-6);
+    }
+
+    #[test]
+    fn isolates_outliers_of_trained_history() {
+        let (flag_tx, _flag_rx) = chan::async();
+        let parameters = doc!("variable_names" => [["latency"], ["loss"]]);
+        let mut analyzer = IsolationForestAnalyzer::new("isolation_forest", "warning", &parameters, flag_tx).unwrap();
+        analyzer.random_state = 0x9e3779b97f4a7c15;
+
+        let points: VecDeque<Vec<f64>> = (0..256).map(|x| vec!(50.0 + (x % 16) as f64, (x / 16) as f64 * 0.1)).collect();
+        let forest = analyzer.train(&points);
+
+        //outliers in either variable isolate sooner than the center of the history, and sooner still in both
+        let center = score(&forest, &vec!(57.0, 0.8));
+        let (latency_outlier, loss_outlier) = (score(&forest, &vec!(200.0, 0.8)), score(&forest, &vec!(57.0, 10.0)));
+        assert!(center < 0.5);
+        assert!(latency_outlier > 0.55 && loss_outlier > 0.55);
+        assert!(score(&forest, &vec!(200.0, 10.0)) > latency_outlier.max(loss_outlier));
+    }
+}
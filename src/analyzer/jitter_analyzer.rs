@@ -0,0 +1,170 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct JitterWindow {
+    previous: Option<(i64, f64)>,
+    differences: VecDeque<f64>,
+    flagged: bool,
+}
+
+//measures jitter as the variation in latency between consecutive results over a window, flagging
+//vantage points whose jitter exceeds a maximum, often an earlier warning than the mean latency
+pub struct JitterAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    window_size: usize,
+    max_jitter: f64,
+    method: String,
+    windows: HashMap<(String, String), JitterWindow>,
+    flag_tx: Sender<Flag>,
+}
+
+impl JitterAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<JitterAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in JitterAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in JitterAnalyzer")),
+        };
+
+        //number of consecutive result differences jitter is computed over
+        let window_size = match parameters.get("window_size") {
+            Some(&Bson::I32(window_size)) if window_size > 1 => window_size as usize,
+            None => 20,
+            _ => return Err(TipupError::from("failed to parse window_size parameter in JitterAnalyzer, expected an integer greater than 1")),
+        };
+
+        let max_jitter = match parameters.get("max_jitter") {
+            Some(&Bson::FloatingPoint(max_jitter)) if max_jitter > 0.0 => max_jitter,
+            Some(&Bson::I32(max_jitter)) if max_jitter > 0 => max_jitter as f64,
+            _ => return Err(TipupError::from("failed to parse max_jitter parameter in JitterAnalyzer as positive number")),
+        };
+
+        //'mean_difference' is the mean absolute difference between consecutive results (rfc 3550),
+        //'std_dev' the standard deviation of those differences
+        let method = match parameters.get("method") {
+            Some(&Bson::String(ref method)) if ["mean_difference", "std_dev"].contains(&method.as_str()) => method.to_owned(),
+            None => "mean_difference".to_owned(),
+            _ => return Err(TipupError::from("failed to parse method parameter in JitterAnalyzer, expected 'mean_difference' or 'std_dev'")),
+        };
+
+        Ok(
+            JitterAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                window_size: window_size,
+                max_jitter: max_jitter,
+                method: method,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for JitterAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let window = self.windows.entry((hostname, domain)).or_insert(
+            JitterWindow {
+                previous: None,
+                differences: VecDeque::new(),
+                flagged: false,
+            }
+        );
+
+        //late results would compare against a result measured after them
+        let previous_value = match window.previous {
+            Some((previous_timestamp, _)) if timestamp <= previous_timestamp => {
+                trace.decision("skipped: result not newer than previous result");
+                return Ok(());
+            },
+            Some((_, previous_value)) => previous_value,
+            None => {
+                window.previous = Some((timestamp, value));
+                trace.decision("no flag: no previous result");
+                return Ok(());
+            },
+        };
+
+        window.previous = Some((timestamp, value));
+        window.differences.push_back(value - previous_value);
+        if window.differences.len() > self.window_size {
+            window.differences.pop_front();
+        }
+
+        trace.statistic("value", value);
+        if window.differences.len() < self.window_size {
+            trace.decision("no flag: filling window");
+            return Ok(());
+        }
+
+        let count = window.differences.len() as f64;
+        let mean_difference = window.differences.iter().map(|x| x.abs()).sum::<f64>() / count;
+        let mean = window.differences.iter().sum::<f64>() / count;
+        let variance = window.differences.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        let jitter = match self.method.as_str() {
+            "std_dev" => variance.sqrt(),
+            _ => mean_difference,
+        };
+
+        trace.statistic("jitter", jitter);
+        trace.statistic("variance", variance);
+        if jitter <= self.max_jitter {
+            window.flagged = false;
+            trace.decision("no flag: jitter within maximum");
+            return Ok(());
+        }
+
+        //flag once per excursion rather than every result while jitter remains high
+        if window.flagged {
+            trace.decision("no flag: jitter excursion already flagged");
+            return Ok(());
+        }
+
+        window.flagged = true;
+        trace.decision("flag: jitter exceeds maximum");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("jitter".to_owned(), jitter.to_string());
+        flag.evidence.insert("variance".to_owned(), variance.to_string());
+        flag.evidence.insert("max_jitter".to_owned(), self.max_jitter.to_string());
+        flag.evidence.insert("method".to_owned(), self.method.to_owned());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.windows.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
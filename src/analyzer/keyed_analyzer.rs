@@ -0,0 +1,93 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::{Receiver, Sender};
+use mongodb::db::Database;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+use partition;
+
+use std::collections::{HashMap, HashSet};
+
+//partitions analyzer state by custom key fields in addition to (hostname, domain) by presenting
+//the wrapped analyzer with results whose domain is extended by the key field values
+pub struct KeyedAnalyzer {
+    analyzer: Box<Analyzer>,
+    analyzer_flag_rx: Receiver<Flag>,
+    key_fields: Vec<Vec<String>>,
+    partitions: HashMap<(String, String), HashSet<String>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl KeyedAnalyzer {
+    pub fn new(analyzer: Box<Analyzer>, analyzer_flag_rx: Receiver<Flag>, key_fields: Vec<Vec<String>>, flag_tx: Sender<Flag>) -> KeyedAnalyzer {
+        KeyedAnalyzer {
+            analyzer: analyzer,
+            analyzer_flag_rx: analyzer_flag_rx,
+            key_fields: key_fields,
+            partitions: HashMap::new(),
+            flag_tx: flag_tx,
+        }
+    }
+}
+
+impl Analyzer for KeyedAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return self.analyzer.process_measurement(document, trace),
+        };
+
+        let partition_domain = partition::partition_domain(&domain, &self.key_fields, document);
+        trace.statistic("partition", partition::suffix(&partition_domain, &domain).to_owned());
+
+        let mut partition_document = document.clone();
+        partition_document.insert("measurement_domain", partition_domain.clone());
+        try!(self.analyzer.process_measurement(&partition_document, trace));
+
+        //flags report the measured domain, recording the partition as evidence
+        for mut flag in drain(&self.analyzer_flag_rx) {
+            flag.domain = domain.clone();
+            flag.evidence.insert("partition".to_owned(), partition::suffix(&partition_domain, &domain).to_owned());
+            self.flag_tx.send(flag);
+        }
+
+        self.partitions.entry((hostname, domain)).or_insert(HashSet::new()).insert(partition_domain);
+        Ok(())
+    }
+
+    fn refresh(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        self.analyzer.refresh(proddle_db)
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        //measurement parameter changes invalidate every partition of the key
+        if let Some(partitions) = self.partitions.remove(&(hostname.to_owned(), domain.to_owned())) {
+            for partition_domain in partitions.iter() {
+                self.analyzer.reset(hostname, partition_domain);
+            }
+        }
+    }
+
+    fn routes_by_target(&self) -> bool {
+        self.analyzer.routes_by_target()
+    }
+
+    fn measurement_classes(&self) -> Vec<String> {
+        self.analyzer.measurement_classes()
+    }
+}
+
+fn drain(flag_rx: &Receiver<Flag>) -> Vec<Flag> {
+    let mut flags = Vec::new();
+    loop {
+        chan_select! {
+            default => return flags,
+            flag_rx.recv() -> flag => match flag {
+                Some(flag) => flags.push(flag),
+                None => return flags,
+            },
+        }
+    }
+}
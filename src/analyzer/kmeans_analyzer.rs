@@ -0,0 +1,422 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use analyzer::{get_value, Analyzer, Trace};
+use clock::Clock;
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+//lloyd iterations before clustering is considered converged regardless
+const MAX_ITERATIONS: usize = 50;
+
+//clusters of a key's historical results, variables standardized so each weighs equally
+struct Model {
+    measurement_class: String,
+    centroids: Vec<Vec<f64>>,
+    means: Vec<f64>,
+    std_devs: Vec<f64>,
+    trained_timestamp: i64,
+}
+
+//clusters each measurement's historical results into behavior groups, e.g. a target served from
+//two datacenters at different latencies, flagging results far from every cluster centroid, with
+//clusters periodically retrained on recent results pulled from the results collection
+pub struct KMeansAnalyzer {
+    name: String,
+    status: String,
+    variable_names: Vec<Vec<String>>,
+    clusters: usize,
+    collection: String,
+    history: i64,
+    history_size: i64,
+    min_samples: usize,
+    retrain_interval: i64,
+    max_distance: f64,
+    models: HashMap<(String, String), Model>,
+    random_state: u64,
+    flag_tx: Sender<Flag>,
+    clock: Arc<Clock>,
+}
+
+impl KMeansAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>, clock: Arc<Clock>) -> Result<KMeansAnalyzer, TipupError> {
+        let variable_names = match parameters.get("variable_names") {
+            Some(&Bson::Array(ref param_variable_names)) if param_variable_names.len() > 0 => {
+                let mut variable_names = Vec::new();
+                for x in param_variable_names {
+                    let mut variable_name = Vec::new();
+                    match x {
+                        &Bson::Array(ref y) => for z in y {
+                            match z {
+                                &Bson::String(ref z) => variable_name.push(z.to_owned()),
+                                _ => return Err(TipupError::from("failed to parse variable name as String in KMeansAnalyzer")),
+                            }
+                        },
+                        _ => return Err(TipupError::from("failed to parse variable name as Array in KMeansAnalyzer")),
+                    }
+
+                    variable_names.push(variable_name);
+                }
+
+                variable_names
+            },
+            _ => return Err(TipupError::from("failed to parse variable_names parameter in KMeansAnalyzer, expected an array of variable names")),
+        };
+
+        let clusters = match parameters.get("clusters") {
+            Some(&Bson::I32(clusters)) if clusters > 0 => clusters as usize,
+            None => 3,
+            _ => return Err(TipupError::from("failed to parse clusters parameter in KMeansAnalyzer as positive integer")),
+        };
+
+        let collection = match parameters.get("collection") {
+            Some(&Bson::String(ref collection)) => collection.to_owned(),
+            None => "measurements".to_owned(),
+            _ => return Err(TipupError::from("failed to parse collection parameter in KMeansAnalyzer")),
+        };
+
+        //seconds of history, and most results of it, clusters are trained on
+        let history = match parameters.get("history") {
+            Some(&Bson::I32(history)) if history > 0 => history as i64,
+            Some(&Bson::I64(history)) if history > 0 => history,
+            None => 604800,
+            _ => return Err(TipupError::from("failed to parse history parameter in KMeansAnalyzer as positive integer")),
+        };
+
+        let history_size = match parameters.get("history_size") {
+            Some(&Bson::I32(history_size)) if history_size > 0 => history_size as i64,
+            None => 1000,
+            _ => return Err(TipupError::from("failed to parse history_size parameter in KMeansAnalyzer as positive integer")),
+        };
+
+        let min_samples = match parameters.get("min_samples") {
+            Some(&Bson::I32(min_samples)) if min_samples as usize >= clusters => min_samples as usize,
+            None => (clusters * 10).max(50),
+            _ => return Err(TipupError::from("failed to parse min_samples parameter in KMeansAnalyzer, expected an integer of at least clusters")),
+        };
+
+        //seconds between retraining a key's clusters
+        let retrain_interval = match parameters.get("retrain_interval") {
+            Some(&Bson::I32(retrain_interval)) if retrain_interval > 0 => retrain_interval as i64,
+            Some(&Bson::I64(retrain_interval)) if retrain_interval > 0 => retrain_interval,
+            None => 3600,
+            _ => return Err(TipupError::from("failed to parse retrain_interval parameter in KMeansAnalyzer as positive integer")),
+        };
+
+        //standardized distance to the nearest centroid beyond which results are flagged
+        let max_distance = match parameters.get("max_distance") {
+            Some(&Bson::FloatingPoint(max_distance)) if max_distance > 0.0 => max_distance,
+            Some(&Bson::I32(max_distance)) if max_distance > 0 => max_distance as f64,
+            None => 3.0,
+            _ => return Err(TipupError::from("failed to parse max_distance parameter in KMeansAnalyzer as positive number")),
+        };
+
+        Ok(
+            KMeansAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_names: variable_names,
+                clusters: clusters,
+                collection: collection,
+                history: history,
+                history_size: history_size,
+                min_samples: min_samples,
+                retrain_interval: retrain_interval,
+                max_distance: max_distance,
+                models: HashMap::new(),
+                random_state: time::precise_time_ns() | 1,
+                flag_tx: flag_tx,
+                clock: clock,
+            }
+        )
+    }
+
+    fn point(&self, document: &OrderedDocument) -> Option<Vec<f64>> {
+        let mut point = Vec::new();
+        for variable_name in self.variable_names.iter() {
+            match get_value(variable_name, document) {
+                Some(value) if value.is_finite() => point.push(value),
+                _ => return None,
+            }
+        }
+
+        Some(point)
+    }
+
+    fn train(&mut self, proddle_db: &Database, hostname: &str, domain: &str, model: &mut Model, now: i64) -> Result<(), TipupError> {
+        model.trained_timestamp = now;
+        let timestamp_gte = doc!("$gte" => (now - self.history));
+        let search_document = doc!(
+            "vantage_hostname" => hostname,
+            "measurement_domain" => domain,
+            "measurement_class" => (model.measurement_class.clone()),
+            "timestamp" => timestamp_gte
+        );
+
+        let negative_one = -1;
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("timestamp" => negative_one));
+        find_options.limit = Some(self.history_size);
+
+        let mut points = Vec::new();
+        for document in try!(proddle_db.collection(&self.collection).find(Some(search_document), Some(find_options))) {
+            if let Some(point) = self.point(&try!(document)) {
+                points.push(point);
+            }
+        }
+
+        if points.len() < self.min_samples {
+            return Ok(());
+        }
+
+        //standardize variables so clusters are not dominated by those of the largest scale
+        let dimensions = self.variable_names.len();
+        let mut means = vec![0.0; dimensions];
+        let mut std_devs = vec![0.0; dimensions];
+        for i in 0..dimensions {
+            means[i] = points.iter().map(|x| x[i]).sum::<f64>() / points.len() as f64;
+            std_devs[i] = (points.iter().map(|x| (x[i] - means[i]).powi(2)).sum::<f64>() / points.len() as f64).sqrt();
+            if std_devs[i] == 0.0 {
+                std_devs[i] = 1.0;
+            }
+        }
+
+        let points: Vec<Vec<f64>> = points.iter().map(|x| standardize(x, &means, &std_devs)).collect();
+        model.centroids = self.cluster(&points);
+        model.means = means;
+        model.std_devs = std_devs;
+        Ok(())
+    }
+
+    //k-means++ seeding followed by lloyd iterations until assignments settle
+    fn cluster(&mut self, points: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        let mut centroids = vec!(points[(self.next_random() * points.len() as f64) as usize].clone());
+        while centroids.len() < self.clusters {
+            let distances: Vec<f64> = points.iter().map(|x| nearest(&centroids, x).1.powi(2)).collect();
+            let total = distances.iter().sum::<f64>();
+            if total == 0.0 {
+                break;
+            }
+
+            let mut target = self.next_random() * total;
+            let mut index = points.len() - 1;
+            for (i, distance) in distances.iter().enumerate() {
+                if target < *distance {
+                    index = i;
+                    break;
+                }
+
+                target -= *distance;
+            }
+
+            centroids.push(points[index].clone());
+        }
+
+        let mut assignments = vec![usize::max_value(); points.len()];
+        for _ in 0..MAX_ITERATIONS {
+            let mut changed = false;
+            for (i, point) in points.iter().enumerate() {
+                let cluster = nearest(&centroids, point).0;
+                if assignments[i] != cluster {
+                    assignments[i] = cluster;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+
+            for (cluster, centroid) in centroids.iter_mut().enumerate() {
+                let members: Vec<&Vec<f64>> = points.iter().zip(assignments.iter()).filter(|x| *x.1 == cluster).map(|x| x.0).collect();
+                if members.len() == 0 {
+                    continue;
+                }
+
+                for j in 0..centroid.len() {
+                    centroid[j] = members.iter().map(|x| x[j]).sum::<f64>() / members.len() as f64;
+                }
+            }
+        }
+
+        centroids
+    }
+
+    //keys whose clusters were last trained at least a retrain interval ago
+    fn retrain_keys(&self, now: i64) -> Vec<(String, String)> {
+        self.models.iter()
+            .filter(|x| now - x.1.trained_timestamp >= self.retrain_interval)
+            .map(|x| x.0.clone())
+            .collect()
+    }
+
+    fn next_random(&mut self) -> f64 {
+        //xorshift64* uniform in [0, 1)
+        self.random_state ^= self.random_state >> 12;
+        self.random_state ^= self.random_state << 25;
+        self.random_state ^= self.random_state >> 27;
+        (self.random_state.wrapping_mul(0x2545f4914f6cdd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Analyzer for KMeansAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, measurement_class) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("measurement_class")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::String(ref measurement_class))) => (hostname.to_owned(), domain.to_owned(), measurement_class.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let point = match self.point(document) {
+            Some(point) => point,
+            None => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        //keys are trained on the next refresh after their first result
+        let model = self.models.entry((hostname, domain)).or_insert(Model { measurement_class: measurement_class, centroids: Vec::new(), means: Vec::new(), std_devs: Vec::new(), trained_timestamp: 0 });
+        if model.centroids.len() == 0 {
+            trace.decision("no flag: clusters not yet trained");
+            return Ok(());
+        }
+
+        let (cluster, distance) = nearest(&model.centroids, &standardize(&point, &model.means, &model.std_devs));
+        trace.statistic("cluster", cluster as i32);
+        trace.statistic("distance", distance);
+        if distance <= self.max_distance {
+            trace.decision("no flag: result near a cluster centroid");
+            return Ok(());
+        }
+
+        trace.decision("flag: result far from every cluster centroid");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("distance".to_owned(), distance.to_string());
+        flag.evidence.insert("max_distance".to_owned(), self.max_distance.to_string());
+        flag.evidence.insert("nearest_cluster".to_owned(), cluster.to_string());
+        flag.evidence.insert("clusters".to_owned(), model.centroids.len().to_string());
+        for (variable_name, value) in self.variable_names.iter().zip(point.iter()) {
+            flag.evidence.insert(variable_name.join("."), value.to_string());
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn refresh(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        let now = self.clock.now();
+        for key in self.retrain_keys(now) {
+            let mut model = self.models.remove(&key).unwrap();
+            let result = self.train(proddle_db, &key.0, &key.1, &mut model, now);
+            self.models.insert(key, model);
+            try!(result);
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.models.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn standardize(point: &Vec<f64>, means: &Vec<f64>, std_devs: &Vec<f64>) -> Vec<f64> {
+    point.iter().zip(means.iter().zip(std_devs.iter())).map(|(x, (mean, std_dev))| (x - mean) / std_dev).collect()
+}
+
+//index of and euclidean distance to the centroid nearest a point
+fn nearest(centroids: &Vec<Vec<f64>>, point: &Vec<f64>) -> (usize, f64) {
+    let mut nearest = (0, ::std::f64::INFINITY);
+    for (i, centroid) in centroids.iter().enumerate() {
+        let distance = centroid.iter().zip(point.iter()).map(|(x, y)| (x - y).powi(2)).sum::<f64>().sqrt();
+        if distance < nearest.1 {
+            nearest = (i, distance);
+        }
+    }
+
+    nearest
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chan;
+
+    use analyzer::{Analyzer, Trace};
+    use clock::{Clock, MockClock};
+
+    use super::{nearest, standardize, KMeansAnalyzer};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn standardizes_by_mean_and_std_dev() {
+        let point = standardize(&vec!(14.0, 1.0), &vec!(10.0, 3.0), &vec!(2.0, 4.0));
+        assert_eq!(point, vec!(2.0, -0.5));
+    }
+
+    #[test]
+    fn finds_nearest_centroid() {
+        let centroids = vec!(vec!(0.0, 0.0), vec!(6.0, 8.0), vec!(-3.0, 0.0));
+        assert_eq!(nearest(&centroids, &vec!(6.0, 4.0)), (1, 4.0));
+        assert_eq!(nearest(&centroids, &vec!(-2.0, 0.0)), (2, 1.0));
+        assert_eq!(nearest(&centroids, &vec!(3.0, 4.0)).0, 0);
+        assert_eq!(nearest(&centroids, &vec!(3.0, 4.0)).1, 5.0);
+    }
+
+    #[test]
+    fn clusters_separated_groups_at_their_means() {
+        let (flag_tx, _flag_rx) = chan::async();
+        let parameters = doc!("variable_names" => [["latency"]], "clusters" => 2);
+        let mut analyzer = KMeansAnalyzer::new("kmeans", "warning", &parameters, flag_tx, Arc::new(MockClock::new(0))).unwrap();
+        analyzer.random_state = 0x9e3779b97f4a7c15;
+
+        //two groups, means 1 and 10, far enough apart that any seeding converges to them
+        let points: Vec<Vec<f64>> = vec!(0.0, 1.0, 2.0, 0.5, 1.5, 9.0, 10.0, 11.0, 9.5, 10.5)
+            .into_iter().map(|x| vec!(x)).collect();
+        let mut centroids: Vec<f64> = analyzer.cluster(&points).iter().map(|x| x[0]).collect();
+        centroids.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(centroids.len(), 2);
+        assert!((centroids[0] - 1.0).abs() < 1e-9);
+        assert!((centroids[1] - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stops_seeding_on_identical_points() {
+        let (flag_tx, _flag_rx) = chan::async();
+        let parameters = doc!("variable_names" => [["latency"]], "clusters" => 3);
+        let mut analyzer = KMeansAnalyzer::new("kmeans", "warning", &parameters, flag_tx, Arc::new(MockClock::new(0))).unwrap();
+        analyzer.random_state = 0x9e3779b97f4a7c15;
+
+        let points = vec!(vec!(4.0); 10);
+        assert_eq!(analyzer.cluster(&points), vec!(vec!(4.0)));
+    }
+
+    #[test]
+    fn retrains_keys_after_retrain_interval() {
+        let (flag_tx, _flag_rx) = chan::async();
+        let clock = Arc::new(MockClock::new(1000000));
+        let parameters = doc!("variable_names" => [["latency"]], "retrain_interval" => 3600);
+        let mut analyzer = KMeansAnalyzer::new("kmeans", "warning", &parameters, flag_tx, clock.clone()).unwrap();
+
+        //keys are first trained on the refresh after their first result
+        let document = doc!("_id" => (ObjectId::new().unwrap()), "vantage_hostname" => "vantage", "measurement_domain" => "example.com",
+            "measurement_class" => "ping", "timestamp" => 1000000i64, "latency" => 10.0);
+        analyzer.process_measurement(&document, &mut Trace::new(false)).unwrap();
+        let key = ("vantage".to_owned(), "example.com".to_owned());
+        assert_eq!(analyzer.retrain_keys(clock.now()), vec!(key.clone()));
+
+        analyzer.models.get_mut(&key).unwrap().trained_timestamp = clock.now();
+        clock.step(3599.0);
+        assert_eq!(analyzer.retrain_keys(clock.now()).len(), 0);
+        clock.step(1.0);
+        assert_eq!(analyzer.retrain_keys(clock.now()), vec!(key));
+    }
+}
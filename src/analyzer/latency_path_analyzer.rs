@@ -0,0 +1,134 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_field, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+pub struct LatencyPathAnalyzer {
+    name: String,
+    status: String,
+    rtt_field: Vec<String>,
+    hop_field: Vec<String>,
+    threshold: f64,
+    window_size: usize,
+    minimum_samples: usize,
+    windows: HashMap<(String, String), VecDeque<(f64, i64)>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl LatencyPathAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<LatencyPathAnalyzer, TipupError> {
+        let rtt_field = try!(parse_field(parameters, "rtt_field", &[], "LatencyPathAnalyzer"));
+        let hop_field = try!(parse_field(parameters, "hop_field", &[], "LatencyPathAnalyzer"));
+
+        //ratio of rtt to its baseline mean at which a flag is raised
+        let threshold = match parameters.get("threshold") {
+            Some(&Bson::FloatingPoint(threshold)) => threshold,
+            Some(&Bson::I32(threshold)) => threshold as f64,
+            None => 1.5,
+            _ => return Err(TipupError::from("failed to parse threshold parameter in LatencyPathAnalyzer")),
+        };
+
+        let window_size = match parameters.get("window_size") {
+            Some(&Bson::I32(window_size)) if window_size > 0 => window_size as usize,
+            None => 20,
+            _ => return Err(TipupError::from("failed to parse window_size parameter in LatencyPathAnalyzer as positive integer")),
+        };
+
+        let minimum_samples = match parameters.get("minimum_samples") {
+            Some(&Bson::I32(minimum_samples)) if minimum_samples > 0 => minimum_samples as usize,
+            None => 5,
+            _ => return Err(TipupError::from("failed to parse minimum_samples parameter in LatencyPathAnalyzer as positive integer")),
+        };
+
+        if threshold <= 1.0 || minimum_samples > window_size {
+            return Err(TipupError::from("LatencyPathAnalyzer requires a threshold greater than 1 and minimum_samples no greater than window_size"));
+        }
+
+        Ok(
+            LatencyPathAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                rtt_field: rtt_field,
+                hop_field: hop_field,
+                threshold: threshold,
+                window_size: window_size,
+                minimum_samples: minimum_samples,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for LatencyPathAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let (rtt, hop_count) = match (get_value(&self.rtt_field, document), get_value(&self.hop_field, document)) {
+            (Some(rtt), Some(hop_count)) => (rtt, hop_count as i64),
+            _ => {
+                trace.decision("skipped: rtt or hop count not present");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("rtt", rtt);
+        trace.statistic("hop_count", hop_count);
+        let window = self.windows.entry((hostname, domain)).or_insert(VecDeque::new());
+        trace.statistic("samples", window.len() as i64);
+        if window.len() < self.minimum_samples {
+            trace.decision("no flag: insufficient baseline samples");
+        } else {
+            let baseline_rtt = window.iter().map(|&(x, _)| x).sum::<f64>() / window.len() as f64;
+            trace.statistic("baseline_rtt", baseline_rtt);
+            if rtt <= baseline_rtt * self.threshold {
+                trace.decision("no flag: rtt within threshold of baseline");
+            } else {
+                //a rise along the usual path indicates congestion, along a different path rerouting
+                let baseline_hop_count = most_common(window.iter().map(|&(_, x)| x));
+                let cause = match hop_count == baseline_hop_count {
+                    true => "congestion",
+                    false => "rerouting",
+                };
+
+                trace.decision(&format!("flag: rtt exceeds threshold of baseline due to {}", cause));
+
+                let mut flag = try!(Flag::new(document, &self.status, &self.name));
+                flag.evidence.insert("cause".to_owned(), cause.to_owned());
+                flag.evidence.insert("rtt".to_owned(), rtt.to_string());
+                flag.evidence.insert("baseline_rtt".to_owned(), baseline_rtt.to_string());
+                flag.evidence.insert("hop_count".to_owned(), hop_count.to_string());
+                flag.evidence.insert("baseline_hop_count".to_owned(), baseline_hop_count.to_string());
+                self.flag_tx.send(flag);
+            }
+        }
+
+        window.push_back((rtt, hop_count));
+        if window.len() > self.window_size {
+            window.pop_front();
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.windows.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn most_common<I: Iterator<Item=i64>>(values: I) -> i64 {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+
+    counts.into_iter().max_by_key(|&(value, count)| (count, -value)).map(|(value, _)| value).unwrap_or(0)
+}
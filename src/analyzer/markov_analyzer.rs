@@ -0,0 +1,217 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_category, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct Chain {
+    transitions: HashMap<(String, String), f64>,
+    totals: HashMap<String, f64>,
+    states: VecDeque<String>,
+    probabilities: VecDeque<f64>,
+}
+
+impl Chain {
+    fn probability(&self, from: &str, to: &str) -> Option<(f64, f64)> {
+        let total = match self.totals.get(from) {
+            Some(total) => *total,
+            None => return None,
+        };
+
+        let count = self.transitions.get(&(from.to_owned(), to.to_owned())).cloned().unwrap_or(0.0);
+        Some((count / total, total))
+    }
+
+    //halving counts of a state once it reaches the observation limit weighs recent transitions
+    //more, so the chain adapts to lasting changes in behavior
+    fn observe(&mut self, from: &str, to: &str, max_observations: f64) {
+        *self.transitions.entry((from.to_owned(), to.to_owned())).or_insert(0.0) += 1.0;
+        let total = {
+            let total = self.totals.entry(from.to_owned()).or_insert(0.0);
+            *total += 1.0;
+            *total
+        };
+
+        if total >= max_observations {
+            for (key, count) in self.transitions.iter_mut() {
+                if key.0 == from {
+                    *count /= 2.0;
+                }
+            }
+
+            self.totals.insert(from.to_owned(), total / 2.0);
+        }
+    }
+}
+
+//learns per key probabilities of transitions between the states of a discrete status field, e.g.
+//up, degraded, and down, flagging improbable transitions and improbable sequences of recent
+//transitions such as up -> degraded -> up -> degraded flapping
+pub struct MarkovAnalyzer {
+    name: String,
+    status: String,
+    state_field: Vec<String>,
+    min_observations: f64,
+    max_observations: f64,
+    min_probability: f64,
+    sequence_length: usize,
+    min_sequence_probability: f64,
+    chains: HashMap<(String, String), Chain>,
+    flag_tx: Sender<Flag>,
+}
+
+impl MarkovAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<MarkovAnalyzer, TipupError> {
+        let state_field = match parameters.get("state_field") {
+            Some(&Bson::Array(ref param_state_field)) => {
+                let mut state_field = Vec::new();
+                for x in param_state_field {
+                    match x {
+                        &Bson::String(ref y) => state_field.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse state_field as String in MarkovAnalyzer")),
+                    }
+                }
+
+                state_field
+            },
+            _ => return Err(TipupError::from("failed to parse state_field parameter in MarkovAnalyzer")),
+        };
+
+        //transitions observed from a state before transitions from it are judged
+        let min_observations = match parameters.get("min_observations") {
+            Some(&Bson::I32(min_observations)) if min_observations > 0 => min_observations as f64,
+            None => 50.0,
+            _ => return Err(TipupError::from("failed to parse min_observations parameter in MarkovAnalyzer as positive integer")),
+        };
+
+        let max_observations = match parameters.get("max_observations") {
+            Some(&Bson::I32(max_observations)) if max_observations as f64 > min_observations * 2.0 => max_observations as f64,
+            None => (min_observations * 20.0).max(1000.0),
+            _ => return Err(TipupError::from("failed to parse max_observations parameter in MarkovAnalyzer, expected an integer over twice min_observations")),
+        };
+
+        let min_probability = try!(parse_probability(parameters, "min_probability", 0.01));
+
+        //recent transitions whose joint probability is judged, 1 judging single transitions only
+        let sequence_length = match parameters.get("sequence_length") {
+            Some(&Bson::I32(sequence_length)) if sequence_length > 0 => sequence_length as usize,
+            None => 4,
+            _ => return Err(TipupError::from("failed to parse sequence_length parameter in MarkovAnalyzer as positive integer")),
+        };
+
+        let min_sequence_probability = try!(parse_probability(parameters, "min_sequence_probability", 0.001));
+
+        Ok(
+            MarkovAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                state_field: state_field,
+                min_observations: min_observations,
+                max_observations: max_observations,
+                min_probability: min_probability,
+                sequence_length: sequence_length,
+                min_sequence_probability: min_sequence_probability,
+                chains: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for MarkovAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let state = match get_category(&self.state_field, document) {
+            Some(state) => state,
+            None => {
+                trace.decision("skipped: state not present");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("state", state.clone());
+        let chain = self.chains.entry((hostname, domain)).or_insert(Chain { transitions: HashMap::new(), totals: HashMap::new(), states: VecDeque::new(), probabilities: VecDeque::new() });
+        let previous_state = chain.states.back().cloned();
+        chain.states.push_back(state.clone());
+        if chain.states.len() > self.sequence_length + 1 {
+            chain.states.pop_front();
+        }
+
+        let previous_state = match previous_state {
+            Some(previous_state) => previous_state,
+            None => {
+                trace.decision("no flag: first state of key");
+                return Ok(());
+            },
+        };
+
+        //judge the transition against the chain learned before it, then learn it
+        let probability = match chain.probability(&previous_state, &state) {
+            Some((probability, total)) if total >= self.min_observations => Some(probability),
+            _ => None,
+        };
+
+        chain.observe(&previous_state, &state, self.max_observations);
+        let probability = match probability {
+            Some(probability) => probability,
+            None => {
+                chain.probabilities.clear();
+                trace.decision("no flag: too few transitions observed from previous state");
+                return Ok(());
+            },
+        };
+
+        chain.probabilities.push_back(probability);
+        if chain.probabilities.len() > self.sequence_length {
+            chain.probabilities.pop_front();
+        }
+
+        let sequence_probability = chain.probabilities.iter().product::<f64>();
+        trace.statistic("probability", probability);
+        trace.statistic("sequence_probability", sequence_probability);
+
+        let reason = if probability < self.min_probability {
+            "improbable_transition"
+        } else if chain.probabilities.len() == self.sequence_length && sequence_probability < self.min_sequence_probability {
+            "improbable_sequence"
+        } else {
+            trace.decision("no flag: transitions probable");
+            return Ok(());
+        };
+
+        trace.decision(&format!("flag: {}", reason.replace('_', " ")));
+        let sequence: Vec<String> = chain.states.iter().skip(chain.states.len() - chain.probabilities.len() - 1).cloned().collect();
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("reason".to_owned(), reason.to_owned());
+        flag.evidence.insert("previous_state".to_owned(), previous_state);
+        flag.evidence.insert("state".to_owned(), state);
+        flag.evidence.insert("probability".to_owned(), probability.to_string());
+        flag.evidence.insert("sequence".to_owned(), sequence.join(" -> "));
+        flag.evidence.insert("sequence_probability".to_owned(), sequence_probability.to_string());
+
+        //a flagged sequence is not flagged again by its later transitions
+        chain.probabilities.clear();
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.chains.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn parse_probability(parameters: &OrderedDocument, key: &str, default: f64) -> Result<f64, TipupError> {
+    match parameters.get(key) {
+        Some(&Bson::FloatingPoint(probability)) if probability > 0.0 && probability < 1.0 => Ok(probability),
+        None => Ok(default),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in MarkovAnalyzer, expected a value between 0 and 1", key))),
+    }
+}
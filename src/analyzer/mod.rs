@@ -1,13 +1,179 @@
+use bson::Bson;
 use bson::ordered::OrderedDocument;
+use mongodb::db::Database;
 
+pub mod burst_analyzer;
+pub mod change_point_analyzer;
+pub mod consensus_analyzer;
+pub mod content_change_analyzer;
+pub mod correlation_analyzer;
+pub mod cusum_analyzer;
+pub mod distribution_drift_analyzer;
+pub mod divergence_analyzer;
+pub mod dns_failure_analyzer;
+pub mod entropy_analyzer;
 pub mod error_analyzer;
+pub mod esd_analyzer;
+pub mod ewma_analyzer;
+pub mod flap_analyzer;
+pub mod geo_dns_analyzer;
+pub mod holt_winters_analyzer;
+pub mod http_status_analyzer;
+pub mod isolation_forest_analyzer;
+pub mod jitter_analyzer;
+pub mod keyed_analyzer;
+pub mod kmeans_analyzer;
+pub mod latency_path_analyzer;
+pub mod markov_analyzer;
+pub mod moving_average_analyzer;
+pub mod packet_loss_analyzer;
+pub mod percentile_analyzer;
+pub mod rate_of_change_analyzer;
 pub mod std_dev_analyzer; 
+pub mod threshold_analyzer;
+pub mod tls_expiry_analyzer;
+pub mod trace;
+pub mod trend_analyzer;
+pub mod uptime_analyzer;
+pub mod windowed_analyzer;
 
+pub use analyzer::burst_analyzer::BurstAnalyzer;
+pub use analyzer::change_point_analyzer::ChangePointAnalyzer;
+pub use analyzer::consensus_analyzer::ConsensusAnalyzer;
+pub use analyzer::content_change_analyzer::ContentChangeAnalyzer;
+pub use analyzer::correlation_analyzer::CorrelationAnalyzer;
+pub use analyzer::cusum_analyzer::CusumAnalyzer;
+pub use analyzer::distribution_drift_analyzer::DistributionDriftAnalyzer;
+pub use analyzer::divergence_analyzer::DivergenceAnalyzer;
+pub use analyzer::dns_failure_analyzer::DnsFailureAnalyzer;
+pub use analyzer::entropy_analyzer::EntropyAnalyzer;
 pub use analyzer::error_analyzer::ErrorAnalyzer;
+pub use analyzer::esd_analyzer::EsdAnalyzer;
+pub use analyzer::ewma_analyzer::EwmaAnalyzer;
+pub use analyzer::flap_analyzer::FlapAnalyzer;
+pub use analyzer::geo_dns_analyzer::GeoDnsAnalyzer;
+pub use analyzer::holt_winters_analyzer::HoltWintersAnalyzer;
+pub use analyzer::http_status_analyzer::HttpStatusAnalyzer;
+pub use analyzer::isolation_forest_analyzer::IsolationForestAnalyzer;
+pub use analyzer::jitter_analyzer::JitterAnalyzer;
+pub use analyzer::keyed_analyzer::KeyedAnalyzer;
+pub use analyzer::kmeans_analyzer::KMeansAnalyzer;
+pub use analyzer::latency_path_analyzer::LatencyPathAnalyzer;
+pub use analyzer::markov_analyzer::MarkovAnalyzer;
+pub use analyzer::moving_average_analyzer::MovingAverageAnalyzer;
+pub use analyzer::packet_loss_analyzer::PacketLossAnalyzer;
+pub use analyzer::percentile_analyzer::PercentileAnalyzer;
+pub use analyzer::rate_of_change_analyzer::RateOfChangeAnalyzer;
 pub use analyzer::std_dev_analyzer::StdDevAnalyzer;
+pub use analyzer::threshold_analyzer::ThresholdAnalyzer;
+pub use analyzer::tls_expiry_analyzer::TlsExpiryAnalyzer;
+pub use analyzer::trace::Trace;
+pub use analyzer::trend_analyzer::TrendAnalyzer;
+pub use analyzer::uptime_analyzer::UptimeAnalyzer;
+pub use analyzer::windowed_analyzer::WindowedAnalyzer;
 
 use error::TipupError;
 
 pub trait Analyzer {
-    fn process_measurement(&mut self, document: &OrderedDocument) -> Result<(), TipupError>;
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError>;
+
+    //periodically reload any database-backed analyzer state
+    fn refresh(&mut self, _proddle_db: &Database) -> Result<(), TipupError> {
+        Ok(())
+    }
+
+    //discard state learned for a key whose measurement parameters changed
+    fn reset(&mut self, _hostname: &str, _domain: &str) {
+    }
+
+    //analyzers comparing vantage points of a target require every vantage point's results of
+    //the target, so keys are split between canaries by target rather than (hostname, domain)
+    fn routes_by_target(&self) -> bool {
+        false
+    }
+
+    //measurement classes whose results are routed to the analyzer in addition to the class of
+    //its definition, e.g. correlating ping and http results of a target
+    fn measurement_classes(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+//numeric value of a field nested by the variable name, e.g. ["http", "latency"]
+pub fn get_value(variable_name: &Vec<String>, document: &OrderedDocument) -> Option<f64> {
+    let mut index_document = document;
+    for variable in variable_name {
+        match index_document.get(variable) {
+            Some(&Bson::Document(ref document)) => index_document = document,
+            Some(&Bson::FloatingPoint(f)) => return Some(f),
+            Some(&Bson::I32(i)) => return Some(i as f64),
+            Some(&Bson::I64(i)) => return Some(i as f64),
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+//field path parameter of an analyzer class, the default if unset unless the default is empty
+pub fn parse_field(parameters: &OrderedDocument, key: &str, default: &[&str], class: &str) -> Result<Vec<String>, TipupError> {
+    match parameters.get(key) {
+        Some(&Bson::Array(ref param_field)) => {
+            let mut field = Vec::new();
+            for x in param_field {
+                match x {
+                    &Bson::String(ref y) => field.push(y.to_owned()),
+                    _ => return Err(TipupError::from(format!("failed to parse {} as String in {}", key, class))),
+                }
+            }
+
+            Ok(field)
+        },
+        None if default.len() > 0 => Ok(default.iter().map(|x| x.to_string()).collect()),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in {}", key, class))),
+    }
+}
+
+//positive integer parameter of an analyzer class, the default if unset
+pub fn parse_size(parameters: &OrderedDocument, key: &str, default: usize, class: &str) -> Result<usize, TipupError> {
+    match parameters.get(key) {
+        Some(&Bson::I32(size)) if size > 0 => Ok(size as usize),
+        None => Ok(default),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in {} as positive integer", key, class))),
+    }
+}
+
+//value of a field nested by the field path, e.g. ["certificate", "not_after"]
+pub fn get_bson<'a>(field: &[String], document: &'a OrderedDocument) -> Option<&'a Bson> {
+    let mut index_document = document;
+    for (i, variable) in field.iter().enumerate() {
+        match index_document.get(variable) {
+            Some(&Bson::Document(ref document)) if i < field.len() - 1 => index_document = document,
+            Some(value) if i == field.len() - 1 => return Some(value),
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+//discrete value of a nested field, categories may be strings, integers like status codes, or booleans
+pub fn get_category(field: &Vec<String>, document: &OrderedDocument) -> Option<String> {
+    match get_bson(field, document) {
+        Some(&Bson::String(ref s)) => Some(s.to_owned()),
+        Some(&Bson::I32(i)) => Some(i.to_string()),
+        Some(&Bson::I64(i)) => Some(i.to_string()),
+        Some(&Bson::Boolean(b)) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+//numeric value of a top level field
+pub fn get_f64(document: &OrderedDocument, key: &str) -> Option<f64> {
+    match document.get(key) {
+        Some(&Bson::FloatingPoint(f)) => Some(f),
+        Some(&Bson::I32(i)) => Some(i as f64),
+        Some(&Bson::I64(i)) => Some(i as f64),
+        _ => None,
+    }
 }
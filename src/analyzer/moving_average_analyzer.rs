@@ -0,0 +1,122 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+//flags results deviating from the mean of a sliding window of preceding results by more than a
+//factor of that mean, e.g. a factor of 0.5 flags values 50% above or below the moving average
+pub struct MovingAverageAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    window_size: usize,
+    minimum_samples: usize,
+    factor: f64,
+    windows: HashMap<(String, String), VecDeque<f64>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl MovingAverageAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<MovingAverageAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in MovingAverageAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in MovingAverageAnalyzer")),
+        };
+
+        let window_size = try!(parse_size(parameters, "window_size", 20, "MovingAverageAnalyzer"));
+        let minimum_samples = try!(parse_size(parameters, "minimum_samples", 10, "MovingAverageAnalyzer"));
+
+        let factor = match parameters.get("factor") {
+            Some(&Bson::FloatingPoint(factor)) if factor > 0.0 => factor,
+            Some(&Bson::I32(factor)) if factor > 0 => factor as f64,
+            None => 0.5,
+            _ => return Err(TipupError::from("failed to parse factor parameter in MovingAverageAnalyzer as positive number")),
+        };
+
+        if minimum_samples > window_size {
+            return Err(TipupError::from("MovingAverageAnalyzer requires minimum_samples no greater than window_size"));
+        }
+
+        Ok(
+            MovingAverageAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                window_size: window_size,
+                minimum_samples: minimum_samples,
+                factor: factor,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for MovingAverageAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        //compare against preceding results before the value joins the window
+        let window = self.windows.entry((hostname, domain)).or_insert(VecDeque::new());
+        let samples = window.len();
+        let mean = window.iter().sum::<f64>() / samples.max(1) as f64;
+        window.push_back(value);
+        if window.len() > self.window_size {
+            window.pop_front();
+        }
+
+        trace.statistic("value", value);
+        trace.statistic("samples", samples as i64);
+        if samples < self.minimum_samples {
+            trace.decision("no flag: insufficient samples");
+            return Ok(());
+        }
+
+        let deviation = (value - mean).abs();
+        trace.statistic("moving_average", mean);
+        trace.statistic("deviation", deviation);
+        if deviation <= mean.abs() * self.factor {
+            trace.decision("no flag: value within factor of moving average");
+            return Ok(());
+        }
+
+        trace.decision("flag: value deviates from moving average");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("moving_average".to_owned(), mean.to_string());
+        flag.evidence.insert("deviation".to_owned(), deviation.to_string());
+        flag.evidence.insert("factor".to_owned(), self.factor.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.windows.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
@@ -0,0 +1,150 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_field, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct LossWindow {
+    packets: VecDeque<(f64, f64)>,
+    exceeded: usize,
+    flagged: bool,
+}
+
+//computes the ratio of packets lost over a window of ping-style results reporting packets sent
+//and received, flagging loss sustained above a percentage for consecutive results rather than
+//single lossy probes
+pub struct PacketLossAnalyzer {
+    name: String,
+    status: String,
+    sent_field: Vec<String>,
+    received_field: Vec<String>,
+    window_size: usize,
+    max_loss: f64,
+    sustain: usize,
+    windows: HashMap<(String, String), LossWindow>,
+    flag_tx: Sender<Flag>,
+}
+
+impl PacketLossAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<PacketLossAnalyzer, TipupError> {
+        let sent_field = try!(parse_field(parameters, "sent_field", &["sent"], "PacketLossAnalyzer"));
+        let received_field = try!(parse_field(parameters, "received_field", &["received"], "PacketLossAnalyzer"));
+
+        //number of results the loss ratio is computed over
+        let window_size = match parameters.get("window_size") {
+            Some(&Bson::I32(window_size)) if window_size > 0 => window_size as usize,
+            None => 10,
+            _ => return Err(TipupError::from("failed to parse window_size parameter in PacketLossAnalyzer as positive integer")),
+        };
+
+        //percentage of packets lost over the window at which loss is exceeded
+        let max_loss = match parameters.get("max_loss") {
+            Some(&Bson::FloatingPoint(max_loss)) if max_loss >= 0.0 && max_loss < 100.0 => max_loss,
+            Some(&Bson::I32(max_loss)) if max_loss >= 0 && max_loss < 100 => max_loss as f64,
+            None => 5.0,
+            _ => return Err(TipupError::from("failed to parse max_loss parameter in PacketLossAnalyzer, expected a percentage in [0, 100)")),
+        };
+
+        //consecutive results the loss must remain exceeded for before flagging
+        let sustain = match parameters.get("sustain") {
+            Some(&Bson::I32(sustain)) if sustain > 0 => sustain as usize,
+            None => 3,
+            _ => return Err(TipupError::from("failed to parse sustain parameter in PacketLossAnalyzer as positive integer")),
+        };
+
+        Ok(
+            PacketLossAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                sent_field: sent_field,
+                received_field: received_field,
+                window_size: window_size,
+                max_loss: max_loss,
+                sustain: sustain,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for PacketLossAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        //failed probes may omit received packets entirely
+        let (sent, received) = match (get_value(&self.sent_field, document), get_value(&self.received_field, document)) {
+            (Some(sent), received) if sent > 0.0 => (sent, received.unwrap_or(0.0).max(0.0).min(sent)),
+            _ => {
+                trace.decision("skipped: no packets sent");
+                return Ok(());
+            },
+        };
+
+        let window = self.windows.entry((hostname, domain)).or_insert(
+            LossWindow {
+                packets: VecDeque::new(),
+                exceeded: 0,
+                flagged: false,
+            }
+        );
+
+        window.packets.push_back((sent, received));
+        if window.packets.len() > self.window_size {
+            window.packets.pop_front();
+        }
+
+        let total_sent = window.packets.iter().map(|&(x, _)| x).sum::<f64>();
+        let total_received = window.packets.iter().map(|&(_, x)| x).sum::<f64>();
+        let loss = 100.0 * (total_sent - total_received) / total_sent;
+        trace.statistic("sent", sent);
+        trace.statistic("received", received);
+        trace.statistic("loss", loss);
+
+        if window.packets.len() < self.window_size {
+            trace.decision("no flag: filling window");
+            return Ok(());
+        }
+
+        if loss <= self.max_loss {
+            window.exceeded = 0;
+            window.flagged = false;
+            trace.decision("no flag: loss within maximum");
+            return Ok(());
+        }
+
+        window.exceeded += 1;
+        trace.statistic("exceeded", window.exceeded as i64);
+        if window.exceeded < self.sustain {
+            trace.decision("no flag: loss not yet sustained");
+            return Ok(());
+        }
+
+        if window.flagged {
+            trace.decision("no flag: sustained loss already flagged");
+            return Ok(());
+        }
+
+        window.flagged = true;
+        trace.decision("flag: sustained loss exceeds maximum");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("loss".to_owned(), loss.to_string());
+        flag.evidence.insert("max_loss".to_owned(), self.max_loss.to_string());
+        flag.evidence.insert("sent".to_owned(), total_sent.to_string());
+        flag.evidence.insert("received".to_owned(), total_received.to_string());
+        flag.evidence.insert("sustained_results".to_owned(), window.exceeded.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.windows.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
@@ -0,0 +1,180 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, parse_size, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+struct PercentileWindows {
+    recent: VecDeque<f64>,
+    baseline: VecDeque<f64>,
+    shifted: bool,
+}
+
+//tracks a percentile, e.g. p95 or p99, of the most recent results against the same percentile of
+//the older results preceding them, flagging when the recent percentile shifts beyond a fraction
+//of the baseline, useful for tail latency regressions that leave the mean untouched
+pub struct PercentileAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    percentile: f64,
+    window_size: usize,
+    baseline_size: usize,
+    max_increase: f64,
+    max_decrease: Option<f64>,
+    windows: HashMap<(String, String), PercentileWindows>,
+    flag_tx: Sender<Flag>,
+}
+
+impl PercentileAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<PercentileAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in PercentileAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in PercentileAnalyzer")),
+        };
+
+        let percentile = match parameters.get("percentile") {
+            Some(&Bson::FloatingPoint(percentile)) if percentile > 0.0 && percentile <= 100.0 => percentile,
+            Some(&Bson::I32(percentile)) if percentile > 0 && percentile <= 100 => percentile as f64,
+            None => 95.0,
+            _ => return Err(TipupError::from("failed to parse percentile parameter in PercentileAnalyzer, expected a value in (0, 100]")),
+        };
+
+        //number of recent results the tracked percentile is computed over
+        let window_size = try!(parse_size(parameters, "window_size", 100, "PercentileAnalyzer"));
+        //number of older results forming the baseline percentile
+        let baseline_size = try!(parse_size(parameters, "baseline_size", 500, "PercentileAnalyzer"));
+
+        //fraction of the baseline percentile the recent percentile may rise by
+        let max_increase = match parameters.get("max_increase") {
+            Some(&Bson::FloatingPoint(max_increase)) if max_increase > 0.0 => max_increase,
+            Some(&Bson::I32(max_increase)) if max_increase > 0 => max_increase as f64,
+            None => 0.5,
+            _ => return Err(TipupError::from("failed to parse max_increase parameter in PercentileAnalyzer as positive number")),
+        };
+
+        //fraction of the baseline percentile the recent percentile may fall by, unbounded by default
+        let max_decrease = match parameters.get("max_decrease") {
+            Some(&Bson::FloatingPoint(max_decrease)) if max_decrease > 0.0 && max_decrease < 1.0 => Some(max_decrease),
+            None => None,
+            _ => return Err(TipupError::from("failed to parse max_decrease parameter in PercentileAnalyzer, expected a value between 0 and 1")),
+        };
+
+        if baseline_size < window_size {
+            return Err(TipupError::from("PercentileAnalyzer requires baseline_size no less than window_size"));
+        }
+
+        Ok(
+            PercentileAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                percentile: percentile,
+                window_size: window_size,
+                baseline_size: baseline_size,
+                max_increase: max_increase,
+                max_decrease: max_decrease,
+                windows: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for PercentileAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        //results age out of the recent window into the baseline
+        let windows = self.windows.entry((hostname, domain)).or_insert(PercentileWindows { recent: VecDeque::new(), baseline: VecDeque::new(), shifted: false });
+        windows.recent.push_back(value);
+        if windows.recent.len() > self.window_size {
+            windows.baseline.push_back(windows.recent.pop_front().unwrap());
+            if windows.baseline.len() > self.baseline_size {
+                windows.baseline.pop_front();
+            }
+        }
+
+        trace.statistic("value", value);
+        trace.statistic("baseline_samples", windows.baseline.len() as i64);
+        if windows.baseline.len() < self.window_size {
+            trace.decision("no flag: insufficient samples");
+            return Ok(());
+        }
+
+        let recent_percentile = percentile(&windows.recent, self.percentile);
+        let baseline_percentile = percentile(&windows.baseline, self.percentile);
+        trace.statistic("recent_percentile", recent_percentile);
+        trace.statistic("baseline_percentile", baseline_percentile);
+
+        let upper_bound = baseline_percentile + (baseline_percentile.abs() * self.max_increase);
+        let lower_bound = self.max_decrease.map(|x| baseline_percentile - (baseline_percentile.abs() * x));
+        let shift = if recent_percentile > upper_bound {
+            "increase"
+        } else if lower_bound.map_or(false, |x| recent_percentile < x) {
+            "decrease"
+        } else {
+            windows.shifted = false;
+            trace.decision("no flag: percentile within bounds");
+            return Ok(());
+        };
+
+        //flag once per shift rather than for every result while the recent window remains shifted
+        if windows.shifted {
+            trace.decision("no flag: percentile shift already flagged");
+            return Ok(());
+        }
+
+        windows.shifted = true;
+        trace.decision(&format!("flag: percentile {}", shift));
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("percentile".to_owned(), self.percentile.to_string());
+        flag.evidence.insert("shift".to_owned(), shift.to_owned());
+        flag.evidence.insert("recent_percentile".to_owned(), recent_percentile.to_string());
+        flag.evidence.insert("baseline_percentile".to_owned(), baseline_percentile.to_string());
+        flag.evidence.insert("upper_bound".to_owned(), upper_bound.to_string());
+        if let Some(lower_bound) = lower_bound {
+            flag.evidence.insert("lower_bound".to_owned(), lower_bound.to_string());
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.windows.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+fn percentile(values: &VecDeque<f64>, percentile: f64) -> f64 {
+    //nearest-rank percentile
+    let mut sorted_values: Vec<f64> = values.iter().cloned().collect();
+    sorted_values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = ((percentile / 100.0) * sorted_values.len() as f64).ceil() as usize;
+    sorted_values[rank.saturating_sub(1).min(sorted_values.len() - 1)]
+}
@@ -0,0 +1,136 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+//flags results whose change from the previous result of the same key, per time unit, exceeds a
+//maximum slope, catching sudden latency or loss jumps while absolute values remain in range
+pub struct RateOfChangeAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    max_slope: f64,
+    time_unit: f64,
+    direction: String,
+    previous: HashMap<(String, String), (i64, f64)>,
+    flag_tx: Sender<Flag>,
+}
+
+impl RateOfChangeAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<RateOfChangeAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in RateOfChangeAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in RateOfChangeAnalyzer")),
+        };
+
+        let max_slope = match parameters.get("max_slope") {
+            Some(&Bson::FloatingPoint(max_slope)) if max_slope > 0.0 => max_slope,
+            Some(&Bson::I32(max_slope)) if max_slope > 0 => max_slope as f64,
+            _ => return Err(TipupError::from("failed to parse max_slope parameter in RateOfChangeAnalyzer as positive number")),
+        };
+
+        //seconds the slope is measured over, e.g. 60 for change per minute
+        let time_unit = match parameters.get("time_unit") {
+            Some(&Bson::I32(time_unit)) if time_unit > 0 => time_unit as f64,
+            None => 1.0,
+            _ => return Err(TipupError::from("failed to parse time_unit parameter in RateOfChangeAnalyzer as positive seconds")),
+        };
+
+        let direction = match parameters.get("direction") {
+            Some(&Bson::String(ref direction)) if ["increase", "decrease", "both"].contains(&direction.as_str()) => direction.to_owned(),
+            None => "both".to_owned(),
+            _ => return Err(TipupError::from("failed to parse direction parameter in RateOfChangeAnalyzer, expected 'increase', 'decrease', or 'both'")),
+        };
+
+        Ok(
+            RateOfChangeAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                max_slope: max_slope,
+                time_unit: time_unit,
+                direction: direction,
+                previous: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for RateOfChangeAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let (previous_timestamp, previous_value) = match self.previous.get(&(hostname.clone(), domain.clone())) {
+            Some(&previous) => previous,
+            None => {
+                self.previous.insert((hostname, domain), (timestamp, value));
+                trace.decision("no flag: no previous result");
+                return Ok(());
+            },
+        };
+
+        //late results and results sharing a timestamp have no defined slope
+        if timestamp <= previous_timestamp {
+            trace.decision("skipped: result not newer than previous result");
+            return Ok(());
+        }
+
+        self.previous.insert((hostname, domain), (timestamp, value));
+        let slope = (value - previous_value) * self.time_unit / (timestamp - previous_timestamp) as f64;
+        trace.statistic("value", value);
+        trace.statistic("previous_value", previous_value);
+        trace.statistic("slope", slope);
+
+        let exceeded = match self.direction.as_str() {
+            "increase" => slope > self.max_slope,
+            "decrease" => -slope > self.max_slope,
+            _ => slope.abs() > self.max_slope,
+        };
+
+        if !exceeded {
+            trace.decision("no flag: slope within maximum");
+            return Ok(());
+        }
+
+        trace.decision("flag: slope exceeds maximum");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("previous_value".to_owned(), previous_value.to_string());
+        flag.evidence.insert("elapsed_seconds".to_owned(), (timestamp - previous_timestamp).to_string());
+        flag.evidence.insert("slope".to_owned(), slope.to_string());
+        flag.evidence.insert("max_slope".to_owned(), self.max_slope.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.previous.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
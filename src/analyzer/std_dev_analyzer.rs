@@ -1,24 +1,51 @@
 use bson::Bson;
 use bson::ordered::OrderedDocument;
 use chan::Sender;
+use mongodb::db::{Database, ThreadedDatabase};
 
-use analyzer::Analyzer;
+use analyzer::{get_value, Analyzer, Trace};
 use error::TipupError;
 use flag_manager::Flag;
 use result_window::{ResultWindow, VariableWindow};
 
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
+//welford's running mean and sum of squared deviations
+struct RunningStatistics {
+    count: usize,
+    mean: f64,
+    m2: f64,
+}
+
+//source of the mean and standard deviation values are compared against, either the shared result
+//window or running statistics per (hostname, domain) which need no history in memory
+enum Baseline {
+    Window(Arc<RwLock<VariableWindow>>),
+    Running {
+        warmup: usize,
+        statistics: HashMap<(String, String), RunningStatistics>,
+    },
+}
+
+struct AdaptiveThreshold {
+    step: f64,
+    maximum: f64,
+    thresholds: HashMap<(String, String), f64>,
+}
+
 pub struct StdDevAnalyzer {
     name: String,
     status: String,
     variable_name: Vec<String>,
-    variable_window: Arc<RwLock<VariableWindow>>,
+    baseline: Baseline,
+    threshold: f64,
+    adaptive_threshold: Option<AdaptiveThreshold>,
     flag_tx: Sender<Flag>,
 }
 
 impl StdDevAnalyzer {
-    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, result_window: Arc<RwLock<ResultWindow>>, flag_tx: Sender<Flag>, ) -> Result<StdDevAnalyzer, TipupError> {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, key_fields: &Vec<Vec<String>>, result_window: Arc<RwLock<ResultWindow>>, flag_tx: Sender<Flag>, ) -> Result<StdDevAnalyzer, TipupError> {
         //parse parameters to retrieve variable name
         let variable_name = match parameters.get("variable_name") {
             Some(&Bson::Array(ref param_variable_name)) => {
@@ -35,18 +62,87 @@ impl StdDevAnalyzer {
             _ => return Err(TipupError::from("failed to parse variable name parameter in StdDevAnalyzer")),
         };
 
-        let variable_window;
-        {
-            let mut result_window = result_window.write().unwrap();
-            variable_window = try!(result_window.register_variable(&variable_name));
+        let threshold = match parameters.get("threshold") {
+            Some(&Bson::FloatingPoint(threshold)) => threshold,
+            Some(&Bson::I32(threshold)) => threshold as f64,
+            None => 1.5,
+            _ => return Err(TipupError::from("failed to parse threshold parameter in StdDevAnalyzer")),
+        };
+
+        if threshold <= 0.0 {
+            return Err(TipupError::from(format!("threshold parameter in StdDevAnalyzer must be positive, found {}", threshold)));
         }
 
+        //adaptive thresholds widen per key on false positive feedback
+        let adaptive_threshold = match parameters.get("adaptive") {
+            Some(&Bson::Document(ref adaptive)) => {
+                let step = match adaptive.get("step") {
+                    Some(&Bson::FloatingPoint(step)) => step,
+                    None => 0.25,
+                    _ => return Err(TipupError::from("failed to parse adaptive step parameter in StdDevAnalyzer")),
+                };
+
+                let maximum = match adaptive.get("maximum") {
+                    Some(&Bson::FloatingPoint(maximum)) => maximum,
+                    Some(&Bson::I32(maximum)) => maximum as f64,
+                    None => threshold * 2.0,
+                    _ => return Err(TipupError::from("failed to parse adaptive maximum parameter in StdDevAnalyzer")),
+                };
+
+                if step < 0.0 || maximum < threshold {
+                    return Err(TipupError::from("adaptive parameters in StdDevAnalyzer require a non-negative step and a maximum no less than threshold"));
+                }
+
+                Some(
+                    AdaptiveThreshold {
+                        step: step,
+                        maximum: maximum,
+                        thresholds: HashMap::new(),
+                    }
+                )
+            },
+            None => None,
+            _ => return Err(TipupError::from("failed to parse adaptive parameter in StdDevAnalyzer")),
+        };
+
+        let method = match parameters.get("method") {
+            Some(&Bson::String(ref method)) if method == "window" || method == "running" => method.to_owned(),
+            None => "window".to_owned(),
+            _ => return Err(TipupError::from("failed to parse method parameter in StdDevAnalyzer, expected 'window' or 'running'")),
+        };
+
+        let baseline = match method.as_ref() {
+            "running" => {
+                //results observed per key before flagging
+                let warmup = match parameters.get("warmup") {
+                    Some(&Bson::I32(warmup)) if warmup > 1 => warmup as usize,
+                    None => 30,
+                    _ => return Err(TipupError::from("failed to parse warmup parameter in StdDevAnalyzer as integer greater than 1")),
+                };
+
+                Baseline::Running {
+                    warmup: warmup,
+                    statistics: HashMap::new(),
+                }
+            },
+            _ => {
+                if parameters.contains_key("warmup") {
+                    return Err(TipupError::from("warmup parameter in StdDevAnalyzer requires the 'running' method"));
+                }
+
+                let mut result_window = result_window.write().unwrap();
+                Baseline::Window(try!(result_window.register_variable(&variable_name, key_fields)))
+            },
+        };
+
         Ok(
             StdDevAnalyzer {
                 name: name.to_owned(),
                 status: status.to_owned(),
                 variable_name: variable_name,
-                variable_window: variable_window,
+                baseline: baseline,
+                threshold: threshold,
+                adaptive_threshold: adaptive_threshold,
                 flag_tx: flag_tx,
             }
         )
@@ -54,66 +150,142 @@ impl StdDevAnalyzer {
 }
 
 impl Analyzer for StdDevAnalyzer {
-    fn process_measurement(&mut self, document: &OrderedDocument) -> Result<(), TipupError> {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
         //retrieve variables from document
-        let hostname = match document.get("hostname") {
+        let hostname = match document.get("vantage_hostname") {
             Some(&Bson::String(ref hostname)) => hostname.to_owned(),
             _ => return Ok(()),
         };
 
-        let url = match document.get("url") {
-            Some(&Bson::String(ref url)) => url.to_owned(),
+        let domain = match document.get("measurement_domain") {
+            Some(&Bson::String(ref domain)) => domain.to_owned(),
             _ => return Ok(()),
         };
 
         let value = match get_value(&self.variable_name, document) {
             Some(value) => value,
+            None => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        let threshold = match self.adaptive_threshold {
+            Some(ref adaptive_threshold) => *adaptive_threshold.thresholds.get(&(hostname.clone(), domain.clone())).unwrap_or(&self.threshold),
+            None => self.threshold,
+        };
+
+        let (mean, std_dev, samples) = match self.baseline {
+            Baseline::Window(ref variable_window) => {
+                //get list of values from result window
+                let variable_window = variable_window.read().unwrap();
+                let values: &Vec<f64> = match variable_window.get_values(&hostname, &domain) {
+                    Some(values) if values.len() > 0 => values,
+                    _ => {
+                        trace.decision("skipped: no values in result window");
+                        return Ok(());
+                    },
+                };
+
+                //compute standard deviation of variable
+                let mut mean = 0.0;
+                for v in values.iter() {
+                    mean += *v;
+                }
+                mean /= values.len() as f64;
+
+                let mut std_dev = 0.0;
+                for v in values.iter() {
+                    std_dev += (*v - mean).powf(2.0);
+                }
+                std_dev = (std_dev / values.len() as f64).sqrt();
+
+                (mean, std_dev, values.len())
+            },
+            Baseline::Running { warmup, ref mut statistics } => {
+                //compare against the preceding values before folding the value into the statistics
+                let statistics = statistics.entry((hostname, domain)).or_insert(RunningStatistics { count: 0, mean: 0.0, m2: 0.0 });
+                let (count, mean, std_dev) = (statistics.count, statistics.mean, (statistics.m2 / statistics.count.max(1) as f64).sqrt());
+                statistics.count += 1;
+                let delta = value - statistics.mean;
+                statistics.mean += delta / statistics.count as f64;
+                statistics.m2 += delta * (value - statistics.mean);
+
+                if count < warmup {
+                    trace.statistic("samples", count as i64);
+                    trace.decision("no flag: insufficient samples for warmup");
+                    return Ok(());
+                }
+
+                (mean, std_dev, count)
+            },
+        };
+
+        trace.statistic("value", value);
+        trace.statistic("samples", samples as i64);
+        trace.statistic("mean", mean);
+        trace.statistic("std_dev", std_dev);
+        trace.statistic("threshold", threshold);
+
+        //if value is greater than threshold standard deviations raise warning
+        if value > mean + (threshold * std_dev) {
+            trace.decision("flag: value exceeds mean plus threshold standard deviations");
+            let flag = try!(Flag::new(document, &self.status, &self.name));
+            self.flag_tx.send(flag);
+        } else {
+            trace.decision("no flag: value within threshold standard deviations");
+        }
+
+        Ok(())
+    }
+
+    fn refresh(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        let adaptive_threshold = match self.adaptive_threshold {
+            Some(ref mut adaptive_threshold) => adaptive_threshold,
             None => return Ok(()),
         };
 
-        {
-            //get list of values from result window
-            let variable_window = self.variable_window.read().unwrap();
-            let values: &Vec<f64> = match variable_window.get_values(&hostname, &url) {
-                Some(values) => values,
-                None => return Ok(()),
+        //count false positive feedback per (hostname, domain)
+        let match_doc = doc!("analyzer" => (self.name.clone()), "feedback" => "false_positive");
+        let id_doc = doc!("hostname" => "$hostname", "domain" => "$domain");
+        let group_doc = doc!("_id" => id_doc, "count" => { "$sum" => 1 });
+        let aggregate_doc = vec!(
+            doc!("$match" => match_doc),
+            doc!("$group" => group_doc),
+        );
+
+        adaptive_threshold.thresholds.clear();
+        for document in try!(proddle_db.collection("flags").aggregate(aggregate_doc, None)) {
+            let document = try!(document);
+
+            let id_document = match document.get("_id") {
+                Some(&Bson::Document(ref id_document)) => id_document,
+                _ => continue,
             };
 
-            //compute standard deviation of variable
-            let mut mean = 0.0;
-            for v in values.iter() {
-                mean += *v;
-            }
-            mean /= values.len() as f64;
+            let (hostname, domain) = match (id_document.get("hostname"), id_document.get("domain")) {
+                (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+                _ => continue,
+            };
 
-            let mut std_dev = 0.0;
-            for v in values.iter() {
-                std_dev += (*v - mean).powf(2.0);
-            }
-            std_dev = std_dev.sqrt();
+            let count = match document.get("count") {
+                Some(&Bson::I32(count)) => count as f64,
+                Some(&Bson::I64(count)) => count as f64,
+                _ => continue,
+            };
 
-            //if value is greater than 1.5 standard deviations raise warning
-            if value > mean + (1.5 * std_dev) {
-                let flag = try!(Flag::new(document, &self.status, &self.name));
-                self.flag_tx.send(flag);
-            }
+            //widen threshold by step for each false positive up to maximum
+            let threshold = (self.threshold + (adaptive_threshold.step * count)).min(adaptive_threshold.maximum);
+            adaptive_threshold.thresholds.insert((hostname, domain), threshold);
         }
 
         Ok(())
     }
-}
 
-fn get_value(variable_name: &Vec<String>, document: &OrderedDocument) -> Option<f64> {
-    let mut index_document = document;
-    for variable in variable_name {
-        match index_document.get(variable) {
-            Some(&Bson::Document(ref document)) => index_document = document,
-            Some(&Bson::FloatingPoint(f)) => return Some(f),
-            Some(&Bson::I32(i)) => return Some(i as f64),
-            Some(&Bson::I64(i)) => return Some(i as f64),
-            _ => return None,
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        //windowed values are reset through the result window
+        if let Baseline::Running { ref mut statistics, .. } = self.baseline {
+            statistics.remove(&(hostname.to_owned(), domain.to_owned()));
         }
     }
-
-    None
 }
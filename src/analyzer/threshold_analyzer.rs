@@ -0,0 +1,125 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+enum Operator {
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operator {
+    fn parse(operator: &str) -> Option<Operator> {
+        match operator {
+            ">" | "gt" => Some(Operator::GreaterThan),
+            ">=" | "gte" => Some(Operator::GreaterThanOrEqual),
+            "<" | "lt" => Some(Operator::LessThan),
+            "<=" | "lte" => Some(Operator::LessThanOrEqual),
+            "==" | "eq" => Some(Operator::Equal),
+            "!=" | "ne" => Some(Operator::NotEqual),
+            _ => None,
+        }
+    }
+
+    fn compare(&self, value: f64, threshold: f64) -> bool {
+        match *self {
+            Operator::GreaterThan => value > threshold,
+            Operator::GreaterThanOrEqual => value >= threshold,
+            Operator::LessThan => value < threshold,
+            Operator::LessThanOrEqual => value <= threshold,
+            Operator::Equal => value == threshold,
+            Operator::NotEqual => value != threshold,
+        }
+    }
+}
+
+//flags results whose variable compares against a fixed value, e.g. ["remote_rtt"] > 500
+pub struct ThresholdAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    operator: Operator,
+    operator_string: String,
+    value: f64,
+    flag_tx: Sender<Flag>,
+}
+
+impl ThresholdAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<ThresholdAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in ThresholdAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in ThresholdAnalyzer")),
+        };
+
+        let (operator, operator_string) = match parameters.get("operator") {
+            Some(&Bson::String(ref operator)) => match Operator::parse(operator) {
+                Some(x) => (x, operator.to_owned()),
+                None => return Err(TipupError::from(format!("unknown operator '{}' in ThresholdAnalyzer, expected one of >, >=, <, <=, ==, !=", operator))),
+            },
+            _ => return Err(TipupError::from("failed to parse operator parameter in ThresholdAnalyzer")),
+        };
+
+        let value = match parameters.get("value") {
+            Some(&Bson::FloatingPoint(value)) => value,
+            Some(&Bson::I32(value)) => value as f64,
+            Some(&Bson::I64(value)) => value as f64,
+            _ => return Err(TipupError::from("failed to parse value parameter in ThresholdAnalyzer")),
+        };
+
+        Ok(
+            ThresholdAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                operator: operator,
+                operator_string: operator_string,
+                value: value,
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for ThresholdAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) => value,
+            None => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("value", value);
+        trace.statistic("threshold", self.value);
+        if !self.operator.compare(value, self.value) {
+            trace.decision("no flag: value within threshold");
+            return Ok(());
+        }
+
+        trace.decision(&format!("flag: value {} {}", self.operator_string, self.value));
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("operator".to_owned(), self.operator_string.to_owned());
+        flag.evidence.insert("threshold".to_owned(), self.value.to_string());
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+}
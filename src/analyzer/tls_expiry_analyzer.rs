@@ -0,0 +1,183 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+use time;
+
+use analyzer::{get_bson, parse_field, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+//formats of certificate expiry dates recorded as strings, e.g. by openssl
+const NOT_AFTER_FORMATS: [&'static str; 4] = ["%Y-%m-%dT%H:%M:%SZ", "%Y-%m-%d %H:%M:%S", "%b %d %H:%M:%S %Y GMT", "%Y%m%d%H%M%SZ"];
+
+struct Certificate {
+    fingerprint: Option<String>,
+    not_after: i64,
+    expiry_flagged: bool,
+}
+
+//flags https results whose certificate expires within a number of days and certificates whose
+//fingerprint changed between consecutive results of a target, unless the change renewed a
+//certificate which was itself nearing expiry
+pub struct TlsExpiryAnalyzer {
+    name: String,
+    status: String,
+    not_after_field: Vec<String>,
+    fingerprint_field: Vec<String>,
+    expiry_days: i64,
+    renewal_days: i64,
+    flag_fingerprint_changes: bool,
+    certificates: HashMap<(String, String), Certificate>,
+    flag_tx: Sender<Flag>,
+}
+
+impl TlsExpiryAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<TlsExpiryAnalyzer, TipupError> {
+        let not_after_field = try!(parse_field(parameters, "not_after_field", &["certificate", "not_after"], "TlsExpiryAnalyzer"));
+        let fingerprint_field = try!(parse_field(parameters, "fingerprint_field", &["certificate", "fingerprint"], "TlsExpiryAnalyzer"));
+
+        //days before expiry at which certificates are flagged
+        let expiry_days = match parameters.get("expiry_days") {
+            Some(&Bson::I32(expiry_days)) if expiry_days > 0 => expiry_days as i64,
+            None => 14,
+            _ => return Err(TipupError::from("failed to parse expiry_days parameter in TlsExpiryAnalyzer as positive integer")),
+        };
+
+        //days before expiry within which a changed fingerprint is an expected renewal
+        let renewal_days = match parameters.get("renewal_days") {
+            Some(&Bson::I32(renewal_days)) if renewal_days >= 0 => renewal_days as i64,
+            None => 30,
+            _ => return Err(TipupError::from("failed to parse renewal_days parameter in TlsExpiryAnalyzer as non-negative integer")),
+        };
+
+        let flag_fingerprint_changes = match parameters.get("flag_fingerprint_changes") {
+            Some(&Bson::Boolean(flag_fingerprint_changes)) => flag_fingerprint_changes,
+            None => true,
+            _ => return Err(TipupError::from("failed to parse flag_fingerprint_changes parameter in TlsExpiryAnalyzer as boolean")),
+        };
+
+        Ok(
+            TlsExpiryAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                not_after_field: not_after_field,
+                fingerprint_field: fingerprint_field,
+                expiry_days: expiry_days,
+                renewal_days: renewal_days,
+                flag_fingerprint_changes: flag_fingerprint_changes,
+                certificates: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for TlsExpiryAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let not_after = match get_bson(&self.not_after_field, document).and_then(parse_not_after) {
+            Some(not_after) => not_after,
+            None => {
+                trace.decision("skipped: certificate expiry not present");
+                return Ok(());
+            },
+        };
+
+        let fingerprint = match get_bson(&self.fingerprint_field, document) {
+            Some(&Bson::String(ref fingerprint)) => Some(fingerprint.to_lowercase().replace(":", "")),
+            _ => None,
+        };
+
+        //expiry is measured from the result so backlogged results are judged as of their timestamp
+        let days_remaining = (not_after - timestamp) as f64 / 86400.0;
+        trace.statistic("days_remaining", days_remaining);
+
+        let mut reasons = Vec::new();
+        let previous = self.certificates.remove(&(hostname.clone(), domain.clone()));
+        let mut certificate = Certificate {
+            fingerprint: fingerprint.clone(),
+            not_after: not_after,
+            expiry_flagged: false,
+        };
+
+        let mut previous_fingerprint = None;
+        if let Some(previous) = previous {
+            let changed = match (&previous.fingerprint, &fingerprint) {
+                (&Some(ref x), &Some(ref y)) => x != y,
+                _ => previous.not_after != not_after,
+            };
+
+            if changed {
+                let renewal = not_after > previous.not_after && previous.not_after - timestamp <= self.renewal_days * 86400;
+                trace.statistic("renewal", renewal);
+                if self.flag_fingerprint_changes && !renewal {
+                    reasons.push("fingerprint_changed");
+                }
+
+                previous_fingerprint = previous.fingerprint;
+            } else {
+                //an unchanged certificate is only flagged as expiring once
+                certificate.expiry_flagged = previous.expiry_flagged;
+            }
+        }
+
+        if days_remaining < self.expiry_days as f64 && !certificate.expiry_flagged {
+            certificate.expiry_flagged = true;
+            reasons.push(match days_remaining < 0.0 {
+                true => "expired",
+                false => "expiring",
+            });
+        }
+
+        self.certificates.insert((hostname, domain), certificate);
+        if reasons.len() == 0 {
+            trace.decision("no flag: certificate neither expiring nor unexpectedly changed");
+            return Ok(());
+        }
+
+        trace.decision(&format!("flag: certificate {}", reasons.join(" and ")));
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("reason".to_owned(), reasons.join(","));
+        flag.evidence.insert("not_after".to_owned(), not_after.to_string());
+        flag.evidence.insert("days_remaining".to_owned(), format!("{:.1}", days_remaining));
+        flag.evidence.insert("expiry_days".to_owned(), self.expiry_days.to_string());
+        if let Some(fingerprint) = fingerprint {
+            flag.evidence.insert("fingerprint".to_owned(), fingerprint);
+        }
+
+        if let Some(previous_fingerprint) = previous_fingerprint {
+            flag.evidence.insert("previous_fingerprint".to_owned(), previous_fingerprint);
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.certificates.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+//certificate expiry as seconds since the unix epoch, either recorded as such or as a date string
+fn parse_not_after(value: &Bson) -> Option<i64> {
+    match value {
+        &Bson::I64(not_after) => Some(not_after),
+        &Bson::I32(not_after) => Some(not_after as i64),
+        &Bson::UtcDatetime(ref not_after) => Some(not_after.timestamp()),
+        &Bson::String(ref not_after) => {
+            //openssl pads single digit days with a second space
+            let not_after = not_after.split_whitespace().collect::<Vec<&str>>().join(" ");
+            NOT_AFTER_FORMATS.iter()
+                .filter_map(|x| time::strptime(&not_after, x).ok())
+                .next()
+                .map(|x| x.to_timespec().sec)
+        },
+        _ => None,
+    }
+}
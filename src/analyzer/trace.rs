@@ -0,0 +1,64 @@
+use bson::{Bson, Document};
+use bson::ordered::OrderedDocument;
+
+//records the statistics and decision of a single analyzer evaluation when tracing is enabled,
+//statistics also being collected for analyzers deriving results from them
+pub struct Trace {
+    enabled: bool,
+    collect_statistics: bool,
+    statistics: Document,
+    decision: Option<String>,
+}
+
+impl Trace {
+    pub fn new(enabled: bool) -> Trace {
+        Trace {
+            enabled: enabled,
+            collect_statistics: false,
+            statistics: Document::new(),
+            decision: None,
+        }
+    }
+
+    pub fn collect_statistics(&mut self) {
+        self.collect_statistics = true;
+    }
+
+    pub fn statistics(&self) -> &Document {
+        &self.statistics
+    }
+
+    pub fn statistic<T: Into<Bson>>(&mut self, name: &str, value: T) {
+        if self.enabled || self.collect_statistics {
+            self.statistics.insert(name, value.into());
+        }
+    }
+
+    pub fn decision(&mut self, decision: &str) {
+        if self.enabled {
+            self.decision = Some(decision.to_owned());
+        }
+    }
+
+    pub fn to_document(self, analyzer: &str, document: &OrderedDocument, timestamp: i64) -> Option<Document> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut trace_document = doc!(
+            "analyzer" => analyzer,
+            "timestamp" => timestamp,
+            "input" => (document.clone()),
+            "statistics" => (self.statistics),
+            "decision" => (self.decision.unwrap_or("no decision recorded".to_owned()))
+        );
+
+        for &(key, field) in [("hostname", "vantage_hostname"), ("domain", "measurement_domain"), ("measurement_id", "_id")].iter() {
+            if let Some(value) = document.get(field) {
+                trace_document.insert(key, value.clone());
+            }
+        }
+
+        Some(trace_document)
+    }
+}
@@ -0,0 +1,221 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{get_value, Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+//least squares fit of values against timestamps relative to the latest, returning the slope per
+//second, the fitted value at the latest timestamp, and the coefficient of determination
+fn fit(points: &VecDeque<(i64, f64)>) -> Option<(f64, f64, f64)> {
+    let latest_timestamp = match points.back() {
+        Some(&(timestamp, _)) => timestamp,
+        None => return None,
+    };
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|x| (x.0 - latest_timestamp) as f64).sum::<f64>() / n;
+    let mean_y = points.iter().map(|x| x.1).sum::<f64>() / n;
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for &(timestamp, value) in points.iter() {
+        let (dx, dy) = ((timestamp - latest_timestamp) as f64 - mean_x, value - mean_y);
+        sxx += dx * dx;
+        sxy += dx * dy;
+        syy += dy * dy;
+    }
+
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let r_squared = match syy > 0.0 {
+        true => (sxy * sxy) / (sxx * syy),
+        false => 1.0,
+    };
+
+    Some((slope, mean_y - (slope * mean_x), r_squared))
+}
+
+//fits a linear trend to the most recent results per key, flagging when the trend predicts the
+//variable breaching a threshold within the horizon, e.g. latency creeping towards an sla limit,
+//for early warning before a threshold analyzer on the same limit would flag
+pub struct TrendAnalyzer {
+    name: String,
+    status: String,
+    variable_name: Vec<String>,
+    threshold: f64,
+    above: bool,
+    window_size: usize,
+    horizon: i64,
+    min_r_squared: f64,
+    histories: HashMap<(String, String), VecDeque<(i64, f64)>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl TrendAnalyzer {
+    pub fn new(name: &str, status: &str, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<TrendAnalyzer, TipupError> {
+        let variable_name = match parameters.get("variable_name") {
+            Some(&Bson::Array(ref param_variable_name)) => {
+                let mut variable_name = Vec::new();
+                for x in param_variable_name {
+                    match x {
+                        &Bson::String(ref y) => variable_name.push(y.to_owned()),
+                        _ => return Err(TipupError::from("failed to parse variable name as String in TrendAnalyzer")),
+                    }
+                }
+
+                variable_name
+            },
+            _ => return Err(TipupError::from("failed to parse variable name parameter in TrendAnalyzer")),
+        };
+
+        let threshold = match parameters.get("threshold") {
+            Some(&Bson::FloatingPoint(threshold)) => threshold,
+            Some(&Bson::I32(threshold)) => threshold as f64,
+            Some(&Bson::I64(threshold)) => threshold as f64,
+            _ => return Err(TipupError::from("failed to parse threshold parameter in TrendAnalyzer")),
+        };
+
+        //whether the threshold is breached by rising above or falling below it
+        let above = match parameters.get("direction") {
+            Some(&Bson::String(ref direction)) if direction == "above" => true,
+            Some(&Bson::String(ref direction)) if direction == "below" => false,
+            None => true,
+            _ => return Err(TipupError::from("failed to parse direction parameter in TrendAnalyzer, expected 'above' or 'below'")),
+        };
+
+        //most recent results the trend is fit to
+        let window_size = match parameters.get("window_size") {
+            Some(&Bson::I32(window_size)) if window_size > 2 => window_size as usize,
+            None => 30,
+            _ => return Err(TipupError::from("failed to parse window_size parameter in TrendAnalyzer, expected an integer over 2")),
+        };
+
+        //seconds ahead of the latest result within which a predicted breach is flagged
+        let horizon = match parameters.get("horizon") {
+            Some(&Bson::I32(horizon)) if horizon > 0 => horizon as i64,
+            Some(&Bson::I64(horizon)) if horizon > 0 => horizon,
+            None => 3600,
+            _ => return Err(TipupError::from("failed to parse horizon parameter in TrendAnalyzer as positive seconds")),
+        };
+
+        //fraction of variance the trend must explain, so noisy windows are not extrapolated
+        let min_r_squared = match parameters.get("min_r_squared") {
+            Some(&Bson::FloatingPoint(min_r_squared)) if min_r_squared >= 0.0 && min_r_squared <= 1.0 => min_r_squared,
+            None => 0.5,
+            _ => return Err(TipupError::from("failed to parse min_r_squared parameter in TrendAnalyzer, expected a value between 0 and 1")),
+        };
+
+        Ok(
+            TrendAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                variable_name: variable_name,
+                threshold: threshold,
+                above: above,
+                window_size: window_size,
+                horizon: horizon,
+                min_r_squared: min_r_squared,
+                histories: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for TrendAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let value = match get_value(&self.variable_name, document) {
+            Some(value) if value.is_finite() => value,
+            _ => {
+                trace.decision("skipped: variable not present");
+                return Ok(());
+            },
+        };
+
+        //results arriving out of order are dropped rather than bending the fit
+        let history = self.histories.entry((hostname, domain)).or_insert(VecDeque::new());
+        if history.back().map_or(false, |x| x.0 > timestamp) {
+            trace.decision("skipped: result older than latest of key");
+            return Ok(());
+        }
+
+        history.push_back((timestamp, value));
+        if history.len() > self.window_size {
+            history.pop_front();
+        }
+
+        trace.statistic("value", value);
+        trace.statistic("history", history.len() as i64);
+        if history.len() < self.window_size {
+            trace.decision("no flag: insufficient history");
+            return Ok(());
+        }
+
+        let (slope, fitted_value, r_squared) = match fit(history) {
+            Some(fit) => fit,
+            None => {
+                trace.decision("no flag: results share a timestamp");
+                return Ok(());
+            },
+        };
+
+        trace.statistic("slope", slope);
+        trace.statistic("r_squared", r_squared);
+
+        let breached = match self.above {
+            true => fitted_value >= self.threshold,
+            false => fitted_value <= self.threshold,
+        };
+
+        if breached {
+            trace.decision("no flag: threshold already breached");
+            return Ok(());
+        }
+
+        if (self.above && slope <= 0.0) || (!self.above && slope >= 0.0) {
+            trace.decision("no flag: trend away from threshold");
+            return Ok(());
+        }
+
+        let seconds_to_breach = (self.threshold - fitted_value) / slope;
+        trace.statistic("seconds_to_breach", seconds_to_breach);
+        if seconds_to_breach > self.horizon as f64 {
+            trace.decision("no flag: breach predicted beyond horizon");
+            return Ok(());
+        }
+
+        if r_squared < self.min_r_squared {
+            trace.decision("no flag: trend does not fit results");
+            return Ok(());
+        }
+
+        trace.decision("flag: breach predicted within horizon");
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("value".to_owned(), value.to_string());
+        flag.evidence.insert("fitted_value".to_owned(), fitted_value.to_string());
+        flag.evidence.insert("slope".to_owned(), slope.to_string());
+        flag.evidence.insert("r_squared".to_owned(), r_squared.to_string());
+        flag.evidence.insert("threshold".to_owned(), self.threshold.to_string());
+        flag.evidence.insert("direction".to_owned(), (if self.above { "above" } else { "below" }).to_owned());
+        flag.evidence.insert("seconds_to_breach".to_owned(), (seconds_to_breach as i64).to_string());
+        flag.evidence.insert("predicted_breach_timestamp".to_owned(), (timestamp + seconds_to_breach as i64).to_string());
+        flag.evidence.insert("horizon".to_owned(), self.horizon.to_string());
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.histories.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
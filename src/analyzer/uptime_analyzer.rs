@@ -0,0 +1,192 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::{HashMap, VecDeque};
+
+//results and errors within one rolling window, starting at the sequence number of its oldest
+//result in the shared history
+struct WindowCount {
+    start: u64,
+    results: u64,
+    errors: u64,
+}
+
+struct History {
+    results: VecDeque<(i64, bool)>,
+    base: u64,
+    windows: Vec<WindowCount>,
+}
+
+//computes availability of each key over rolling windows, by default 1h, 24h, and 7d, flagging when
+//uptime in any window falls below the sla percentage along with the error budget it has left
+pub struct UptimeAnalyzer {
+    name: String,
+    status: String,
+    fields: Vec<String>,
+    sla: f64,
+    windows: Vec<i64>,
+    min_results: u64,
+    histories: HashMap<(String, String), History>,
+    flag_tx: Sender<Flag>,
+}
+
+impl UptimeAnalyzer {
+    pub fn new(name: &str, status: &str, fields: Vec<String>, parameters: &OrderedDocument, flag_tx: Sender<Flag>) -> Result<UptimeAnalyzer, TipupError> {
+        //results carrying any of the fields are downtime
+        let fields = match fields.len() {
+            0 => vec!("measurement_error_message".to_owned()),
+            _ => fields,
+        };
+
+        //percentage of results which must succeed, e.g. 99.9
+        let sla = match parameters.get("sla") {
+            Some(&Bson::FloatingPoint(sla)) if sla > 0.0 && sla < 100.0 => sla,
+            Some(&Bson::I32(sla)) if sla > 0 && sla < 100 => sla as f64,
+            _ => return Err(TipupError::from("failed to parse sla parameter in UptimeAnalyzer, expected a percentage between 0 and 100")),
+        };
+
+        let windows = match parameters.get("windows") {
+            Some(&Bson::Array(ref param_windows)) if param_windows.len() > 0 => {
+                let mut windows = Vec::new();
+                for x in param_windows {
+                    match x {
+                        &Bson::I32(window) if window > 0 => windows.push(window as i64),
+                        &Bson::I64(window) if window > 0 => windows.push(window),
+                        _ => return Err(TipupError::from("failed to parse window as positive seconds in UptimeAnalyzer")),
+                    }
+                }
+
+                windows.sort();
+                windows.dedup();
+                windows
+            },
+            None => vec!(3600, 86400, 604800),
+            _ => return Err(TipupError::from("failed to parse windows parameter in UptimeAnalyzer, expected an array of seconds")),
+        };
+
+        //results a window must hold before its uptime is judged
+        let min_results = match parameters.get("min_results") {
+            Some(&Bson::I32(min_results)) if min_results > 0 => min_results as u64,
+            None => 10,
+            _ => return Err(TipupError::from("failed to parse min_results parameter in UptimeAnalyzer as positive integer")),
+        };
+
+        Ok(
+            UptimeAnalyzer {
+                name: name.to_owned(),
+                status: status.to_owned(),
+                fields: fields,
+                sla: sla,
+                windows: windows,
+                min_results: min_results,
+                histories: HashMap::new(),
+                flag_tx: flag_tx,
+            }
+        )
+    }
+}
+
+impl Analyzer for UptimeAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        let (hostname, domain, timestamp) = match (document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp))) => (hostname.to_owned(), domain.to_owned(), timestamp),
+            _ => return Ok(()),
+        };
+
+        let error = self.fields.iter().any(|x| document.contains_key(x));
+        trace.statistic("error", error);
+
+        //every window counts the new result, then drops results which have aged out of it
+        let window_count = self.windows.len();
+        let history = self.histories.entry((hostname, domain)).or_insert(History { results: VecDeque::new(), base: 0, windows: (0..window_count).map(|_| WindowCount { start: 0, results: 0, errors: 0 }).collect() });
+        history.results.push_back((timestamp, error));
+        for (window, count) in self.windows.iter().zip(history.windows.iter_mut()) {
+            count.results += 1;
+            if error {
+                count.errors += 1;
+            }
+
+            while let Some(&(result_timestamp, result_error)) = history.results.get((count.start - history.base) as usize) {
+                if result_timestamp > timestamp - window {
+                    break;
+                }
+
+                count.start += 1;
+                count.results -= 1;
+                if result_error {
+                    count.errors -= 1;
+                }
+            }
+        }
+
+        //the longest window starts at the oldest result any window holds
+        let oldest = history.windows.last().map_or(history.base, |x| x.start);
+        while history.base < oldest {
+            history.results.pop_front();
+            history.base += 1;
+        }
+
+        let mut breached_windows = Vec::new();
+        let mut uptimes = Vec::new();
+        for (window, count) in self.windows.iter().zip(history.windows.iter()) {
+            if count.results < self.min_results {
+                continue;
+            }
+
+            let uptime = ((count.results - count.errors) as f64 / count.results as f64) * 100.0;
+            trace.statistic(&format!("uptime_{}", format_window(*window)), uptime);
+            if uptime < self.sla {
+                breached_windows.push(format_window(*window));
+            }
+
+            uptimes.push((*window, count.results, count.errors, uptime));
+        }
+
+        if uptimes.len() == 0 {
+            trace.decision("no flag: insufficient results in every window");
+            return Ok(());
+        }
+
+        if breached_windows.len() == 0 {
+            trace.decision("no flag: uptime within sla");
+            return Ok(());
+        }
+
+        trace.decision(&format!("flag: uptime below sla over {}", breached_windows.join(", ")));
+        let mut flag = try!(Flag::new(document, &self.status, &self.name));
+        flag.evidence.insert("sla".to_owned(), self.sla.to_string());
+        flag.evidence.insert("breached_windows".to_owned(), breached_windows.join(","));
+        for &(window, results, errors, uptime) in uptimes.iter() {
+            //errors the sla allows over the window less those spent, negative once overspent
+            let allowed_errors = (1.0 - (self.sla / 100.0)) * results as f64;
+            let window = format_window(window);
+            flag.evidence.insert(format!("uptime_{}", window), uptime.to_string());
+            flag.evidence.insert(format!("results_{}", window), results.to_string());
+            flag.evidence.insert(format!("errors_{}", window), errors.to_string());
+            flag.evidence.insert(format!("error_budget_remaining_{}", window), (allowed_errors - errors as f64).to_string());
+            flag.evidence.insert(format!("error_budget_remaining_fraction_{}", window), ((allowed_errors - errors as f64) / allowed_errors).to_string());
+        }
+
+        self.flag_tx.send(flag);
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.histories.remove(&(hostname.to_owned(), domain.to_owned()));
+    }
+}
+
+//windows as the largest whole unit, e.g. 1h, 24h, or 7d
+fn format_window(window: i64) -> String {
+    match window {
+        _ if window % 604800 == 0 => format!("{}d", window / 86400),
+        _ if window % 3600 == 0 => format!("{}h", window / 3600),
+        _ if window % 60 == 0 => format!("{}m", window / 60),
+        _ => format!("{}s", window),
+    }
+}
@@ -0,0 +1,216 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::{Receiver, Sender};
+use mongodb::db::Database;
+
+use analyzer::{Analyzer, Trace};
+use error::TipupError;
+use flag_manager::Flag;
+use time_window::TimeWindow;
+
+use std::collections::HashMap;
+
+struct Variant {
+    window: String,
+    analyzer: Box<Analyzer>,
+    flag_rx: Receiver<Flag>,
+}
+
+//evaluates every result with each time window variant of an analyzer, so all variants learn the
+//same history, forwarding only the flags of the variant whose window contains the result
+pub struct WindowedAnalyzer {
+    default: Box<Analyzer>,
+    default_flag_rx: Receiver<Flag>,
+    variants: Vec<Variant>,
+    time_windows: HashMap<String, TimeWindow>,
+    flag_tx: Sender<Flag>,
+}
+
+impl WindowedAnalyzer {
+    pub fn new(default: (Box<Analyzer>, Receiver<Flag>), variants: Vec<(String, Box<Analyzer>, Receiver<Flag>)>, flag_tx: Sender<Flag>) -> WindowedAnalyzer {
+        WindowedAnalyzer {
+            default: default.0,
+            default_flag_rx: default.1,
+            variants: variants.into_iter().map(|(window, analyzer, flag_rx)| {
+                Variant {
+                    window: window,
+                    analyzer: analyzer,
+                    flag_rx: flag_rx,
+                }
+            }).collect(),
+            time_windows: HashMap::new(),
+            flag_tx: flag_tx,
+        }
+    }
+}
+
+impl Analyzer for WindowedAnalyzer {
+    fn process_measurement(&mut self, document: &OrderedDocument, trace: &mut Trace) -> Result<(), TipupError> {
+        //the first listed variant whose window contains the result timestamp takes effect
+        let active = match document.get("timestamp") {
+            Some(&Bson::I64(timestamp)) => {
+                let time_windows = &self.time_windows;
+                self.variants.iter().position(|x| time_windows.get(&x.window).map_or(false, |x| x.contains(timestamp)))
+            },
+            _ => None,
+        };
+
+        trace.statistic("time_window", active.map_or("default".to_owned(), |x| self.variants[x].window.clone()));
+
+        //evaluate inactive variants without tracing so only the active decision is recorded
+        for (i, variant) in self.variants.iter_mut().enumerate() {
+            if active == Some(i) {
+                try!(variant.analyzer.process_measurement(document, trace));
+                for flag in drain(&variant.flag_rx) {
+                    self.flag_tx.send(flag);
+                }
+            } else {
+                try!(variant.analyzer.process_measurement(document, &mut Trace::new(false)));
+                drain(&variant.flag_rx);
+            }
+        }
+
+        if active.is_none() {
+            try!(self.default.process_measurement(document, trace));
+            for flag in drain(&self.default_flag_rx) {
+                self.flag_tx.send(flag);
+            }
+        } else {
+            try!(self.default.process_measurement(document, &mut Trace::new(false)));
+            drain(&self.default_flag_rx);
+        }
+
+        Ok(())
+    }
+
+    fn refresh(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //reload named windows so they may be edited at runtime
+        let time_windows = try!(TimeWindow::load(proddle_db));
+        if let Some(variant) = self.variants.iter().find(|x| !time_windows.contains_key(&x.window)) {
+            return Err(TipupError::from(format!("unknown time window '{}'", variant.window)));
+        }
+
+        self.time_windows = time_windows;
+        try!(self.default.refresh(proddle_db));
+        for variant in self.variants.iter_mut() {
+            try!(variant.analyzer.refresh(proddle_db));
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        self.default.reset(hostname, domain);
+        for variant in self.variants.iter_mut() {
+            variant.analyzer.reset(hostname, domain);
+        }
+    }
+
+    fn routes_by_target(&self) -> bool {
+        self.default.routes_by_target()
+    }
+
+    fn measurement_classes(&self) -> Vec<String> {
+        self.default.measurement_classes()
+    }
+}
+
+fn drain(flag_rx: &Receiver<Flag>) -> Vec<Flag> {
+    let mut flags = Vec::new();
+    loop {
+        chan_select! {
+            default => return flags,
+            flag_rx.recv() -> flag => match flag {
+                Some(flag) => flags.push(flag),
+                None => return flags,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use chan::{self, Receiver, Sender};
+
+    use analyzer::{Analyzer, ConsensusAnalyzer, CorrelationAnalyzer, DivergenceAnalyzer};
+use canary::Canary;
+    use clock::MockClock;
+    use flag_manager::Flag;
+    use metrics::Metrics;
+    use pipe::Pipe;
+
+    use super::WindowedAnalyzer;
+
+    use std::sync::Arc;
+
+    //a default and one variant built by the closure, along with the windowed analyzer's flags
+    fn windowed<F: Fn(Sender<Flag>) -> Box<Analyzer>>(build: F) -> (WindowedAnalyzer, Receiver<Flag>) {
+        let (flag_tx, flag_rx) = chan::async();
+        let (default_flag_tx, default_flag_rx) = chan::async();
+        let (variant_flag_tx, variant_flag_rx) = chan::async();
+        let (default, variant) = (build(default_flag_tx), build(variant_flag_tx));
+        (WindowedAnalyzer::new((default, default_flag_rx), vec!(("business_hours".to_owned(), variant, variant_flag_rx)), flag_tx), flag_rx)
+    }
+
+    #[test]
+    fn receives_every_correlated_measurement_class() {
+        let parameters = doc!("signals" => [{ "measurement_class" => "ping" }, { "measurement_class" => "http" }]);
+        let (analyzer, flag_rx) = windowed(|x| Box::new(CorrelationAnalyzer::new("correlation", "warning", &parameters, x).unwrap()));
+        assert_eq!(analyzer.measurement_classes(), vec!("ping".to_owned(), "http".to_owned()));
+
+        //failed results of both classes are correlated anomalies only if both reach the analyzer
+        let mut pipe = Pipe::new(Metrics::new(), Arc::new(MockClock::new(0)));
+        pipe.add_analyzer("correlation".to_owned(), "ping".to_owned(), Box::new(analyzer)).unwrap();
+        for measurement_class in ["ping", "http"].iter() {
+            pipe.send_measurement(&doc!(
+                "_id" => (ObjectId::new().unwrap()),
+                "vantage_hostname" => "vantage",
+                "measurement_domain" => "example.com",
+                "measurement_class" => (measurement_class.to_string()),
+                "timestamp" => 60i64,
+                "measurement_error_message" => "timeout"
+            )).unwrap();
+        }
+
+        drop(pipe);
+        let flags: Vec<Flag> = flag_rx.iter().collect();
+        assert_eq!(flags.len(), 1);
+    }
+
+    #[test]
+    fn routes_divergence_by_target() {
+        let parameters = doc!("variable_name" => ["latency"]);
+        let (analyzer, _flag_rx) = windowed(|x| Box::new(DivergenceAnalyzer::new("divergence", "warning", &parameters, x).unwrap()));
+        assert!(analyzer.routes_by_target());
+    }
+
+    #[test]
+    fn canary_consensus_votes_over_every_vantage_point() {
+        //canary arms receive whole targets, so one arm sees all three failing vantage points
+        let parameters = doc!("min_failing" => 3);
+        let (baseline, baseline_flag_rx) = windowed(|x| Box::new(ConsensusAnalyzer::new("consensus", "warning", Vec::new(), &parameters, x).unwrap()));
+        let (canary, canary_flag_rx) = windowed(|x| Box::new(ConsensusAnalyzer::new("consensus_canary", "warning", Vec::new(), &parameters, x).unwrap()));
+        assert!(canary.routes_by_target());
+
+        let mut pipe = Pipe::new(Metrics::new(), Arc::new(MockClock::new(0)));
+        pipe.add_analyzer("consensus".to_owned(), "http".to_owned(), Box::new(baseline)).unwrap();
+        pipe.add_analyzer("consensus_canary".to_owned(), "http".to_owned(), Box::new(canary)).unwrap();
+        let canary_document = doc!("name" => "consensus_canary", "canary" => { "baseline" => "consensus", "percentage" => 50 });
+        pipe.add_canary(Canary::parse(&canary_document).unwrap().unwrap()).unwrap();
+        for hostname in ["vantage-a", "vantage-b", "vantage-c"].iter() {
+            pipe.send_measurement(&doc!(
+                "_id" => (ObjectId::new().unwrap()),
+                "vantage_hostname" => (hostname.to_string()),
+                "measurement_domain" => "example.com",
+                "measurement_class" => "http",
+                "timestamp" => 60i64,
+                "measurement_error_message" => "timeout"
+            )).unwrap();
+        }
+
+        drop(pipe);
+        let flags: Vec<Flag> = baseline_flag_rx.iter().chain(canary_flag_rx.iter()).collect();
+        assert_eq!(flags.len(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+use bson::Bson;
+use chan::Sender;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+use flag_manager::Flag;
+use flag_store::FlagStore;
+use metrics::Metrics;
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+//status and analyzer of flags raised when an analyzer is disabled
+const HEALTH_STATUS: &'static str = "critical";
+const HEALTH_ANALYZER: &'static str = "analyzer_health";
+
+//feedback on this many flags is required before acceptance counts towards the score
+const MIN_FEEDBACK: usize = 5;
+
+pub enum Outcome {
+    Ok,
+    Error,
+    Panic,
+}
+
+struct Counts {
+    evaluations: u64,
+    errors: u64,
+    panics: u64,
+    latency_sum: f64,
+}
+
+struct HealthState {
+    counts: BTreeMap<String, Counts>,
+    disabled: HashSet<String>,
+}
+
+//evaluations of each analyzer recorded by the pipe since the health manager last scored them,
+//along with the analyzers it has disabled which the pipe skips
+#[derive(Clone)]
+pub struct AnalyzerHealth {
+    state: Arc<Mutex<HealthState>>,
+}
+
+impl AnalyzerHealth {
+    pub fn new() -> AnalyzerHealth {
+        AnalyzerHealth {
+            state: Arc::new(Mutex::new(
+                HealthState {
+                    counts: BTreeMap::new(),
+                    disabled: HashSet::new(),
+                }
+            )),
+        }
+    }
+
+    pub fn record(&self, name: &str, latency: f64, outcome: Outcome) {
+        let mut state = self.state.lock().unwrap();
+        let counts = state.counts.entry(name.to_owned()).or_insert(Counts { evaluations: 0, errors: 0, panics: 0, latency_sum: 0.0 });
+        counts.evaluations += 1;
+        counts.latency_sum += latency;
+        match outcome {
+            Outcome::Ok => {},
+            Outcome::Error => counts.errors += 1,
+            Outcome::Panic => counts.panics += 1,
+        }
+    }
+
+    pub fn is_disabled(&self, name: &str) -> bool {
+        self.state.lock().unwrap().disabled.contains(name)
+    }
+}
+
+//scores each analyzer from 0 to 100 as the product of its success rate, a penalty halving per
+//panic, its mean processing latency relative to the maximum, and the fraction of its flags which
+//operators marked true positives, disabling analyzers scoring below the minimum with a critical
+//flag until an operator re-enables them
+pub struct HealthManager {
+    health: AnalyzerHealth,
+    min_score: f64,
+    min_evaluations: u64,
+    max_latency: f64,
+    flag_store: Arc<FlagStore>,
+    flag_tx: Sender<Flag>,
+    metrics: Metrics,
+    clock: Arc<Clock>,
+}
+
+impl HealthManager {
+    pub fn new(health: AnalyzerHealth, min_score: f64, min_evaluations: u64, max_latency: f64, flag_store: Arc<FlagStore>, flag_tx: Sender<Flag>, metrics: Metrics, clock: Arc<Clock>) -> HealthManager {
+        HealthManager {
+            health: health,
+            min_score: min_score,
+            min_evaluations: min_evaluations,
+            max_latency: max_latency,
+            flag_store: flag_store,
+            flag_tx: flag_tx,
+            metrics: metrics,
+            clock: clock,
+        }
+    }
+
+    pub fn execute(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        //load disabled analyzers, so those re-enabled by operators are analyzed again
+        let mut disabled = HashSet::new();
+        for document in try!(proddle_db.collection("analyzer_health").find(Some(doc!("disabled" => true)), None)) {
+            match try!(document).get("analyzer") {
+                Some(&Bson::String(ref analyzer)) => { disabled.insert(analyzer.to_owned()); },
+                _ => return Err(TipupError::from("failed to parse analyzer health analyzer")),
+            }
+        }
+
+        //score evaluations since the last execution, leaving analyzers with too few to accrue more
+        let counts: Vec<(String, Counts)> = {
+            let mut state = self.health.state.lock().unwrap();
+            state.disabled = disabled.clone();
+            let names: Vec<String> = state.counts.iter()
+                .filter(|&(_, counts)| counts.evaluations >= self.min_evaluations)
+                .map(|(name, _)| name.to_owned()).collect();
+            names.into_iter().filter_map(|x| state.counts.remove(&x).map(|y| (x, y))).collect()
+        };
+
+        let now = self.clock.now();
+        for (name, counts) in counts {
+            if disabled.contains(&name) {
+                continue;
+            }
+
+            let error_rate = (counts.errors + counts.panics) as f64 / counts.evaluations as f64;
+            let mean_latency = (counts.latency_sum / counts.evaluations as f64) * 1000.0;
+            let (true_positives, false_positives) = try!(self.flag_store.find_with_feedback(&name)).iter()
+                .fold((0, 0), |(x, y), flag| match flag.feedback.as_ref().map(|x| x.as_str()) {
+                    Some("true_positive") => (x + 1, y),
+                    Some("false_positive") => (x, y + 1),
+                    _ => (x, y),
+                });
+
+            let acceptance = match true_positives + false_positives {
+                feedback if feedback >= MIN_FEEDBACK => Some(true_positives as f64 / feedback as f64),
+                _ => None,
+            };
+
+            let score = 100.0 * (1.0 - error_rate)
+                * 0.5f64.powi(counts.panics.min(32) as i32)
+                * (self.max_latency / mean_latency).min(1.0)
+                * acceptance.unwrap_or(1.0);
+
+            self.metrics.set("tipup_analyzer_health_score", &[("analyzer", &name)], score);
+            let disable = score < self.min_score;
+            let mut health_document = doc!(
+                "timestamp" => now,
+                "score" => score,
+                "evaluations" => (counts.evaluations as i64),
+                "errors" => (counts.errors as i64),
+                "panics" => (counts.panics as i64),
+                "error_rate" => error_rate,
+                "mean_latency_ms" => mean_latency,
+                "disabled" => disable
+            );
+
+            if let Some(acceptance) = acceptance {
+                health_document.insert("acceptance", acceptance);
+            }
+
+            let mut update_options = UpdateOptions::new();
+            update_options.upsert = Some(true);
+            try!(proddle_db.collection("analyzer_health").update_one(doc!("analyzer" => (name.clone())), doc!("$set" => health_document), Some(update_options)));
+            if !disable {
+                continue;
+            }
+
+            error!("disabled analyzer '{}' with health score {:.1} below {}", name, score, self.min_score);
+            self.health.state.lock().unwrap().disabled.insert(name.clone());
+
+            let mut flag = Flag::for_domain(&name, now, HEALTH_STATUS, HEALTH_ANALYZER);
+            flag.evidence.insert("reason".to_owned(), "analyzer_disabled".to_owned());
+            flag.evidence.insert("score".to_owned(), score.to_string());
+            flag.evidence.insert("min_score".to_owned(), self.min_score.to_string());
+            flag.evidence.insert("evaluations".to_owned(), counts.evaluations.to_string());
+            flag.evidence.insert("error_rate".to_owned(), error_rate.to_string());
+            flag.evidence.insert("panics".to_owned(), counts.panics.to_string());
+            flag.evidence.insert("mean_latency_ms".to_owned(), mean_latency.to_string());
+            if let Some(acceptance) = acceptance {
+                flag.evidence.insert("acceptance".to_owned(), acceptance.to_string());
+            }
+
+            self.flag_tx.send(flag);
+        }
+
+        Ok(())
+    }
+}
+
+//re-enables a disabled analyzer, effective at the next health manager execution
+pub fn enable(proddle_db: &Database, name: &str) -> Result<bool, TipupError> {
+    let result = try!(proddle_db.collection("analyzer_health").update_one(doc!("analyzer" => name, "disabled" => true), doc!("$set" => { "disabled" => false }), None));
+    Ok(result.matched_count > 0)
+}
@@ -0,0 +1,152 @@
+use bson::{Bson, Document};
+use bson::ordered::OrderedDocument;
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+
+pub struct Revision {
+    pub revision: i32,
+    pub timestamp: i64,
+    pub author: String,
+    pub changes: Vec<String>,
+    pub definition: Document,
+}
+
+impl Revision {
+    fn from_document(document: &OrderedDocument) -> Result<Revision, TipupError> {
+        let (revision, timestamp) = match (document.get("revision"), document.get("timestamp")) {
+            (Some(&Bson::I32(revision)), Some(&Bson::I64(timestamp))) => (revision, timestamp),
+            _ => return Err(TipupError::from("failed to parse analyzer revision number and timestamp")),
+        };
+
+        let author = match document.get("author") {
+            Some(&Bson::String(ref author)) => author.to_owned(),
+            _ => return Err(TipupError::from("failed to parse analyzer revision author")),
+        };
+
+        let changes = match document.get("changes") {
+            Some(&Bson::Array(ref changes)) => changes.iter().filter_map(|x| match x {
+                &Bson::String(ref change) => Some(change.to_owned()),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        };
+
+        let definition = match document.get("definition") {
+            Some(&Bson::Document(ref definition)) => definition.clone(),
+            _ => return Err(TipupError::from("failed to parse analyzer revision definition")),
+        };
+
+        Ok(
+            Revision {
+                revision: revision,
+                timestamp: timestamp,
+                author: author,
+                changes: changes,
+                definition: definition,
+            }
+        )
+    }
+}
+
+pub fn record(proddle_db: &Database, document: &OrderedDocument, timestamp: i64) -> Result<Option<i32>, TipupError> {
+    //record a new revision when an analyzer definition differs from its latest revision
+    let name = match document.get("name") {
+        Some(&Bson::String(ref name)) => name,
+        _ => return Err(TipupError::from("failed to parse analyzer name")),
+    };
+
+    let author = match document.get("modified_by") {
+        Some(&Bson::String(ref author)) => author.to_owned(),
+        _ => "unknown".to_owned(),
+    };
+
+    let mut definition = document.clone();
+    definition.remove("_id");
+
+    let (revision, changes) = match try!(latest(proddle_db, name)) {
+        Some(previous) => {
+            let changes = diff(&previous.definition, &definition);
+            if changes.len() == 0 {
+                return Ok(None);
+            }
+
+            (previous.revision + 1, changes)
+        },
+        None => (1, Vec::new()),
+    };
+
+    let changes: Vec<Bson> = changes.into_iter().map(|x| Bson::String(x)).collect();
+    let revision_document = doc!(
+        "analyzer" => (name.to_owned()),
+        "revision" => revision,
+        "timestamp" => timestamp,
+        "author" => author,
+        "changes" => changes,
+        "definition" => definition
+    );
+
+    try!(proddle_db.collection("analyzer_revisions").insert_one(revision_document, None));
+    info!("recorded revision {} of analyzer '{}'", revision, name);
+    Ok(Some(revision))
+}
+
+pub fn history(proddle_db: &Database, name: &str) -> Result<Vec<Revision>, TipupError> {
+    let mut find_options = FindOptions::new();
+    find_options.sort = Some(doc!("revision" => 1));
+
+    let mut revisions = Vec::new();
+    for document in try!(proddle_db.collection("analyzer_revisions").find(Some(doc!("analyzer" => name)), Some(find_options))) {
+        revisions.push(try!(Revision::from_document(&try!(document))));
+    }
+
+    Ok(revisions)
+}
+
+pub fn rollback(proddle_db: &Database, name: &str, revision: i32, author: &str) -> Result<(), TipupError> {
+    //restore the definition of a previous revision, which is recorded as a new revision on the next load
+    let search_document = doc!("analyzer" => name, "revision" => revision);
+    let target = match try!(proddle_db.collection("analyzer_revisions").find_one(Some(search_document), None)) {
+        Some(document) => try!(Revision::from_document(&document)),
+        None => return Err(TipupError::from(format!("revision {} of analyzer '{}' not found", revision, name))),
+    };
+
+    let mut definition = target.definition;
+    definition.insert("modified_by", author.to_owned());
+
+    let result = try!(proddle_db.collection("analyzers").replace_one(doc!("name" => name), definition, None));
+    if result.matched_count == 0 {
+        return Err(TipupError::from(format!("analyzer '{}' not found", name)));
+    }
+
+    Ok(())
+}
+
+fn latest(proddle_db: &Database, name: &str) -> Result<Option<Revision>, TipupError> {
+    let negative_one = -1;
+    let mut find_options = FindOptions::new();
+    find_options.sort = Some(doc!("revision" => negative_one));
+    match try!(proddle_db.collection("analyzer_revisions").find_one(Some(doc!("analyzer" => name)), Some(find_options))) {
+        Some(document) => Ok(Some(try!(Revision::from_document(&document)))),
+        None => Ok(None),
+    }
+}
+
+fn diff(previous: &Document, current: &Document) -> Vec<String> {
+    //top level keys added, removed, or modified between definitions, ignoring authorship
+    let mut changes = Vec::new();
+    for (key, value) in current.iter() {
+        if key != "modified_by" && previous.get(key) != Some(value) {
+            changes.push(key.to_owned());
+        }
+    }
+
+    for key in previous.keys() {
+        if key != "modified_by" && !current.contains_key(key) {
+            changes.push(key.to_owned());
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,323 @@
+use bson::{Bson, Document};
+use bson::oid::ObjectId;
+use chan::{self, Sender};
+use mongodb::ClientInner;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use command::parse_duration;
+use dry_run;
+use error::TipupError;
+use event::Event;
+use feed;
+use flag_manager;
+use flag_store::{FlagStore, FlagUpdate};
+use kpi;
+use metrics::Metrics;
+use pipe::PipeStatistics;
+use quality_gate::QualityStatuses;
+use self_healing;
+use supervisor::Supervisor;
+
+use std;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct Api {
+    client: Arc<ClientInner>,
+    username: String,
+    password: String,
+    token: String,
+    flag_store: Arc<FlagStore>,
+    metrics: Metrics,
+    pipe_statistics: PipeStatistics,
+    quality_statuses: QualityStatuses,
+    reload_tx: Sender<Sender<Result<Vec<String>, String>>>,
+}
+
+impl Api {
+    pub fn new(client: Arc<ClientInner>, username: &str, password: &str, token: &str, flag_store: Arc<FlagStore>, metrics: Metrics, pipe_statistics: PipeStatistics, quality_statuses: QualityStatuses, reload_tx: Sender<Sender<Result<Vec<String>, String>>>) -> Api {
+        Api {
+            client: client,
+            username: username.to_owned(),
+            password: password.to_owned(),
+            token: token.to_owned(),
+            flag_store: flag_store,
+            metrics: metrics,
+            pipe_statistics: pipe_statistics,
+            quality_statuses: quality_statuses,
+            reload_tx: reload_tx,
+        }
+    }
+
+    pub fn start(self, address: &str, workers: usize, supervisor: &Supervisor) -> Result<(), TipupError> {
+        let server = match Server::http(address) {
+            Ok(server) => Arc::new(server),
+            Err(e) => return Err(TipupError::from(format!("failed to start api on '{}': {}", address, e))),
+        };
+
+        //serve requests from a pool of workers sharing the listener
+        let api = Arc::new(self);
+        for i in 0..workers {
+            let (server, api) = (server.clone(), api.clone());
+            try!(supervisor.spawn(&format!("api_worker_{}", i), move || {
+                for request in server.incoming_requests() {
+                    api.respond(request);
+                }
+            }));
+        }
+
+        Ok(())
+    }
+
+    fn respond(&self, mut request: Request) {
+        let (status_code, body) = if *request.method() == Method::Post && !self.authorized(&request) {
+            (401, "{\"error\":\"unauthorized\"}".to_owned())
+        } else {
+            match self.route(&mut request) {
+                Ok(Some(body)) => (200, body),
+                Ok(None) => (404, "{\"error\":\"not found\"}".to_owned()),
+                Err(e) => {
+                    error!("{}", e);
+                    (500, format!("{{\"error\":{:?}}}", e.to_string()))
+                },
+            }
+        };
+
+        let content_type = match parse_url(request.url()).0.as_ref() {
+            "/metrics" => &b"text/plain; version=0.0.4"[..],
+            "/feeds/flags.atom" if status_code == 200 => &b"application/atom+xml"[..],
+            _ => &b"application/json"[..],
+        };
+
+        let header = Header::from_bytes(&b"Content-Type"[..], content_type).unwrap();
+        let response = Response::from_string(body).with_status_code(status_code).with_header(header);
+        if let Err(e) = request.respond(response) {
+            error!("failed to respond to api request: {}", e);
+        }
+    }
+
+    //mutating requests must carry the configured bearer token, if any
+    fn authorized(&self, request: &Request) -> bool {
+        if self.token.len() == 0 {
+            return true;
+        }
+
+        let expected = format!("Bearer {}", self.token);
+        request.headers().iter().any(|x| x.field.equiv("Authorization") && x.value.as_str() == expected)
+    }
+
+    fn route(&self, request: &mut Request) -> Result<Option<String>, TipupError> {
+        let (path, parameters) = parse_url(request.url());
+        match (request.method(), path.as_ref()) {
+            (&Method::Get, "/availability") => {
+                let search_document = filter_document(&parameters, &["domain", "vantage_hostname", "window"]);
+                let documents = try!(find_documents(&try!(self.db()), "availability", search_document));
+                Ok(Some(to_json(documents)))
+            },
+            (&Method::Get, "/target_stats") => {
+                //rolling latency percentiles per target, optionally of one domain or measurement class
+                let search_document = filter_document(&parameters, &["domain", "measurement_class"]);
+                let documents = try!(find_documents(&try!(self.db()), "target_stats", search_document));
+                Ok(Some(to_json(documents)))
+            },
+            (&Method::Get, "/feeds/flags.atom") => Ok(Some(try!(feed::flags_atom(&try!(self.db()), &parameters)))),
+            (&Method::Get, "/kpi") => {
+                //mttd and mttr distributions, by analyzer unless grouped by 'domain' or 'label.<key>'
+                let group_by = parameters.get("group_by").map_or("analyzer", |x| x.as_str());
+                let since = try!(parse_duration(parameters.get("since").map_or("7d", |x| x.as_str())));
+                let timestamp = time::now_utc().to_timespec().sec - since;
+                Ok(Some(to_json(try!(kpi::statistics(&try!(self.db()), group_by, timestamp)))))
+            },
+            (&Method::Get, "/metrics") => Ok(Some(self.metrics.render())),
+            (&Method::Get, "/admin/pipe") => Ok(Some(to_json(self.pipe_statistics.to_documents()))),
+            (&Method::Get, "/admin/quality") => {
+                //per-key quality gate status, optionally only keys with insufficient data
+                let insufficient_only = parameters.get("status").map_or(false, |x| x == "insufficient_data");
+                Ok(Some(to_json(self.quality_statuses.to_documents(insufficient_only))))
+            },
+            (&Method::Get, "/admin/self_healing") => Ok(Some(to_json(try!(self_healing::statistics(&try!(self.db())))))),
+            (&Method::Get, "/admin/routing") => {
+                //current measurement class to analyzer routing table
+                match try!(find_documents(&try!(self.db()), "routing_table", doc!("_id" => "pipe"))).pop() {
+                    Some(document) => Ok(Some(Bson::Document(document).to_json().to_string())),
+                    None => Ok(None),
+                }
+            },
+            (&Method::Post, "/admin/reload") => {
+                //reload configuration on the main thread, reporting changed settings or why it was rejected
+                let (reply_tx, reply_rx) = chan::sync(1);
+                self.reload_tx.send(reply_tx);
+                match reply_rx.recv() {
+                    Some(Ok(changes)) => Ok(Some(Bson::Document(doc!("changes" => (changes.into_iter().map(Bson::String).collect::<Vec<Bson>>()))).to_json().to_string())),
+                    Some(Err(e)) => Err(TipupError::from(e)),
+                    None => Err(TipupError::from("configuration reload was not answered")),
+                }
+            },
+            (&Method::Post, path) if path.len() > 19 && path.starts_with("/analyzers/") && path.ends_with("/dry-run") => {
+                //evaluate the sample result in the request body with a copy of the analyzer
+                let name = path[11..path.len() - 8].to_owned();
+                let mut sample = String::new();
+                if let Err(e) = request.as_reader().read_to_string(&mut sample) {
+                    return Err(TipupError::from(format!("failed to read sample result: {}", e)));
+                }
+
+                let evaluation = try!(dry_run::evaluate(&try!(self.db()), &name, &sample));
+                Ok(Some(Bson::Document(evaluation).to_json().to_string()))
+            },
+            (&Method::Post, "/events") => {
+                //record an external event for incident correlation
+                let (kind, description) = match (parameters.get("kind"), parameters.get("description")) {
+                    (Some(kind), Some(description)) => (kind, description),
+                    _ => return Err(TipupError::from("events require 'kind' and 'description' parameters")),
+                };
+
+                let start_timestamp = match parameters.get("timestamp").map(|x| x.parse::<i64>()) {
+                    Some(Ok(timestamp)) => timestamp,
+                    Some(Err(_)) => return Err(TipupError::from("failed to parse event 'timestamp' parameter")),
+                    None => time::now_utc().to_timespec().sec,
+                };
+
+                let duration = match parameters.get("duration").map(|x| x.parse::<i64>()) {
+                    Some(Ok(duration)) => duration,
+                    Some(Err(_)) => return Err(TipupError::from("failed to parse event 'duration' parameter as seconds")),
+                    None => 0,
+                };
+
+                let event = try!(Event::new(kind, description, parameters.get("domain").cloned(),
+                    parameters.get("hostname").cloned(), start_timestamp, start_timestamp + duration));
+                try!(event.insert(&try!(self.db())));
+                Ok(Some(format!("{{\"id\":\"{}\"}}", event.id)))
+            },
+            (&Method::Post, path) if path.starts_with("/flags/") => {
+                let mut fields = path[7..].splitn(2, '/');
+                match (fields.next(), fields.next()) {
+                    (Some(id), Some("feedback")) => self.flag_feedback(id, &parameters),
+                    (Some(id), Some("ack")) => to_found(try!(flag_manager::set_state(&*self.flag_store, &try!(self.db()), id, "acknowledged"))),
+                    (Some(id), Some("resolve")) => to_found(try!(flag_manager::set_state(&*self.flag_store, &try!(self.db()), id, "resolved"))),
+                    (Some(id), Some("snooze")) => self.flag_snooze(id, &parameters),
+                    _ => Ok(None),
+                }
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn flag_feedback(&self, id: &str, parameters: &HashMap<String, String>) -> Result<Option<String>, TipupError> {
+        //record operator feedback on a flag
+        let object_id = match ObjectId::with_string(id) {
+            Ok(object_id) => object_id,
+            Err(_) => return Err(TipupError::from(format!("invalid flag id '{}'", id))),
+        };
+
+        let feedback = match parameters.get("type").map(|x| x.as_str()) {
+            Some("false_positive") => "false_positive",
+            Some("true_positive") => "true_positive",
+            _ => return Err(TipupError::from("feedback type must be 'false_positive' or 'true_positive'")),
+        };
+
+        to_found(try!(self.flag_store.update(&[object_id], &FlagUpdate::new().set("feedback", feedback))) > 0)
+    }
+
+    fn flag_snooze(&self, id: &str, parameters: &HashMap<String, String>) -> Result<Option<String>, TipupError> {
+        //snooze flag notifications for a duration and/or until a field increases
+        let until_timestamp = match parameters.get("for") {
+            Some(duration) => Some(time::now_utc().to_timespec().sec + try!(parse_duration(duration))),
+            None => None,
+        };
+
+        let condition = match (parameters.get("field"), parameters.get("increase").map(|x| x.parse::<f64>())) {
+            (Some(field), Some(Ok(increase))) => Some((field.split('.').map(|x| x.to_owned()).collect(), increase)),
+            (None, None) => None,
+            _ => return Err(TipupError::from("snooze conditions require 'field' and numeric 'increase' parameters")),
+        };
+
+        if until_timestamp.is_none() && condition.is_none() {
+            return Err(TipupError::from("snooze requires 'for' and/or 'field' with 'increase' parameters"));
+        }
+
+        to_found(try!(flag_manager::snooze(&*self.flag_store, id, until_timestamp, condition)))
+    }
+
+    fn db(&self) -> Result<Database, TipupError> {
+        ::initialize_db(&self.client, "proddle", &self.username, &self.password)
+    }
+}
+
+fn find_documents(proddle_db: &Database, collection: &str, search_document: Document) -> Result<Vec<Document>, TipupError> {
+    let mut documents = Vec::new();
+    for document in try!(proddle_db.collection(collection).find(Some(search_document), None)) {
+        let mut document = try!(document);
+        document.remove("_id");
+        documents.push(document);
+    }
+
+    Ok(documents)
+}
+
+fn filter_document(parameters: &HashMap<String, String>, keys: &[&str]) -> Document {
+    let mut document = Document::new();
+    for key in keys.iter() {
+        if let Some(value) = parameters.get(*key) {
+            document.insert(*key, value.to_owned());
+        }
+    }
+
+    document
+}
+
+fn to_json(documents: Vec<Document>) -> String {
+    let array: Vec<Bson> = documents.into_iter().map(|x| Bson::Document(x)).collect();
+    Bson::Array(array).to_json().to_string()
+}
+
+fn to_found(found: bool) -> Result<Option<String>, TipupError> {
+    match found {
+        true => Ok(Some("{}".to_owned())),
+        false => Ok(None),
+    }
+}
+
+fn parse_url(url: &str) -> (String, HashMap<String, String>) {
+    let mut parameters = HashMap::new();
+    let (path, query) = match url.find('?') {
+        Some(index) => (&url[..index], &url[index + 1..]),
+        None => (url, ""),
+    };
+
+    for pair in query.split('&').filter(|x| x.len() > 0) {
+        let mut fields = pair.splitn(2, '=');
+        let key = decode_component(fields.next().unwrap_or(""));
+        let value = decode_component(fields.next().unwrap_or(""));
+        parameters.insert(key, value);
+    }
+
+    (path.to_owned(), parameters)
+}
+
+fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    },
+                    Err(_) => decoded.push(b'%'),
+                }
+            },
+            byte => decoded.push(byte),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,273 @@
+use bson::{Bson, Document};
+use bson::oid::ObjectId;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use rustc_serialize::base64::FromBase64;
+use serde_json;
+
+use analyzer::get_f64;
+use clock::Clock;
+use error::TipupError;
+use http;
+
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+//number of mapped results inserted at once
+const INSERT_BATCH_SIZE: usize = 500;
+
+//pulls results of ripe atlas measurements from the rest api, which streams newline delimited
+//results, mapping each into a proddle result of a vantage host per atlas probe so analyzers run
+//over atlas and proddle results alike
+pub struct AtlasSource {
+    url: String,
+    key: String,
+    measurement_ids: Vec<u64>,
+    collection: String,
+    //seconds of results pulled for measurements without earlier results
+    backfill: i64,
+    clock: Arc<Clock>,
+}
+
+impl AtlasSource {
+    pub fn new(url: &str, key: &str, measurement_ids: &Vec<String>, collection: &str, backfill: i64, clock: Arc<Clock>) -> Result<AtlasSource, TipupError> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(TipupError::from(format!("atlas url '{}' must be an http or https url", url)));
+        }
+
+        let mut ids = Vec::new();
+        for measurement_id in measurement_ids.iter() {
+            match measurement_id.trim().parse::<u64>() {
+                Ok(id) => ids.push(id),
+                Err(_) => return Err(TipupError::from(format!("failed to parse atlas measurement id '{}'", measurement_id))),
+            }
+        }
+
+        Ok(
+            AtlasSource {
+                url: url.trim_end_matches('/').to_owned(),
+                key: key.to_owned(),
+                measurement_ids: ids,
+                collection: collection.to_owned(),
+                backfill: backfill,
+                clock: clock,
+            }
+        )
+    }
+
+    pub fn execute(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let client = try!(http::client());
+        for measurement_id in self.measurement_ids.iter() {
+            //resume after the newest result pulled for the measurement
+            let search_document = Some(doc!("measurement_id" => (*measurement_id as i64)));
+            let start_timestamp = match try!(proddle_db.collection("atlas_measurements").find_one(search_document, None)) {
+                Some(document) => match document.get("timestamp") {
+                    Some(&Bson::I64(timestamp)) => timestamp + 1,
+                    _ => return Err(TipupError::from(format!("failed to parse 'timestamp' value in atlas_measurements for measurement {}", measurement_id))),
+                },
+                None => self.clock.now() - self.backfill,
+            };
+
+            let mut url = format!("{}/measurements/{}/results/?format=txt&start={}", self.url, measurement_id, start_timestamp);
+            if self.key.len() > 0 {
+                url.push_str(&format!("&key={}", self.key));
+            }
+
+            let response = try!(client.get(&url).send());
+            if !response.status.is_success() {
+                return Err(TipupError::from(format!("atlas measurement {} results responded with status {}", measurement_id, response.status)));
+            }
+
+            //map results as they stream rather than buffering the whole response
+            let (mut count, mut skipped, mut max_timestamp) = (0, 0, -1);
+            let mut documents = Vec::new();
+            for line in BufReader::new(response).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => return Err(TipupError::from(format!("failed to read atlas measurement {} results: {}", measurement_id, e))),
+                };
+
+                if line.trim().len() == 0 {
+                    continue;
+                }
+
+                let result = match serde_json::from_str(&line) {
+                    Ok(value) => match Bson::from_json(&value) {
+                        Bson::Document(document) => document,
+                        _ => return Err(TipupError::from(format!("atlas measurement {} result is not a json object", measurement_id))),
+                    },
+                    Err(e) => return Err(TipupError::from(format!("failed to parse atlas measurement {} result: {}", measurement_id, e))),
+                };
+
+                let document = match map_result(result) {
+                    Some(document) => document,
+                    None => {
+                        skipped += 1;
+                        continue;
+                    },
+                };
+
+                if let Some(&Bson::I64(timestamp)) = document.get("timestamp") {
+                    max_timestamp = ::std::cmp::max(max_timestamp, timestamp);
+                }
+
+                documents.push(document);
+                if documents.len() >= INSERT_BATCH_SIZE {
+                    count += documents.len();
+                    try!(proddle_db.collection(&self.collection).insert_many(documents, None));
+                    documents = Vec::new();
+                }
+            }
+
+            if documents.len() > 0 {
+                count += documents.len();
+                try!(proddle_db.collection(&self.collection).insert_many(documents, None));
+            }
+
+            if skipped > 0 {
+                warn!("skipped {} unmappable results of atlas measurement {}", skipped, measurement_id);
+            }
+
+            if max_timestamp < 0 {
+                continue;
+            }
+
+            let mut update_options = UpdateOptions::new();
+            update_options.upsert = Some(true);
+            let search_document = doc!("measurement_id" => (*measurement_id as i64));
+            let update_document = doc!("$set" => { "timestamp" => max_timestamp });
+            try!(proddle_db.collection("atlas_measurements").update_one(search_document, update_document, Some(update_options)));
+            info!("pulled {} results of atlas measurement {}", count, measurement_id);
+        }
+
+        Ok(())
+    }
+}
+
+//maps an atlas result into a proddle result, whose vantage host is the atlas probe and whose
+//measurement class is derived from the atlas measurement type, e.g. 'AtlasPing', with commonly
+//analyzed values lifted to top-level fields and the original result kept under 'atlas'
+fn map_result(result: Document) -> Option<Document> {
+    let (probe_id, timestamp) = match (get_i64(&result, "prb_id"), get_i64(&result, "timestamp")) {
+        (Some(probe_id), Some(timestamp)) => (probe_id, timestamp),
+        _ => return None,
+    };
+
+    let measurement_type = match result.get("type") {
+        Some(&Bson::String(ref measurement_type)) if measurement_type.len() > 0 => measurement_type.to_owned(),
+        _ => return None,
+    };
+
+    let domain = match (result.get("dst_name"), result.get("uri"), result.get("dst_addr")) {
+        (Some(&Bson::String(ref dst_name)), _, _) if dst_name.len() > 0 => dst_name.to_owned(),
+        (_, Some(&Bson::String(ref uri)), _) => uri_host(uri),
+        (_, _, Some(&Bson::String(ref dst_addr))) => dst_addr.to_owned(),
+        _ => match get_i64(&result, "msm_id") {
+            Some(measurement_id) => format!("atlas-measurement-{}", measurement_id),
+            None => return None,
+        },
+    };
+
+    let mut document = doc!(
+        "_id" => (ObjectId::new().unwrap()),
+        "timestamp" => timestamp,
+        "vantage_hostname" => (format!("atlas-probe-{}", probe_id)),
+        "measurement_domain" => domain,
+        "measurement_class" => (format!("Atlas{}{}", measurement_type[..1].to_uppercase(), &measurement_type[1..]))
+    );
+
+    match measurement_type.as_str() {
+        "ping" => {
+            if let (Some(sent), Some(received)) = (get_i64(&result, "sent"), get_i64(&result, "rcvd")) {
+                document.insert("sent", sent);
+                document.insert("received", received);
+                if sent > 0 && received == 0 {
+                    document.insert("measurement_error_message", "no ping replies received");
+                }
+            }
+
+            if let Some(latency) = get_f64(&result, "avg").filter(|x| *x >= 0.0) {
+                document.insert("latency", latency);
+            }
+        },
+        "http" => if let Some(&Bson::Array(ref responses)) = result.get("result") {
+            if let Some(&Bson::Document(ref response)) = responses.first() {
+                if let Some(status_code) = get_i64(response, "res") {
+                    document.insert("status_code", status_code);
+                }
+
+                if let Some(latency) = get_f64(response, "rt") {
+                    document.insert("latency", latency);
+                }
+
+                if let Some(&Bson::String(ref error)) = response.get("err") {
+                    document.insert("error", error.to_owned());
+                    document.insert("measurement_error_message", error.to_owned());
+                }
+            }
+        },
+        "dns" => match (result.get("result"), result.get("error")) {
+            (Some(&Bson::Document(ref response)), _) => {
+                if let Some(rcode) = response.get("abuf").and_then(|x| x.as_str()).and_then(abuf_rcode) {
+                    document.insert("rcode", rcode);
+                }
+
+                if let Some(latency) = get_f64(response, "rt") {
+                    document.insert("latency", latency);
+                }
+            },
+            (_, Some(&Bson::Document(ref error))) => {
+                let error = match error.contains_key("timeout") {
+                    true => "timeout".to_owned(),
+                    false => Bson::Document(error.clone()).to_json().to_string(),
+                };
+
+                document.insert("error", error.clone());
+                document.insert("measurement_error_message", error);
+            },
+            _ => {},
+        },
+        "sslcert" => {
+            if let Some(latency) = get_f64(&result, "rt") {
+                document.insert("latency", latency);
+            }
+
+            if let Some(&Bson::String(ref error)) = result.get("err") {
+                document.insert("measurement_error_message", error.to_owned());
+            }
+        },
+        "traceroute" => if let Some(&Bson::Array(ref hops)) = result.get("result") {
+            document.insert("hops", hops.len() as i64);
+        },
+        _ => {},
+    }
+
+    document.insert("atlas", result);
+    Some(document)
+}
+
+//the response code of the header of a base64 encoded dns message
+fn abuf_rcode(abuf: &str) -> Option<i64> {
+    match abuf.from_base64() {
+        Ok(ref message) if message.len() >= 4 => Some((message[3] & 0x0f) as i64),
+        _ => None,
+    }
+}
+
+fn uri_host(uri: &str) -> String {
+    let uri = match uri.find("://") {
+        Some(index) => &uri[index + 3..],
+        None => uri,
+    };
+
+    uri.split(|c| c == '/' || c == ':' || c == '?').next().unwrap_or(uri).to_owned()
+}
+
+fn get_i64(document: &Document, key: &str) -> Option<i64> {
+    match document.get(key) {
+        Some(&Bson::I32(value)) => Some(value as i64),
+        Some(&Bson::I64(value)) => Some(value),
+        Some(&Bson::FloatingPoint(value)) if value.fract() == 0.0 => Some(value as i64),
+        _ => None,
+    }
+}
@@ -0,0 +1,94 @@
+use bson::Bson;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+
+use std::sync::Arc;
+
+//rolling windows over which availability is computed
+const WINDOWS: [(&'static str, i64); 3] = [("1h", 3600), ("24h", 86400), ("7d", 604800)];
+
+pub struct AvailabilityManager {
+    clock: Arc<Clock>,
+}
+
+impl AvailabilityManager {
+    pub fn new(clock: Arc<Clock>) -> AvailabilityManager {
+        AvailabilityManager {
+            clock: clock,
+        }
+    }
+
+    pub fn execute(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let now = self.clock.now();
+        let mut count = 0;
+        for &(window, duration) in WINDOWS.iter() {
+            //count total and failed measurements per (domain, vantage)
+            let timestamp_gte = doc!("$gte" => (now - duration));
+            let match_doc = doc!("timestamp" => timestamp_gte);
+            let id_doc = doc!("domain" => "$measurement_domain", "vantage_hostname" => "$vantage_hostname");
+            let error_doc = doc!("$cond" => [{ "$ifNull" => ["$measurement_error_message", false] }, 1, 0]);
+            let group_doc = doc!(
+                "_id" => id_doc,
+                "total" => { "$sum" => 1 },
+                "errors" => { "$sum" => error_doc }
+            );
+            let aggregate_doc = vec!(
+                doc!("$match" => match_doc),
+                doc!("$group" => group_doc),
+            );
+
+            for document in try!(proddle_db.collection("measurements").aggregate(aggregate_doc, None)) {
+                let document = try!(document);
+
+                let id_document = match document.get("_id") {
+                    Some(&Bson::Document(ref id_document)) => id_document,
+                    _ => continue,
+                };
+
+                let (domain, hostname) = match (id_document.get("domain"), id_document.get("vantage_hostname")) {
+                    (Some(&Bson::String(ref domain)), Some(&Bson::String(ref hostname))) => (domain.to_owned(), hostname.to_owned()),
+                    _ => continue,
+                };
+
+                let (total, errors) = match (document.get("total"), document.get("errors")) {
+                    (Some(&Bson::I32(total)), Some(&Bson::I32(errors))) => (total as i64, errors as i64),
+                    (Some(&Bson::I64(total)), Some(&Bson::I64(errors))) => (total, errors),
+                    _ => continue,
+                };
+
+                if total == 0 {
+                    continue;
+                }
+
+                //persist availability percentage for window
+                let availability = ((total - errors) as f64 / total as f64) * 100.0;
+                let search_document = doc!(
+                    "domain" => (domain.clone()),
+                    "vantage_hostname" => (hostname.clone()),
+                    "window" => window
+                );
+                let set_document = doc!(
+                    "availability" => availability,
+                    "total" => total,
+                    "errors" => errors,
+                    "timestamp" => now
+                );
+                let update_document = doc!("$set" => set_document);
+
+                let mut update_options = UpdateOptions::new();
+                update_options.upsert = Some(true);
+                try!(proddle_db.collection("availability").update_one(search_document, update_document, Some(update_options)));
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            info!("updated {} availability record(s)", count);
+        }
+
+        Ok(())
+    }
+}
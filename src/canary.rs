@@ -0,0 +1,108 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+
+//an analyzer evaluating a percentage of keys in place of the baseline analyzer it is a version of
+pub struct Canary {
+    pub name: String,
+    pub baseline: String,
+    pub percentage: u32,
+}
+
+pub struct CanaryArm {
+    pub analyzer: String,
+    pub keys: usize,
+    pub results: i64,
+    pub flags: i64,
+}
+
+impl Canary {
+    pub fn parse(document: &OrderedDocument) -> Result<Option<Canary>, TipupError> {
+        let name = match document.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return Err(TipupError::from("failed to parse analyzer name")),
+        };
+
+        let canary = match document.get("canary") {
+            Some(&Bson::Document(ref canary)) => canary,
+            None => return Ok(None),
+            _ => return Err(TipupError::from(format!("failed to parse canary of analyzer '{}'", name))),
+        };
+
+        let baseline = match canary.get("baseline") {
+            Some(&Bson::String(ref baseline)) if baseline != &name => baseline.to_owned(),
+            _ => return Err(TipupError::from(format!("failed to parse canary baseline of analyzer '{}'", name))),
+        };
+
+        let percentage = match canary.get("percentage") {
+            Some(&Bson::I32(percentage)) if percentage > 0 && percentage < 100 => percentage as u32,
+            _ => return Err(TipupError::from(format!("canary percentage of analyzer '{}' must be between 1 and 99", name))),
+        };
+
+        Ok(Some(
+            Canary {
+                name: name,
+                baseline: baseline,
+                percentage: percentage,
+            }
+        ))
+    }
+
+    //true if the (hostname, domain) key is evaluated by the canary rather than the baseline
+    pub fn contains(&self, hostname: &str, domain: &str) -> bool {
+        //fnv-1a keeps assignments stable across restarts and tipup versions
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in hostname.bytes().chain(Some(0)).chain(domain.bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        (hash % 100) < self.percentage as u64
+    }
+
+    //results and flags of the baseline and canary since a timestamp, keys assigned as when routed
+    pub fn compare(&self, proddle_db: &Database, measurement_class: &str, since: i64) -> Result<(CanaryArm, CanaryArm), TipupError> {
+        let mut baseline = CanaryArm { analyzer: self.baseline.clone(), keys: 0, results: 0, flags: 0 };
+        let mut canary = CanaryArm { analyzer: self.name.clone(), keys: 0, results: 0, flags: 0 };
+
+        let timestamp_gte = doc!("$gte" => since);
+        let match_document = doc!("measurement_class" => measurement_class, "timestamp" => timestamp_gte);
+        let id_document = doc!("vantage_hostname" => "$vantage_hostname", "domain" => "$measurement_domain");
+        let group_document = doc!("_id" => id_document, "results" => { "$sum" => 1 });
+        let pipeline = vec!(
+            doc!("$match" => match_document),
+            doc!("$group" => group_document),
+        );
+
+        for document in try!(proddle_db.collection("measurements").aggregate(pipeline, None)) {
+            let document = try!(document);
+            let (hostname, domain) = match document.get("_id") {
+                Some(&Bson::Document(ref id_document)) => match (id_document.get("vantage_hostname"), id_document.get("domain")) {
+                    (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            let results = match document.get("results") {
+                Some(&Bson::I32(results)) => results as i64,
+                Some(&Bson::I64(results)) => results,
+                _ => return Err(TipupError::from("failed to parse canary result count")),
+            };
+
+            let arm = if self.contains(&hostname, &domain) { &mut canary } else { &mut baseline };
+            arm.keys += 1;
+            arm.results += results;
+        }
+
+        for arm in [&mut baseline, &mut canary].iter_mut() {
+            let timestamp_gte = doc!("$gte" => since);
+            let search_document = doc!("analyzer" => (arm.analyzer.clone()), "timestamp" => timestamp_gte);
+            arm.flags = try!(proddle_db.collection("flags").count(Some(search_document), None));
+        }
+
+        Ok((baseline, canary))
+    }
+}
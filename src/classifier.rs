@@ -0,0 +1,72 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use mongodb::db::{Database, ThreadedDatabase};
+use regex::Regex;
+
+use error::TipupError;
+
+//default (category, pattern) mappings evaluated after configured categories
+const DEFAULT_CATEGORIES: [(&'static str, &'static str); 5] = [
+    ("dns_failure", r"(?i)(dns|name resolution|nxdomain|servfail|failed to lookup|no such host)"),
+    ("timeout", r"(?i)(timed? ?out|deadline exceeded)"),
+    ("connection_refused", r"(?i)connection refused"),
+    ("connection_reset", r"(?i)(connection reset|broken pipe)"),
+    ("tls_error", r"(?i)(tls|ssl|certificate|handshake)"),
+];
+
+pub struct ErrorClassifier {
+    categories: Vec<(String, Regex)>,
+}
+
+impl ErrorClassifier {
+    pub fn new() -> ErrorClassifier {
+        ErrorClassifier {
+            categories: Vec::new(),
+        }
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //query mongodb for configured error categories
+        self.categories.clear();
+        for document in try!(proddle_db.collection("error_categories").find(None, None)) {
+            let document = try!(document);
+            let (category, pattern) = match (document.get("category"), document.get("pattern")) {
+                (Some(&Bson::String(ref category)), Some(&Bson::String(ref pattern))) => (category.to_owned(), pattern),
+                _ => return Err(TipupError::from("failed to parse error category and pattern")),
+            };
+
+            self.categories.push((category, try!(compile(pattern))));
+        }
+
+        if self.categories.len() > 0 {
+            info!("loaded {} error category(s)", self.categories.len());
+        }
+
+        for &(category, pattern) in DEFAULT_CATEGORIES.iter() {
+            self.categories.push((category.to_owned(), try!(compile(pattern))));
+        }
+
+        Ok(())
+    }
+
+    //tags failed results with the first matching error category
+    pub fn classify(&self, document: &mut OrderedDocument) {
+        let category = match document.get("measurement_error_message") {
+            Some(&Bson::String(ref error_message)) => self.categories.iter()
+                .find(|&&(_, ref regex)| regex.is_match(error_message))
+                .map_or("other", |&(ref category, _)| category.as_str())
+                .to_owned(),
+            Some(_) => "other".to_owned(),
+            None => return,
+        };
+
+        document.insert("measurement_error_category", category);
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, TipupError> {
+    match Regex::new(pattern) {
+        Ok(regex) => Ok(regex),
+        Err(e) => Err(TipupError::from(format!("invalid error category pattern '{}': {}", pattern, e))),
+    }
+}
@@ -0,0 +1,59 @@
+use time;
+
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+
+pub trait Clock: Send + Sync {
+    //seconds since the unix epoch
+    fn now(&self) -> i64;
+
+    //fractional seconds from an arbitrary origin for measuring durations
+    fn precise_now(&self) -> f64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        time::now_utc().to_timespec().sec
+    }
+
+    fn precise_now(&self) -> f64 {
+        time::precise_time_s()
+    }
+}
+
+pub fn system() -> Arc<Clock> {
+    Arc::new(SystemClock)
+}
+
+//manually stepped clock for deterministic tests of time based behavior
+#[cfg(test)]
+pub struct MockClock {
+    seconds: Mutex<f64>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(timestamp: i64) -> MockClock {
+        MockClock {
+            seconds: Mutex::new(timestamp as f64),
+        }
+    }
+
+    pub fn step(&self, seconds: f64) {
+        *self.seconds.lock().unwrap() += seconds;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> i64 {
+        *self.seconds.lock().unwrap() as i64
+    }
+
+    fn precise_now(&self) -> f64 {
+        *self.seconds.lock().unwrap()
+    }
+}
@@ -0,0 +1,133 @@
+use bson::Bson;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time::{self, Timespec};
+
+use analyzer_health;
+use analyzer_revision;
+use canary::Canary;
+use dry_run;
+use command::parse_duration;
+use error::TipupError;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("history", Some(matches)) => history(matches, proddle_db),
+        ("enable", Some(matches)) => enable(matches, proddle_db),
+        ("rollback", Some(matches)) => rollback(matches, proddle_db),
+        ("canary", Some(matches)) => canary(matches, proddle_db),
+        ("dry-run", Some(matches)) => dry_run(matches, proddle_db),
+        _ => Err(TipupError::from("unknown analyzer command")),
+    }
+}
+
+fn history(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let name = try!(value_t!(matches.value_of("NAME"), String));
+    let window = try!(parse_duration(&try!(value_t!(matches.value_of("WINDOW"), String))));
+    let revisions = try!(analyzer_revision::history(proddle_db, &name));
+    if revisions.len() == 0 {
+        return Err(TipupError::from(format!("no revisions recorded for analyzer '{}'", name)));
+    }
+
+    println!("{:<10}{:<22}{:<16}{:>14}{:>14}  {}", "revision", "timestamp", "author", "flags_before", "flags_after", "changes");
+    for revision in revisions.iter() {
+        //flag volume on either side of the revision to gauge the effect of the change
+        let (before, after) = (revision.timestamp - window, revision.timestamp + window);
+        let before_document = doc!("analyzer" => (name.clone()), "timestamp" => { "$gte" => before, "$lt" => (revision.timestamp) });
+        let after_document = doc!("analyzer" => (name.clone()), "timestamp" => { "$gte" => (revision.timestamp), "$lt" => after });
+        let flags_before = try!(proddle_db.collection("flags").count(Some(before_document), None));
+        let flags_after = try!(proddle_db.collection("flags").count(Some(after_document), None));
+
+        let timestamp = match time::at_utc(Timespec::new(revision.timestamp, 0)).strftime("%Y-%m-%d %H:%M:%S") {
+            Ok(timestamp) => timestamp.to_string(),
+            Err(_) => revision.timestamp.to_string(),
+        };
+
+        let changes = match revision.changes.len() {
+            0 => "initial".to_owned(),
+            _ => revision.changes.join(","),
+        };
+
+        println!("{:<10}{:<22}{:<16}{:>14}{:>14}  {}", revision.revision, timestamp, revision.author, flags_before, flags_after, changes);
+    }
+
+    Ok(())
+}
+
+fn enable(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let name = try!(value_t!(matches.value_of("NAME"), String));
+    match try!(analyzer_health::enable(proddle_db, &name)) {
+        true => println!("analyzer '{}' enabled", name),
+        false => return Err(TipupError::from(format!("analyzer '{}' is not disabled", name))),
+    }
+
+    Ok(())
+}
+
+fn rollback(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let name = try!(value_t!(matches.value_of("NAME"), String));
+    let revision = try!(value_t!(matches.value_of("REVISION"), i32));
+    let author = match matches.value_of("AUTHOR") {
+        Some(author) => author.to_owned(),
+        None => env::var("USER").unwrap_or("unknown".to_owned()),
+    };
+
+    try!(analyzer_revision::rollback(proddle_db, &name, revision, &author));
+    println!("analyzer '{}' rolled back to revision {}", name, revision);
+    Ok(())
+}
+
+fn canary(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let name = try!(value_t!(matches.value_of("NAME"), String));
+    let window = try!(parse_duration(&try!(value_t!(matches.value_of("WINDOW"), String))));
+    let document = match try!(proddle_db.collection("analyzers").find_one(Some(doc!("name" => (name.clone()))), None)) {
+        Some(document) => document,
+        None => return Err(TipupError::from(format!("analyzer '{}' not found", name))),
+    };
+
+    let canary = match try!(Canary::parse(&document)) {
+        Some(canary) => canary,
+        None => return Err(TipupError::from(format!("analyzer '{}' is not a canary", name))),
+    };
+
+    let measurement_class = match document.get("measurement_class") {
+        Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+        _ => return Err(TipupError::from("failed to parse analyzer measurement_class")),
+    };
+
+    //flag rates are normalized by results since each side evaluates a different share of keys
+    let since = time::now_utc().to_timespec().sec - window;
+    let (baseline, canary_arm) = try!(canary.compare(proddle_db, &measurement_class, since));
+    println!("{:<10}{:<32}{:>8}{:>12}{:>10}{:>22}", "arm", "analyzer", "keys", "results", "flags", "flags_per_1k_results");
+    for &(arm, label) in [(&baseline, "baseline"), (&canary_arm, "canary")].iter() {
+        let rate = match arm.results {
+            0 => 0.0,
+            results => arm.flags as f64 * 1000.0 / results as f64,
+        };
+
+        println!("{:<10}{:<32}{:>8}{:>12}{:>10}{:>22.2}", label, arm.analyzer, arm.keys, arm.results, arm.flags, rate);
+    }
+
+    Ok(())
+}
+
+fn dry_run(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let name = try!(value_t!(matches.value_of("NAME"), String));
+    let mut sample = String::new();
+    let read = match matches.value_of("RESULT") {
+        Some(path) => File::open(path).and_then(|mut file| file.read_to_string(&mut sample)),
+        None => io::stdin().read_to_string(&mut sample),
+    };
+
+    if let Err(e) = read {
+        return Err(TipupError::from(format!("failed to read sample result: {}", e)));
+    }
+
+    let evaluation = try!(dry_run::evaluate(proddle_db, &name, &sample));
+    println!("{}", Bson::Document(evaluation).to_json());
+    Ok(())
+}
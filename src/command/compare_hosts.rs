@@ -0,0 +1,168 @@
+use bson::Bson;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use classifier::ErrorClassifier;
+use analyzer::get_value;
+use command::{parse_duration, percentile};
+use error::TipupError;
+use topology::Topology;
+
+use std::collections::{BTreeMap, HashSet};
+
+struct HostSummary {
+    results: usize,
+    errors: usize,
+    error_categories: BTreeMap<String, usize>,
+    domains: HashSet<String>,
+    values: Vec<f64>,
+    flags: BTreeMap<String, usize>,
+}
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let hostnames = [try!(value_t!(matches.value_of("HOST_A"), String)), try!(value_t!(matches.value_of("HOST_B"), String))];
+    let measurement_class = try!(value_t!(matches.value_of("MEASUREMENT"), String));
+    let field: Option<Vec<String>> = matches.value_of("FIELD").map(|x| x.split('.').map(|x| x.to_owned()).collect());
+    let since = try!(parse_duration(&try!(value_t!(matches.value_of("SINCE"), String))));
+    let timestamp = time::now_utc().to_timespec().sec - since;
+
+    let mut classifier = ErrorClassifier::new();
+    try!(classifier.initialize(proddle_db));
+
+    let mut summaries = Vec::new();
+    for hostname in hostnames.iter() {
+        summaries.push(try!(summarize(proddle_db, &classifier, hostname, &measurement_class, &field, timestamp)));
+    }
+
+    //print statistics side by side
+    println!("measurement:{} since:{}", measurement_class, try!(value_t!(matches.value_of("SINCE"), String)));
+    print_row("", &hostnames[0], &hostnames[1]);
+
+    let topology = try!(Topology::load(proddle_db));
+    for attribute in ["region", "site", "asn"].iter() {
+        let values: Vec<&str> = hostnames.iter().map(|x| topology.get(x, attribute).map_or("-", |x| x.as_str())).collect();
+        print_row(attribute, values[0], values[1]);
+    }
+
+    print_row("results", &summaries[0].results.to_string(), &summaries[1].results.to_string());
+    print_row("errors", &summaries[0].errors.to_string(), &summaries[1].errors.to_string());
+    print_row("error_rate", &format_rate(&summaries[0]), &format_rate(&summaries[1]));
+
+    let mut error_categories: Vec<&String> = summaries[0].error_categories.keys().chain(summaries[1].error_categories.keys()).collect();
+    error_categories.sort();
+    error_categories.dedup();
+    for error_category in error_categories {
+        let counts: Vec<usize> = summaries.iter().map(|x| *x.error_categories.get(error_category).unwrap_or(&0)).collect();
+        print_row(&format!("  {}", error_category), &counts[0].to_string(), &counts[1].to_string());
+    }
+
+    print_row("domains", &summaries[0].domains.len().to_string(), &summaries[1].domains.len().to_string());
+
+    let shared_domains = summaries[0].domains.intersection(&summaries[1].domains).count();
+    print_row("shared_domains", &shared_domains.to_string(), &shared_domains.to_string());
+
+    if let Some(ref field) = field {
+        println!("field:{}", field.join("."));
+        print_row("values", &summaries[0].values.len().to_string(), &summaries[1].values.len().to_string());
+        for &(name, statistic) in [("min", 0.0), ("median", 50.0), ("p95", 95.0), ("p99", 99.0), ("max", 100.0)].iter() {
+            print_row(name, &format_percentile(&summaries[0].values, statistic), &format_percentile(&summaries[1].values, statistic));
+        }
+
+        print_row("mean", &format_mean(&summaries[0].values), &format_mean(&summaries[1].values));
+    }
+
+    //print flag counts per analyzer
+    let mut analyzers: Vec<&String> = summaries[0].flags.keys().chain(summaries[1].flags.keys()).collect();
+    analyzers.sort();
+    analyzers.dedup();
+
+    println!("flags:");
+    let totals: Vec<usize> = summaries.iter().map(|x| x.flags.values().fold(0, |sum, x| sum + x)).collect();
+    print_row("total", &totals[0].to_string(), &totals[1].to_string());
+    for analyzer in analyzers {
+        let counts: Vec<usize> = summaries.iter().map(|x| *x.flags.get(analyzer).unwrap_or(&0)).collect();
+        print_row(analyzer, &counts[0].to_string(), &counts[1].to_string());
+    }
+
+    Ok(())
+}
+
+fn summarize(proddle_db: &Database, classifier: &ErrorClassifier, hostname: &str, measurement_class: &str, field: &Option<Vec<String>>, timestamp: i64) -> Result<HostSummary, TipupError> {
+    let mut summary = HostSummary {
+        results: 0,
+        errors: 0,
+        error_categories: BTreeMap::new(),
+        domains: HashSet::new(),
+        values: Vec::new(),
+        flags: BTreeMap::new(),
+    };
+
+    //collect result statistics
+    let timestamp_gte = doc!("$gte" => timestamp);
+    let search_document = Some(doc!(
+        "vantage_hostname" => hostname,
+        "measurement_class" => measurement_class,
+        "timestamp" => timestamp_gte
+    ));
+    for document in try!(proddle_db.collection("measurements").find(search_document, None)) {
+        let mut document = try!(document);
+        summary.results += 1;
+        classifier.classify(&mut document);
+        if let Some(&Bson::String(ref error_category)) = document.get("measurement_error_category") {
+            summary.errors += 1;
+            *summary.error_categories.entry(error_category.to_owned()).or_insert(0) += 1;
+        }
+
+        if let Some(&Bson::String(ref domain)) = document.get("measurement_domain") {
+            summary.domains.insert(domain.to_owned());
+        }
+
+        if let Some(value) = field.as_ref().and_then(|x| get_value(x, &document)) {
+            summary.values.push(value);
+        }
+    }
+
+    summary.values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    //count flags raised for the measurement class
+    let timestamp_gte = doc!("$gte" => timestamp);
+    let search_document = Some(doc!(
+        "hostname" => hostname,
+        "labels.measurement_class" => measurement_class,
+        "timestamp" => timestamp_gte
+    ));
+    for document in try!(proddle_db.collection("flags").find(search_document, None)) {
+        let document = try!(document);
+        if let Some(&Bson::String(ref analyzer)) = document.get("analyzer") {
+            *summary.flags.entry(analyzer.to_owned()).or_insert(0) += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+fn print_row(name: &str, a: &str, b: &str) {
+    println!("{:<16} {:>24} {:>24}", name, a, b);
+}
+
+fn format_rate(summary: &HostSummary) -> String {
+    match summary.results {
+        0 => "-".to_owned(),
+        results => format!("{:.2}%", summary.errors as f64 / results as f64 * 100.0),
+    }
+}
+
+fn format_percentile(values: &Vec<f64>, statistic: f64) -> String {
+    match values.len() {
+        0 => "-".to_owned(),
+        _ => format!("{:.3}", percentile(values, statistic)),
+    }
+}
+
+fn format_mean(values: &Vec<f64>) -> String {
+    match values.len() {
+        0 => "-".to_owned(),
+        _ => format!("{:.3}", values.iter().fold(0.0, |sum, x| sum + x) / values.len() as f64),
+    }
+}
@@ -0,0 +1,46 @@
+use clap::ArgMatches;
+
+use config::{self, Config, Validation};
+use error::TipupError;
+
+use std;
+
+pub fn execute(matches: &ArgMatches, config: &Config, validation: Validation) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("validate", Some(_)) => validate(config, validation),
+        _ => Err(TipupError::from("unknown config command")),
+    }
+}
+
+fn validate(config: &Config, mut validation: Validation) -> Result<(), TipupError> {
+    //definitions may only be checked when mongodb is reachable
+    if validation.errors.len() == 0 {
+        match ::initialize_mongodb_client(config) {
+            Ok(client) => match ::initialize_db(&client, "proddle", &config.username, &config.password) {
+                Ok(db) => match config.severity_taxonomy() {
+                    Ok(taxonomy) => try!(config::validate_definitions(&db, &taxonomy, &mut validation)),
+                    Err(e) => validation.error(format!("invalid severity taxonomy: {}", e)),
+                },
+                Err(e) => validation.error(format!("failed to authenticate with mongodb: {}", e)),
+            },
+            Err(e) => validation.error(format!("failed to connect to mongodb: {}", e)),
+        }
+    } else {
+        validation.warning("skipped validating stored definitions due to argument errors");
+    }
+
+    for warning in validation.warnings.iter() {
+        println!("warning: {}", warning);
+    }
+
+    for error in validation.errors.iter() {
+        println!("error: {}", error);
+    }
+
+    println!("{} error(s), {} warning(s)", validation.errors.len(), validation.warnings.len());
+    if validation.errors.len() > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
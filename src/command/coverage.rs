@@ -0,0 +1,107 @@
+use bson::Bson;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use command::{analyzers_by_measurement, parse_duration};
+use error::TipupError;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let since_value = try!(value_t!(matches.value_of("SINCE"), String));
+    let since = try!(parse_duration(&since_value));
+    let timestamp = time::now_utc().to_timespec().sec - since;
+
+    //count results per (measurement class, vantage hostname)
+    let timestamp_gte = doc!("$gte" => timestamp);
+    let id_document = doc!("measurement_class" => "$measurement_class", "vantage_hostname" => "$vantage_hostname");
+    let group_document = doc!("_id" => id_document, "results" => { "$sum" => 1 });
+    let pipeline = vec!(
+        doc!("$match" => { "timestamp" => timestamp_gte }),
+        doc!("$group" => group_document),
+    );
+
+    let mut results: BTreeMap<String, BTreeMap<String, i64>> = BTreeMap::new();
+    let mut hostnames = BTreeSet::new();
+    for document in try!(proddle_db.collection("measurements").aggregate(pipeline, None)) {
+        let document = try!(document);
+        let (measurement_class, hostname) = match document.get("_id") {
+            Some(&Bson::Document(ref id_document)) => match (id_document.get("measurement_class"), id_document.get("vantage_hostname")) {
+                (Some(&Bson::String(ref measurement_class)), Some(&Bson::String(ref hostname))) => (measurement_class.to_owned(), hostname.to_owned()),
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let count = match document.get("results") {
+            Some(&Bson::I32(count)) => count as i64,
+            Some(&Bson::I64(count)) => count,
+            _ => return Err(TipupError::from("failed to parse coverage result count")),
+        };
+
+        hostnames.insert(hostname.clone());
+        results.entry(measurement_class).or_insert(BTreeMap::new()).insert(hostname, count);
+    }
+
+    let analyzers = try!(analyzers_by_measurement(proddle_db));
+    let measurement_classes: BTreeSet<&String> = results.keys().chain(analyzers.keys()).collect();
+    if measurement_classes.len() == 0 {
+        println!("no measurements or analyzers within {}", since_value);
+        return Ok(());
+    }
+
+    //print matrix of analyzers covering each measurement per host, '-' marks uncovered results
+    let width = hostnames.iter().map(|x| x.len()).max().unwrap_or(0).max(6);
+    print!("{:<32}", "measurement");
+    for hostname in hostnames.iter() {
+        print!(" {:>width$}", hostname, width = width);
+    }
+    println!("  analyzers");
+
+    for measurement_class in measurement_classes.iter() {
+        let names = analyzers.get(*measurement_class);
+        print!("{:<32}", measurement_class);
+        for hostname in hostnames.iter() {
+            let cell = match (results.get(*measurement_class).and_then(|x| x.get(hostname)), names) {
+                (None, _) => ".".to_owned(),
+                (Some(_), None) => "-".to_owned(),
+                (Some(_), Some(names)) => names.len().to_string(),
+            };
+
+            print!(" {:>width$}", cell, width = width);
+        }
+
+        println!("  {}", names.map_or("-".to_owned(), |x| x.join(",")));
+    }
+
+    //highlight blind spots and analyzers evaluating nothing
+    let mut gaps = Vec::new();
+    for (measurement_class, host_results) in results.iter() {
+        if !analyzers.contains_key(measurement_class) {
+            gaps.push(format!("measurement '{}' has {} result(s) from {} host(s) but no analyzer", measurement_class,
+                host_results.values().fold(0, |sum, x| sum + x), host_results.len()));
+        }
+    }
+
+    for (measurement_class, names) in analyzers.iter() {
+        if !results.contains_key(measurement_class) {
+            for name in names.iter() {
+                gaps.push(format!("analyzer '{}' has no '{}' results within {}", name, measurement_class, since_value));
+            }
+        }
+    }
+
+    println!("");
+    match gaps.len() {
+        0 => println!("no coverage gaps"),
+        _ => {
+            println!("{} coverage gap(s):", gaps.len());
+            for gap in gaps.iter() {
+                println!("  {}", gap);
+            }
+        },
+    }
+
+    Ok(())
+}
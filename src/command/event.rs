@@ -0,0 +1,31 @@
+use clap::ArgMatches;
+use mongodb::db::Database;
+use time;
+
+use command::parse_duration;
+use error::TipupError;
+use event::Event;
+
+pub fn execute(matches: &ArgMatches, tipup_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("add", Some(matches)) => add(matches, tipup_db),
+        _ => Err(TipupError::from("unknown event command")),
+    }
+}
+
+fn add(matches: &ArgMatches, tipup_db: &Database) -> Result<(), TipupError> {
+    let kind = try!(value_t!(matches.value_of("KIND"), String));
+    let description = try!(value_t!(matches.value_of("DESCRIPTION"), String));
+    let domain = matches.value_of("DOMAIN").map(|x| x.to_owned());
+    let hostname = matches.value_of("HOSTNAME").map(|x| x.to_owned());
+    let start_timestamp = match matches.value_of("TIMESTAMP") {
+        Some(_) => try!(value_t!(matches.value_of("TIMESTAMP"), i64)),
+        None => time::now_utc().to_timespec().sec,
+    };
+    let duration = try!(parse_duration(&try!(value_t!(matches.value_of("DURATION"), String))));
+
+    let event = try!(Event::new(&kind, &description, domain, hostname, start_timestamp, start_timestamp + duration));
+    try!(event.insert(tipup_db));
+    println!("recorded event {}", event.id);
+    Ok(())
+}
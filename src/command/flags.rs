@@ -0,0 +1,200 @@
+use bson::Bson;
+use bson::oid::ObjectId;
+use clap::ArgMatches;
+use mongodb::db::Database;
+use time::{self, Timespec};
+
+use command::parse_duration;
+use error::TipupError;
+use flag_manager::{self, Flag};
+use flag_store::FlagStore;
+use self_healing;
+
+//number of buckets evidence and label features are hashed into
+const FEATURE_BUCKETS: usize = 64;
+
+pub fn execute(matches: &ArgMatches, tipup_db: &Database, flag_store: &FlagStore) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("ack", Some(matches)) => set_state(matches, tipup_db, flag_store, "acknowledged"),
+        ("resolve", Some(matches)) => set_state(matches, tipup_db, flag_store, "resolved"),
+        ("snooze", Some(matches)) => snooze(matches, flag_store),
+        ("self-healing", Some(_)) => self_healing(tipup_db),
+        ("similar", Some(matches)) => similar(matches, flag_store),
+        _ => Err(TipupError::from("unknown flags command")),
+    }
+}
+
+fn set_state(matches: &ArgMatches, tipup_db: &Database, flag_store: &FlagStore, state: &str) -> Result<(), TipupError> {
+    let id = try!(value_t!(matches.value_of("ID"), String));
+    match try!(flag_manager::set_state(flag_store, tipup_db, &id, state)) {
+        true => println!("flag {} {}", id, state),
+        false => return Err(TipupError::from(format!("flag '{}' not found", id))),
+    }
+
+    Ok(())
+}
+
+fn snooze(matches: &ArgMatches, flag_store: &FlagStore) -> Result<(), TipupError> {
+    let id = try!(value_t!(matches.value_of("ID"), String));
+    let until_timestamp = match matches.value_of("FOR") {
+        Some(duration) => Some(time::now_utc().to_timespec().sec + try!(parse_duration(duration))),
+        None => None,
+    };
+
+    let condition = match matches.value_of("FIELD") {
+        Some(field) => Some((field.split('.').map(|x| x.to_owned()).collect(), try!(value_t!(matches.value_of("INCREASE"), f64)))),
+        None => None,
+    };
+
+    if until_timestamp.is_none() && condition.is_none() {
+        return Err(TipupError::from("snooze requires '--for' and/or '--field' with '--increase'"));
+    }
+
+    match try!(flag_manager::snooze(flag_store, &id, until_timestamp, condition)) {
+        true => println!("flag {} snoozed", id),
+        false => return Err(TipupError::from(format!("flag '{}' not found", id))),
+    }
+
+    Ok(())
+}
+
+fn self_healing(tipup_db: &Database) -> Result<(), TipupError> {
+    let documents = try!(self_healing::statistics(tipup_db));
+    if documents.len() == 0 {
+        println!("no flags have been auto-resolved");
+        return Ok(());
+    }
+
+    println!("{:<32} {:>10} {:>12} {:>8} {:>14}", "analyzer", "resolved", "self_healed", "ratio", "confirmation");
+    for document in documents.iter() {
+        match (document.get("analyzer"), document.get("resolved"), document.get("self_healed"), document.get("ratio"), document.get("confirmation")) {
+            (Some(&Bson::String(ref analyzer)), Some(&Bson::I64(resolved)), Some(&Bson::I64(self_healed)), Some(&Bson::FloatingPoint(ratio)), Some(&Bson::I64(confirmation))) =>
+                println!("{:<32} {:>10} {:>12} {:>8.2} {:>13}s", analyzer, resolved, self_healed, ratio, confirmation),
+            _ => return Err(TipupError::from("failed to parse self healing statistics")),
+        }
+    }
+
+    Ok(())
+}
+
+fn similar(matches: &ArgMatches, flag_store: &FlagStore) -> Result<(), TipupError> {
+    let id = try!(value_t!(matches.value_of("ID"), String));
+    let limit = try!(value_t!(matches.value_of("LIMIT"), usize));
+    let object_id = match ObjectId::with_string(&id) {
+        Ok(object_id) => object_id,
+        Err(_) => return Err(TipupError::from(format!("invalid flag id '{}'", id))),
+    };
+
+    let flag = match try!(flag_store.get(&object_id)) {
+        Some(flag) => flag,
+        None => return Err(TipupError::from(format!("flag '{}' not found", id))),
+    };
+
+    //rank historical flags of the same analyzer and target by feature similarity
+    let features = hash_features(&flag);
+    let mut candidates = Vec::new();
+    for candidate in try!(flag_store.find_by_target(&flag.analyzer, &flag.domain)) {
+        if candidate.id == object_id {
+            continue;
+        }
+
+        let similarity = cosine_similarity(&features, &hash_features(&candidate));
+        let resolution = resolution(&candidate);
+        candidates.push((similarity, candidate, resolution));
+    }
+
+    println!("flag:{} analyzer:{} domain:{} hostname:{} candidates:{}", id, flag.analyzer, flag.domain,
+        flag.hostname.as_ref().map_or("-", |x| x.as_str()), candidates.len());
+    if candidates.len() == 0 {
+        return Ok(());
+    }
+
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(b.1.timestamp.cmp(&a.1.timestamp)));
+    println!("{:<24} {:<20} {:<24} {:<12} {:>10} {}", "id", "timestamp", "hostname", "state", "similarity", "resolution");
+    for &(similarity, ref candidate, ref resolution) in candidates.iter().take(limit) {
+        println!("{:<24} {:<20} {:<24} {:<12} {:>10.3} {}", candidate.id.to_hex(), format_timestamp(candidate.timestamp),
+            candidate.hostname.as_ref().map_or("-", |x| x.as_str()), candidate.state, similarity, resolution);
+    }
+
+    Ok(())
+}
+
+fn hash_features(flag: &Flag) -> Vec<f64> {
+    //hash status, hostname, labels, and evidence into a fixed width count vector
+    let mut features = vec!(format!("status={}", flag.status));
+    if let Some(ref hostname) = flag.hostname {
+        features.push(format!("hostname={}", hostname));
+    }
+
+    for (key, value) in flag.labels.iter() {
+        features.push(format!("label:{}={}", key, value));
+    }
+
+    //numeric evidence is compared by order of magnitude so nearby values collide
+    for (key, value) in flag.evidence.iter() {
+        features.push(format!("evidence:{}", key));
+        match value.parse::<f64>() {
+            Ok(value) if value.is_finite() => features.push(format!("evidence:{}~{}", key, value.abs().log2().floor().max(-64.0) as i64 * value.signum() as i64)),
+            _ => features.push(format!("evidence:{}={}", key, value)),
+        }
+    }
+
+    let mut vector = vec![0.0; FEATURE_BUCKETS];
+    for feature in features.iter() {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in feature.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        vector[(hash % FEATURE_BUCKETS as u64) as usize] += 1.0;
+    }
+
+    vector
+}
+
+fn cosine_similarity(a: &Vec<f64>, b: &Vec<f64>) -> f64 {
+    let dot_product = a.iter().zip(b.iter()).fold(0.0, |sum, (x, y)| sum + (x * y));
+    let (a_norm, b_norm) = (a.iter().fold(0.0, |sum, x| sum + (x * x)).sqrt(), b.iter().fold(0.0, |sum, x| sum + (x * x)).sqrt());
+    match a_norm * b_norm {
+        0.0 => 0.0,
+        norm => dot_product / norm,
+    }
+}
+
+fn resolution(flag: &Flag) -> String {
+    //describe how and how quickly a historical flag was handled
+    let mut resolution = Vec::new();
+    if let Some(acknowledged_timestamp) = flag.acknowledged_timestamp {
+        resolution.push(format!("acknowledged after {}", format_duration(acknowledged_timestamp - flag.timestamp)));
+    }
+
+    if let Some(resolved_timestamp) = flag.resolved_timestamp {
+        resolution.push(format!("resolved after {}", format_duration(resolved_timestamp - flag.timestamp)));
+    }
+
+    if let Some(ref feedback) = flag.feedback {
+        resolution.push(format!("feedback {}", feedback));
+    }
+
+    match resolution.len() {
+        0 => "-".to_owned(),
+        _ => resolution.join(", "),
+    }
+}
+
+fn format_duration(seconds: i64) -> String {
+    match seconds {
+        x if x >= 86400 => format!("{}d{}h", x / 86400, (x % 86400) / 3600),
+        x if x >= 3600 => format!("{}h{}m", x / 3600, (x % 3600) / 60),
+        x if x >= 60 => format!("{}m", x / 60),
+        x => format!("{}s", x.max(0)),
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    match time::at_utc(Timespec::new(timestamp, 0)).strftime("%Y-%m-%d %H:%M:%S") {
+        Ok(formatted) => formatted.to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
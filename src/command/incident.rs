@@ -0,0 +1,73 @@
+use bson::{self, Bson};
+use bson::oid::ObjectId;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time::{self, Timespec};
+
+use error::TipupError;
+use incident_manager::Incident;
+use timeline::Timeline;
+
+pub fn execute(matches: &ArgMatches, tipup_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("show", Some(matches)) => show(matches, tipup_db),
+        _ => Err(TipupError::from("unknown incident command")),
+    }
+}
+
+fn show(matches: &ArgMatches, tipup_db: &Database) -> Result<(), TipupError> {
+    let id = try!(value_t!(matches.value_of("ID"), String));
+    let object_id = match ObjectId::with_string(&id) {
+        Ok(object_id) => object_id,
+        Err(_) => return Err(TipupError::from(format!("invalid incident id '{}'", id))),
+    };
+
+    let incident: Incident = match try!(tipup_db.collection("incidents").find_one(Some(doc!("_id" => (object_id.clone()))), None)) {
+        Some(document) => match bson::from_bson(Bson::Document(document)) {
+            Ok(incident) => incident,
+            Err(_) => return Err(TipupError::from("failed to parse bson document into incident")),
+        },
+        None => return Err(TipupError::from(format!("incident '{}' not found", id))),
+    };
+
+    //build timelines which the incident manager has not yet persisted
+    let timeline = match try!(Timeline::load(tipup_db, &object_id)) {
+        Some(timeline) => timeline,
+        None => {
+            let timeline = try!(Timeline::build(&incident, tipup_db, time::now_utc().to_timespec().sec));
+            try!(timeline.persist(tipup_db));
+            timeline
+        },
+    };
+
+    let state = match timeline.resolved_timestamp {
+        Some(resolved_timestamp) => format!("resolved after {}", format_duration(resolved_timestamp - timeline.start_timestamp)),
+        None => "ongoing".to_owned(),
+    };
+
+    println!("incident:{} domain:{} hostnames:{} flags:{} events:{} state:{}", id, incident.domain, incident.hostnames.len(),
+        incident.flag_ids.len(), incident.related_event_ids.len(), state);
+    println!("{:<20} {:<16} {:<24} {}", "timestamp", "kind", "hostname", "description");
+    for entry in timeline.entries.iter() {
+        println!("{:<20} {:<16} {:<24} {}", format_timestamp(entry.timestamp), entry.kind,
+            entry.hostname.as_ref().map_or("-", |x| x.as_str()), entry.description);
+    }
+
+    Ok(())
+}
+
+fn format_duration(seconds: i64) -> String {
+    match seconds {
+        x if x >= 86400 => format!("{}d{}h", x / 86400, (x % 86400) / 3600),
+        x if x >= 3600 => format!("{}h{}m", x / 3600, (x % 3600) / 60),
+        x if x >= 60 => format!("{}m", x / 60),
+        x => format!("{}s", x.max(0)),
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    match time::at_utc(Timespec::new(timestamp, 0)).strftime("%Y-%m-%d %H:%M:%S") {
+        Ok(formatted) => formatted.to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
@@ -0,0 +1,63 @@
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use analyzer::get_value;
+use command::{parse_duration, percentile};
+use error::TipupError;
+
+const HISTOGRAM_BUCKETS: usize = 10;
+const HISTOGRAM_WIDTH: usize = 50;
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let measurement_class = try!(value_t!(matches.value_of("MEASUREMENT"), String));
+    let field: Vec<String> = try!(value_t!(matches.value_of("FIELD"), String)).split('.').map(|x| x.to_owned()).collect();
+    let since = try!(parse_duration(&try!(value_t!(matches.value_of("SINCE"), String))));
+
+    //collect field values from recent results
+    let timestamp_gte = doc!("$gte" => (time::now_utc().to_timespec().sec - since));
+    let search_document = Some(doc!("measurement_class" => (measurement_class.clone()), "timestamp" => timestamp_gte));
+    let (mut total, mut values) = (0, Vec::new());
+    for document in try!(proddle_db.collection("measurements").find(search_document, None)) {
+        let document = try!(document);
+        total += 1;
+        if let Some(value) = get_value(&field, &document) {
+            values.push(value);
+        }
+    }
+
+    println!("measurement:{} field:{} results:{} values:{}", measurement_class, field.join("."), total, values.len());
+    if values.len() == 0 {
+        return Ok(());
+    }
+
+    //compute distribution statistics
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mean = values.iter().fold(0.0, |sum, x| sum + x) / values.len() as f64;
+    let variance = values.iter().fold(0.0, |sum, x| sum + (x - mean).powf(2.0)) / values.len() as f64;
+    let (minimum, maximum) = (values[0], values[values.len() - 1]);
+
+    println!("min:{:.3} mean:{:.3} std_dev:{:.3} max:{:.3}", minimum, mean, variance.sqrt(), maximum);
+    println!("median:{:.3} p95:{:.3} p99:{:.3}", percentile(&values, 50.0), percentile(&values, 95.0), percentile(&values, 99.0));
+
+    //print histogram of values
+    let bucket_width = (maximum - minimum) / HISTOGRAM_BUCKETS as f64;
+    let mut buckets = vec![0; HISTOGRAM_BUCKETS];
+    for value in values.iter() {
+        let index = match bucket_width {
+            0.0 => 0,
+            _ => (((value - minimum) / bucket_width) as usize).min(HISTOGRAM_BUCKETS - 1),
+        };
+
+        buckets[index] += 1;
+    }
+
+    let largest_bucket = *buckets.iter().max().unwrap();
+    for (i, count) in buckets.iter().enumerate() {
+        let bar_length = (*count as f64 / largest_bucket as f64 * HISTOGRAM_WIDTH as f64).round() as usize;
+        println!("[{:>12.3}, {:>12.3}) {:>8} {}", minimum + (i as f64 * bucket_width),
+            minimum + ((i + 1) as f64 * bucket_width), count, "#".repeat(bar_length));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use bson::Bson;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+use flag_store::FlagStore;
+
+use std::collections::BTreeMap;
+
+pub mod analyzer;
+pub mod compare_hosts;
+pub mod config;
+pub mod coverage;
+pub mod event;
+pub mod flags;
+pub mod incident;
+pub mod inspect;
+pub mod onboard_target;
+pub mod provider;
+pub mod silence;
+pub mod sinks;
+pub mod snapshot;
+pub mod top;
+pub mod topology;
+pub mod trace;
+
+pub fn execute(name: &str, matches: &ArgMatches, proddle_db: &Database, flag_store: &FlagStore) -> Result<(), TipupError> {
+    match name {
+        "analyzer" => analyzer::execute(matches, proddle_db),
+        "compare-hosts" => compare_hosts::execute(matches, proddle_db),
+        "coverage" => coverage::execute(matches, proddle_db),
+        "event" => event::execute(matches, proddle_db),
+        "flags" => flags::execute(matches, proddle_db, flag_store),
+        "incident" => incident::execute(matches, proddle_db),
+        "inspect" => inspect::execute(matches, proddle_db),
+        "onboard-target" => onboard_target::execute(matches, proddle_db),
+        "provider" => provider::execute(matches, proddle_db),
+        "silence" => silence::execute(matches, proddle_db, flag_store),
+        "sinks" => sinks::execute(matches, proddle_db),
+        "snapshot" => snapshot::execute(matches, proddle_db),
+        "top" => top::execute(matches, proddle_db, flag_store),
+        "topology" => topology::execute(matches, proddle_db),
+        "trace" => trace::execute(matches, proddle_db),
+        _ => Err(TipupError::from(format!("unknown command '{}'", name))),
+    }
+}
+
+pub fn parse_duration(duration: &str) -> Result<i64, TipupError> {
+    //parse durations like '90s', '30m', '24h', '7d', and '2w' into seconds
+    let duration = duration.trim();
+    let (value, unit) = match duration.find(|c: char| !c.is_digit(10)) {
+        Some(index) => (&duration[..index], &duration[index..]),
+        None => (duration, "s"),
+    };
+
+    let value = match value.parse::<i64>() {
+        Ok(value) => value,
+        Err(_) => return Err(TipupError::from(format!("failed to parse duration '{}'", duration))),
+    };
+
+    match unit {
+        "s" => Ok(value),
+        "m" => Ok(value * 60),
+        "h" => Ok(value * 3600),
+        "d" => Ok(value * 86400),
+        "w" => Ok(value * 604800),
+        _ => Err(TipupError::from(format!("unknown duration unit '{}'", unit))),
+    }
+}
+
+pub fn percentile(sorted_values: &Vec<f64>, percentile: f64) -> f64 {
+    //nearest-rank percentile of sorted values
+    let rank = ((percentile / 100.0) * sorted_values.len() as f64).ceil() as usize;
+    sorted_values[rank.saturating_sub(1).min(sorted_values.len() - 1)]
+}
+
+pub fn analyzers_by_measurement(proddle_db: &Database) -> Result<BTreeMap<String, Vec<String>>, TipupError> {
+    //names of the analyzers registered to each measurement class
+    let mut analyzers = BTreeMap::new();
+    for document in try!(proddle_db.collection("analyzers").find(None, None)) {
+        let document = try!(document);
+        if let (Some(&Bson::String(ref name)), Some(&Bson::String(ref measurement_class))) = (document.get("name"), document.get("measurement_class")) {
+            analyzers.entry(measurement_class.to_owned()).or_insert(Vec::new()).push(name.to_owned());
+        }
+    }
+
+    Ok(analyzers)
+}
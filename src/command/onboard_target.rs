@@ -0,0 +1,146 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use command::{analyzers_by_measurement, parse_duration};
+use error::TipupError;
+
+use std::collections::BTreeMap;
+
+struct MeasurementCoverage {
+    results: i64,
+    hosts: usize,
+    analyzers: Vec<String>,
+}
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let domain = try!(value_t!(matches.value_of("DOMAIN"), String));
+    let since = try!(parse_duration(&try!(value_t!(matches.value_of("SINCE"), String))));
+    let create_analyzers = matches.is_present("CREATE_ANALYZERS");
+
+    //verify measurements exist for the target
+    let mut measurements = try!(collect_measurements(proddle_db, &domain, time::now_utc().to_timespec().sec - since));
+    println!("[{}] measurements exist for '{}'", if measurements.len() > 0 { "ok" } else { "missing" }, domain);
+    if measurements.len() == 0 {
+        return Err(TipupError::from(format!("no measurements of '{}' within {}", domain, try!(value_t!(matches.value_of("SINCE"), String)))));
+    }
+
+    //check each measurement class is covered by at least one analyzer
+    for (measurement_class, analyzers) in try!(analyzers_by_measurement(proddle_db)) {
+        if let Some(coverage) = measurements.get_mut(&measurement_class) {
+            coverage.analyzers = analyzers;
+        }
+    }
+
+    //optionally create default analyzers for uncovered measurements from templates
+    if create_analyzers {
+        for (measurement_class, coverage) in measurements.iter_mut().filter(|&(_, ref x)| x.analyzers.len() == 0) {
+            let search_document = Some(doc!("measurement_class" => (measurement_class.clone())));
+            for template in try!(proddle_db.collection("analyzer_templates").find(search_document, None)) {
+                let name = try!(create_analyzer(proddle_db, &try!(template)));
+                println!("created analyzer '{}' for measurement '{}'", name, measurement_class);
+                coverage.analyzers.push(name);
+            }
+        }
+    }
+
+    let mut uncovered = 0;
+    for (measurement_class, coverage) in measurements.iter() {
+        match coverage.analyzers.len() {
+            0 => {
+                println!("[missing] analyzer covering measurement '{}'", measurement_class);
+                uncovered += 1;
+            },
+            _ => println!("[ok] analyzer covering measurement '{}'", measurement_class),
+        }
+    }
+
+    //print coverage report
+    println!("");
+    println!("{:<32} {:>10} {:>8}  {}", "measurement", "results", "hosts", "analyzers");
+    for (measurement_class, coverage) in measurements.iter() {
+        let analyzers = match coverage.analyzers.len() {
+            0 => "-".to_owned(),
+            _ => coverage.analyzers.join(","),
+        };
+
+        println!("{:<32} {:>10} {:>8}  {}", measurement_class, coverage.results, coverage.hosts, analyzers);
+    }
+
+    match uncovered {
+        0 => Ok(()),
+        _ => Err(TipupError::from(format!("{} measurement(s) of '{}' are not covered by any analyzer", uncovered, domain))),
+    }
+}
+
+fn collect_measurements(proddle_db: &Database, domain: &str, since: i64) -> Result<BTreeMap<String, MeasurementCoverage>, TipupError> {
+    //count results and vantage hosts per measurement class of the target
+    let timestamp_gte = doc!("$gte" => since);
+    let match_document = doc!("measurement_domain" => domain, "timestamp" => timestamp_gte);
+    let group_document = doc!(
+        "_id" => "$measurement_class",
+        "results" => { "$sum" => 1 },
+        "hosts" => { "$addToSet" => "$vantage_hostname" }
+    );
+    let pipeline = vec!(
+        doc!("$match" => match_document),
+        doc!("$group" => group_document),
+    );
+
+    let mut measurements = BTreeMap::new();
+    for document in try!(proddle_db.collection("measurements").aggregate(pipeline, None)) {
+        let document = try!(document);
+        let measurement_class = match document.get("_id") {
+            Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+            _ => continue,
+        };
+
+        let results = match document.get("results") {
+            Some(&Bson::I32(results)) => results as i64,
+            Some(&Bson::I64(results)) => results,
+            _ => return Err(TipupError::from("failed to parse measurement result count")),
+        };
+
+        let hosts = match document.get("hosts") {
+            Some(&Bson::Array(ref hosts)) => hosts.len(),
+            _ => return Err(TipupError::from("failed to parse measurement vantage hosts")),
+        };
+
+        measurements.insert(measurement_class,
+            MeasurementCoverage {
+                results: results,
+                hosts: hosts,
+                analyzers: Vec::new(),
+            }
+        );
+    }
+
+    Ok(measurements)
+}
+
+fn create_analyzer(proddle_db: &Database, template: &OrderedDocument) -> Result<String, TipupError> {
+    let (class, measurement_class) = match (template.get("class"), template.get("measurement_class")) {
+        (Some(&Bson::String(ref class)), Some(&Bson::String(ref measurement_class))) => (class, measurement_class),
+        _ => return Err(TipupError::from("failed to parse analyzer template class and measurement_class")),
+    };
+
+    //templates are named after their measurement and class unless given a name
+    let name = match template.get("name") {
+        Some(&Bson::String(ref name)) => name.to_owned(),
+        None => format!("{}_{}", measurement_class, class.to_lowercase()),
+        _ => return Err(TipupError::from("failed to parse analyzer template name")),
+    };
+
+    if try!(proddle_db.collection("analyzers").count(Some(doc!("name" => (name.clone()))), None)) > 0 {
+        return Err(TipupError::from(format!("analyzer '{}' already exists for a different measurement", name)));
+    }
+
+    let mut document = template.clone();
+    document.remove("_id");
+    document.insert("name", name.clone());
+    document.insert("modified_by", "onboard-target");
+    try!(proddle_db.collection("analyzers").insert_one(document, None));
+    Ok(name)
+}
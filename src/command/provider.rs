@@ -0,0 +1,50 @@
+use bson::Bson;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use command::parse_duration;
+use error::TipupError;
+use provider_manager;
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("import", Some(matches)) => import(matches, proddle_db),
+        _ => Err(TipupError::from("unknown provider command")),
+    }
+}
+
+fn import(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let filename = try!(value_t!(matches.value_of("FILE"), String));
+    if !matches.is_present("BGP") {
+        let count = try!(provider_manager::import_table(proddle_db, &filename));
+        println!("imported origin as of {} domain(s)", count);
+        return Ok(());
+    }
+
+    //resolve the listed domains, or every domain measured recently
+    let domains = match matches.values_of("DOMAIN") {
+        Some(domains) => domains.map(|x| x.to_owned()).collect(),
+        None => {
+            let since = try!(parse_duration(&try!(value_t!(matches.value_of("SINCE"), String))));
+            let timestamp_gte = doc!("$gte" => (time::now_utc().to_timespec().sec - since));
+            let pipeline = vec!(
+                doc!("$match" => { "timestamp" => timestamp_gte }),
+                doc!("$group" => { "_id" => "$measurement_domain" }),
+            );
+
+            let mut domains = Vec::new();
+            for document in try!(proddle_db.collection("measurements").aggregate(pipeline, None)) {
+                if let Some(&Bson::String(ref domain)) = try!(document).get("_id") {
+                    domains.push(domain.to_owned());
+                }
+            }
+
+            domains
+        },
+    };
+
+    let (count, unmatched) = try!(provider_manager::import_bgp(proddle_db, &filename, &domains));
+    println!("imported origin as of {} domain(s), {} domain(s) unresolved or outside announced prefixes", count, unmatched);
+    Ok(())
+}
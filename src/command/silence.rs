@@ -0,0 +1,107 @@
+use clap::ArgMatches;
+use glob::Pattern;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use command::parse_duration;
+use error::TipupError;
+use flag_manager::Flag;
+use flag_store::FlagStore;
+use label::LabelSelector;
+
+use std::collections::BTreeSet;
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database, flag_store: &FlagStore) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("from-query", Some(matches)) => from_query(matches, proddle_db, flag_store),
+        _ => Err(TipupError::from("unknown silence command")),
+    }
+}
+
+fn from_query(matches: &ArgMatches, proddle_db: &Database, flag_store: &FlagStore) -> Result<(), TipupError> {
+    let duration = try!(parse_duration(matches.value_of("DURATION").unwrap()));
+    if duration <= 0 {
+        return Err(TipupError::from("silence duration must be positive"));
+    }
+
+    let states: Vec<&str> = matches.values_of("STATE").unwrap().collect();
+    let mut patterns: Vec<(&str, Pattern)> = Vec::new();
+    for key in ["analyzer", "hostname", "domain", "status"].iter() {
+        if let Some(glob) = matches.value_of(&key.to_uppercase()) {
+            match Pattern::new(glob) {
+                Ok(pattern) => patterns.push((*key, pattern)),
+                Err(e) => return Err(TipupError::from(format!("invalid --{} pattern '{}': {}", key, glob, e))),
+            }
+        }
+    }
+
+    if patterns.len() == 0 {
+        return Err(TipupError::from("silence from-query requires at least one of '--analyzer', '--hostname', '--domain', or '--status'"));
+    }
+
+    //one silence per distinct target of the matching flags, or per target across analyzers
+    let any_analyzer = matches.is_present("ANY_ANALYZER");
+    let mut selectors = BTreeSet::new();
+    let mut flag_count = 0;
+    for flag in try!(flag_store.find_by_states(&states)) {
+        if !patterns.iter().all(|&(key, ref pattern)| field(&flag, key).map_or(false, |x| pattern.matches(x))) {
+            continue;
+        }
+
+        let mut expressions = Vec::new();
+        if !any_analyzer {
+            expressions.push(format!("analyzer={}", flag.analyzer));
+        }
+
+        match flag.hostname {
+            Some(ref hostname) => expressions.push(format!("hostname={}", hostname)),
+            None => expressions.push("!hostname".to_owned()),
+        }
+
+        expressions.push(format!("domain={}", flag.domain));
+        selectors.insert(expressions.join(","));
+        flag_count += 1;
+    }
+
+    if selectors.len() == 0 {
+        println!("no {} flags match the query", states.join("/"));
+        return Ok(());
+    }
+
+    let now = time::now_utc().to_timespec().sec;
+    let comment = matches.value_of("COMMENT").unwrap_or("");
+    let dry_run = matches.is_present("DRY_RUN");
+    for selector in selectors.iter() {
+        try!(LabelSelector::parse(selector));
+        if !dry_run {
+            let document = doc!(
+                "selector" => (selector.to_owned()),
+                "start_timestamp" => now,
+                "end_timestamp" => (now + duration),
+                "comment" => comment,
+                "created_by" => "silence_from_query"
+            );
+
+            try!(proddle_db.collection("silences").insert_one(document, None));
+        }
+
+        println!("{}", selector);
+    }
+
+    match dry_run {
+        true => println!("would create {} silence(s) for {} matching flag(s)", selectors.len(), flag_count),
+        false => println!("created {} silence(s) for {} matching flag(s), ending in {}s", selectors.len(), flag_count, duration),
+    }
+
+    Ok(())
+}
+
+fn field<'a>(flag: &'a Flag, key: &str) -> Option<&'a str> {
+    match key {
+        "analyzer" => Some(&flag.analyzer),
+        "hostname" => flag.hostname.as_ref().map(|x| x.as_str()),
+        "domain" => Some(&flag.domain),
+        "status" => Some(&flag.status),
+        _ => None,
+    }
+}
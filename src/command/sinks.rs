@@ -0,0 +1,69 @@
+use bson::Bson;
+use clap::ArgMatches;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use error::TipupError;
+use metrics::Metrics;
+use sink::{self, Sink};
+
+use std::collections::HashMap;
+
+pub fn execute(matches: &ArgMatches, tipup_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("retry-dead-letters", Some(matches)) => retry_dead_letters(matches, tipup_db),
+        _ => Err(TipupError::from("unknown sinks command")),
+    }
+}
+
+fn retry_dead_letters(matches: &ArgMatches, tipup_db: &Database) -> Result<(), TipupError> {
+    //create configured sinks
+    let mut sinks: HashMap<String, Box<Sink + Send>> = HashMap::new();
+    for document in try!(tipup_db.collection("sinks").find(None, None)) {
+        let (name, _, sink, _) = try!(::create_sink(&try!(document)));
+        sinks.insert(name, sink);
+    }
+
+    let search_document = matches.value_of("SINK").map(|x| doc!("sink" => x));
+    let documents: Vec<_> = try!(tipup_db.collection("dead_letters").find(search_document, None)).collect();
+
+    let metrics = Metrics::new();
+    let (mut delivered, mut failed) = (0, 0);
+    for document in documents {
+        let document = try!(document);
+        let id = match document.get("_id") {
+            Some(&Bson::ObjectId(ref id)) => id.clone(),
+            _ => return Err(TipupError::from("failed to parse dead letter '_id'")),
+        };
+
+        let (name, flag) = try!(sink::parse_dead_letter(&document));
+        let sink = match sinks.get_mut(&name) {
+            Some(sink) => sink,
+            None => {
+                println!("skipping dead letter {} for unknown sink '{}'", id, name);
+                failed += 1;
+                continue;
+            },
+        };
+
+        //remove delivered dead letters, recording the latest error otherwise
+        match sink::deliver(&name, &mut **sink, &flag, &metrics) {
+            Ok(()) => {
+                try!(tipup_db.collection("dead_letters").delete_one(doc!("_id" => id), None));
+                delivered += 1;
+            },
+            Err(e) => {
+                println!("failed to deliver dead letter {} to sink '{}': {}", id, name, e);
+                let update_document = doc!(
+                    "$inc" => { "attempts" => (sink::DELIVERY_ATTEMPTS as i32) },
+                    "$set" => { "error" => (e.to_string()), "timestamp" => (time::now_utc().to_timespec().sec) }
+                );
+                try!(tipup_db.collection("dead_letters").update_one(doc!("_id" => id), update_document, None));
+                failed += 1;
+            },
+        }
+    }
+
+    println!("delivered {} dead letter(s), {} remaining", delivered, failed);
+    Ok(())
+}
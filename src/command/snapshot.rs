@@ -0,0 +1,182 @@
+use bson::{self, Bson, Document};
+use bson::ordered::OrderedDocument;
+use clap::ArgMatches;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use mongodb::coll::options::{FindOptions, UpdateOptions};
+use mongodb::db::{Database, ThreadedDatabase};
+use time::{self, Timespec};
+
+use error::TipupError;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+const ARCHIVE_FORMAT: &'static str = "tipup-snapshot";
+const ARCHIVE_VERSION: i32 = 1;
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("create", Some(matches)) => create(matches, proddle_db),
+        ("restore", Some(matches)) => restore(matches, proddle_db),
+        _ => Err(TipupError::from("unknown snapshot command")),
+    }
+}
+
+//(collection, filter) pairs of the state captured in an archive, learned analyzer state beginning
+//with the most recent full snapshot
+fn captured_state(proddle_db: &Database) -> Result<Vec<(&'static str, Option<Document>)>, TipupError> {
+    let negative_one = -1;
+    let mut find_options = FindOptions::new();
+    find_options.sort = Some(doc!("sequence" => negative_one));
+    let state_snapshot_filter = match try!(proddle_db.collection("state_snapshots").find_one(Some(doc!("kind" => "full")), Some(find_options))) {
+        Some(document) => match document.get("sequence") {
+            Some(&Bson::I64(sequence)) => Some(doc!("sequence" => { "$gte" => sequence })),
+            _ => return Err(TipupError::from("failed to parse state snapshot sequence")),
+        },
+        None => None,
+    };
+
+    let state_in = doc!("$in" => ["open", "acknowledged"]);
+    Ok(vec!(
+        ("state_snapshots", state_snapshot_filter),
+        ("analyzed_measurements", None),
+        ("flags", Some(doc!("state" => state_in))),
+        ("silences", None),
+    ))
+}
+
+fn create(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let path = try!(value_t!(matches.value_of("FILE"), String));
+
+    //read everything before writing so the archive header records exact counts
+    let mut entries = Vec::new();
+    let mut counts = Document::new();
+    for (collection, filter) in try!(captured_state(proddle_db)) {
+        let mut count = 0;
+        for document in try!(proddle_db.collection(collection).find(filter, None)) {
+            entries.push(doc!("collection" => collection, "document" => (try!(document))));
+            count += 1;
+        }
+
+        counts.insert(collection, count);
+    }
+
+    let file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => return Err(TipupError::from(format!("failed to create snapshot archive '{}': {}", path, e))),
+    };
+
+    let now = time::now_utc().to_timespec().sec;
+    let header = doc!(
+        "format" => ARCHIVE_FORMAT,
+        "version" => ARCHIVE_VERSION,
+        "created_timestamp" => now,
+        "documents" => (entries.len() as i64),
+        "collections" => (counts.clone())
+    );
+
+    //archives are a gzipped stream of bson documents, the header followed by each captured document
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::Default);
+    for document in Some(header).iter().chain(entries.iter()) {
+        if let Err(e) = bson::encode_document(&mut encoder, document) {
+            return Err(TipupError::from(format!("failed to write snapshot archive '{}': {}", path, e)));
+        }
+    }
+
+    if let Err(e) = encoder.finish().and_then(|mut writer| writer.flush()) {
+        return Err(TipupError::from(format!("failed to write snapshot archive '{}': {}", path, e)));
+    }
+
+    println!("wrote snapshot archive '{}' created {}", path, format_timestamp(now));
+    print_counts(&counts);
+    Ok(())
+}
+
+fn restore(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let path = try!(value_t!(matches.value_of("FILE"), String));
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => return Err(TipupError::from(format!("failed to open snapshot archive '{}': {}", path, e))),
+    };
+
+    let mut decoder = match GzDecoder::new(BufReader::new(file)) {
+        Ok(decoder) => decoder,
+        Err(e) => return Err(TipupError::from(format!("failed to read snapshot archive '{}': {}", path, e))),
+    };
+
+    let header = match bson::decode_document(&mut decoder) {
+        Ok(header) => header,
+        Err(e) => return Err(TipupError::from(format!("failed to read snapshot archive '{}': {}", path, e))),
+    };
+
+    let (created_timestamp, documents) = match (header.get("format"), header.get("version"), header.get("created_timestamp"), header.get("documents")) {
+        (Some(&Bson::String(ref format)), Some(&Bson::I32(ARCHIVE_VERSION)), Some(&Bson::I64(created_timestamp)), Some(&Bson::I64(documents))) if format == ARCHIVE_FORMAT => (created_timestamp, documents),
+        _ => return Err(TipupError::from(format!("'{}' is not a version {} tipup snapshot archive", path, ARCHIVE_VERSION))),
+    };
+
+    //read the whole archive before modifying any state so truncated archives restore nothing
+    let mut entries: Vec<(String, OrderedDocument)> = Vec::new();
+    for _ in 0..documents {
+        let mut entry = match bson::decode_document(&mut decoder) {
+            Ok(entry) => entry,
+            Err(e) => return Err(TipupError::from(format!("failed to read snapshot archive '{}': {}", path, e))),
+        };
+
+        match (entry.remove("collection"), entry.remove("document")) {
+            (Some(Bson::String(collection)), Some(Bson::Document(document))) => entries.push((collection, document)),
+            _ => return Err(TipupError::from(format!("failed to parse snapshot archive '{}' entry", path))),
+        }
+    }
+
+    //replace clears the captured state so the restored state is all that remains, otherwise
+    //archived documents are merged over existing ones
+    if matches.is_present("replace") {
+        for (collection, filter) in try!(captured_state(proddle_db)) {
+            let filter = match collection {
+                "state_snapshots" => Document::new(),
+                _ => filter.unwrap_or(Document::new()),
+            };
+
+            try!(proddle_db.collection(collection).delete_many(filter, None));
+        }
+    }
+
+    let mut counts = Document::new();
+    for (collection, document) in entries {
+        let id = match document.get("_id") {
+            Some(id) => id.clone(),
+            None => return Err(TipupError::from(format!("snapshot archive '{}' contains a '{}' document without an _id", path, collection))),
+        };
+
+        let mut update_options = UpdateOptions::new();
+        update_options.upsert = Some(true);
+        try!(proddle_db.collection(&collection).replace_one(doc!("_id" => id), document, Some(update_options)));
+
+        let count = match counts.get(&collection) {
+            Some(&Bson::I32(count)) => count + 1,
+            _ => 1,
+        };
+
+        counts.insert(collection, count);
+    }
+
+    println!("restored snapshot archive '{}' created {}", path, format_timestamp(created_timestamp));
+    print_counts(&counts);
+    Ok(())
+}
+
+fn print_counts(counts: &Document) {
+    println!("{:<24}{:>12}", "COLLECTION", "DOCUMENTS");
+    for (collection, count) in counts.iter() {
+        println!("{:<24}{:>12}", collection, count);
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    match time::at_utc(Timespec::new(timestamp, 0)).strftime("%Y-%m-%d %H:%M:%S") {
+        Ok(formatted) => formatted.to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
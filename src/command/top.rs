@@ -0,0 +1,349 @@
+use bson::{self, Bson};
+use chan;
+use clap::ArgMatches;
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use serde_json;
+use time;
+
+use analyzer::get_f64;
+use error::TipupError;
+use flag_manager::{self, Flag};
+use flag_store::FlagStore;
+use http;
+
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+//number of most recently active flags listed
+const FLAG_LIMIT: i64 = 200;
+
+struct Dashboard {
+    flags: Vec<Flag>,
+    analyzer_rates: Vec<(String, i64, i64)>,
+    pipe: Result<Vec<(String, f64, f64, f64)>, String>,
+    queues: Result<Vec<(String, f64)>, String>,
+    selected: usize,
+    offset: usize,
+    message: String,
+}
+
+//puts the controlling terminal in non-canonical mode without echo for single key input, restoring
+//the saved settings when dropped
+struct Terminal {
+    settings: String,
+}
+
+impl Terminal {
+    fn open() -> Result<Terminal, TipupError> {
+        let settings = try!(stty(&["-g"]));
+        try!(stty(&["-icanon", "-echo", "min", "1"]));
+        print!("\x1b[?25l");
+        Ok(Terminal { settings: settings.trim().to_owned() })
+    }
+
+    fn size(&self) -> (usize, usize) {
+        let size = stty(&["size"]).unwrap_or(String::new());
+        let mut fields = size.split_whitespace().map(|x| x.parse::<usize>().ok());
+        match (fields.next(), fields.next()) {
+            (Some(Some(rows)), Some(Some(columns))) => (rows, columns),
+            _ => (24, 80),
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.settings]);
+        print!("\x1b[?25h\x1b[H\x1b[2J");
+        let _ = io::stdout().flush();
+    }
+}
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database, flag_store: &FlagStore) -> Result<(), TipupError> {
+    let interval = try!(value_t!(matches.value_of("INTERVAL"), u32));
+    let api = matches.value_of("API").map(|x| match x.starts_with("http://") || x.starts_with("https://") {
+        true => x.trim_end_matches('/').to_owned(),
+        false => format!("http://{}", x),
+    });
+
+    //keys are read on their own thread so refreshes continue while waiting for input
+    let (key_tx, key_rx) = chan::async();
+    let input_thread = thread::Builder::new().name("top_input".to_owned()).spawn(move || {
+        let stdin = io::stdin();
+        for byte in stdin.lock().bytes() {
+            match byte {
+                Ok(byte) => key_tx.send(byte),
+                Err(_) => break,
+            }
+        }
+    });
+
+    if let Err(e) = input_thread {
+        return Err(TipupError::from(format!("failed to start input thread: {}", e)));
+    }
+
+    let terminal = try!(Terminal::open());
+    let mut dashboard = Dashboard {
+        flags: Vec::new(),
+        analyzer_rates: Vec::new(),
+        pipe: Ok(Vec::new()),
+        queues: Ok(Vec::new()),
+        selected: 0,
+        offset: 0,
+        message: "j/k or arrows select, a acknowledges, r refreshes, q quits".to_owned(),
+    };
+
+    try!(refresh(&mut dashboard, proddle_db, &api));
+    render(&mut dashboard, terminal.size());
+
+    let refresh_tick = chan::tick_ms(interval * 1000);
+    let mut escape = Vec::new();
+    loop {
+        chan_select! {
+            key_rx.recv() -> key => {
+                let key = match key {
+                    Some(key) => key,
+                    None => return Ok(()),
+                };
+
+                //arrow keys arrive as 'ESC [ A' and 'ESC [ B'
+                if key == 0x1b || escape.len() > 0 {
+                    escape.push(key);
+                    if escape.len() < 3 {
+                        continue;
+                    }
+                }
+
+                let key = match escape.drain(..).collect::<Vec<u8>>().as_slice() {
+                    &[0x1b, b'[', b'A'] => b'k',
+                    &[0x1b, b'[', b'B'] => b'j',
+                    &[] => key,
+                    _ => continue,
+                };
+
+                match key {
+                    b'q' => return Ok(()),
+                    b'j' => dashboard.selected = (dashboard.selected + 1).min(dashboard.flags.len().saturating_sub(1)),
+                    b'k' => dashboard.selected = dashboard.selected.saturating_sub(1),
+                    b'a' => {
+                        dashboard.message = match dashboard.flags.get(dashboard.selected) {
+                            Some(flag) if flag.state == "open" => match flag_manager::set_state(flag_store, proddle_db, &flag.id.to_hex(), "acknowledged") {
+                                Ok(true) => format!("acknowledged flag {}", flag.id),
+                                Ok(false) => format!("flag {} no longer exists", flag.id),
+                                Err(e) => format!("failed to acknowledge flag {}: {}", flag.id, e),
+                            },
+                            Some(flag) => format!("flag {} is already {}", flag.id, flag.state),
+                            None => "no flag selected".to_owned(),
+                        };
+
+                        try!(refresh(&mut dashboard, proddle_db, &api));
+                    },
+                    b'r' => try!(refresh(&mut dashboard, proddle_db, &api)),
+                    _ => continue,
+                }
+
+                render(&mut dashboard, terminal.size());
+            },
+            refresh_tick.recv() => {
+                try!(refresh(&mut dashboard, proddle_db, &api));
+                render(&mut dashboard, terminal.size());
+            },
+        }
+    }
+}
+
+fn refresh(dashboard: &mut Dashboard, proddle_db: &Database, api: &Option<String>) -> Result<(), TipupError> {
+    //unresolved flags, most recently raised first, keeping the selection on the same flag
+    let selected_id = dashboard.flags.get(dashboard.selected).map(|x| x.id.clone());
+    let negative_one = -1;
+    let mut find_options = FindOptions::new();
+    find_options.sort = Some(doc!("last_timestamp" => negative_one));
+    find_options.limit = Some(FLAG_LIMIT);
+    let state_in = doc!("$in" => ["open", "acknowledged"]);
+    let mut flags = Vec::new();
+    for document in try!(proddle_db.collection("flags").find(Some(doc!("state" => state_in)), Some(find_options))) {
+        match bson::from_bson::<Flag>(Bson::Document(try!(document))) {
+            Ok(flag) => flags.push(flag),
+            Err(_) => return Err(TipupError::from("failed to parse bson document into flag")),
+        }
+    }
+
+    dashboard.selected = match selected_id.and_then(|id| flags.iter().position(|x| x.id == id)) {
+        Some(selected) => selected,
+        None => dashboard.selected.min(flags.len().saturating_sub(1)),
+    };
+    dashboard.flags = flags;
+
+    //flags raised per analyzer over the last hour and day
+    let now = time::now_utc().to_timespec().sec;
+    let timestamp_gte = doc!("$gte" => (now - 86400));
+    let hour_cond = doc!("$cond" => [ { "$gte" => ["$timestamp", (now - 3600)] }, 1, 0 ]);
+    let group_document = doc!("_id" => "$analyzer", "hour" => { "$sum" => hour_cond }, "day" => { "$sum" => 1 });
+    let pipeline = vec!(
+        doc!("$match" => { "timestamp" => timestamp_gte }),
+        doc!("$group" => group_document),
+    );
+
+    let mut analyzer_rates = Vec::new();
+    for document in try!(proddle_db.collection("flags").aggregate(pipeline, None)) {
+        let document = try!(document);
+        if let Some(&Bson::String(ref analyzer)) = document.get("_id") {
+            analyzer_rates.push((analyzer.to_owned(), get_i64(&document, "hour"), get_i64(&document, "day")));
+        }
+    }
+
+    analyzer_rates.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+    dashboard.analyzer_rates = analyzer_rates;
+
+    //ingestion lag and queue depths are only known to the running tipup
+    match *api {
+        Some(ref api) => {
+            dashboard.pipe = fetch_pipe(api);
+            dashboard.queues = fetch_queues(api);
+        },
+        None => {
+            dashboard.pipe = Err("pass --api to show ingestion lag".to_owned());
+            dashboard.queues = Err("pass --api to show queue depths".to_owned());
+        },
+    }
+
+    Ok(())
+}
+
+fn fetch_pipe(api: &str) -> Result<Vec<(String, f64, f64, f64)>, String> {
+    let client = try!(http::client().map_err(|e| e.to_string()));
+    let response = match client.get(&format!("{}/admin/pipe", api)).send() {
+        Ok(ref response) if !response.status.is_success() => return Err(format!("api responded with status {}", response.status)),
+        Ok(response) => response,
+        Err(e) => return Err(format!("failed to reach api: {}", e)),
+    };
+
+    let value: serde_json::Value = match serde_json::from_reader(response) {
+        Ok(value) => value,
+        Err(e) => return Err(format!("failed to parse pipe statistics: {}", e)),
+    };
+
+    let mut pipe = Vec::new();
+    if let Bson::Array(documents) = Bson::from_json(&value) {
+        for document in documents {
+            if let Bson::Document(document) = document {
+                if let Some(&Bson::String(ref measurement_class)) = document.get("measurement_class") {
+                    pipe.push((measurement_class.to_owned(), get_f64(&document, "results_per_second").unwrap_or(0.0), get_f64(&document, "last_lag").unwrap_or(0.0), get_f64(&document, "mean_lag").unwrap_or(0.0)));
+                }
+            }
+        }
+    }
+
+    Ok(pipe)
+}
+
+fn fetch_queues(api: &str) -> Result<Vec<(String, f64)>, String> {
+    let client = try!(http::client().map_err(|e| e.to_string()));
+    let mut body = String::new();
+    match client.get(&format!("{}/metrics", api)).send() {
+        Ok(ref response) if !response.status.is_success() => return Err(format!("api responded with status {}", response.status)),
+        Ok(mut response) => if let Err(e) = response.read_to_string(&mut body) {
+            return Err(format!("failed to read metrics: {}", e));
+        },
+        Err(e) => return Err(format!("failed to reach api: {}", e)),
+    }
+
+    //prometheus text lines of the form 'name{queue="x"} value'
+    let mut queues = Vec::new();
+    for line in body.lines() {
+        let (name, value) = match line.rfind(' ') {
+            Some(index) if !line.starts_with('#') => (&line[..index], line[index + 1..].parse::<f64>().unwrap_or(0.0)),
+            _ => continue,
+        };
+
+        if name == "tipup_flag_spill_queue_length" {
+            queues.push(("flag_spill_queue".to_owned(), value));
+        } else if name.starts_with("tipup_flag_queue_depth{queue=\"") {
+            queues.push((name[30..].trim_end_matches("\"}").to_owned(), value));
+        }
+    }
+
+    Ok(queues)
+}
+
+fn render(dashboard: &mut Dashboard, (rows, columns): (usize, usize)) {
+    let mut lines = Vec::new();
+    lines.push(format!("tipup top - {} unresolved flag(s)", dashboard.flags.len()));
+    lines.push(String::new());
+
+    lines.push(format!("\x1b[1m{:<32}{:>12}{:>12}{:>12}\x1b[0m", "MEASUREMENT CLASS", "RESULTS/S", "LAG", "MEAN LAG"));
+    match dashboard.pipe {
+        Ok(ref pipe) => for &(ref measurement_class, results_per_second, last_lag, mean_lag) in pipe.iter() {
+            lines.push(format!("{:<32}{:>12.1}{:>11.0}s{:>11.0}s", measurement_class, results_per_second, last_lag, mean_lag));
+        },
+        Err(ref e) => lines.push(e.to_owned()),
+    }
+
+    let queues = match dashboard.queues {
+        Ok(ref queues) => queues.iter().map(|&(ref queue, depth)| format!("{} {}", queue, depth)).collect::<Vec<String>>().join("  "),
+        Err(ref e) => e.to_owned(),
+    };
+    lines.push(format!("queues: {}", queues));
+    lines.push(String::new());
+
+    lines.push(format!("\x1b[1m{:<32}{:>12}{:>12}\x1b[0m", "ANALYZER", "FLAGS/1H", "FLAGS/24H"));
+    for &(ref analyzer, hour, day) in dashboard.analyzer_rates.iter().take(8) {
+        lines.push(format!("{:<32}{:>12}{:>12}", analyzer, hour, day));
+    }
+    lines.push(String::new());
+
+    //scroll the flag list to keep the selection visible
+    lines.push(format!("\x1b[1m{:<26}{:<14}{:<24}{:<24}{:<24}{:<10}{:>6}\x1b[0m", "ID", "STATE", "ANALYZER", "DOMAIN", "HOSTNAME", "STATUS", "COUNT"));
+    let visible = rows.saturating_sub(lines.len() + 2).max(1);
+    if dashboard.selected < dashboard.offset {
+        dashboard.offset = dashboard.selected;
+    } else if dashboard.selected >= dashboard.offset + visible {
+        dashboard.offset = dashboard.selected + 1 - visible;
+    }
+
+    for (i, flag) in dashboard.flags.iter().enumerate().skip(dashboard.offset).take(visible) {
+        let line = format!("{:<26}{:<14}{:<24}{:<24}{:<24}{:<10}{:>6}", flag.id, flag.state, truncate(&flag.analyzer, 23),
+            truncate(&flag.domain, 23), truncate(flag.hostname.as_ref().map_or("-", |x| x.as_str()), 23), flag.status, flag.count);
+        match i == dashboard.selected {
+            true => lines.push(format!("\x1b[7m{}\x1b[0m", truncate(&line, columns))),
+            false => lines.push(truncate(&line, columns)),
+        }
+    }
+
+    let mut output = String::from("\x1b[H\x1b[2J");
+    for line in lines.iter().take(rows.saturating_sub(1)) {
+        output.push_str(line);
+        output.push_str("\r\n");
+    }
+
+    output.push_str(&format!("\x1b[{};1H{}", rows, truncate(&dashboard.message, columns)));
+    print!("{}", output);
+    let _ = io::stdout().flush();
+}
+
+fn stty(arguments: &[&str]) -> Result<String, TipupError> {
+    //stty operates on its standard input, which must be the terminal
+    let output = match Command::new("stty").args(arguments).stdin(Stdio::inherit()).output() {
+        Ok(output) => output,
+        Err(e) => return Err(TipupError::from(format!("failed to run stty: {}", e))),
+    };
+
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        false => Err(TipupError::from("tipup top requires an interactive terminal")),
+    }
+}
+
+fn truncate(value: &str, length: usize) -> String {
+    value.chars().take(length).collect()
+}
+
+fn get_i64(document: &bson::Document, key: &str) -> i64 {
+    match document.get(key) {
+        Some(&Bson::I32(i)) => i as i64,
+        Some(&Bson::I64(i)) => i,
+        _ => 0,
+    }
+}
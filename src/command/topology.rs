@@ -0,0 +1,19 @@
+use clap::ArgMatches;
+use mongodb::db::Database;
+
+use error::TipupError;
+use topology;
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("import", Some(matches)) => import(matches, proddle_db),
+        _ => Err(TipupError::from("unknown topology command")),
+    }
+}
+
+fn import(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let filename = try!(value_t!(matches.value_of("FILE"), String));
+    let count = try!(topology::import_csv(proddle_db, &filename));
+    println!("imported topology for {} hostname(s)", count);
+    Ok(())
+}
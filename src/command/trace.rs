@@ -0,0 +1,74 @@
+use bson::{Bson, Document};
+use clap::ArgMatches;
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use command::parse_duration;
+use error::TipupError;
+
+pub fn execute(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    match matches.subcommand() {
+        ("enable", Some(matches)) => enable(matches, proddle_db),
+        ("disable", Some(matches)) => disable(matches, proddle_db),
+        ("export", Some(matches)) => export(matches, proddle_db),
+        _ => Err(TipupError::from("unknown trace command")),
+    }
+}
+
+fn enable(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let mut document = subscription_document(matches);
+    if let Some(duration) = matches.value_of("FOR") {
+        document.insert("until_timestamp", time::now_utc().to_timespec().sec + try!(parse_duration(duration)));
+    }
+
+    try!(proddle_db.collection("trace_subscriptions").insert_one(document, None));
+    println!("tracing enabled for analyzer '{}'", matches.value_of("ANALYZER").unwrap_or(""));
+    Ok(())
+}
+
+fn disable(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    //match subscriptions exactly so a domain wide trace is not removed by a hostname specific disable
+    let mut search_document = subscription_document(matches);
+    for key in ["hostname", "domain"].iter() {
+        if !search_document.contains_key(key) {
+            search_document.insert(*key, doc!("$exists" => false));
+        }
+    }
+
+    let result = try!(proddle_db.collection("trace_subscriptions").delete_many(search_document, None));
+    if result.deleted_count == 0 {
+        return Err(TipupError::from("no matching trace subscription found"));
+    }
+
+    println!("removed {} trace subscription(s)", result.deleted_count);
+    Ok(())
+}
+
+fn export(matches: &ArgMatches, proddle_db: &Database) -> Result<(), TipupError> {
+    let search_document = subscription_document(matches);
+    let limit = try!(value_t!(matches.value_of("LIMIT"), i64));
+
+    let negative_one = -1;
+    let mut find_options = FindOptions::new();
+    find_options.sort = Some(doc!("timestamp" => negative_one));
+    find_options.limit = Some(limit);
+    for document in try!(proddle_db.collection("analyzer_traces").find(Some(search_document), Some(find_options))) {
+        let mut document = try!(document);
+        document.remove("_id");
+        println!("{}", Bson::Document(document).to_json());
+    }
+
+    Ok(())
+}
+
+fn subscription_document(matches: &ArgMatches) -> Document {
+    let mut document = doc!("analyzer" => (matches.value_of("ANALYZER").unwrap_or("").to_owned()));
+    for &(key, name) in [("hostname", "HOSTNAME"), ("domain", "DOMAIN")].iter() {
+        if let Some(value) = matches.value_of(name) {
+            document.insert(key, value.to_owned());
+        }
+    }
+
+    document
+}
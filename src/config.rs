@@ -0,0 +1,769 @@
+use bson::{Bson, Document};
+use chan;
+use clap::{App, ArgMatches};
+use mongodb::db::{Database, ThreadedDatabase};
+use regex::Regex;
+
+use analyzer::get_f64;
+use atlas_source::AtlasSource;
+use clock::{self, Clock};
+use dependency::Dependency;
+use derivation::Derivation;
+use error::TipupError;
+use filter::ResultFilter;
+use flag_store;
+use flag_url::FlagUrlTemplate;
+use label::LabelSelector;
+use lifecycle::LifecycleWebhook;
+use log_level;
+use maintenance_manager::MaintenanceSchedule;
+use resources;
+use retention_manager::{self, RetentionManager};
+use result_source::ResultSource;
+use result_window::ResultWindow;
+use severity::SeverityTaxonomy;
+use verification;
+
+use std::fs::File;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+//(collection, required keys, optional keys) of definitions stored in mongodb
+const DEFINITION_KEYS: [(&'static str, &'static [&'static str], &'static [&'static str]); 21] = [
+    ("analyzers", &["name", "class", "status", "measurement_class"], &["fields", "parameters", "labels", "modified_by", "confirmation", "canary", "overrides", "key_fields", "derive", "depends_on"]),
+    ("analyzer_templates", &["class", "status", "measurement_class"], &["name", "fields", "parameters", "labels", "confirmation", "key_fields"]),
+    ("sinks", &["name", "class"], &["selector", "parameters", "incident_update_interval"]),
+    ("lifecycle_webhooks", &["name", "url", "transitions"], &["selector", "template"]),
+    ("enrichment_rules", &["selector", "labels"], &[]),
+    ("silences", &["selector", "start_timestamp", "end_timestamp"], &["comment", "created_by", "schedule"]),
+    ("maintenance_schedules", &["name", "schedule", "duration"], &["hostname", "domain", "selector", "utc_offset", "comment"]),
+    ("suppressions", &["vantage_hostname", "domain"], &["analyzer", "expiration_timestamp", "comment"]),
+    ("sanitization_policies", &["measurement_class", "field", "policy"], &["status", "minimum", "maximum"]),
+    ("error_categories", &["category", "pattern"], &[]),
+    ("aggregation_policies", &["measurement_class"], &["window", "fields"]),
+    ("measurement_parameters", &["measurement_class", "fields"], &[]),
+    ("quality_gates", &["measurement_class"], &["window", "minimum_samples", "maximum_failure_rate"]),
+    ("escalation_policies", &["analyzer_class", "stages"], &[]),
+    ("retention_policies", &["collection", "max_age"], &["timestamp_field"]),
+    ("topology", &["hostname"], &["region", "site", "asn"]),
+    ("provider_ranges", &["domain", "ranges"], &[]),
+    ("target_asns", &["domain", "asn"], &["name", "source"]),
+    ("trace_subscriptions", &["analyzer"], &["hostname", "domain", "until_timestamp"]),
+    ("time_windows", &["name", "start", "end"], &["days", "utc_offset"]),
+    ("slas", &["domain"], &["measurement_class", "latency_field", "max_p95_latency", "min_availability", "status"]),
+];
+
+//settings a reload applies to the running process, others taking effect on restart
+const RELOADABLE_SETTINGS: [&'static str; 9] = ["FETCH_JITTER", "MIN_POLL_INTERVAL", "MAX_POLL_INTERVAL", "FETCH_LIMIT",
+    "HOSTNAME_ALLOW", "HOSTNAME_DENY", "MEASUREMENT_ALLOW", "MEASUREMENT_DENY", "LOG_LEVEL"];
+
+//settings whose values are never logged
+const SECRET_SETTINGS: [&'static str; 3] = ["PASSWORD", "ATLAS_KEY", "API_TOKEN"];
+
+#[derive(Clone)]
+pub struct Config {
+    pub mongodb_ip_address: String,
+    pub mongodb_port: u16,
+    pub ca_file: String,
+    pub certificate_file: String,
+    pub key_file: String,
+    pub username: String,
+    pub password: String,
+    pub update_flags_interval: u32,
+    pub fetch_jitter: f64,
+    pub min_poll_interval: u32,
+    pub max_poll_interval: u32,
+    pub fetch_limit: u32,
+    pub results_collection: String,
+    pub results_pipeline: String,
+    pub atlas_url: String,
+    pub atlas_key: String,
+    pub atlas_collection: String,
+    pub atlas_interval: u32,
+    pub update_incidents_interval: u32,
+    pub incident_window: u32,
+    pub snapshot_interval: u32,
+    pub full_snapshot_interval: u32,
+    pub target_stats_field: String,
+    pub target_stats_window: u32,
+    pub target_stats_interval: u32,
+    pub sla_interval: u32,
+    pub availability_interval: u32,
+    pub provider_interval: u32,
+    pub provider_window: i64,
+    pub provider_min_targets: u32,
+    pub analyzer_health_interval: u32,
+    pub analyzer_health_min_score: f64,
+    pub analyzer_health_min_evaluations: u32,
+    pub analyzer_health_max_latency: f64,
+    pub api_address: String,
+    pub api_token: String,
+    pub retention_time: String,
+    pub maintenance_interval: u32,
+    pub escalation_interval: u32,
+    pub flag_resolve_timeout: i64,
+    pub flag_merge: bool,
+    pub flag_id_window: u32,
+    pub flag_url_template: String,
+    pub flag_url_window: u32,
+    pub flag_store: String,
+    pub flag_notification_rate: u32,
+    pub self_healing_window: i64,
+    pub self_healing_adjust: f64,
+    pub noise_report_interval: u32,
+    pub noise_report_size: u32,
+    pub noise_digest_url: String,
+    pub workers: u32,
+    pub state_memory_limit: u32,
+    pub flag_spill_file: String,
+    pub max_thread_restarts: u32,
+    pub crash_report_directory: String,
+    pub crash_report_collection: String,
+    pub config_file: String,
+    pub log_level: String,
+    pub hostname_allow: Vec<String>,
+    pub hostname_deny: Vec<String>,
+    pub measurement_allow: Vec<String>,
+    pub measurement_deny: Vec<String>,
+    pub severity_levels: Vec<String>,
+    pub severity_mappings: Vec<String>,
+    pub atlas_measurements: Vec<String>,
+}
+
+impl Config {
+    //parses every argument, recording all problems in validation rather than failing on the first
+    pub fn parse(matches: &ArgMatches, validation: &mut Validation) -> Config {
+        let config = Config {
+            mongodb_ip_address: parse_value(matches, "MONGODB_IP_ADDRESS", validation),
+            mongodb_port: parse_value(matches, "MONGODB_PORT", validation),
+            ca_file: parse_value(matches, "CA_FILE", validation),
+            certificate_file: parse_value(matches, "CERTIFICATE_FILE", validation),
+            key_file: parse_value(matches, "KEY_FILE", validation),
+            username: parse_value(matches, "USERNAME", validation),
+            password: parse_value(matches, "PASSWORD", validation),
+            update_flags_interval: parse_value(matches, "UPDATE_FLAGS_INTERVAL", validation),
+            fetch_jitter: parse_value(matches, "FETCH_JITTER", validation),
+            min_poll_interval: parse_value(matches, "MIN_POLL_INTERVAL", validation),
+            max_poll_interval: parse_value(matches, "MAX_POLL_INTERVAL", validation),
+            fetch_limit: parse_value(matches, "FETCH_LIMIT", validation),
+            results_collection: parse_value(matches, "RESULTS_COLLECTION", validation),
+            results_pipeline: parse_value(matches, "RESULTS_PIPELINE", validation),
+            atlas_url: parse_value(matches, "ATLAS_URL", validation),
+            atlas_key: parse_value(matches, "ATLAS_KEY", validation),
+            atlas_collection: parse_value(matches, "ATLAS_COLLECTION", validation),
+            atlas_interval: parse_value(matches, "ATLAS_INTERVAL", validation),
+            update_incidents_interval: parse_value(matches, "UPDATE_INCIDENTS_INTERVAL", validation),
+            incident_window: parse_value(matches, "INCIDENT_WINDOW", validation),
+            snapshot_interval: parse_value(matches, "SNAPSHOT_INTERVAL", validation),
+            full_snapshot_interval: parse_value(matches, "FULL_SNAPSHOT_INTERVAL", validation),
+            target_stats_field: parse_value(matches, "TARGET_STATS_FIELD", validation),
+            target_stats_window: parse_value(matches, "TARGET_STATS_WINDOW", validation),
+            target_stats_interval: parse_value(matches, "TARGET_STATS_INTERVAL", validation),
+            sla_interval: parse_value(matches, "SLA_INTERVAL", validation),
+            availability_interval: parse_value(matches, "AVAILABILITY_INTERVAL", validation),
+            provider_interval: parse_value(matches, "PROVIDER_INTERVAL", validation),
+            provider_window: parse_value(matches, "PROVIDER_WINDOW", validation),
+            provider_min_targets: parse_value(matches, "PROVIDER_MIN_TARGETS", validation),
+            analyzer_health_interval: parse_value(matches, "ANALYZER_HEALTH_INTERVAL", validation),
+            analyzer_health_min_score: parse_value(matches, "ANALYZER_HEALTH_MIN_SCORE", validation),
+            analyzer_health_min_evaluations: parse_value(matches, "ANALYZER_HEALTH_MIN_EVALUATIONS", validation),
+            analyzer_health_max_latency: parse_value(matches, "ANALYZER_HEALTH_MAX_LATENCY", validation),
+            api_address: parse_value(matches, "API_ADDRESS", validation),
+            api_token: parse_value(matches, "API_TOKEN", validation),
+            retention_time: parse_value(matches, "RETENTION_TIME", validation),
+            maintenance_interval: parse_value(matches, "MAINTENANCE_INTERVAL", validation),
+            escalation_interval: parse_value(matches, "ESCALATION_INTERVAL", validation),
+            flag_resolve_timeout: parse_value(matches, "FLAG_RESOLVE_TIMEOUT", validation),
+            flag_merge: parse_value(matches, "FLAG_MERGE", validation),
+            flag_id_window: parse_value(matches, "FLAG_ID_WINDOW", validation),
+            flag_url_template: parse_value(matches, "FLAG_URL_TEMPLATE", validation),
+            flag_url_window: parse_value(matches, "FLAG_URL_WINDOW", validation),
+            flag_store: parse_value(matches, "FLAG_STORE", validation),
+            flag_notification_rate: parse_value(matches, "FLAG_NOTIFICATION_RATE", validation),
+            self_healing_window: parse_value(matches, "SELF_HEALING_WINDOW", validation),
+            self_healing_adjust: parse_value(matches, "SELF_HEALING_ADJUST", validation),
+            noise_report_interval: parse_value(matches, "NOISE_REPORT_INTERVAL", validation),
+            noise_report_size: parse_value(matches, "NOISE_REPORT_SIZE", validation),
+            noise_digest_url: parse_value(matches, "NOISE_DIGEST_URL", validation),
+            workers: parse_value(matches, "WORKERS", validation),
+            state_memory_limit: parse_value(matches, "STATE_MEMORY_LIMIT", validation),
+            flag_spill_file: parse_value(matches, "FLAG_SPILL_FILE", validation),
+            max_thread_restarts: parse_value(matches, "MAX_THREAD_RESTARTS", validation),
+            crash_report_directory: parse_value(matches, "CRASH_REPORT_DIRECTORY", validation),
+            crash_report_collection: parse_value(matches, "CRASH_REPORT_COLLECTION", validation),
+            config_file: parse_value(matches, "CONFIG_FILE", validation),
+            log_level: parse_value(matches, "LOG_LEVEL", validation),
+            hostname_allow: parse_values(matches, "HOSTNAME_ALLOW"),
+            hostname_deny: parse_values(matches, "HOSTNAME_DENY"),
+            measurement_allow: parse_values(matches, "MEASUREMENT_ALLOW"),
+            measurement_deny: parse_values(matches, "MEASUREMENT_DENY"),
+            severity_levels: parse_values(matches, "SEVERITY_LEVELS"),
+            severity_mappings: parse_values(matches, "SEVERITY_MAPPINGS"),
+            atlas_measurements: parse_values(matches, "ATLAS_MEASUREMENTS"),
+        };
+
+        //validate ranges
+        let intervals = [
+            ("UPDATE_FLAGS_INTERVAL", config.update_flags_interval),
+            ("MIN_POLL_INTERVAL", config.min_poll_interval),
+            ("MAX_POLL_INTERVAL", config.max_poll_interval),
+            ("UPDATE_INCIDENTS_INTERVAL", config.update_incidents_interval),
+            ("INCIDENT_WINDOW", config.incident_window),
+            ("SNAPSHOT_INTERVAL", config.snapshot_interval),
+            ("FULL_SNAPSHOT_INTERVAL", config.full_snapshot_interval),
+            ("TARGET_STATS_WINDOW", config.target_stats_window),
+            ("TARGET_STATS_INTERVAL", config.target_stats_interval),
+            ("ATLAS_INTERVAL", config.atlas_interval),
+            ("SLA_INTERVAL", config.sla_interval),
+            ("AVAILABILITY_INTERVAL", config.availability_interval),
+            ("PROVIDER_INTERVAL", config.provider_interval),
+            ("PROVIDER_MIN_TARGETS", config.provider_min_targets),
+            ("ANALYZER_HEALTH_INTERVAL", config.analyzer_health_interval),
+            ("ANALYZER_HEALTH_MIN_EVALUATIONS", config.analyzer_health_min_evaluations),
+            ("MAINTENANCE_INTERVAL", config.maintenance_interval),
+            ("ESCALATION_INTERVAL", config.escalation_interval),
+            ("FLAG_ID_WINDOW", config.flag_id_window),
+            ("FLAG_URL_WINDOW", config.flag_url_window),
+            ("NOISE_REPORT_INTERVAL", config.noise_report_interval),
+            ("NOISE_REPORT_SIZE", config.noise_report_size),
+        ];
+
+        for &(name, interval) in intervals.iter() {
+            if interval == 0 {
+                validation.error(format!("{} must be greater than 0", name));
+            }
+        }
+
+        if config.fetch_jitter < 0.0 || config.fetch_jitter > 1.0 {
+            validation.error("FETCH_JITTER must be between 0 and 1");
+        }
+
+        if config.min_poll_interval > config.max_poll_interval {
+            validation.error("MIN_POLL_INTERVAL must not exceed MAX_POLL_INTERVAL");
+        }
+
+        if config.flag_resolve_timeout < 0 {
+            validation.error("FLAG_RESOLVE_TIMEOUT must not be negative");
+        }
+
+        if config.provider_window <= 0 {
+            validation.error("PROVIDER_WINDOW must be greater than 0");
+        }
+
+        if config.analyzer_health_min_score < 0.0 || config.analyzer_health_min_score > 100.0 {
+            validation.error("ANALYZER_HEALTH_MIN_SCORE must be between 0 and 100");
+        }
+
+        if config.analyzer_health_max_latency <= 0.0 {
+            validation.error("ANALYZER_HEALTH_MAX_LATENCY must be greater than 0");
+        }
+
+        if config.self_healing_window <= 0 {
+            validation.error("SELF_HEALING_WINDOW must be greater than 0");
+        }
+
+        if config.self_healing_adjust < 0.0 || config.self_healing_adjust > 1.0 {
+            validation.error("SELF_HEALING_ADJUST must be between 0 and 1");
+        }
+
+        if config.mongodb_port == 0 {
+            validation.error("MONGODB_PORT must be greater than 0");
+        }
+
+        if config.api_address.len() > 0 {
+            //mutating api requests are only served unauthenticated to the local host
+            match config.api_address.parse::<SocketAddr>() {
+                Ok(address) if !address.ip().is_loopback() && config.api_token.len() == 0 => validation.error(format!("API_TOKEN must be set to serve the api on non-loopback address '{}'", config.api_address)),
+                Ok(_) => {},
+                Err(_) => validation.error(format!("API_ADDRESS '{}' must be of the form ip:port", config.api_address)),
+            }
+        }
+
+        if let Err(e) = retention_manager::parse_time_of_day(&config.retention_time) {
+            validation.error(format!("RETENTION_TIME: {}", e));
+        }
+
+        if config.noise_digest_url.len() > 0 && !config.noise_digest_url.starts_with("http://") && !config.noise_digest_url.starts_with("https://") {
+            validation.error(format!("NOISE_DIGEST_URL '{}' must be an http or https url", config.noise_digest_url));
+        }
+
+        if !Path::new(&config.crash_report_directory).is_dir() {
+            validation.warning(format!("CRASH_REPORT_DIRECTORY '{}' is not a directory, crash reports will not be written to disk", config.crash_report_directory));
+        }
+
+        let ssl_files = [&config.ca_file, &config.certificate_file, &config.key_file];
+        if ssl_files.iter().any(|x| x.len() > 0) && ssl_files.iter().any(|x| x.len() == 0) {
+            validation.error("CA_FILE, CERTIFICATE_FILE, and KEY_FILE must be provided together for ssl connections");
+        }
+
+        if let Err(e) = config.result_filter() {
+            validation.error(format!("invalid result filter pattern: {}", e));
+        }
+
+        if config.target_stats_field.len() == 0 {
+            validation.error("TARGET_STATS_FIELD must not be empty");
+        }
+
+        if config.results_collection.len() == 0 {
+            validation.error("RESULTS_COLLECTION must not be empty");
+        }
+
+        if let Err(e) = config.result_source() {
+            validation.error(format!("invalid RESULTS_PIPELINE: {}", e));
+        }
+
+        if let Err(e) = config.atlas_source(clock::system()) {
+            validation.error(format!("invalid RIPE Atlas configuration: {}", e));
+        }
+
+        if config.atlas_measurements.len() > 0 && config.atlas_collection == config.results_collection {
+            validation.error("ATLAS_COLLECTION must differ from RESULTS_COLLECTION");
+        }
+
+        if let Err(e) = config.flag_url_template() {
+            validation.error(format!("invalid FLAG_URL_TEMPLATE: {}", e));
+        }
+
+        if !flag_store::BACKENDS.contains(&config.flag_store.as_str()) {
+            validation.error(format!("FLAG_STORE '{}' must be one of {}", config.flag_store, flag_store::BACKENDS.join(", ")));
+        }
+
+        if let Err(e) = config.severity_taxonomy() {
+            validation.error(format!("invalid severity taxonomy: {}", e));
+        }
+
+        if let Err(e) = log_level::parse(&config.log_level) {
+            validation.error(format!("LOG_LEVEL: {}", e));
+        }
+
+        config
+    }
+
+    //settings which differ from another configuration as 'NAME: old -> new', those applied without
+    //a restart first
+    pub fn changes(&self, other: &Config) -> (Vec<String>, Vec<String>) {
+        let (mut applied, mut restart) = (Vec::new(), Vec::new());
+        for (&(name, ref old), &(_, ref new)) in self.settings().iter().zip(other.settings().iter()) {
+            if old == new {
+                continue;
+            }
+
+            let change = match SECRET_SETTINGS.contains(&name) {
+                true => format!("{}: changed", name),
+                false => format!("{}: '{}' -> '{}'", name, old, new),
+            };
+
+            match RELOADABLE_SETTINGS.contains(&name) {
+                true => applied.push(change),
+                false => restart.push(change),
+            }
+        }
+
+        (applied, restart)
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        vec!(
+            ("MONGODB_IP_ADDRESS", self.mongodb_ip_address.clone()),
+            ("MONGODB_PORT", self.mongodb_port.to_string()),
+            ("CA_FILE", self.ca_file.clone()),
+            ("CERTIFICATE_FILE", self.certificate_file.clone()),
+            ("KEY_FILE", self.key_file.clone()),
+            ("USERNAME", self.username.clone()),
+            ("PASSWORD", self.password.clone()),
+            ("UPDATE_FLAGS_INTERVAL", self.update_flags_interval.to_string()),
+            ("FETCH_JITTER", self.fetch_jitter.to_string()),
+            ("MIN_POLL_INTERVAL", self.min_poll_interval.to_string()),
+            ("MAX_POLL_INTERVAL", self.max_poll_interval.to_string()),
+            ("FETCH_LIMIT", self.fetch_limit.to_string()),
+            ("RESULTS_COLLECTION", self.results_collection.clone()),
+            ("RESULTS_PIPELINE", self.results_pipeline.clone()),
+            ("ATLAS_URL", self.atlas_url.clone()),
+            ("ATLAS_KEY", self.atlas_key.clone()),
+            ("ATLAS_COLLECTION", self.atlas_collection.clone()),
+            ("ATLAS_INTERVAL", self.atlas_interval.to_string()),
+            ("UPDATE_INCIDENTS_INTERVAL", self.update_incidents_interval.to_string()),
+            ("INCIDENT_WINDOW", self.incident_window.to_string()),
+            ("SNAPSHOT_INTERVAL", self.snapshot_interval.to_string()),
+            ("FULL_SNAPSHOT_INTERVAL", self.full_snapshot_interval.to_string()),
+            ("TARGET_STATS_FIELD", self.target_stats_field.clone()),
+            ("TARGET_STATS_WINDOW", self.target_stats_window.to_string()),
+            ("TARGET_STATS_INTERVAL", self.target_stats_interval.to_string()),
+            ("SLA_INTERVAL", self.sla_interval.to_string()),
+            ("AVAILABILITY_INTERVAL", self.availability_interval.to_string()),
+            ("PROVIDER_INTERVAL", self.provider_interval.to_string()),
+            ("PROVIDER_WINDOW", self.provider_window.to_string()),
+            ("PROVIDER_MIN_TARGETS", self.provider_min_targets.to_string()),
+            ("ANALYZER_HEALTH_INTERVAL", self.analyzer_health_interval.to_string()),
+            ("ANALYZER_HEALTH_MIN_SCORE", self.analyzer_health_min_score.to_string()),
+            ("ANALYZER_HEALTH_MIN_EVALUATIONS", self.analyzer_health_min_evaluations.to_string()),
+            ("ANALYZER_HEALTH_MAX_LATENCY", self.analyzer_health_max_latency.to_string()),
+            ("API_ADDRESS", self.api_address.clone()),
+            ("API_TOKEN", self.api_token.clone()),
+            ("RETENTION_TIME", self.retention_time.clone()),
+            ("MAINTENANCE_INTERVAL", self.maintenance_interval.to_string()),
+            ("ESCALATION_INTERVAL", self.escalation_interval.to_string()),
+            ("FLAG_RESOLVE_TIMEOUT", self.flag_resolve_timeout.to_string()),
+            ("FLAG_MERGE", self.flag_merge.to_string()),
+            ("FLAG_ID_WINDOW", self.flag_id_window.to_string()),
+            ("FLAG_URL_TEMPLATE", self.flag_url_template.clone()),
+            ("FLAG_URL_WINDOW", self.flag_url_window.to_string()),
+            ("FLAG_STORE", self.flag_store.clone()),
+            ("FLAG_NOTIFICATION_RATE", self.flag_notification_rate.to_string()),
+            ("SELF_HEALING_WINDOW", self.self_healing_window.to_string()),
+            ("SELF_HEALING_ADJUST", self.self_healing_adjust.to_string()),
+            ("NOISE_REPORT_INTERVAL", self.noise_report_interval.to_string()),
+            ("NOISE_REPORT_SIZE", self.noise_report_size.to_string()),
+            ("NOISE_DIGEST_URL", self.noise_digest_url.clone()),
+            ("WORKERS", self.workers.to_string()),
+            ("STATE_MEMORY_LIMIT", self.state_memory_limit.to_string()),
+            ("FLAG_SPILL_FILE", self.flag_spill_file.clone()),
+            ("MAX_THREAD_RESTARTS", self.max_thread_restarts.to_string()),
+            ("CRASH_REPORT_DIRECTORY", self.crash_report_directory.clone()),
+            ("CRASH_REPORT_COLLECTION", self.crash_report_collection.clone()),
+            ("CONFIG_FILE", self.config_file.clone()),
+            ("LOG_LEVEL", self.log_level.clone()),
+            ("HOSTNAME_ALLOW", self.hostname_allow.join(",")),
+            ("HOSTNAME_DENY", self.hostname_deny.join(",")),
+            ("MEASUREMENT_ALLOW", self.measurement_allow.join(",")),
+            ("MEASUREMENT_DENY", self.measurement_deny.join(",")),
+            ("SEVERITY_LEVELS", self.severity_levels.join(",")),
+            ("SEVERITY_MAPPINGS", self.severity_mappings.join(",")),
+            ("ATLAS_MEASUREMENTS", self.atlas_measurements.join(",")),
+        )
+    }
+
+    pub fn result_filter(&self) -> Result<ResultFilter, TipupError> {
+        ResultFilter::new(&self.hostname_allow, &self.hostname_deny, &self.measurement_allow, &self.measurement_deny)
+    }
+
+    pub fn result_source(&self) -> Result<ResultSource, TipupError> {
+        let adapted_collections = match self.atlas_measurements.len() {
+            0 => Vec::new(),
+            _ => vec!(self.atlas_collection.clone()),
+        };
+
+        ResultSource::new(&self.results_collection, &self.results_pipeline, adapted_collections)
+    }
+
+    pub fn retention_manager(&self, clock: Arc<Clock>) -> Result<RetentionManager, TipupError> {
+        Ok(RetentionManager::new(try!(retention_manager::parse_time_of_day(&self.retention_time)), &self.crash_report_collection, clock))
+    }
+
+    //pulls results of the configured ripe atlas measurements, if any, starting an interval back
+    pub fn atlas_source(&self, clock: Arc<Clock>) -> Result<Option<AtlasSource>, TipupError> {
+        match self.atlas_measurements.len() {
+            0 => Ok(None),
+            _ => Ok(Some(try!(AtlasSource::new(&self.atlas_url, &self.atlas_key, &self.atlas_measurements, &self.atlas_collection, self.atlas_interval as i64, clock)))),
+        }
+    }
+
+    pub fn flag_url_template(&self) -> Result<Option<FlagUrlTemplate>, TipupError> {
+        match self.flag_url_template.len() {
+            0 => Ok(None),
+            _ => FlagUrlTemplate::parse(&self.flag_url_template, self.flag_url_window).map(|x| Some(x)),
+        }
+    }
+
+    pub fn severity_taxonomy(&self) -> Result<SeverityTaxonomy, TipupError> {
+        SeverityTaxonomy::new(&self.severity_levels, &self.severity_mappings)
+    }
+
+    pub fn workers(&self) -> usize {
+        match self.workers {
+            0 => resources::available_cpus(),
+            workers => workers as usize,
+        }
+    }
+
+    //bytes of cached state to retain, defaulting to half of any container memory limit
+    pub fn state_memory_limit(&self) -> Option<usize> {
+        match self.state_memory_limit {
+            0 => resources::memory_limit().map(|x| (x / 2) as usize),
+            limit => Some(limit as usize * 1024 * 1024),
+        }
+    }
+}
+
+pub struct Validation {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Validation {
+    pub fn new() -> Validation {
+        Validation {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn error<T: Into<String>>(&mut self, error: T) {
+        self.errors.push(error.into());
+    }
+
+    pub fn warning<T: Into<String>>(&mut self, warning: T) {
+        self.warnings.push(warning.into());
+    }
+
+    //logs warnings and returns all errors as one
+    pub fn check(&self) -> Result<(), TipupError> {
+        for warning in self.warnings.iter() {
+            warn!("{}", warning);
+        }
+
+        match self.errors.len() {
+            0 => Ok(()),
+            _ => Err(TipupError::from(format!("invalid configuration:\n  {}", self.errors.join("\n  ")))),
+        }
+    }
+}
+
+//merges arguments of any config file, 'long_name = value' lines with '#' comments, ahead of the
+//command line arguments, e.g. 'hostname_allow = vantage-*', repeated for multiple values, so
+//arguments given on the command line take precedence
+pub fn arguments(args: Vec<String>) -> Result<Vec<String>, TipupError> {
+    let config_file = match args.iter().position(|x| x == "--config_file") {
+        Some(index) => args.get(index + 1).cloned(),
+        None => args.iter().find(|x| x.starts_with("--config_file=")).map(|x| x[14..].to_owned()),
+    };
+
+    let config_file = match config_file {
+        Some(ref config_file) if config_file.len() > 0 => config_file.to_owned(),
+        _ => return Ok(args),
+    };
+
+    let mut contents = String::new();
+    if let Err(e) = File::open(&config_file).and_then(|mut x| x.read_to_string(&mut contents)) {
+        return Err(TipupError::from(format!("failed to read config file '{}': {}", config_file, e)));
+    }
+
+    let mut file_args = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.len() == 0 || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = match line.find('=') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => return Err(TipupError::from(format!("failed to parse line {} of config file '{}', expected 'long_name = value'", i + 1, config_file))),
+        };
+
+        let flag = format!("--{}", name);
+        if args.iter().any(|x| x == &flag || x.starts_with(&format!("{}=", flag))) {
+            continue;
+        }
+
+        file_args.push(flag);
+        file_args.push(value.to_owned());
+    }
+
+    let mut merged = args[..1].to_vec();
+    merged.extend(file_args);
+    merged.extend(args[1..].iter().cloned());
+    Ok(merged)
+}
+
+//rereads the arguments the process started with, including any config file, failing on any
+//problem rather than applying part of the configuration
+pub fn reload(args: &Vec<String>) -> Result<Config, TipupError> {
+    let yaml = load_yaml!("args.yaml");
+    let matches = try!(App::from_yaml(yaml).get_matches_from_safe(try!(arguments(args.clone()))));
+    let mut validation = Validation::new();
+    let config = Config::parse(&matches, &mut validation);
+    try!(validation.check());
+    Ok(config)
+}
+
+pub fn validate_definitions(proddle_db: &Database, taxonomy: &SeverityTaxonomy, validation: &mut Validation) -> Result<(), TipupError> {
+    //check required and unknown keys for each definition collection
+    for &(collection, required_keys, optional_keys) in DEFINITION_KEYS.iter() {
+        for document in try!(proddle_db.collection(collection).find(None, None)) {
+            let document = try!(document);
+            let identifier = identify(collection, &document);
+            for key in required_keys.iter() {
+                if !document.contains_key(key) {
+                    validation.error(format!("{} is missing required key '{}'", identifier, key));
+                }
+            }
+
+            for key in document.keys() {
+                if key != "_id" && !required_keys.contains(&key.as_str()) && !optional_keys.contains(&key.as_str()) {
+                    validation.warning(format!("{} has unknown key '{}'", identifier, key));
+                }
+            }
+
+            if let Err(e) = validate_definition(collection, &document) {
+                validation.error(format!("{} is invalid: {}", identifier, e));
+            }
+
+            if let Err(e) = validate_severities(collection, &document, taxonomy) {
+                validation.error(format!("{} is invalid: {}", identifier, e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_definition(collection: &str, document: &Document) -> Result<(), TipupError> {
+    match collection {
+        "analyzers" => {
+            //construct analyzers against scratch state to surface parameter errors
+            let result_window = Arc::new(RwLock::new(ResultWindow::new()));
+            let (flag_tx, _) = chan::sync(0);
+            try!(::create_analyzer(document, flag_tx, result_window, clock::system()));
+            try!(Derivation::parse(document));
+            try!(Dependency::parse(document));
+        },
+        "sinks" => {
+            try!(::create_sink(document));
+        },
+        "lifecycle_webhooks" => {
+            try!(LifecycleWebhook::parse(document));
+        },
+        "enrichment_rules" | "silences" => {
+            if let Some(&Bson::String(ref selector)) = document.get("selector") {
+                try!(LabelSelector::parse(selector));
+            }
+        },
+        "sanitization_policies" => {
+            match document.get("policy") {
+                Some(&Bson::String(ref policy)) if policy == "drop" || policy == "flag" => {},
+                Some(&Bson::String(ref policy)) if policy == "clamp" => {
+                    if get_f64(document, "minimum").is_none() && get_f64(document, "maximum").is_none() {
+                        return Err(TipupError::from("clamp policy requires a minimum or maximum"));
+                    }
+                },
+                _ => return Err(TipupError::from("policy must be one of 'drop', 'clamp', or 'flag'")),
+            }
+
+            if let (Some(minimum), Some(maximum)) = (get_f64(document, "minimum"), get_f64(document, "maximum")) {
+                if minimum > maximum {
+                    return Err(TipupError::from(format!("minimum {} exceeds maximum {}", minimum, maximum)));
+                }
+            }
+        },
+        "error_categories" => {
+            if let Some(&Bson::String(ref pattern)) = document.get("pattern") {
+                if let Err(e) = Regex::new(pattern) {
+                    return Err(TipupError::from(format!("invalid pattern: {}", e)));
+                }
+            }
+        },
+        "aggregation_policies" => {
+            if get_f64(document, "window").map_or(false, |x| x <= 0.0) {
+                return Err(TipupError::from("window must be greater than 0"));
+            }
+        },
+        "escalation_policies" => {
+            if let Some(&Bson::Array(ref stages)) = document.get("stages") {
+                for stage in stages.iter() {
+                    let valid = match stage {
+                        &Bson::Document(ref stage) => match stage.get("status") {
+                            Some(&Bson::String(_)) => get_f64(stage, "after").map_or(false, |x| x >= 0.0),
+                            _ => false,
+                        },
+                        _ => false,
+                    };
+
+                    if !valid {
+                        return Err(TipupError::from("stages require a non-negative 'after' and a 'status'"));
+                    }
+
+                    if let &Bson::Document(ref stage) = stage {
+                        match stage.get("verify") {
+                            Some(&Bson::String(ref verify)) if verification::METHODS.contains(&verify.as_str()) => {},
+                            None => {},
+                            _ => return Err(TipupError::from(format!("stage 'verify' must be one of {}", verification::METHODS.join(", ")))),
+                        }
+                    }
+                }
+            }
+        },
+        "maintenance_schedules" => {
+            try!(MaintenanceSchedule::parse(document));
+        },
+        "retention_policies" => {
+            if get_f64(document, "max_age").map_or(false, |x| x < 0.0) {
+                return Err(TipupError::from("max_age must not be negative"));
+            }
+        },
+        "slas" => {
+            if get_f64(document, "min_availability").map_or(false, |x| x < 0.0 || x > 100.0) {
+                return Err(TipupError::from("min_availability must be a percentage between 0 and 100"));
+            }
+
+            if document.contains_key("max_p95_latency") && !document.contains_key("latency_field") {
+                return Err(TipupError::from("max_p95_latency requires a latency_field"));
+            }
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+fn validate_severities(collection: &str, document: &Document, taxonomy: &SeverityTaxonomy) -> Result<(), TipupError> {
+    //statuses a definition raises flags or escalates with must resolve to a configured level
+    let mut statuses = Vec::new();
+    if let Some(&Bson::String(ref status)) = document.get("status") {
+        statuses.push(status.to_owned());
+    }
+
+    for key in ["overrides", "stages"].iter() {
+        if let Some(&Bson::Array(ref array)) = document.get(key) {
+            for value in array.iter() {
+                if let &Bson::Document(ref value) = value {
+                    if let Some(&Bson::String(ref status)) = value.get("status") {
+                        statuses.push(status.to_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    for status in statuses.iter() {
+        if taxonomy.resolve(status).is_none() {
+            return Err(TipupError::from(format!("status '{}' is not a severity level ({}) or mapped to one", status, taxonomy.levels().join(", "))));
+        }
+    }
+
+    match (collection, document.get("selector")) {
+        ("sinks", Some(&Bson::String(ref selector))) | ("silences", Some(&Bson::String(ref selector))) | ("enrichment_rules", Some(&Bson::String(ref selector))) => {
+            try!(try!(LabelSelector::parse(selector)).with_levels("status", taxonomy.levels()));
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+fn identify(collection: &str, document: &Document) -> String {
+    match (document.get("name"), document.get("_id")) {
+        (Some(&Bson::String(ref name)), _) => format!("{} '{}'", collection, name),
+        (_, Some(id)) => format!("{} {}", collection, id),
+        _ => collection.to_owned(),
+    }
+}
+
+fn parse_value<T: FromStr + Default>(matches: &ArgMatches, name: &str, validation: &mut Validation) -> T {
+    match value_t!(matches.value_of(name), T) {
+        Ok(value) => value,
+        Err(e) => {
+            validation.error(format!("{}: {}", name, e));
+            T::default()
+        },
+    }
+}
+
+fn parse_values(matches: &ArgMatches, name: &str) -> Vec<String> {
+    match matches.values_of(name) {
+        Some(values) => values.map(|x| x.to_owned()).collect(),
+        None => Vec::new(),
+    }
+}
@@ -0,0 +1,173 @@
+use bson::{Bson, Document};
+use bson::ordered::OrderedDocument;
+use mongodb::{Client, ThreadedClient};
+use mongodb::db::ThreadedDatabase;
+use time;
+
+use std;
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::panic;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+struct CrashState {
+    document_id: Option<String>,
+    queue_depths: BTreeMap<String, usize>,
+    supervised_threads: HashSet<String>,
+}
+
+//processing state recorded by running threads for inclusion in crash reports
+#[derive(Clone)]
+pub struct CrashContext {
+    state: Arc<Mutex<CrashState>>,
+}
+
+impl CrashContext {
+    pub fn new() -> CrashContext {
+        CrashContext {
+            state: Arc::new(Mutex::new(
+                CrashState {
+                    document_id: None,
+                    queue_depths: BTreeMap::new(),
+                    supervised_threads: HashSet::new(),
+                }
+            )),
+        }
+    }
+
+    pub fn set_document(&self, document: &OrderedDocument) {
+        let document_id = match document.get("_id") {
+            Some(&Bson::ObjectId(ref id)) => id.to_hex(),
+            Some(id) => id.to_string(),
+            None => return,
+        };
+
+        self.lock().document_id = Some(document_id);
+    }
+
+    pub fn set_queue_depth(&self, name: &str, depth: usize) {
+        self.lock().queue_depths.insert(name.to_owned(), depth);
+    }
+
+    //supervised threads are restarted after a panic rather than exiting the process
+    pub fn supervise(&self, thread: &str) {
+        self.lock().supervised_threads.insert(thread.to_owned());
+    }
+
+    fn lock<'a>(&'a self) -> MutexGuard<'a, CrashState> {
+        //the panicking thread may have poisoned the lock, state is still worth reporting
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        }
+    }
+}
+
+thread_local!(static CONTAINED: Cell<bool> = Cell::new(false));
+
+//runs the task, catching a panic within it rather than exiting the process so the caller may
+//recover, the panic still being reported
+pub fn contain<F, T>(task: F) -> thread::Result<T> where F: FnOnce() -> T {
+    let contained = CONTAINED.with(|x| x.replace(true));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(task));
+    CONTAINED.with(|x| x.set(contained));
+    result
+}
+
+pub struct CrashReporter {
+    directory: String,
+    collection: String,
+    client: Client,
+    username: String,
+    password: String,
+    context: CrashContext,
+}
+
+impl CrashReporter {
+    pub fn new(directory: &str, collection: &str, client: Client, username: &str, password: &str, context: CrashContext) -> CrashReporter {
+        CrashReporter {
+            directory: directory.to_owned(),
+            collection: collection.to_owned(),
+            client: client,
+            username: username.to_owned(),
+            password: password.to_owned(),
+            context: context,
+        }
+    }
+
+    //replace the panic hook so a panic on any thread writes a crash report and, unless the thread
+    //is supervised or the panic contained, exits the process
+    pub fn install(self) {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match info.payload().downcast_ref::<String>() {
+                    Some(message) => message.to_owned(),
+                    None => "unknown panic payload".to_owned(),
+                },
+            };
+
+            let location = info.location().map_or("unknown".to_owned(), |x| format!("{}:{}:{}", x.file(), x.line(), x.column()));
+            let report = self.report(&message, &location);
+            default_hook(info);
+
+            self.write(report);
+            let supervised = thread::current().name().map_or(false, |x| self.context.lock().supervised_threads.contains(x));
+            if !supervised && !CONTAINED.with(|x| x.get()) {
+                std::process::exit(101);
+            }
+        }));
+    }
+
+    fn report(&self, message: &str, location: &str) -> Document {
+        let state = self.context.lock();
+        let mut queue_depths = Document::new();
+        for (name, depth) in state.queue_depths.iter() {
+            queue_depths.insert(name.to_owned(), *depth as i64);
+        }
+
+        let mut report = doc!(
+            "timestamp" => (time::now_utc().to_timespec().sec),
+            "thread" => (thread::current().name().unwrap_or("<unnamed>").to_owned()),
+            "message" => message,
+            "location" => location,
+            "queue_depths" => queue_depths,
+            "backtrace" => (Backtrace::force_capture().to_string())
+        );
+
+        if let Some(ref document_id) = state.document_id {
+            report.insert("document_id", document_id.to_owned());
+        }
+
+        report
+    }
+
+    fn write(&self, report: Document) {
+        let timestamp = match report.get("timestamp") {
+            Some(&Bson::I64(timestamp)) => timestamp,
+            _ => 0,
+        };
+
+        //a local file is written first so reports survive an unreachable mongodb
+        let path = Path::new(&self.directory).join(format!("tipup-crash-{}.json", timestamp));
+        match File::create(&path).and_then(|mut file| writeln!(file, "{}", Bson::Document(report.clone()).to_json())) {
+            Ok(_) => eprintln!("wrote crash report to '{}'", path.display()),
+            Err(e) => eprintln!("failed to write crash report to '{}': {}", path.display(), e),
+        }
+
+        if self.collection.len() == 0 {
+            return;
+        }
+
+        let db = self.client.db("proddle");
+        if let Err(e) = db.auth(&self.username, &self.password).and_then(|_| db.collection(&self.collection).insert_one(report, None)) {
+            eprintln!("failed to write crash report to collection '{}': {}", self.collection, e);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+
+use error::TipupError;
+
+use std::collections::{HashMap, HashSet};
+
+//analyzers whose per-result statistics an analyzer consumes, e.g. an ensemble voting over the
+//scores of its members, which are dispatched each result before it
+pub struct Dependency {
+    pub name: String,
+    pub depends_on: Vec<String>,
+}
+
+impl Dependency {
+    pub fn parse(document: &OrderedDocument) -> Result<Option<Dependency>, TipupError> {
+        let name = match document.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return Err(TipupError::from("failed to parse analyzer name")),
+        };
+
+        let depends_on = match document.get("depends_on") {
+            Some(&Bson::Array(ref depends_on)) => {
+                let mut names = Vec::new();
+                for dependency in depends_on.iter() {
+                    match dependency {
+                        &Bson::String(ref dependency) if dependency == &name => return Err(TipupError::from(format!("analyzer '{}' may not depend on itself", name))),
+                        &Bson::String(ref dependency) => names.push(dependency.to_owned()),
+                        _ => return Err(TipupError::from(format!("failed to parse depends_on of analyzer '{}' as String", name))),
+                    }
+                }
+
+                names
+            },
+            None => return Ok(None),
+            _ => return Err(TipupError::from(format!("failed to parse depends_on of analyzer '{}'", name))),
+        };
+
+        Ok(Some(
+            Dependency {
+                name: name,
+                depends_on: depends_on,
+            }
+        ))
+    }
+}
+
+//orders names so each follows the names it depends on, failing with the path of any cycle
+pub fn topological_order(names: &Vec<String>, dependencies: &HashMap<String, Vec<String>>) -> Result<Vec<String>, TipupError> {
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    for name in names.iter() {
+        try!(visit(name, dependencies, &mut visited, &mut Vec::new(), &mut order));
+    }
+
+    Ok(order)
+}
+
+fn visit(name: &String, dependencies: &HashMap<String, Vec<String>>, visited: &mut HashSet<String>, path: &mut Vec<String>, order: &mut Vec<String>) -> Result<(), TipupError> {
+    if let Some(index) = path.iter().position(|x| x == name) {
+        let mut cycle = path[index..].to_vec();
+        cycle.push(name.to_owned());
+        return Err(TipupError::from(format!("analyzer dependencies form a cycle: {}", cycle.join(" -> "))));
+    }
+
+    if visited.contains(name) {
+        return Ok(());
+    }
+
+    path.push(name.to_owned());
+    for dependency in dependencies.get(name).map_or(&[][..], |x| &x[..]) {
+        try!(visit(dependency, dependencies, visited, path, order));
+    }
+
+    path.pop();
+    visited.insert(name.to_owned());
+    order.push(name.to_owned());
+    Ok(())
+}
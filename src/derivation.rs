@@ -0,0 +1,105 @@
+use bson::{Bson, Document};
+use bson::oid::ObjectId;
+use bson::ordered::OrderedDocument;
+
+use error::TipupError;
+
+//number of derived results a result may pass through, bounding cyclic derivations
+const MAX_DERIVATION_DEPTH: i32 = 4;
+
+//statistics an analyzer computes for each result emitted as a derived result of another
+//measurement class, e.g. jitter or anomaly scores, which analyzers of that class consume
+pub struct Derivation {
+    pub name: String,
+    pub measurement_class: String,
+    pub statistics: Vec<String>,
+}
+
+impl Derivation {
+    pub fn parse(document: &OrderedDocument) -> Result<Option<Derivation>, TipupError> {
+        let name = match document.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return Err(TipupError::from("failed to parse analyzer name")),
+        };
+
+        let derive = match document.get("derive") {
+            Some(&Bson::Document(ref derive)) => derive,
+            None => return Ok(None),
+            _ => return Err(TipupError::from(format!("failed to parse derive of analyzer '{}'", name))),
+        };
+
+        let measurement_class = match (derive.get("measurement_class"), document.get("measurement_class")) {
+            (Some(&Bson::String(ref measurement_class)), Some(&Bson::String(ref analyzer_class))) if measurement_class != analyzer_class => measurement_class.to_owned(),
+            _ => return Err(TipupError::from(format!("derived measurement_class of analyzer '{}' must differ from the class it analyzes", name))),
+        };
+
+        //all statistics the analyzer records when none are listed
+        let statistics = match derive.get("statistics") {
+            Some(&Bson::Array(ref statistics)) => {
+                let mut names = Vec::new();
+                for statistic in statistics.iter() {
+                    match statistic {
+                        &Bson::String(ref statistic) => names.push(statistic.to_owned()),
+                        _ => return Err(TipupError::from(format!("failed to parse derived statistic of analyzer '{}' as String", name))),
+                    }
+                }
+
+                names
+            },
+            None => Vec::new(),
+            _ => return Err(TipupError::from(format!("failed to parse derived statistics of analyzer '{}'", name))),
+        };
+
+        Ok(Some(
+            Derivation {
+                name: name,
+                measurement_class: measurement_class,
+                statistics: statistics,
+            }
+        ))
+    }
+
+    //a derived result of the analyzed result's statistics, none if the analyzer recorded none
+    pub fn derive(&self, document: &OrderedDocument, statistics: &Document) -> Option<Document> {
+        let depth = match document.get("derivation_depth") {
+            Some(&Bson::I32(depth)) => depth + 1,
+            _ => 1,
+        };
+
+        if depth > MAX_DERIVATION_DEPTH {
+            return None;
+        }
+
+        let mut derived_document = Document::new();
+        if let Ok(id) = ObjectId::new() {
+            derived_document.insert("_id", id);
+        }
+
+        for field in ["vantage_hostname", "measurement_domain", "timestamp"].iter() {
+            match document.get(field) {
+                Some(value) => derived_document.insert(*field, value.clone()),
+                None => return None,
+            };
+        }
+
+        derived_document.insert("measurement_class", self.measurement_class.to_owned());
+        derived_document.insert("analyzer", self.name.to_owned());
+        derived_document.insert("derivation_depth", depth);
+        if let Some(id) = document.get("_id") {
+            derived_document.insert("source_id", id.clone());
+        }
+
+        let mut count = 0;
+        for (key, value) in statistics.iter() {
+            if self.statistics.len() == 0 || self.statistics.contains(key) {
+                derived_document.insert(key.to_owned(), value.clone());
+                count += 1;
+            }
+        }
+
+        match count {
+            0 => None,
+            _ => Some(derived_document),
+        }
+    }
+}
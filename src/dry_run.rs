@@ -0,0 +1,85 @@
+use bson::{self, Bson, Document};
+use bson::oid::ObjectId;
+use chan;
+use mongodb::db::{Database, ThreadedDatabase};
+use serde_json;
+
+use analyzer::Trace;
+use clock;
+use error::TipupError;
+use flag_manager::Flag;
+use result_window::ResultWindow;
+
+use std::sync::{Arc, RwLock};
+
+//evaluates a sample result with a freshly built copy of the named analyzer so running analyzers are
+//untouched, the copy restores result windows from snapshots but otherwise starts without history
+pub fn evaluate(proddle_db: &Database, name: &str, sample: &str) -> Result<Document, TipupError> {
+    let mut document = match serde_json::from_str(sample) {
+        Ok(value) => match Bson::from_json(&value) {
+            Bson::Document(document) => document,
+            _ => return Err(TipupError::from("sample result must be a json object")),
+        },
+        Err(e) => return Err(TipupError::from(format!("failed to parse sample result as json: {}", e))),
+    };
+
+    //fill fields required to raise flags which samples commonly omit
+    let clock = clock::system();
+    let now = clock.now();
+    if !document.contains_key("_id") {
+        document.insert("_id", ObjectId::new().unwrap());
+    }
+
+    if !document.contains_key("timestamp") {
+        document.insert("timestamp", now);
+    }
+
+    let definition = match try!(proddle_db.collection("analyzers").find_one(Some(doc!("name" => name)), None)) {
+        Some(definition) => definition,
+        None => return Err(TipupError::from(format!("analyzer '{}' not found", name))),
+    };
+
+    //capture flags on a private channel rather than forwarding them to the flag manager
+    let (flag_tx, flag_rx) = chan::async();
+    let result_window = Arc::new(RwLock::new(ResultWindow::new()));
+    let (_, measurement_class, mut analyzer) = try!(::create_analyzer(&definition, flag_tx, result_window.clone(), clock));
+    try!(analyzer.refresh(proddle_db));
+    try!(result_window.write().unwrap().initialize(proddle_db));
+
+    let mut trace = Trace::new(true);
+    let result = analyzer.process_measurement(&document, &mut trace);
+
+    //dropping the analyzer closes the channel once its flags are drained
+    drop(analyzer);
+    let flags: Vec<Flag> = flag_rx.iter().collect();
+
+    let mut evaluation = trace.to_document(name, &document, now).unwrap_or(Document::new());
+    evaluation.insert("would_flag", flags.len() > 0);
+
+    let mut flag_documents = Vec::new();
+    for flag in flags.iter() {
+        match bson::to_bson(flag) {
+            Ok(Bson::Document(mut flag_document)) => {
+                flag_document.remove("_id");
+                flag_document.remove("measurement_id");
+                flag_documents.push(Bson::Document(flag_document));
+            },
+            _ => return Err(TipupError::from("failed to parse flag json as Bson::Document")),
+        }
+    }
+
+    evaluation.insert("flags", flag_documents);
+
+    match document.get("measurement_class") {
+        Some(&Bson::String(ref sample_class)) if sample_class != &measurement_class => {
+            evaluation.insert("warning", format!("analyzer evaluates '{}' results but the sample is '{}'", measurement_class, sample_class));
+        },
+        _ => {},
+    }
+
+    if let Err(e) = result {
+        evaluation.insert("error", e.to_string());
+    }
+
+    Ok(evaluation)
+}
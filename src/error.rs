@@ -1,4 +1,6 @@
 extern crate clap;
+extern crate glob;
+extern crate hyper;
 extern crate mongodb;
 
 use flag_manager::Flag;
@@ -9,6 +11,8 @@ use std::fmt::{Display, Formatter, Result};
 #[derive(Debug)]
 pub enum TipupError {
     Clap(clap::Error),
+    Glob(glob::PatternError),
+    Hyper(hyper::Error),
     MongoDB(mongodb::Error),
     Send(std::sync::mpsc::SendError<Flag>),
     Tipup(String),
@@ -18,6 +22,8 @@ impl Display for TipupError {
     fn fmt(&self, f: &mut Formatter) -> Result {
         match *self {
             TipupError::Clap(ref err) => write!(f, "ClapError: {}", err),
+            TipupError::Glob(ref err) => write!(f, "GlobError: {}", err),
+            TipupError::Hyper(ref err) => write!(f, "HyperError: {}", err),
             TipupError::MongoDB(ref err) => write!(f, "MongoDBError: {}", err),
             TipupError::Send(ref err) => write!(f, "Send: {}", err),
             TipupError::Tipup(ref err) => write!(f, "TipupError: {}", err),
@@ -31,6 +37,18 @@ impl From<clap::Error> for TipupError {
     }
 }
 
+impl From<glob::PatternError> for TipupError {
+    fn from(err: glob::PatternError) -> TipupError {
+        TipupError::Glob(err)
+    }
+}
+
+impl From<hyper::Error> for TipupError {
+    fn from(err: hyper::Error) -> TipupError {
+        TipupError::Hyper(err)
+    }
+}
+
 impl From<mongodb::Error> for TipupError {
     fn from(err: mongodb::Error) -> TipupError {
         TipupError::MongoDB(err)
@@ -0,0 +1,49 @@
+use bson::{self, Bson, Document};
+use bson::oid::ObjectId;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Event {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub kind: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+}
+
+impl Event {
+    pub fn new(kind: &str, description: &str, domain: Option<String>, hostname: Option<String>, start_timestamp: i64, end_timestamp: i64) -> Result<Event, TipupError> {
+        if end_timestamp < start_timestamp {
+            return Err(TipupError::from("event end timestamp precedes start timestamp"));
+        }
+
+        Ok(
+            Event {
+                id: ObjectId::new().unwrap(),
+                kind: kind.to_owned(),
+                description: description.to_owned(),
+                domain: domain,
+                hostname: hostname,
+                start_timestamp: start_timestamp,
+                end_timestamp: end_timestamp,
+            }
+        )
+    }
+
+    pub fn insert(&self, tipup_db: &Database) -> Result<(), TipupError> {
+        let document: Document = match bson::to_bson(self) {
+            Ok(Bson::Document(document)) => document,
+            _ => return Err(TipupError::from("failed to parse event as Bson::Document")),
+        };
+
+        try!(tipup_db.collection("events").insert_one(document, None));
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use bson::{self, Bson, Document};
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use time::{self, Timespec};
+
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+//query parameters which filter feed entries by the equally named flag field
+const FILTER_PARAMETERS: [&'static str; 5] = ["status", "domain", "hostname", "analyzer", "state"];
+
+pub fn flags_atom(tipup_db: &Database, parameters: &HashMap<String, String>) -> Result<String, TipupError> {
+    let mut search_document = Document::new();
+    for parameter in FILTER_PARAMETERS.iter() {
+        if let Some(value) = parameters.get(*parameter) {
+            search_document.insert(*parameter, value.to_owned());
+        }
+    }
+
+    let limit = match parameters.get("limit").map(|x| x.parse::<i64>()) {
+        Some(Ok(limit)) if limit > 0 => limit,
+        Some(_) => return Err(TipupError::from("failed to parse feed 'limit' parameter as positive integer")),
+        None => 50,
+    };
+
+    let negative_one = -1;
+    let mut find_options = FindOptions::new();
+    find_options.sort = Some(doc!("timestamp" => negative_one));
+    find_options.limit = Some(limit);
+
+    let mut flags = Vec::new();
+    for document in try!(tipup_db.collection("flags").find(Some(search_document), Some(find_options))) {
+        match bson::from_bson(Bson::Document(try!(document))) {
+            Ok(flag) => flags.push(flag),
+            Err(_) => return Err(TipupError::from("failed to parse bson document into flag")),
+        }
+    }
+
+    let updated = flags.iter().map(|x: &Flag| x.last_timestamp.max(x.timestamp)).max()
+        .unwrap_or(time::now_utc().to_timespec().sec);
+
+    let mut feed = String::new();
+    feed.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    feed.push_str("  <id>urn:tipup:flags</id>\n");
+    feed.push_str("  <title>tipup flags</title>\n");
+    feed.push_str(&format!("  <updated>{}</updated>\n", format_timestamp(updated)));
+    for flag in flags.iter() {
+        feed.push_str(&format_entry(flag));
+    }
+
+    feed.push_str("</feed>\n");
+    Ok(feed)
+}
+
+fn format_entry(flag: &Flag) -> String {
+    let target = match flag.hostname {
+        Some(ref hostname) => format!("{} from {}", flag.domain, hostname),
+        None => flag.domain.to_owned(),
+    };
+
+    //summarize labels and evidence in the entry content
+    let mut content = format!("state: {}\ncount: {}\nfirst seen: {}\n", flag.state, flag.count, format_timestamp(flag.timestamp));
+    let mut labels: Vec<(&String, &String)> = flag.labels.iter().chain(flag.evidence.iter()).collect();
+    labels.sort();
+    for (key, value) in labels {
+        content.push_str(&format!("{}: {}\n", key, value));
+    }
+
+    let link = match flag.url {
+        Some(ref url) => format!("\n    <link href=\"{}\"/>", escape(url)),
+        None => String::new(),
+    };
+
+    format!("  <entry>\n    <id>urn:tipup:flag:{}</id>\n    <title>{}</title>{}\n    <updated>{}</updated>\n    <category term=\"{}\"/>\n    <author><name>{}</name></author>\n    <content type=\"text\">{}</content>\n  </entry>\n",
+        flag.id,
+        escape(&format!("[{}] {} flagged {}", flag.status, flag.analyzer, target)),
+        link,
+        format_timestamp(flag.last_timestamp.max(flag.timestamp)),
+        escape(&flag.status),
+        escape(&flag.analyzer),
+        escape(&content))
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    time::at_utc(Timespec::new(timestamp, 0)).rfc3339().to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
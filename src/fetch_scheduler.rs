@@ -0,0 +1,163 @@
+use time;
+
+use clock::Clock;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+//weight of the newest observation in a measurement's learned sampling interval
+const LEARNING_RATE: f64 = 0.2;
+
+struct Schedule {
+    due: f64,
+    sampling_interval: Option<f64>,
+}
+
+//schedules result fetches per (hostname, measurement_class), polling each measurement as often as
+//it is sampled within configured bounds, e.g. 1 minute pings every minute and daily traceroutes
+//hourly, with random jitter so fetches do not reach the results collection back-to-back
+pub struct FetchScheduler {
+    default_interval: f64,
+    minimum_interval: f64,
+    maximum_interval: f64,
+    jitter: f64,
+    schedules: HashMap<(String, String), Schedule>,
+    random_state: u64,
+    clock: Arc<Clock>,
+}
+
+impl FetchScheduler {
+    pub fn new(default_interval: u32, minimum_interval: u32, maximum_interval: u32, jitter: f64, clock: Arc<Clock>) -> FetchScheduler {
+        FetchScheduler {
+            default_interval: default_interval as f64,
+            minimum_interval: minimum_interval as f64,
+            maximum_interval: maximum_interval as f64,
+            jitter: jitter,
+            schedules: HashMap::new(),
+            random_state: time::precise_time_ns() | 1,
+            clock: clock,
+        }
+    }
+
+    //reconfigured bounds apply from each key's next fetch
+    pub fn configure(&mut self, minimum_interval: u32, maximum_interval: u32, jitter: f64) {
+        self.minimum_interval = minimum_interval as f64;
+        self.maximum_interval = maximum_interval as f64;
+        self.jitter = jitter;
+    }
+
+    //track the measured (hostname, measurement_class) keys, spreading newly discovered keys evenly
+    //over the default interval and dropping keys no longer measured
+    pub fn schedule(&mut self, keys: Vec<(String, String)>) {
+        let keys: HashSet<(String, String)> = keys.into_iter().collect();
+        self.schedules.retain(|key, _| keys.contains(key));
+
+        let mut new_keys: Vec<(String, String)> = keys.into_iter().filter(|x| !self.schedules.contains_key(x)).collect();
+        new_keys.sort();
+
+        let now = self.clock.precise_now();
+        let slot = self.default_interval / new_keys.len().max(1) as f64;
+        for (i, key) in new_keys.into_iter().enumerate() {
+            let jitter = (self.next_random() - 0.5) * self.jitter * slot;
+            self.schedules.insert(key, Schedule { due: now + ((i as f64 + 0.5) * slot) + jitter, sampling_interval: None });
+        }
+    }
+
+    pub fn due(&self) -> Vec<(String, String)> {
+        let now = self.clock.precise_now();
+        let mut due: Vec<(f64, &(String, String))> = self.schedules.iter()
+            .filter(|x| x.1.due <= now)
+            .map(|(key, schedule)| (schedule.due, key))
+            .collect();
+
+        due.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        due.into_iter().map(|x| x.1.clone()).collect()
+    }
+
+    //reschedule a fetched key, learning its sampling interval from the spacing of fetched results
+    pub fn fetched(&mut self, key: &(String, String), sampling_interval: Option<f64>) {
+        let jitter = (self.next_random() - 0.5) * self.jitter;
+        let now = self.clock.precise_now();
+        let (default_interval, minimum_interval, maximum_interval) = (self.default_interval, self.minimum_interval, self.maximum_interval);
+        if let Some(schedule) = self.schedules.get_mut(key) {
+            if let Some(sampling_interval) = sampling_interval {
+                schedule.sampling_interval = Some(match schedule.sampling_interval {
+                    Some(x) => x + (LEARNING_RATE * (sampling_interval - x)),
+                    None => sampling_interval,
+                });
+            }
+
+            let interval = schedule.sampling_interval.unwrap_or(default_interval).max(minimum_interval).min(maximum_interval);
+            schedule.due = now + (interval * (1.0 + jitter));
+        }
+    }
+
+    //poll a key with a remaining backlog again on the next tick, behind keys already due
+    pub fn expedite(&mut self, key: &(String, String)) {
+        let now = self.clock.precise_now();
+        if let Some(schedule) = self.schedules.get_mut(key) {
+            schedule.due = now;
+        }
+    }
+
+    fn next_random(&mut self) -> f64 {
+        //xorshift64* uniform in [0, 1)
+        self.random_state ^= self.random_state >> 12;
+        self.random_state ^= self.random_state << 25;
+        self.random_state ^= self.random_state >> 27;
+        (self.random_state.wrapping_mul(0x2545f4914f6cdd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clock::MockClock;
+
+    use super::FetchScheduler;
+
+    use std::sync::Arc;
+
+    fn key(hostname: &str) -> (String, String) {
+        (hostname.to_owned(), "ping".to_owned())
+    }
+
+    #[test]
+    fn spreads_new_keys_over_default_interval() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut fetch_scheduler = FetchScheduler::new(300, 60, 3600, 0.0, clock.clone());
+        fetch_scheduler.schedule(vec!(key("a"), key("b")));
+
+        clock.step(74.0);
+        assert_eq!(fetch_scheduler.due().len(), 0);
+        clock.step(1.0);
+        assert_eq!(fetch_scheduler.due(), vec!(key("a")));
+        clock.step(150.0);
+        assert_eq!(fetch_scheduler.due(), vec!(key("a"), key("b")));
+    }
+
+    #[test]
+    fn reschedules_by_bounded_sampling_interval() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut fetch_scheduler = FetchScheduler::new(300, 60, 3600, 0.0, clock.clone());
+        fetch_scheduler.schedule(vec!(key("a")));
+
+        //unknown sampling intervals fall back to the default interval
+        clock.step(150.0);
+        fetch_scheduler.fetched(&key("a"), None);
+        clock.step(299.0);
+        assert_eq!(fetch_scheduler.due().len(), 0);
+        clock.step(1.0);
+        assert_eq!(fetch_scheduler.due().len(), 1);
+
+        //sampling intervals below the minimum poll at the minimum
+        fetch_scheduler.fetched(&key("a"), Some(10.0));
+        clock.step(59.0);
+        assert_eq!(fetch_scheduler.due().len(), 0);
+        clock.step(1.0);
+        assert_eq!(fetch_scheduler.due().len(), 1);
+
+        fetch_scheduler.fetched(&key("a"), Some(120.0));
+        fetch_scheduler.expedite(&key("a"));
+        assert_eq!(fetch_scheduler.due().len(), 1);
+    }
+}
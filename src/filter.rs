@@ -0,0 +1,46 @@
+use glob::Pattern;
+
+use error::TipupError;
+
+pub struct ResultFilter {
+    hostname_allow: Vec<Pattern>,
+    hostname_deny: Vec<Pattern>,
+    measurement_allow: Vec<Pattern>,
+    measurement_deny: Vec<Pattern>,
+}
+
+impl ResultFilter {
+    pub fn new(hostname_allow: &Vec<String>, hostname_deny: &Vec<String>, measurement_allow: &Vec<String>, measurement_deny: &Vec<String>) -> Result<ResultFilter, TipupError> {
+        Ok(
+            ResultFilter {
+                hostname_allow: try!(compile_patterns(hostname_allow)),
+                hostname_deny: try!(compile_patterns(hostname_deny)),
+                measurement_allow: try!(compile_patterns(measurement_allow)),
+                measurement_deny: try!(compile_patterns(measurement_deny)),
+            }
+        )
+    }
+
+    pub fn hostname_allowed(&self, hostname: &str) -> bool {
+        allowed(&self.hostname_allow, &self.hostname_deny, hostname)
+    }
+
+    pub fn measurement_allowed(&self, measurement_class: &str) -> bool {
+        allowed(&self.measurement_allow, &self.measurement_deny, measurement_class)
+    }
+}
+
+fn compile_patterns(globs: &Vec<String>) -> Result<Vec<Pattern>, TipupError> {
+    let mut patterns = Vec::new();
+    for glob in globs.iter() {
+        patterns.push(try!(Pattern::new(glob)));
+    }
+
+    Ok(patterns)
+}
+
+fn allowed(allow: &Vec<Pattern>, deny: &Vec<Pattern>, value: &str) -> bool {
+    //an empty allow list permits everything not explicitly denied
+    (allow.len() == 0 || allow.iter().any(|x| x.matches(value)))
+        && !deny.iter().any(|x| x.matches(value))
+}
@@ -2,16 +2,87 @@ use bson::{self, Bson, Document};
 use bson::oid::ObjectId;
 use bson::ordered::OrderedDocument;
 use mongodb::db::{Database, ThreadedDatabase};
+use time;
 
+use clock::Clock;
 use error::TipupError;
+use flag_store::{FlagStore, FlagUpdate};
+use flag_url::FlagUrlTemplate;
+use incident_manager::Incident;
+use label::LabelSelector;
+use lifecycle::{self, LifecycleWebhook};
+use metrics::Metrics;
+use resources::{self, Evictable};
+use self_healing;
+use severity::SeverityTaxonomy;
+use sink::{self, IncidentUpdate, Sink};
+use topology::Topology;
+use verification;
 
-#[derive(Debug, Deserialize, Serialize)]
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+//seconds after an incident's latest flag in which flags of its domain are coalesced into it,
+//half the day over which the incident manager scales timestamp distance
+const INCIDENT_WINDOW_SECONDS: i64 = 43200;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Flag {
     #[serde(rename = "_id")]
     pub id: ObjectId,
-    pub measurement_id: ObjectId,
+    //flags written before timestamps, hostnames, and domains were recorded default them
+    #[serde(default)]
+    pub measurement_id: Option<ObjectId>,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub domain: String,
     pub status: String,
     pub analyzer: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub silenced: bool,
+    #[serde(default = "default_state")]
+    pub state: String,
+    #[serde(default)]
+    pub last_timestamp: i64,
+    #[serde(default = "default_count")]
+    pub count: i32,
+    #[serde(default)]
+    pub escalation_level: i32,
+    #[serde(default)]
+    pub snooze: Option<Snooze>,
+    #[serde(default)]
+    pub evidence: HashMap<String, String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub tickets: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acknowledged_timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Snooze {
+    pub until_timestamp: Option<i64>,
+    pub field: Option<Vec<String>>,
+    pub baseline: Option<f64>,
+    pub increase: Option<f64>,
+}
+
+fn default_state() -> String {
+    "open".to_owned()
+}
+
+fn default_count() -> i32 {
+    1
 }
 
 impl Flag {
@@ -21,35 +92,1166 @@ impl Flag {
             _ => return Err(TipupError::from("failed to parse measurement '_id' as ObjectId")),
         };
 
+        let timestamp = match document.get("timestamp") {
+            Some(&Bson::I64(timestamp)) => timestamp,
+            _ => return Err(TipupError::from("failed to parse measurement 'timestamp' as i64")),
+        };
+
+        let hostname = match document.get("vantage_hostname") {
+            Some(&Bson::String(ref hostname)) => hostname.to_owned(),
+            _ => return Err(TipupError::from("failed to parse measurement 'vantage_hostname' as String")),
+        };
+
+        let domain = match document.get("measurement_domain") {
+            Some(&Bson::String(ref domain)) => domain.to_owned(),
+            _ => return Err(TipupError::from("failed to parse measurement 'measurement_domain' as String")),
+        };
+
+        let mut labels = HashMap::new();
+        if let Some(&Bson::String(ref measurement_class)) = document.get("measurement_class") {
+            labels.insert("measurement_class".to_owned(), measurement_class.to_owned());
+        }
+
+        if let Some(&Bson::String(ref error_category)) = document.get("measurement_error_category") {
+            labels.insert("error_category".to_owned(), error_category.to_owned());
+        }
+
         Ok(
             Flag {
                 id: ObjectId::new().unwrap(),
-                measurement_id: measurement_id,
+                measurement_id: Some(measurement_id),
+                timestamp: timestamp,
+                hostname: Some(hostname),
+                domain: domain,
                 status: status.to_owned(),
                 analyzer: analyzer.to_owned(),
+                labels: labels,
+                silenced: false,
+                state: default_state(),
+                last_timestamp: timestamp,
+                count: 1,
+                escalation_level: 0,
+                snooze: None,
+                evidence: HashMap::new(),
+                url: None,
+                tickets: HashMap::new(),
+                acknowledged_timestamp: None,
+                resolved_timestamp: None,
+                feedback: None,
             }
         )
     }
+
+    pub fn for_domain(domain: &str, timestamp: i64, status: &str, analyzer: &str) -> Flag {
+        Flag {
+            id: ObjectId::new().unwrap(),
+            measurement_id: None,
+            timestamp: timestamp,
+            hostname: None,
+            domain: domain.to_owned(),
+            status: status.to_owned(),
+            analyzer: analyzer.to_owned(),
+            labels: HashMap::new(),
+            silenced: false,
+            state: default_state(),
+            last_timestamp: timestamp,
+            count: 1,
+            escalation_level: 0,
+            snooze: None,
+            evidence: HashMap::new(),
+            url: None,
+            tickets: HashMap::new(),
+            acknowledged_timestamp: None,
+            resolved_timestamp: None,
+            feedback: None,
+        }
+    }
+
+    pub fn selector_labels(&self) -> HashMap<String, String> {
+        //expose core flag fields alongside free-form labels for selector matching
+        let mut labels = self.labels.clone();
+        labels.insert("analyzer".to_owned(), self.analyzer.clone());
+        labels.insert("status".to_owned(), self.status.clone());
+        labels.insert("domain".to_owned(), self.domain.clone());
+        if let Some(ref hostname) = self.hostname {
+            labels.insert("hostname".to_owned(), hostname.clone());
+        }
+
+        labels
+    }
+
+    pub fn key(&self) -> (String, Option<String>, String) {
+        (self.analyzer.clone(), self.hostname.clone(), self.domain.clone())
+    }
+
+    //derives the id from (analyzer, hostname, domain, window start), the window start leading so
+    //ids still sort by time, and fnv-1a keeping ids stable across restarts and tipup versions
+    pub fn deterministic_id(&self, window: i64) -> ObjectId {
+        let window_start = self.timestamp - (((self.timestamp % window) + window) % window);
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let hostname = self.hostname.as_ref().map_or("", |x| x.as_str());
+        for byte in self.analyzer.bytes().chain(Some(0)).chain(hostname.bytes()).chain(Some(0)).chain(self.domain.bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        let mut bytes = [0u8; 12];
+        for i in 0..4 {
+            bytes[i] = (window_start as u32 >> (24 - (i * 8))) as u8;
+        }
+
+        for i in 0..8 {
+            bytes[4 + i] = (hash >> (56 - (i * 8))) as u8;
+        }
+
+        ObjectId::with_bytes(bytes)
+    }
+}
+
+struct EnrichmentRule {
+    selector: LabelSelector,
+    labels: HashMap<String, String>,
+}
+
+struct Suppression {
+    hostname: String,
+    domain: String,
+    analyzer: Option<String>,
+}
+
+impl Suppression {
+    fn matches(&self, flag: &Flag) -> bool {
+        flag.hostname.as_ref().map_or(false, |x| x == &self.hostname)
+            && flag.domain == self.domain
+            && self.analyzer.as_ref().map_or(true, |x| x == &flag.analyzer)
+    }
+}
+
+struct EscalationStage {
+    after: i64,
+    status: String,
+    verify: Option<String>,
+}
+
+struct Route {
+    name: String,
+    selector: LabelSelector,
+    sink: Box<Sink + Send>,
+    incident_update_interval: Option<i64>,
+    pending_incidents: HashMap<ObjectId, i64>,
+    incident_updated: HashMap<ObjectId, i64>,
 }
 
 pub struct FlagManager {
+    routes: Vec<Route>,
+    analyzer_labels: HashMap<String, HashMap<String, String>>,
+    enrichment_rules: Vec<EnrichmentRule>,
+    silences: Vec<LabelSelector>,
+    suppressions: Vec<Suppression>,
+    analyzer_classes: HashMap<String, String>,
+    escalation_policies: HashMap<String, Vec<EscalationStage>>,
+    open_flags: HashMap<(String, Option<String>, String), ObjectId>,
+    confirmations: HashMap<String, i64>,
+    unconfirmed_flags: HashMap<(String, Option<String>, String), Flag>,
+    resolve_timeout: i64,
+    merge: bool,
+    id_window: i64,
+    self_healing_window: i64,
+    metrics: Metrics,
+    seen_keys: HashSet<(String, Option<String>, String)>,
+    notification_rate: f64,
+    notification_tokens: f64,
+    notification_refill_time: f64,
+    deferred_flags: VecDeque<Flag>,
+    topology: Option<Topology>,
+    lifecycle_webhooks: Vec<LifecycleWebhook>,
+    incidents: Vec<Incident>,
+    taxonomy: SeverityTaxonomy,
+    url_template: Option<FlagUrlTemplate>,
+    store: Arc<FlagStore>,
+    clock: Arc<Clock>,
 }
 
 impl FlagManager {
-    pub fn new() -> FlagManager {
+    pub fn new(resolve_timeout: i64, merge: bool, id_window: i64, self_healing_window: i64, notification_rate: u32, taxonomy: SeverityTaxonomy, url_template: Option<FlagUrlTemplate>, store: Arc<FlagStore>, metrics: Metrics, clock: Arc<Clock>) -> FlagManager {
         FlagManager {
+            routes: Vec::new(),
+            analyzer_labels: HashMap::new(),
+            enrichment_rules: Vec::new(),
+            silences: Vec::new(),
+            suppressions: Vec::new(),
+            analyzer_classes: HashMap::new(),
+            escalation_policies: HashMap::new(),
+            open_flags: HashMap::new(),
+            confirmations: HashMap::new(),
+            unconfirmed_flags: HashMap::new(),
+            resolve_timeout: resolve_timeout,
+            merge: merge,
+            id_window: id_window,
+            self_healing_window: self_healing_window,
+            metrics: metrics,
+            seen_keys: HashSet::new(),
+            notification_rate: notification_rate as f64,
+            notification_tokens: notification_rate as f64,
+            notification_refill_time: clock.precise_now(),
+            deferred_flags: VecDeque::new(),
+            topology: None,
+            lifecycle_webhooks: Vec::new(),
+            incidents: Vec::new(),
+            taxonomy: taxonomy,
+            url_template: url_template,
+            store: store,
+            clock: clock,
+        }
+    }
+
+    pub fn add_sink(&mut self, name: String, selector: LabelSelector, sink: Box<Sink + Send>, incident_update_interval: Option<i64>) -> Result<(), TipupError> {
+        let selector = try!(selector.with_levels("status", self.taxonomy.levels()));
+        self.routes.push(
+            Route {
+                name: name,
+                selector: selector,
+                sink: sink,
+                incident_update_interval: incident_update_interval,
+                pending_incidents: HashMap::new(),
+                incident_updated: HashMap::new(),
+            }
+        );
+
+        Ok(())
+    }
+
+    pub fn clear_sinks(&mut self) {
+        self.routes.clear();
+    }
+
+    pub fn refresh(&mut self, tipup_db: &Database) -> Result<(), TipupError> {
+        //load labels and classes attached to analyzer definitions
+        self.analyzer_labels.clear();
+        self.analyzer_classes.clear();
+        for document in try!(tipup_db.collection("analyzers").find(None, None)) {
+            let document = try!(document);
+            if let (Some(&Bson::String(ref name)), Some(&Bson::Document(ref labels))) = (document.get("name"), document.get("labels")) {
+                self.analyzer_labels.insert(name.to_owned(), parse_labels(labels));
+            }
+
+            if let (Some(&Bson::String(ref name)), Some(&Bson::String(ref class))) = (document.get("name"), document.get("class")) {
+                self.analyzer_classes.insert(name.to_owned(), class.to_owned());
+            }
+        }
+
+        //load durations flags must persist before sinks are notified
+        self.confirmations = try!(self_healing::load_confirmations(tipup_db));
+
+        //load vantage hostname topology
+        self.topology = Some(try!(Topology::load(tipup_db)));
+
+        //load webhooks subscribed to flag lifecycle transitions
+        self.lifecycle_webhooks = try!(LifecycleWebhook::load(tipup_db));
+
+        //load recent incidents when sinks coalesce notifications by incident
+        self.incidents.clear();
+        if self.routes.iter().any(|x| x.incident_update_interval.is_some()) {
+            let timestamp_gte = doc!("$gte" => (self.clock.now() - INCIDENT_WINDOW_SECONDS));
+            for document in try!(tipup_db.collection("incidents").find(Some(doc!("maximum_timestamp" => timestamp_gte)), None)) {
+                match bson::from_bson(Bson::Document(try!(document))) {
+                    Ok(incident) => self.incidents.push(incident),
+                    Err(_) => return Err(TipupError::from("failed to parse bson document into incident")),
+                }
+            }
+        }
+
+        //load escalation policies keyed by analyzer class
+        self.escalation_policies.clear();
+        for document in try!(tipup_db.collection("escalation_policies").find(None, None)) {
+            let document = try!(document);
+            let analyzer_class = match document.get("analyzer_class") {
+                Some(&Bson::String(ref analyzer_class)) => analyzer_class.to_owned(),
+                _ => return Err(TipupError::from("failed to parse escalation policy analyzer_class")),
+            };
+
+            let mut stages = Vec::new();
+            if let Some(&Bson::Array(ref stage_array)) = document.get("stages") {
+                for stage in stage_array.iter() {
+                    let stage = match stage {
+                        &Bson::Document(ref stage) => stage,
+                        _ => return Err(TipupError::from(format!("failed to parse escalation stage for class '{}'", analyzer_class))),
+                    };
+
+                    let after = match stage.get("after") {
+                        Some(&Bson::I32(after)) => after as i64,
+                        Some(&Bson::I64(after)) => after,
+                        _ => return Err(TipupError::from(format!("failed to parse escalation stage 'after' for class '{}'", analyzer_class))),
+                    };
+
+                    let status = match stage.get("status") {
+                        Some(&Bson::String(ref status)) => match self.taxonomy.resolve(status) {
+                            Some(status) => status,
+                            None => return Err(TipupError::from(format!("escalation stage status '{}' for class '{}' is not a severity level", status, analyzer_class))),
+                        },
+                        _ => return Err(TipupError::from(format!("failed to parse escalation stage 'status' for class '{}'", analyzer_class))),
+                    };
+
+                    //stages may verify the target from the tipup host before escalating to them
+                    let verify = match stage.get("verify") {
+                        Some(&Bson::String(ref verify)) if verification::METHODS.contains(&verify.as_str()) => Some(verify.to_owned()),
+                        None => None,
+                        _ => return Err(TipupError::from(format!("failed to parse escalation stage 'verify' for class '{}', expected one of {}", analyzer_class, verification::METHODS.join(", ")))),
+                    };
+
+                    stages.push(
+                        EscalationStage {
+                            after: after,
+                            status: status,
+                            verify: verify,
+                        }
+                    );
+                }
+            }
+
+            stages.sort_by_key(|x| x.after);
+            self.escalation_policies.insert(analyzer_class, stages);
         }
+
+        //load unresolved flags so repeated flags merge into them
+        let mut resolved_ids: HashSet<ObjectId> = self.open_flags.values().cloned().collect();
+        self.open_flags.clear();
+        for flag in try!(self.store.find_by_states(&["open", "acknowledged"])) {
+            //restore sink references (e.g. ticket keys) recorded on the flag
+            for route in self.routes.iter_mut() {
+                if let Some(reference) = flag.tickets.get(&route.name) {
+                    route.sink.restore(&flag, reference);
+                }
+            }
+
+            resolved_ids.remove(&flag.id);
+            self.open_flags.insert(flag.key(), flag.id);
+        }
+
+        //notify sinks of flags resolved outside the flag manager, e.g. through the cli or api
+        if resolved_ids.len() > 0 {
+            let resolved_ids: Vec<ObjectId> = resolved_ids.into_iter().collect();
+            for flag in try!(self.store.find_by_ids(&resolved_ids)) {
+                if flag.state == "resolved" {
+                    self.resolve(&flag);
+                }
+            }
+        }
+
+        let open_flags = &self.open_flags;
+        self.unconfirmed_flags.retain(|key, _| open_flags.contains_key(key));
+
+        //load enrichment rules
+        self.enrichment_rules.clear();
+        for document in try!(tipup_db.collection("enrichment_rules").find(None, None)) {
+            let document = try!(document);
+            let selector = match document.get("selector") {
+                Some(&Bson::String(ref selector)) => try!(try!(LabelSelector::parse(selector)).with_levels("status", self.taxonomy.levels())),
+                _ => return Err(TipupError::from("failed to parse enrichment rule selector")),
+            };
+
+            let labels = match document.get("labels") {
+                Some(&Bson::Document(ref labels)) => parse_labels(labels),
+                _ => return Err(TipupError::from("failed to parse enrichment rule labels")),
+            };
+
+            self.enrichment_rules.push(
+                EnrichmentRule {
+                    selector: selector,
+                    labels: labels,
+                }
+            );
+        }
+
+        //load currently active silences
+        self.silences.clear();
+        let now = self.clock.now();
+        let (start_lte, end_gt) = (doc!("$lte" => now), doc!("$gt" => now));
+        let search_document = Some(doc!("start_timestamp" => start_lte, "end_timestamp" => end_gt));
+        for document in try!(tipup_db.collection("silences").find(search_document, None)) {
+            let document = try!(document);
+            match document.get("selector") {
+                Some(&Bson::String(ref selector)) => self.silences.push(try!(try!(LabelSelector::parse(selector)).with_levels("status", self.taxonomy.levels()))),
+                _ => return Err(TipupError::from("failed to parse silence selector")),
+            }
+        }
+
+        //load unexpired (hostname, domain) suppressions
+        self.suppressions.clear();
+        let (expiration_exists, expiration_gt) = (doc!("$exists" => false), doc!("$gt" => now));
+        let search_document = Some(doc!("$or" => [{ "expiration_timestamp" => expiration_exists }, { "expiration_timestamp" => expiration_gt }]));
+        for document in try!(tipup_db.collection("suppressions").find(search_document, None)) {
+            let document = try!(document);
+            let (hostname, domain) = match (document.get("vantage_hostname"), document.get("domain")) {
+                (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+                _ => return Err(TipupError::from("failed to parse suppression vantage_hostname and domain")),
+            };
+
+            let analyzer = match document.get("analyzer") {
+                Some(&Bson::String(ref analyzer)) => Some(analyzer.to_owned()),
+                _ => None,
+            };
+
+            self.suppressions.push(
+                Suppression {
+                    hostname: hostname,
+                    domain: domain,
+                    analyzer: analyzer,
+                }
+            );
+        }
+
+        Ok(())
     }
 
-    pub fn process_flag(&mut self, flag: &Flag, tipup_db: &Database) -> Result<(), TipupError> {
-        //write to database
-        let document: Document = match bson::to_bson(flag) {
-            Ok(Bson::Document(document)) => document,
-            _ => return Err(TipupError::from("failed to parse flag json as Bson::Document")),
+    pub fn process_flag(&mut self, flag: &mut Flag) -> Result<bool, TipupError> {
+        //drop flags for suppressed (hostname, domain) pairs entirely
+        if self.suppressions.iter().any(|x| x.matches(flag)) {
+            return Ok(false);
+        }
+
+        //express the status in the deployment's severity taxonomy
+        match self.taxonomy.resolve(&flag.status) {
+            Some(status) => flag.status = status,
+            None => warn!("flag status '{}' of analyzer '{}' is not a severity level", flag.status, flag.analyzer),
+        }
+
+        //first occurrences bypass notification rate limiting
+        let first_occurrence = try!(self.first_occurrence(flag));
+        self.seen_keys.insert(flag.key());
+
+        //merge into an unresolved flag for the same (analyzer, hostname, domain) if configured, or
+        //while that flag awaits confirmation since confirmation counts on its recurrences
+        let merge = self.merge || self.unconfirmed_flags.contains_key(&flag.key());
+        if let (true, Some(object_id)) = (merge, self.open_flags.get(&flag.key())) {
+            try!(self.store.update(&[object_id.clone()], &FlagUpdate::new().inc("count", 1).max("last_timestamp", flag.timestamp)));
+
+            //notify sinks once an unconfirmed flag has persisted for its confirmation duration
+            let confirmation = self.confirmations.get(&flag.analyzer).cloned().unwrap_or(0);
+            let confirmed = self.unconfirmed_flags.get(&flag.key()).map_or(false, |x| flag.timestamp - x.timestamp >= confirmation);
+            if confirmed {
+                let unconfirmed_flag = self.unconfirmed_flags.remove(&flag.key()).unwrap();
+                info!("confirmed flag {} after {}s", unconfirmed_flag.id, flag.timestamp - unconfirmed_flag.timestamp);
+                lifecycle::notify(&self.lifecycle_webhooks, "opened", &unconfirmed_flag);
+                self.dispatch(&unconfirmed_flag, first_occurrence);
+            }
+
+            return Ok(false);
+        }
+
+        //apply topology labels, analyzer labels, and enrichment rules
+        let topology_labels = match (&self.topology, &flag.hostname) {
+            (&Some(ref topology), &Some(ref hostname)) => topology.labels(hostname),
+            _ => None,
         };
 
-        try!(tipup_db.collection("flags").insert_one(document, None));
+        if let Some(labels) = topology_labels {
+            for (key, value) in labels.iter() {
+                flag.labels.entry(key.to_owned()).or_insert(value.to_owned());
+            }
+        }
+
+        if let Some(labels) = self.analyzer_labels.get(&flag.analyzer) {
+            for (key, value) in labels.iter() {
+                flag.labels.entry(key.to_owned()).or_insert(value.to_owned());
+            }
+        }
+
+        for enrichment_rule in self.enrichment_rules.iter() {
+            if enrichment_rule.selector.matches(&flag.selector_labels()) {
+                for (key, value) in enrichment_rule.labels.iter() {
+                    flag.labels.insert(key.to_owned(), value.to_owned());
+                }
+            }
+        }
+
+        let selector_labels = flag.selector_labels();
+        flag.silenced = self.silences.iter().any(|x| x.matches(&selector_labels));
+
+        //link the flag to the relevant graphs of its target, including any labels applied above
+        if let Some(ref url_template) = self.url_template {
+            flag.url = Some(url_template.render(flag));
+        }
+
+        //store by deterministic id so flags retried after a crash or raced by another instance are
+        //stored and notified once
+        flag.id = flag.deterministic_id(self.id_window);
+        let mut inserted = try!(self.store.insert(flag));
+        if !inserted && try!(self.store.get(&flag.id)).map_or(false, |x| x.state == "resolved") {
+            //a recurrence within the window of a resolved flag is a new flag rather than a retry
+            flag.id = ObjectId::new().unwrap();
+            inserted = try!(self.store.insert(flag));
+        }
+
+        self.open_flags.insert(flag.key(), flag.id.clone());
+        if !inserted {
+            info!("flag {} of analyzer '{}' on domain '{}' was already written", flag.id, flag.analyzer, flag.domain);
+            return Ok(false);
+        }
+
+        //route to matching sinks unless silenced, holding flags back until confirmed
+        if !flag.silenced {
+            match self.confirmations.get(&flag.analyzer) {
+                Some(&confirmation) if confirmation > 0 => {
+                    self.unconfirmed_flags.insert(flag.key(), flag.clone());
+                },
+                _ => {
+                    lifecycle::notify(&self.lifecycle_webhooks, "opened", flag);
+                    self.dispatch(flag, first_occurrence);
+                },
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn seen(&self, flag: &Flag) -> bool {
+        self.seen_keys.contains(&flag.key())
+    }
+
+    //true if no flag has been raised by the analyzer for the (hostname, domain) target
+    pub fn first_occurrence(&mut self, flag: &Flag) -> Result<bool, TipupError> {
+        if self.seen(flag) {
+            return Ok(false);
+        }
+
+        match try!(self.store.exists(&flag.analyzer, flag.hostname.as_ref().map(|x| x.as_str()), &flag.domain)) {
+            true => {
+                self.seen_keys.insert(flag.key());
+                Ok(false)
+            },
+            false => Ok(true),
+        }
+    }
+
+    pub fn deferred_count(&self) -> usize {
+        self.deferred_flags.len()
+    }
+
+    //notify sinks of deferred flags as the notification rate limit allows
+    pub fn flush_deferred(&mut self) {
+        while self.deferred_flags.len() > 0 && self.take_notification_token() {
+            let flag = self.deferred_flags.pop_front().unwrap();
+            self.notify(&flag);
+        }
+
+        if self.deferred_flags.len() > 0 {
+            info!("deferred notification of {} flag(s) by rate limit", self.deferred_flags.len());
+        }
+    }
+
+    //send each coalescing sink an update of the incidents it was notified of, at most once per
+    //its update interval, in place of the incidents' individual flags
+    pub fn flush_incident_updates(&mut self, tipup_db: &Database) {
+        let now = self.clock.now();
+        let incident_ids: HashSet<ObjectId> = self.incidents.iter().map(|x| x.id.clone()).collect();
+        for route in self.routes.iter_mut() {
+            let interval = match route.incident_update_interval {
+                Some(interval) => interval,
+                None => continue,
+            };
+
+            let due_ids: Vec<ObjectId> = {
+                let incident_updated = &route.incident_updated;
+                route.pending_incidents.keys().filter(|x| incident_updated.get(x).map_or(true, |&updated| now - updated >= interval)).cloned().collect()
+            };
+
+            for id in due_ids {
+                let update = match incident_update(tipup_db, &*self.store, &id, route.pending_incidents[&id]) {
+                    Ok(Some(update)) => update,
+                    Ok(None) => {
+                        route.pending_incidents.remove(&id);
+                        continue;
+                    },
+                    Err(e) => {
+                        error!("failed to build update of incident {}: {}", id, e);
+                        continue;
+                    },
+                };
+
+                //failed updates remain pending and are retried on the next flush
+                match route.sink.send_incident(&update) {
+                    Ok(()) => {
+                        self.metrics.increment("tipup_sink_incident_updates_total", &[("sink", &route.name), ("result", "success")], 1.0);
+                        route.pending_incidents.remove(&id);
+                        route.incident_updated.insert(id, now);
+                    },
+                    Err(e) => {
+                        self.metrics.increment("tipup_sink_incident_updates_total", &[("sink", &route.name), ("result", "failure")], 1.0);
+                        error!("failed to send update of incident {} to sink '{}': {}", id, route.name, e);
+                    },
+                }
+            }
+
+            route.incident_updated.retain(|id, _| incident_ids.contains(id));
+        }
+    }
+
+    //the recent incident a flag belongs to, flags raised before the incident manager clusters
+    //them matched by domain and time
+    fn incident_of(&self, flag: &Flag) -> Option<ObjectId> {
+        self.incidents.iter().find(|x| x.flag_ids.contains(&flag.id) || (x.domain == flag.domain
+            && flag.timestamp >= x.minimum_timestamp && flag.timestamp <= x.maximum_timestamp + INCIDENT_WINDOW_SECONDS)).map(|x| x.id.clone())
+    }
+
+    fn dispatch(&mut self, flag: &Flag, bypass_rate_limit: bool) {
+        if bypass_rate_limit || (self.deferred_flags.len() == 0 && self.take_notification_token()) {
+            self.notify(flag);
+        } else {
+            self.deferred_flags.push_back(flag.clone());
+        }
+    }
+
+    fn take_notification_token(&mut self) -> bool {
+        //token bucket refilled at notification_rate per minute, zero disables limiting
+        if self.notification_rate == 0.0 {
+            return true;
+        }
+
+        let now = self.clock.precise_now();
+        let elapsed = now - self.notification_refill_time;
+        self.notification_tokens = (self.notification_tokens + elapsed * self.notification_rate / 60.0).min(self.notification_rate);
+        self.notification_refill_time = now;
+
+        if self.notification_tokens >= 1.0 {
+            self.notification_tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn escalate(&mut self) -> Result<usize, TipupError> {
+        let now = self.clock.now();
+        try!(self.unsnooze(now));
+
+        //record flags resolving without acknowledgement for self healing statistics
+        let quiet_flags = try!(self.resolve_quiet(now));
+        try!(self.store.record_self_healing(&quiet_flags, self.self_healing_window, now));
+
+        //escalate unacknowledged flags through their analyzer class stages
+        let flags = try!(self.store.find_by_states(&["open"]));
+        let mut count = 0;
+        for mut flag in flags {
+            let (level, status, verify) = {
+                let analyzer_class = self.analyzer_classes.get(&flag.analyzer).unwrap_or(&flag.analyzer);
+                let stages = match self.escalation_policies.get(analyzer_class) {
+                    Some(stages) => stages,
+                    None => continue,
+                };
+
+                let level = stages.iter().filter(|x| now - flag.timestamp >= x.after).count();
+                if level as i32 <= flag.escalation_level {
+                    continue;
+                }
+
+                (level, stages[level - 1].status.clone(), stages[level - 1].verify.clone())
+            };
+
+            //hold escalation of targets reachable from the tipup host, re-verifying them on later
+            //escalations until they resolve or the probe fails
+            if let Some(method) = verify {
+                let verification = try!(verification::verify(&method, &flag.domain));
+                let outcome = match verification.reachable {
+                    true => "reachable",
+                    false => "unreachable",
+                };
+
+                flag.evidence.insert("verification".to_owned(), verification.method.clone());
+                flag.evidence.insert("verification_result".to_owned(), outcome.to_owned());
+                flag.evidence.insert("verification_detail".to_owned(), verification.detail.clone());
+                flag.evidence.insert("verification_timestamp".to_owned(), now.to_string());
+                let update = FlagUpdate::new()
+                    .set("evidence.verification", verification.method.clone())
+                    .set("evidence.verification_result", outcome)
+                    .set("evidence.verification_detail", verification.detail.clone())
+                    .set("evidence.verification_timestamp", now.to_string());
+                try!(self.store.update(&[flag.id.clone()], &update));
+
+                self.metrics.increment("tipup_escalation_verifications_total", &[("result", outcome)], 1.0);
+                if verification.reachable {
+                    info!("held escalation of flag {} to '{}', target '{}' is reachable: {}", flag.id, status, flag.domain, verification.detail);
+                    continue;
+                }
+            }
+
+            let escalation_document = doc!(
+                "timestamp" => now,
+                "previous_status" => (flag.status.clone()),
+                "status" => (status.clone())
+            );
+            let update = FlagUpdate::new()
+                .set("status", status.clone())
+                .set("escalation_level", level as i32)
+                .push("escalations", escalation_document);
+            try!(self.store.update(&[flag.id.clone()], &update));
+
+            //re-notify sinks with the escalated status unless snoozed
+            info!("escalated flag {} from '{}' to '{}'", flag.id, flag.status, status);
+            flag.status = status;
+            flag.escalation_level = level as i32;
+            if !self.unconfirmed_flags.contains_key(&flag.key()) {
+                lifecycle::notify(&self.lifecycle_webhooks, "escalated", &flag);
+                if !flag.silenced && flag.snooze.is_none() {
+                    self.dispatch(&flag, false);
+                }
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    //resolves flags which have not recurred within the resolve timeout, returning them
+    fn resolve_quiet(&mut self, now: i64) -> Result<Vec<Flag>, TipupError> {
+        let resolve_timeout = self.resolve_timeout;
+        if resolve_timeout == 0 {
+            return Ok(Vec::new());
+        }
+
+        let quiet_flags: Vec<Flag> = try!(self.store.find_by_states(&["open", "acknowledged"])).into_iter()
+            .filter(|x| x.last_timestamp < now - resolve_timeout).collect();
+        if quiet_flags.len() == 0 {
+            return Ok(quiet_flags);
+        }
+
+        let quiet_ids: Vec<ObjectId> = quiet_flags.iter().map(|x| x.id.clone()).collect();
+        let count = try!(self.store.update(&quiet_ids, &FlagUpdate::new().set("state", "resolved").set("resolved_timestamp", now)));
+        info!("resolved {} quiet flag(s)", count);
+
+        for flag in quiet_flags.iter() {
+            if self_healing::is_self_healed(flag, self.self_healing_window) {
+                self.metrics.increment("tipup_flags_self_healed_total", &[("analyzer", &flag.analyzer)], 1.0);
+            }
+
+            self.open_flags.remove(&flag.key());
+            self.resolve(flag);
+
+            //flags resolving before confirmation were never opened to lifecycle webhooks
+            if self.unconfirmed_flags.remove(&flag.key()).is_none() {
+                let mut flag = flag.clone();
+                flag.state = "resolved".to_owned();
+                lifecycle::notify(&self.lifecycle_webhooks, "resolved", &flag);
+            }
+        }
+
+        Ok(quiet_flags)
+    }
+
+    fn unsnooze(&mut self, now: i64) -> Result<(), TipupError> {
+        for mut flag in try!(self.store.find_by_states(&["open", "acknowledged"])) {
+            let expired = match flag.snooze {
+                Some(ref snooze) => try!(snooze_expired(&*self.store, &flag, snooze, now)),
+                None => continue,
+            };
+
+            if !expired {
+                continue;
+            }
+
+            try!(self.store.update(&[flag.id.clone()], &FlagUpdate::new().unset("snooze").set("unsnoozed_timestamp", now)));
+
+            //resume notifications with the current flag status
+            info!("unsnoozed flag {}", flag.id);
+            flag.snooze = None;
+            if !flag.silenced {
+                self.dispatch(&flag, false);
+            }
+        }
 
         Ok(())
     }
+
+    fn notify(&mut self, flag: &Flag) {
+        let selector_labels = flag.selector_labels();
+        let incident_id = self.incident_of(flag);
+        for route in self.routes.iter_mut() {
+            if !route.selector.matches(&selector_labels) {
+                continue;
+            }
+
+            //coalesce flags of an incident into its next update
+            if let (Some(_), Some(incident_id)) = (route.incident_update_interval, incident_id.as_ref()) {
+                *route.pending_incidents.entry(incident_id.clone()).or_insert(0) += 1;
+                continue;
+            }
+
+            //dead-letter flags which exhaust their delivery attempts so they can be retried
+            if let Err(e) = sink::deliver(&route.name, &mut *route.sink, flag, &self.metrics) {
+                error!("failed to send flag to sink '{}', writing dead letter: {}", route.name, e);
+                self.metrics.increment("tipup_sink_dead_letters_total", &[("sink", &route.name)], 1.0);
+                if let Err(e) = self.store.dead_letter(&route.name, flag, &e) {
+                    error!("failed to write dead letter for sink '{}': {}", route.name, e);
+                }
+
+                continue;
+            }
+
+            //link the flag document to references the sink created for it
+            if let Some(reference) = route.sink.reference(flag) {
+                let update = FlagUpdate::new().set(&format!("tickets.{}", route.name), reference);
+                if let Err(e) = self.store.update(&[flag.id.clone()], &update) {
+                    error!("failed to record sink '{}' reference on flag {}: {}", route.name, flag.id, e);
+                }
+            }
+        }
+    }
+
+    fn resolve(&mut self, flag: &Flag) {
+        let selector_labels = flag.selector_labels();
+        let incident_id = self.incident_of(flag);
+        for route in self.routes.iter_mut().filter(|x| x.selector.matches(&selector_labels)) {
+            //the incident's next update reports the reduced unresolved count
+            if let (Some(_), Some(incident_id)) = (route.incident_update_interval, incident_id.as_ref()) {
+                route.pending_incidents.entry(incident_id.clone()).or_insert(0);
+                continue;
+            }
+
+            if let Err(e) = route.sink.resolve_flag(flag) {
+                error!("failed to notify sink '{}' of resolved flag {}: {}", route.name, flag.id, e);
+            }
+        }
+    }
+}
+
+impl Evictable for FlagManager {
+    fn memory_usage(&self) -> usize {
+        self.seen_keys.iter().map(|&(ref analyzer, ref hostname, ref domain)| {
+            resources::key_size(&[analyzer, hostname.as_ref().map_or("", |x| x.as_str()), domain])
+        }).sum()
+    }
+
+    fn evict(&mut self, _bytes: usize) -> usize {
+        //seen keys carry no recency, evicted keys fall back to the flags collection lookup
+        let count = self.seen_keys.len();
+        self.seen_keys.clear();
+        count
+    }
+}
+
+fn incident_update(tipup_db: &Database, store: &FlagStore, id: &ObjectId, new_flags: i64) -> Result<Option<IncidentUpdate>, TipupError> {
+    let incident: Incident = match try!(tipup_db.collection("incidents").find_one(Some(doc!("_id" => (id.clone()))), None)) {
+        Some(document) => match bson::from_bson(Bson::Document(document)) {
+            Ok(incident) => incident,
+            Err(_) => return Err(TipupError::from("failed to parse bson document into incident")),
+        },
+        None => return Ok(None),
+    };
+
+    let (mut flags, mut unresolved_flags) = (0, 0);
+    let mut statuses = BTreeMap::new();
+    let flag_ids: Vec<ObjectId> = incident.flag_ids.iter().cloned().collect();
+    for flag in try!(store.find_by_ids(&flag_ids)) {
+        flags += 1;
+        if flag.state != "resolved" {
+            unresolved_flags += 1;
+        }
+
+        *statuses.entry(flag.status).or_insert(0) += 1;
+    }
+
+    let mut hostnames: Vec<String> = incident.hostnames.into_iter().collect();
+    hostnames.sort();
+    Ok(Some(
+        IncidentUpdate {
+            id: incident.id,
+            domain: incident.domain,
+            minimum_timestamp: incident.minimum_timestamp,
+            maximum_timestamp: incident.maximum_timestamp,
+            hostnames: hostnames,
+            flags: flags,
+            unresolved_flags: unresolved_flags,
+            new_flags: new_flags,
+            statuses: statuses,
+        }
+    ))
+}
+
+pub fn set_state(store: &FlagStore, tipup_db: &Database, id: &str, state: &str) -> Result<bool, TipupError> {
+    //transition a flag to 'acknowledged' or 'resolved'
+    let object_id = match ObjectId::with_string(id) {
+        Ok(object_id) => object_id,
+        Err(_) => return Err(TipupError::from(format!("invalid flag id '{}'", id))),
+    };
+
+    let now = time::now_utc().to_timespec().sec;
+    let timestamp_field = format!("{}_timestamp", state);
+    if try!(store.update(&[object_id.clone()], &FlagUpdate::new().set("state", state).set(&timestamp_field, now))) == 0 {
+        return Ok(false);
+    }
+
+    //notify lifecycle webhooks of the transition
+    let transition = match state {
+        "resolved" => "resolved",
+        _ => "acknowledged",
+    };
+
+    if let Some(flag) = try!(store.get(&object_id)) {
+        lifecycle::notify(&try!(LifecycleWebhook::load(tipup_db)), transition, &flag);
+    }
+
+    Ok(true)
+}
+
+pub fn snooze(store: &FlagStore, id: &str, until_timestamp: Option<i64>, condition: Option<(Vec<String>, f64)>) -> Result<bool, TipupError> {
+    //snooze notifications for a flag until a timestamp and/or until a field increases past its current value
+    let object_id = match ObjectId::with_string(id) {
+        Ok(object_id) => object_id,
+        Err(_) => return Err(TipupError::from(format!("invalid flag id '{}'", id))),
+    };
+
+    let flag = match try!(store.get(&object_id)) {
+        Some(flag) => flag,
+        None => return Ok(false),
+    };
+
+    let mut snooze = Snooze {
+        until_timestamp: until_timestamp,
+        field: None,
+        baseline: None,
+        increase: None,
+    };
+
+    if let Some((field, increase)) = condition {
+        match try!(store.latest_value(&flag, &field)) {
+            Some(baseline) => snooze.baseline = Some(baseline),
+            None => return Err(TipupError::from(format!("no recent measurement of field '{}' for flag '{}'", field.join("."), id))),
+        }
+
+        snooze.field = Some(field);
+        snooze.increase = Some(increase);
+    }
+
+    let snooze_document = match bson::to_bson(&snooze) {
+        Ok(snooze_document) => snooze_document,
+        Err(_) => return Err(TipupError::from("failed to parse snooze as Bson")),
+    };
+
+    try!(store.update(&[object_id], &FlagUpdate::new().set("snooze", snooze_document)));
+    Ok(true)
+}
+
+fn snooze_expired(store: &FlagStore, flag: &Flag, snooze: &Snooze, now: i64) -> Result<bool, TipupError> {
+    if snooze.until_timestamp.map_or(false, |x| now >= x) {
+        return Ok(true);
+    }
+
+    match (&snooze.field, snooze.baseline, snooze.increase) {
+        (&Some(ref field), Some(baseline), Some(increase)) => {
+            let value = try!(store.latest_value(flag, field));
+            Ok(value.map_or(false, |x| x >= baseline + increase))
+        },
+        _ => Ok(false),
+    }
+}
+
+fn parse_labels(document: &Document) -> HashMap<String, String> {
+    let mut labels = HashMap::new();
+    for (key, value) in document.iter() {
+        match value {
+            &Bson::String(ref value) => labels.insert(key.to_owned(), value.to_owned()),
+            value => labels.insert(key.to_owned(), value.to_string()),
+        };
+    }
+
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{self, Bson};
+    use bson::oid::ObjectId;
+
+    use clock::{Clock, MockClock};
+    use flag_store::{FlagStore, FlagUpdate, MemoryFlagStore};
+    use metrics::Metrics;
+    use severity::SeverityTaxonomy;
+
+    use super::{snooze, EscalationStage, Flag, FlagManager};
+
+    use std::sync::Arc;
+
+    fn flag_manager(store: Arc<FlagStore>, clock: Arc<MockClock>) -> FlagManager {
+        let taxonomy = SeverityTaxonomy::new(&Vec::new(), &Vec::new()).unwrap();
+        FlagManager::new(3600, true, 300, 3600, 0, taxonomy, None, store, Metrics::new(), clock)
+    }
+
+    #[test]
+    fn recurrence_after_resolution_opens_new_flag() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let mut flag_manager = flag_manager(store.clone(), Arc::new(MockClock::new(1000)));
+        let mut flag = Flag::for_domain("example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut flag).unwrap());
+
+        //resolve the flag as escalate does, then recur within its id window
+        store.update(&[flag.id.clone()], &FlagUpdate::new().set("state", "resolved")).unwrap();
+        flag_manager.open_flags.remove(&flag.key());
+        let mut recurrence = Flag::for_domain("example.com", 1100, "error", "latency");
+        assert!(flag_manager.process_flag(&mut recurrence).unwrap());
+        assert!(recurrence.id != flag.id);
+        assert_eq!(flag_manager.open_flags.get(&recurrence.key()), Some(&recurrence.id));
+        assert_eq!(store.find_by_states(&["open"]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parses_legacy_flag_documents() {
+        let document = doc!("_id" => (ObjectId::new().unwrap()), "measurement_id" => (ObjectId::new().unwrap()), "status" => "warning", "analyzer" => "latency");
+        let flag: Flag = bson::from_bson(Bson::Document(document)).unwrap();
+        assert_eq!(flag.timestamp, 0);
+        assert_eq!(flag.hostname, None);
+        assert_eq!(flag.domain, "");
+        assert_eq!(flag.state, "open");
+        assert_eq!(flag.count, 1);
+    }
+
+    #[test]
+    fn recurrences_merge_into_open_flag() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let mut flag_manager = flag_manager(store.clone(), Arc::new(MockClock::new(1000)));
+        let mut flag = Flag::for_domain("example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut flag).unwrap());
+
+        //a recurrence past the id window still merges while the flag is unresolved
+        let mut recurrence = Flag::for_domain("example.com", 1400, "error", "latency");
+        assert!(!flag_manager.process_flag(&mut recurrence).unwrap());
+        let flags = store.find_by_states(&["open"]).unwrap();
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].id, flag.id);
+        assert_eq!(flags[0].count, 2);
+        assert_eq!(flags[0].last_timestamp, 1400);
+    }
+
+    #[test]
+    fn acknowledged_flags_are_not_escalated() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        flag_manager.escalation_policies.insert("latency".to_owned(), vec!(EscalationStage { after: 60, status: "critical".to_owned(), verify: None }));
+
+        let mut acknowledged = Flag::for_domain("a.example.com", 1000, "error", "latency");
+        let mut open = Flag::for_domain("b.example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut acknowledged).unwrap());
+        assert!(flag_manager.process_flag(&mut open).unwrap());
+        store.update(&[acknowledged.id.clone()], &FlagUpdate::new().set("state", "acknowledged")).unwrap();
+
+        clock.step(60.0);
+        assert_eq!(flag_manager.escalate().unwrap(), 1);
+        assert_eq!(store.get(&acknowledged.id).unwrap().unwrap().status, "error");
+        assert_eq!(store.get(&open.id).unwrap().unwrap().status, "critical");
+
+        //acknowledged flags still absorb recurrences
+        let mut recurrence = Flag::for_domain("a.example.com", 1060, "error", "latency");
+        assert!(!flag_manager.process_flag(&mut recurrence).unwrap());
+        assert_eq!(store.get(&acknowledged.id).unwrap().unwrap().count, 2);
+    }
+
+    #[test]
+    fn snoozed_flags_unsnooze_at_their_timestamp() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        let mut flag = Flag::for_domain("example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut flag).unwrap());
+        assert!(snooze(&*store, &flag.id.to_hex(), Some(1600), None).unwrap());
+
+        clock.step(300.0);
+        flag_manager.escalate().unwrap();
+        assert_eq!(store.get(&flag.id).unwrap().unwrap().snooze.and_then(|x| x.until_timestamp), Some(1600));
+
+        clock.step(300.0);
+        flag_manager.escalate().unwrap();
+        assert!(store.get(&flag.id).unwrap().unwrap().snooze.is_none());
+    }
+
+    #[test]
+    fn snoozed_flags_unsnooze_when_field_increases() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        let mut flag = Flag::for_domain("example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut flag).unwrap());
+
+        let field = vec!("result".to_owned(), "errors".to_owned());
+        assert!(snooze(&*store, &flag.id.to_hex(), None, Some((field.clone(), 5.0))).is_err());
+        store.add_measurement(doc!("measurement_domain" => "example.com", "timestamp" => 1000i64, "result" => { "errors" => 2.0 }));
+        assert!(snooze(&*store, &flag.id.to_hex(), None, Some((field, 5.0))).unwrap());
+
+        store.add_measurement(doc!("measurement_domain" => "example.com", "timestamp" => 1060i64, "result" => { "errors" => 6.0 }));
+        flag_manager.escalate().unwrap();
+        assert!(store.get(&flag.id).unwrap().unwrap().snooze.is_some());
+
+        store.add_measurement(doc!("measurement_domain" => "example.com", "timestamp" => 1120i64, "result" => { "errors" => 7.0 }));
+        flag_manager.escalate().unwrap();
+        assert!(store.get(&flag.id).unwrap().unwrap().snooze.is_none());
+    }
+
+    #[test]
+    fn escalation_records_self_healing_of_quiet_flags() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        let mut healed = Flag::for_domain("a.example.com", 1000, "error", "latency");
+        let mut acknowledged = Flag::for_domain("b.example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut healed).unwrap());
+        assert!(flag_manager.process_flag(&mut acknowledged).unwrap());
+        store.update(&[acknowledged.id.clone()], &FlagUpdate::new().set("state", "acknowledged")).unwrap();
+
+        clock.step(3601.0);
+        flag_manager.escalate().unwrap();
+        assert_eq!(store.self_healing.lock().unwrap().get("latency"), Some(&(2, 1)));
+    }
+
+    #[test]
+    fn quiet_flags_resolve_after_timeout() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        let mut flag = Flag::for_domain("example.com", 1000, "error", "latency");
+        assert!(flag_manager.process_flag(&mut flag).unwrap());
+
+        clock.step(3600.0);
+        assert_eq!(flag_manager.resolve_quiet(clock.now()).unwrap().len(), 0);
+        clock.step(1.0);
+        let quiet_flags = flag_manager.resolve_quiet(clock.now()).unwrap();
+        assert_eq!(quiet_flags.len(), 1);
+        assert_eq!(quiet_flags[0].id, flag.id);
+        assert_eq!(store.get(&flag.id).unwrap().unwrap().state, "resolved");
+        assert!(flag_manager.open_flags.is_empty());
+    }
+
+    #[test]
+    fn recurrences_store_new_flags_without_merge_or_resolution() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        flag_manager.merge = false;
+        flag_manager.resolve_timeout = 0;
+
+        let mut flag = Flag::for_domain("example.com", 1000, "error", "latency");
+        let mut recurrence = Flag::for_domain("example.com", 1400, "error", "latency");
+        assert!(flag_manager.process_flag(&mut flag).unwrap());
+        assert!(flag_manager.process_flag(&mut recurrence).unwrap());
+        assert_eq!(store.find_by_states(&["open"]).unwrap().len(), 2);
+
+        clock.step(86400.0);
+        assert_eq!(flag_manager.resolve_quiet(clock.now()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn deduplicates_flags_within_id_window() {
+        let store = Arc::new(MemoryFlagStore::new());
+        let clock = Arc::new(MockClock::new(1000));
+        let mut flag_manager = flag_manager(store.clone(), clock.clone());
+        flag_manager.merge = false;
+
+        //flags of a target are stored once per 300 second id window
+        for &(step, stored) in [(0.0, true), (150.0, false), (49.0, false), (1.0, true)].iter() {
+            clock.step(step);
+            let mut flag = Flag::for_domain("example.com", clock.now(), "error", "latency");
+            assert_eq!(flag_manager.process_flag(&mut flag).unwrap(), stored);
+        }
+
+        assert_eq!(store.find_by_states(&["open"]).unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,461 @@
+use bson::{self, Bson, Document};
+use bson::oid::ObjectId;
+use mongodb::ClientInner;
+use mongodb::coll::options::{FindOptions, UpdateOptions};
+use mongodb::db::{Database, ThreadedDatabase};
+
+use analyzer::get_value;
+use error::TipupError;
+use flag_manager::Flag;
+use self_healing;
+use sink;
+
+#[cfg(test)]
+use std::collections::HashMap;
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+
+pub const BACKENDS: [&'static str; 1] = ["mongodb"];
+
+//changes to stored flags in the subset of mongodb update operators every backend applies, keys
+//of nested fields being dotted like 'evidence.verification'
+pub struct FlagUpdate {
+    set: Document,
+    unset: Vec<String>,
+    push: Document,
+    inc: Document,
+    max: Document,
+}
+
+impl FlagUpdate {
+    pub fn new() -> FlagUpdate {
+        FlagUpdate {
+            set: Document::new(),
+            unset: Vec::new(),
+            push: Document::new(),
+            inc: Document::new(),
+            max: Document::new(),
+        }
+    }
+
+    pub fn set<T: Into<Bson>>(mut self, key: &str, value: T) -> FlagUpdate {
+        self.set.insert(key, value.into());
+        self
+    }
+
+    pub fn unset(mut self, key: &str) -> FlagUpdate {
+        self.unset.push(key.to_owned());
+        self
+    }
+
+    pub fn push<T: Into<Bson>>(mut self, key: &str, value: T) -> FlagUpdate {
+        self.push.insert(key, value.into());
+        self
+    }
+
+    pub fn inc(mut self, key: &str, value: i32) -> FlagUpdate {
+        self.inc.insert(key, value);
+        self
+    }
+
+    pub fn max(mut self, key: &str, value: i64) -> FlagUpdate {
+        self.max.insert(key, value);
+        self
+    }
+
+    fn to_document(&self) -> Document {
+        let mut document = Document::new();
+        let unset: Document = self.unset.iter().map(|x| (x.to_owned(), Bson::String(String::new()))).collect();
+        for &(operator, ref operands) in [("$set", &self.set), ("$unset", &unset), ("$push", &self.push), ("$inc", &self.inc), ("$max", &self.max)].iter() {
+            if operands.len() > 0 {
+                document.insert(operator, (*operands).clone());
+            }
+        }
+
+        document
+    }
+}
+
+//storage of flags through their lifecycle, shared by the flag manager, api, and cli so backends
+//other than mongodb, e.g. the in-memory store of tests, can hold them. the store also takes the
+//flag manager's dead letters and self healing counts and serves the measurement values snoozes
+//wait on, so the lifecycle runs against a store alone. reports and aggregations
+//over flag history query the flags collection directly and so assume the mongodb store, namely
+//incident clustering, the flag feed, kpis, incident timelines, canary comparisons, the noise
+//report, adaptive thresholds, and the analyzer, compare-hosts, and top commands
+pub trait FlagStore: Send + Sync {
+    fn get(&self, id: &ObjectId) -> Result<Option<Flag>, TipupError>;
+    fn find_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Flag>, TipupError>;
+    fn find_by_states(&self, states: &[&str]) -> Result<Vec<Flag>, TipupError>;
+    fn find_by_target(&self, analyzer: &str, domain: &str) -> Result<Vec<Flag>, TipupError>;
+    fn find_with_feedback(&self, analyzer: &str) -> Result<Vec<Flag>, TipupError>;
+    fn exists(&self, analyzer: &str, hostname: Option<&str>, domain: &str) -> Result<bool, TipupError>;
+
+    //stores the flag unless one with its id is stored, returning whether it was
+    fn insert(&self, flag: &Flag) -> Result<bool, TipupError>;
+
+    //applies the update to the stored flags of the ids, returning how many were found
+    fn update(&self, ids: &[ObjectId], update: &FlagUpdate) -> Result<usize, TipupError>;
+
+    //records a flag which exhausted its delivery attempts to the sink for a later retry
+    fn dead_letter(&self, sink: &str, flag: &Flag, error: &TipupError) -> Result<(), TipupError>;
+
+    //counts the resolved flags of each analyzer, and those of them which healed on their own
+    fn record_self_healing(&self, flags: &[Flag], window: i64, timestamp: i64) -> Result<(), TipupError>;
+
+    //the field of the most recent measurement behind the flag
+    fn latest_value(&self, flag: &Flag, field: &Vec<String>) -> Result<Option<f64>, TipupError>;
+}
+
+pub fn create(backend: &str, client: Arc<ClientInner>, username: &str, password: &str) -> Result<Arc<FlagStore>, TipupError> {
+    match backend {
+        "mongodb" => Ok(Arc::new(MongoFlagStore::new(client, username, password))),
+        _ => Err(TipupError::from(format!("unknown flag store '{}', expected one of {}", backend, BACKENDS.join(", ")))),
+    }
+}
+
+//the flags collection, authenticating per operation as the api does
+pub struct MongoFlagStore {
+    client: Arc<ClientInner>,
+    username: String,
+    password: String,
+}
+
+impl MongoFlagStore {
+    pub fn new(client: Arc<ClientInner>, username: &str, password: &str) -> MongoFlagStore {
+        MongoFlagStore {
+            client: client,
+            username: username.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    fn db(&self) -> Result<Database, TipupError> {
+        ::initialize_db(&self.client, "proddle", &self.username, &self.password)
+    }
+
+    fn find(&self, search_document: Document) -> Result<Vec<Flag>, TipupError> {
+        let mut flags = Vec::new();
+        for document in try!(try!(self.db()).collection("flags").find(Some(search_document), None)) {
+            flags.push(try!(parse_flag(try!(document))));
+        }
+
+        Ok(flags)
+    }
+}
+
+impl FlagStore for MongoFlagStore {
+    fn get(&self, id: &ObjectId) -> Result<Option<Flag>, TipupError> {
+        match try!(try!(self.db()).collection("flags").find_one(Some(doc!("_id" => (id.clone()))), None)) {
+            Some(document) => parse_flag(document).map(|x| Some(x)),
+            None => Ok(None),
+        }
+    }
+
+    fn find_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Flag>, TipupError> {
+        let id_in = doc!("$in" => (ids.iter().map(|x| Bson::ObjectId(x.clone())).collect::<Vec<Bson>>()));
+        self.find(doc!("_id" => id_in))
+    }
+
+    fn find_by_states(&self, states: &[&str]) -> Result<Vec<Flag>, TipupError> {
+        let state_in = doc!("$in" => (states.iter().map(|x| Bson::String((*x).to_owned())).collect::<Vec<Bson>>()));
+        self.find(doc!("state" => state_in))
+    }
+
+    fn find_by_target(&self, analyzer: &str, domain: &str) -> Result<Vec<Flag>, TipupError> {
+        self.find(doc!("analyzer" => analyzer, "domain" => domain))
+    }
+
+    fn find_with_feedback(&self, analyzer: &str) -> Result<Vec<Flag>, TipupError> {
+        self.find(doc!("analyzer" => analyzer, "feedback" => { "$exists" => true }))
+    }
+
+    fn exists(&self, analyzer: &str, hostname: Option<&str>, domain: &str) -> Result<bool, TipupError> {
+        let hostname = match hostname {
+            Some(hostname) => Bson::String(hostname.to_owned()),
+            None => Bson::Null,
+        };
+
+        let search_document = doc!("analyzer" => analyzer, "hostname" => hostname, "domain" => domain);
+        Ok(try!(try!(self.db()).collection("flags").find_one(Some(search_document), None)).is_some())
+    }
+
+    fn insert(&self, flag: &Flag) -> Result<bool, TipupError> {
+        //upsert by id so flags raced by another instance are stored once
+        let mut document = try!(to_document(flag));
+        document.remove("_id");
+        let mut update_options = UpdateOptions::new();
+        update_options.upsert = Some(true);
+        let result = try!(try!(self.db()).collection("flags").update_one(doc!("_id" => (flag.id.clone())), doc!("$setOnInsert" => document), Some(update_options)));
+        Ok(result.upserted_id.is_some())
+    }
+
+    fn update(&self, ids: &[ObjectId], update: &FlagUpdate) -> Result<usize, TipupError> {
+        let id_in = doc!("$in" => (ids.iter().map(|x| Bson::ObjectId(x.clone())).collect::<Vec<Bson>>()));
+        let result = try!(try!(self.db()).collection("flags").update_many(doc!("_id" => id_in), update.to_document(), None));
+        Ok(result.matched_count as usize)
+    }
+
+    fn dead_letter(&self, sink: &str, flag: &Flag, error: &TipupError) -> Result<(), TipupError> {
+        sink::dead_letter(&try!(self.db()), sink, flag, error)
+    }
+
+    fn record_self_healing(&self, flags: &[Flag], window: i64, timestamp: i64) -> Result<(), TipupError> {
+        self_healing::record(&try!(self.db()), flags, window, timestamp)
+    }
+
+    fn latest_value(&self, flag: &Flag, field: &Vec<String>) -> Result<Option<f64>, TipupError> {
+        let mut search_document = doc!("measurement_domain" => (flag.domain.clone()));
+        if let Some(ref hostname) = flag.hostname {
+            search_document.insert("vantage_hostname", hostname.to_owned());
+        }
+
+        if let Some(measurement_class) = flag.labels.get("measurement_class") {
+            search_document.insert("measurement_class", measurement_class.to_owned());
+        }
+
+        let negative_one = -1;
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("timestamp" => negative_one));
+        match try!(try!(self.db()).collection("measurements").find_one(Some(search_document), Some(find_options))) {
+            Some(document) => Ok(get_value(field, &document)),
+            None => Ok(None),
+        }
+    }
+}
+
+//flags held in process for tests of the flag lifecycle; documents are stored rather than flags so
+//fields outside the flag struct, e.g. feedback and escalations, are kept as mongodb would
+#[cfg(test)]
+pub struct MemoryFlagStore {
+    documents: Mutex<HashMap<ObjectId, Document>>,
+    measurements: Mutex<Vec<Document>>,
+    pub dead_letters: Mutex<Vec<(String, Flag)>>,
+    pub self_healing: Mutex<HashMap<String, (i64, i64)>>,
+}
+
+#[cfg(test)]
+impl MemoryFlagStore {
+    pub fn new() -> MemoryFlagStore {
+        MemoryFlagStore {
+            documents: Mutex::new(HashMap::new()),
+            measurements: Mutex::new(Vec::new()),
+            dead_letters: Mutex::new(Vec::new()),
+            self_healing: Mutex::new(HashMap::new()),
+        }
+    }
+
+    //makes a measurement document available to latest_value
+    pub fn add_measurement(&self, document: Document) {
+        self.measurements.lock().unwrap().push(document);
+    }
+
+    fn find<F>(&self, predicate: F) -> Result<Vec<Flag>, TipupError> where F: Fn(&Flag) -> bool {
+        let documents = match self.documents.lock() {
+            Ok(documents) => documents,
+            Err(_) => return Err(TipupError::from("failed to lock in-memory flag store")),
+        };
+
+        let mut flags = Vec::new();
+        for document in documents.values() {
+            let flag = try!(parse_flag(document.clone()));
+            if predicate(&flag) {
+                flags.push(flag);
+            }
+        }
+
+        flags.sort_by_key(|x| x.timestamp);
+        Ok(flags)
+    }
+}
+
+#[cfg(test)]
+impl FlagStore for MemoryFlagStore {
+    fn get(&self, id: &ObjectId) -> Result<Option<Flag>, TipupError> {
+        Ok(try!(self.find(|x| x.id == *id)).pop())
+    }
+
+    fn find_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Flag>, TipupError> {
+        self.find(|x| ids.contains(&x.id))
+    }
+
+    fn find_by_states(&self, states: &[&str]) -> Result<Vec<Flag>, TipupError> {
+        self.find(|x| states.contains(&x.state.as_str()))
+    }
+
+    fn find_by_target(&self, analyzer: &str, domain: &str) -> Result<Vec<Flag>, TipupError> {
+        self.find(|x| x.analyzer == analyzer && x.domain == domain)
+    }
+
+    fn find_with_feedback(&self, analyzer: &str) -> Result<Vec<Flag>, TipupError> {
+        self.find(|x| x.analyzer == analyzer && x.feedback.is_some())
+    }
+
+    fn exists(&self, analyzer: &str, hostname: Option<&str>, domain: &str) -> Result<bool, TipupError> {
+        Ok(try!(self.find(|x| x.analyzer == analyzer && x.hostname.as_ref().map(|x| x.as_str()) == hostname && x.domain == domain)).len() > 0)
+    }
+
+    fn insert(&self, flag: &Flag) -> Result<bool, TipupError> {
+        let document = try!(to_document(flag));
+        let mut documents = match self.documents.lock() {
+            Ok(documents) => documents,
+            Err(_) => return Err(TipupError::from("failed to lock in-memory flag store")),
+        };
+
+        if documents.contains_key(&flag.id) {
+            return Ok(false);
+        }
+
+        documents.insert(flag.id.clone(), document);
+        Ok(true)
+    }
+
+    fn update(&self, ids: &[ObjectId], update: &FlagUpdate) -> Result<usize, TipupError> {
+        let mut documents = match self.documents.lock() {
+            Ok(documents) => documents,
+            Err(_) => return Err(TipupError::from("failed to lock in-memory flag store")),
+        };
+
+        let mut count = 0;
+        for id in ids.iter() {
+            let document = match documents.get_mut(id) {
+                Some(document) => document,
+                None => continue,
+            };
+
+            for (key, value) in update.set.iter() {
+                *try!(field(document, key)) = value.clone();
+            }
+
+            for key in update.unset.iter() {
+                remove_field(document, key);
+            }
+
+            for (key, value) in update.push.iter() {
+                let field = try!(field(document, key));
+                match field {
+                    &mut Bson::Array(ref mut array) => array.push(value.clone()),
+                    &mut Bson::Null => *field = Bson::Array(vec!(value.clone())),
+                    _ => return Err(TipupError::from(format!("failed to push to non-array flag field '{}'", key))),
+                }
+            }
+
+            for (key, value) in update.inc.iter() {
+                let field = try!(field(document, key));
+                *field = match (&*field, value) {
+                    (&Bson::I32(current), &Bson::I32(increment)) => Bson::I32(current + increment),
+                    (&Bson::Null, &Bson::I32(increment)) => Bson::I32(increment),
+                    _ => return Err(TipupError::from(format!("failed to increment non-integer flag field '{}'", key))),
+                };
+            }
+
+            for (key, value) in update.max.iter() {
+                let field = try!(field(document, key));
+                *field = match (&*field, value) {
+                    (&Bson::I64(current), &Bson::I64(maximum)) => Bson::I64(current.max(maximum)),
+                    (&Bson::Null, &Bson::I64(maximum)) => Bson::I64(maximum),
+                    _ => return Err(TipupError::from(format!("failed to take maximum of non-integer flag field '{}'", key))),
+                };
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn dead_letter(&self, sink: &str, flag: &Flag, _error: &TipupError) -> Result<(), TipupError> {
+        match self.dead_letters.lock() {
+            Ok(mut dead_letters) => dead_letters.push((sink.to_owned(), flag.clone())),
+            Err(_) => return Err(TipupError::from("failed to lock in-memory dead letters")),
+        }
+
+        Ok(())
+    }
+
+    fn record_self_healing(&self, flags: &[Flag], window: i64, _timestamp: i64) -> Result<(), TipupError> {
+        let mut counts = match self.self_healing.lock() {
+            Ok(counts) => counts,
+            Err(_) => return Err(TipupError::from("failed to lock in-memory self healing counts")),
+        };
+
+        for flag in flags.iter() {
+            let count = counts.entry(flag.analyzer.clone()).or_insert((0, 0));
+            count.0 += 1;
+            if self_healing::is_self_healed(flag, window) {
+                count.1 += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn latest_value(&self, flag: &Flag, field: &Vec<String>) -> Result<Option<f64>, TipupError> {
+        let measurements = match self.measurements.lock() {
+            Ok(measurements) => measurements,
+            Err(_) => return Err(TipupError::from("failed to lock in-memory measurements")),
+        };
+
+        let matches = |document: &&Document| {
+            document.get_str("measurement_domain").ok() == Some(flag.domain.as_str())
+                && flag.hostname.as_ref().map_or(true, |x| document.get_str("vantage_hostname").ok() == Some(x.as_str()))
+                && flag.labels.get("measurement_class").map_or(true, |x| document.get_str("measurement_class").ok() == Some(x.as_str()))
+        };
+
+        Ok(measurements.iter().filter(matches).max_by_key(|x| x.get_i64("timestamp").unwrap_or(0)).and_then(|x| get_value(field, x)))
+    }
+}
+
+//the field at a dotted key, created as null along with any missing parent documents
+#[cfg(test)]
+fn field<'a>(document: &'a mut Document, key: &str) -> Result<&'a mut Bson, TipupError> {
+    let mut fields: Vec<&str> = key.split('.').collect();
+    let last = fields.pop().unwrap();
+    let mut index_document = document;
+    for name in fields {
+        if !index_document.contains_key(name) {
+            index_document.insert(name, Document::new());
+        }
+
+        index_document = match index_document.get_mut(name) {
+            Some(&mut Bson::Document(ref mut document)) => document,
+            _ => return Err(TipupError::from(format!("failed to update flag field '{}' within a non-document", key))),
+        };
+    }
+
+    if !index_document.contains_key(last) {
+        index_document.insert(last, Bson::Null);
+    }
+
+    Ok(index_document.get_mut(last).unwrap())
+}
+
+#[cfg(test)]
+fn remove_field(document: &mut Document, key: &str) {
+    let mut fields: Vec<&str> = key.split('.').collect();
+    let last = fields.pop().unwrap();
+    let mut index_document = document;
+    for name in fields {
+        index_document = match index_document.get_mut(name) {
+            Some(&mut Bson::Document(ref mut document)) => document,
+            _ => return,
+        };
+    }
+
+    index_document.remove(last);
+}
+
+fn to_document(flag: &Flag) -> Result<Document, TipupError> {
+    match bson::to_bson(flag) {
+        Ok(Bson::Document(document)) => Ok(document),
+        _ => Err(TipupError::from("failed to parse flag json as Bson::Document")),
+    }
+}
+
+fn parse_flag(document: Document) -> Result<Flag, TipupError> {
+    match bson::from_bson(Bson::Document(document)) {
+        Ok(flag) => Ok(flag),
+        Err(_) => Err(TipupError::from("failed to parse bson document into flag")),
+    }
+}
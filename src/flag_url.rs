@@ -0,0 +1,95 @@
+use error::TipupError;
+use flag_manager::Flag;
+use lifecycle;
+
+//placeholders beyond the flag fields lifecycle webhook templates substitute, bounding the
+//linked time range around the flag
+const RANGE_KEYS: [&'static str; 5] = ["from", "to", "from_ms", "to_ms", "timestamp_ms"];
+
+//url to a measurement viewer, e.g. a proddle web ui or grafana dashboard, rendered into each
+//flag with '{{key}}' placeholders substituted by url encoded flag values
+#[derive(Clone)]
+pub struct FlagUrlTemplate {
+    template: String,
+    window: i64,
+}
+
+impl FlagUrlTemplate {
+    pub fn parse(template: &str, window: u32) -> Result<FlagUrlTemplate, TipupError> {
+        if !template.starts_with("http://") && !template.starts_with("https://") {
+            return Err(TipupError::from(format!("flag url template '{}' must be an http or https url", template)));
+        }
+
+        let mut remaining = template;
+        while let Some(start) = remaining.find("{{") {
+            let end = match remaining[start..].find("}}") {
+                Some(end) => start + end,
+                None => return Err(TipupError::from(format!("unterminated placeholder in flag url template '{}'", template))),
+            };
+
+            let key = remaining[start + 2..end].trim();
+            if key.len() == 0 {
+                return Err(TipupError::from(format!("empty placeholder in flag url template '{}'", template)));
+            }
+
+            remaining = &remaining[end + 2..];
+        }
+
+        Ok(
+            FlagUrlTemplate {
+                template: template.to_owned(),
+                window: window as i64,
+            }
+        )
+    }
+
+    //placeholders of values the flag lacks, e.g. the hostname of domain flags, are left empty
+    pub fn render(&self, flag: &Flag) -> String {
+        let mut url = String::new();
+        let mut remaining = self.template.as_str();
+        while let Some(start) = remaining.find("{{") {
+            let end = match remaining[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+
+            url.push_str(&remaining[..start]);
+            let key = remaining[start + 2..end].trim();
+            let value = match RANGE_KEYS.contains(&key) {
+                true => Some(self.range_value(flag, key)),
+                false => lifecycle::lookup(flag, key),
+            };
+
+            url.push_str(&encode(&value.unwrap_or(String::new())));
+            remaining = &remaining[end + 2..];
+        }
+
+        url.push_str(remaining);
+        url
+    }
+
+    //the window before the flag's first occurrence through the window after its latest
+    fn range_value(&self, flag: &Flag, key: &str) -> String {
+        let (from, to) = (flag.timestamp - self.window, flag.last_timestamp.max(flag.timestamp) + self.window);
+        match key {
+            "from" => from.to_string(),
+            "to" => to.to_string(),
+            "from_ms" => (from * 1000).to_string(),
+            "to_ms" => (to * 1000).to_string(),
+            _ => (flag.timestamp * 1000).to_string(),
+        }
+    }
+}
+
+//percent encodes all but unreserved characters so values are safe in paths and query strings
+fn encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            true => encoded.push(byte as char),
+            false => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
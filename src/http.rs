@@ -0,0 +1,19 @@
+use hyper::Client;
+use hyper::net::HttpsConnector;
+use hyper_openssl::OpensslClient;
+
+use error::TipupError;
+
+use std::time::Duration;
+
+pub fn client() -> Result<Client, TipupError> {
+    let ssl = match OpensslClient::new() {
+        Ok(ssl) => ssl,
+        Err(e) => return Err(TipupError::from(format!("failed to initialize ssl client: {}", e))),
+    };
+
+    let mut client = Client::with_connector(HttpsConnector::new(ssl));
+    client.set_read_timeout(Some(Duration::from_secs(10)));
+    client.set_write_timeout(Some(Duration::from_secs(10)));
+    Ok(client)
+}
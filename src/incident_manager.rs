@@ -0,0 +1,284 @@
+use bson::{self, Bson, Document};
+use bson::oid::ObjectId;
+use dbscan::{DBSCAN, SymmetricMatrix};
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+use flag_manager::Flag;
+use timeline::Timeline;
+
+use std;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+//seconds around an incident in which external events are considered related
+const CORRELATION_WINDOW_SECONDS: i64 = 3600;
+
+//most recent flags clustered per execution, bounding the quadratic distance matrix
+const MAX_CLUSTERED_FLAGS: i64 = 2000;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Incident {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub minimum_timestamp: i64,
+    pub maximum_timestamp: i64,
+    pub domain: String,
+    pub hostnames: HashSet<String>,
+    pub flag_ids: HashSet<ObjectId>,
+    #[serde(default)]
+    pub related_event_ids: HashSet<ObjectId>,
+}
+
+pub struct IncidentManager {
+    duration_seconds: i64,
+    cluster_window: i64,
+    maximum_distance: f64,
+    minimum_points: usize,
+    clock: Arc<Clock>,
+}
+
+impl IncidentManager {
+    pub fn new(duration_seconds: i64, cluster_window: i64, clock: Arc<Clock>) -> IncidentManager {
+        IncidentManager {
+            duration_seconds: duration_seconds,
+            cluster_window: cluster_window,
+            maximum_distance: 1.5,
+            minimum_points: 4,
+            clock: clock,
+        }
+    }
+
+    pub fn execute(&self, tipup_db: &Database) -> Result<(), TipupError> {
+        let timestamp = self.clock.now() - self.duration_seconds;
+        try!(self.cluster_flags(tipup_db, timestamp, self.clock.now() - self.cluster_window));
+        self.update_timelines(tipup_db, timestamp)
+    }
+
+    fn cluster_flags(&self, tipup_db: &Database, timestamp: i64, flag_timestamp: i64) -> Result<(), TipupError> {
+        //retrieve active incidents
+        let mut active_incidents: HashMap<String, Vec<Incident>> = HashMap::new();
+        let timestamp_gte = doc!("$gte" => timestamp);
+        let incident_search_document = Some(doc!("maximum_timestamp" => timestamp_gte));
+        let cursor = try!(tipup_db.collection("incidents").find(incident_search_document, None));
+        for document in cursor {
+            let document = try!(document);
+
+            //parse document into Incident
+            let incident: Incident = match bson::from_bson(Bson::Document(document)) {
+                Ok(incident) => incident,
+                Err(_) => return Err(TipupError::from("failed to parse bson document into incident")),
+            };
+
+            active_incidents.entry(incident.domain.clone()).or_insert(Vec::new()).push(incident);
+        }
+
+        //iterate over the most recent flag documents within the cluster window
+        let mut flags: Vec<Flag> = Vec::new();
+        let timestamp_gte = doc!("$gte" => flag_timestamp);
+        let flag_search_document = Some(doc!("timestamp" => timestamp_gte));
+        let negative_one = -1;
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("timestamp" => negative_one));
+        find_options.limit = Some(MAX_CLUSTERED_FLAGS);
+        let cursor = try!(tipup_db.collection("flags").find(flag_search_document, Some(find_options)));
+        for document in cursor {
+            let document = try!(document);
+
+            //skip flags which fail to parse, e.g. those predating required fields, rather than
+            //failing every execution on them
+            let id = document.get("_id").map_or("unknown".to_owned(), |x| x.to_string());
+            match bson::from_bson(Bson::Document(document)) {
+                Ok(flag) => flags.push(flag),
+                Err(e) => warn!("skipping unparseable flag {} in incident clustering: {}", id, e),
+            }
+        }
+
+        if flags.len() as i64 == MAX_CLUSTERED_FLAGS {
+            warn!("clustering only the {} most recent flags into incidents", MAX_CLUSTERED_FLAGS);
+        }
+
+        if flags.len() == 0 {
+            return Ok(());
+        }
+
+        //execute dbscan algorithm
+        let mut dbscan = DBSCAN::new(self.maximum_distance, self.minimum_points);
+        let mut symmetric_matrix = SymmetricMatrix::<f64>::new(flags.len());
+        for i in 0..flags.len()-1 {
+            for j in i+1..flags.len() {
+                symmetric_matrix.set(i, j, compute_flag_distance(&flags[i], &flags[j]));
+            }
+        }
+
+        let clusters = dbscan.perform_clustering(&symmetric_matrix);
+
+        //create hashmap of clusters
+        let mut cluster_map: HashMap<usize, Vec<&Flag>> = HashMap::new();
+        for (i, cluster) in clusters.iter().enumerate() {
+            let ref flag = flags[i];
+            if let &Some(cluster_id) = cluster {
+                cluster_map.entry(cluster_id).or_insert(Vec::new()).push(flag);
+            }
+        }
+
+        //process new incidents
+        for flags in cluster_map.values() {
+            if let Err(e) = process_incident(&flags, &mut active_incidents, tipup_db) {
+                error!("{}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_timelines(&self, tipup_db: &Database, timestamp: i64) -> Result<(), TipupError> {
+        //rebuild timelines of active incidents to capture acknowledgements, silences, and resolutions
+        let now = self.clock.now();
+        let timestamp_gte = doc!("$gte" => timestamp);
+        let cursor = try!(tipup_db.collection("incidents").find(Some(doc!("maximum_timestamp" => timestamp_gte)), None));
+        for document in cursor {
+            let document = try!(document);
+            let incident: Incident = match bson::from_bson(Bson::Document(document)) {
+                Ok(incident) => incident,
+                Err(_) => return Err(TipupError::from("failed to parse bson document into incident")),
+            };
+
+            if let Err(e) = Timeline::build(&incident, tipup_db, now).and_then(|x| x.persist(tipup_db)) {
+                error!("failed to update timeline of incident {}: {}", incident.id, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn compute_flag_distance(flag_one: &Flag, flag_two: &Flag) -> f64 {
+    //timestamp
+    let timestamp_difference = (flag_one.timestamp - flag_two.timestamp).abs();
+    let timestamp_score = match timestamp_difference {
+        x if x <= 86400 => x as f64 / 86400.0,
+        _ => 1.0,
+    };
+
+    //TODO status
+    let status_score = 0.0;
+
+    //domains
+    let domain_score = match flag_one.domain.eq(&flag_two.domain) {
+        true => 0.0,
+        false => 1.0,
+    };
+
+    //hostnames
+    let hostname_score = match flag_one.hostname.eq(&flag_two.hostname) {
+        true => 0.0,
+        false => 1.0,
+    };
+
+    return (timestamp_score) + (status_score) + (domain_score * 1.3) + (hostname_score);
+}
+
+fn process_incident(flags: &Vec<&Flag>, active_incidents: &mut HashMap<String, Vec<Incident>>, tipup_db: &Database) -> Result<(), TipupError> {
+    let mut minimum_timestamp = i64::max_value();
+    let mut maximum_timestamp = i64::min_value();
+    let mut domains = HashSet::new();
+    let mut hostnames = HashSet::new();
+    let mut flag_ids = HashSet::new();
+
+    for flag in flags {
+        minimum_timestamp = std::cmp::min(minimum_timestamp, flag.timestamp);
+        maximum_timestamp = std::cmp::max(maximum_timestamp, flag.timestamp);
+        domains.insert(flag.domain.clone());
+        if let Some(ref hostname) = flag.hostname {
+            hostnames.insert(hostname.clone());
+        }
+        flag_ids.insert(flag.id.clone());
+    }
+
+    if domains.len() != 1 {
+        return Err(TipupError::from(format!("cluster with {} domain(s) found", domains.len())));
+    }
+
+    //create incident object and check if incident already exists
+    let mut incident = Incident {
+        id: ObjectId::new().unwrap(),
+        minimum_timestamp: minimum_timestamp,
+        maximum_timestamp: maximum_timestamp,
+        domain: domains.into_iter().next().unwrap(),
+        hostnames: hostnames,
+        flag_ids: flag_ids,
+        related_event_ids: HashSet::new(),
+    };
+
+    //compare current incident with active incidents from same domain (based on timestamps)
+    if let Some(active_incidents) = active_incidents.get_mut(&incident.domain) {
+        for active_incident in active_incidents.iter_mut() {
+            if (incident.minimum_timestamp >= active_incident.minimum_timestamp && incident.minimum_timestamp <= active_incident.maximum_timestamp)
+                    || (incident.maximum_timestamp <= active_incident.maximum_timestamp && incident.maximum_timestamp >= active_incident.minimum_timestamp) {
+                //update active incident in database
+                active_incident.minimum_timestamp = std::cmp::min(incident.minimum_timestamp, active_incident.minimum_timestamp);
+                active_incident.maximum_timestamp = std::cmp::max(incident.maximum_timestamp, active_incident.maximum_timestamp);
+
+                for hostname in incident.hostnames.iter() {
+                    active_incident.hostnames.insert(hostname.clone());
+                }
+
+                let mut update = false;
+                for flag_id in incident.flag_ids.iter() {
+                    update = active_incident.flag_ids.insert(flag_id.clone()) || update;
+                }
+
+                update = try!(correlate_events(active_incident, tipup_db)) || update;
+
+                //update document in mongodb
+                if update {
+                    let object_id = active_incident.id.clone();
+                    let search_document = doc!("_id" => object_id);
+                    let incident_document: Document = match bson::to_bson(active_incident) {
+                        Ok(Bson::Document(incident_document)) => incident_document,
+                        _ => return Err(TipupError::from("failed to parse updated incident document as Bson::Document")),
+                    };
+                    try!(tipup_db.collection("incidents").find_one_and_replace(search_document, incident_document, None));
+                }
+
+                return Ok(());
+            }
+        }
+    }
+
+    //write to database
+    try!(correlate_events(&mut incident, tipup_db));
+    let incident_document: Document = match bson::to_bson(&incident) {
+        Ok(Bson::Document(incident_document)) => incident_document,
+        _ => return Err(TipupError::from("failed to parse incident document as Bson::Document")),
+    };
+
+    try!(tipup_db.collection("incidents").insert_one(incident_document, None));
+    info!("created incident for domain '{}' with {} flag(s) and {} related event(s)", incident.domain, incident.flag_ids.len(), incident.related_event_ids.len());
+    Ok(())
+}
+
+fn correlate_events(incident: &mut Incident, tipup_db: &Database) -> Result<bool, TipupError> {
+    //find external events for the domain (or global events) near the incident
+    let start_lte = doc!("$lte" => (incident.maximum_timestamp + CORRELATION_WINDOW_SECONDS));
+    let end_gte = doc!("$gte" => (incident.minimum_timestamp - CORRELATION_WINDOW_SECONDS));
+    let domain_exists = doc!("$exists" => false);
+    let search_document = Some(doc!(
+        "start_timestamp" => start_lte,
+        "end_timestamp" => end_gte,
+        "$or" => [{ "domain" => (incident.domain.clone()) }, { "domain" => domain_exists }]
+    ));
+
+    let mut update = false;
+    for document in try!(tipup_db.collection("events").find(search_document, None)) {
+        let document = try!(document);
+        if let Some(&Bson::ObjectId(ref event_id)) = document.get("_id") {
+            update = incident.related_event_ids.insert(event_id.clone()) || update;
+        }
+    }
+
+    Ok(update)
+}
@@ -0,0 +1,83 @@
+use bson::{self, Bson, Document};
+use mongodb::db::{Database, ThreadedDatabase};
+
+use command::percentile;
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::BTreeMap;
+
+struct Durations {
+    detect: Vec<f64>,
+    resolve: Vec<f64>,
+}
+
+//mean-time-to-detect (result timestamp to flag creation) and mean-time-to-resolve (flag creation
+//to resolution) distributions of flags raised since timestamp, grouped by 'analyzer', 'domain',
+//or a flag label as 'label.<key>'
+pub fn statistics(proddle_db: &Database, group_by: &str, timestamp: i64) -> Result<Vec<Document>, TipupError> {
+    let label = match group_by {
+        "analyzer" | "domain" => None,
+        x if x.starts_with("label.") && x.len() > 6 => Some(&x[6..]),
+        _ => return Err(TipupError::from(format!("unknown kpi grouping '{}', expected 'analyzer', 'domain', or 'label.<key>'", group_by))),
+    };
+
+    let mut groups: BTreeMap<String, Durations> = BTreeMap::new();
+    let timestamp_gte = doc!("$gte" => timestamp);
+    for document in try!(proddle_db.collection("flags").find(Some(doc!("timestamp" => timestamp_gte)), None)) {
+        let document = try!(document);
+        let flag: Flag = match bson::from_bson(Bson::Document(document.clone())) {
+            Ok(flag) => flag,
+            Err(_) => return Err(TipupError::from("failed to parse bson document into flag")),
+        };
+
+        let group = match label {
+            Some(label) => match flag.labels.get(label) {
+                Some(value) => value.to_owned(),
+                None => continue,
+            },
+            None if group_by == "analyzer" => flag.analyzer.clone(),
+            None => flag.domain.clone(),
+        };
+
+        //flag ids record when the flag was created
+        let created_timestamp = flag.id.timestamp() as i64;
+        let durations = groups.entry(group).or_insert(Durations { detect: Vec::new(), resolve: Vec::new() });
+        durations.detect.push((created_timestamp - flag.timestamp).max(0) as f64);
+        if let (true, Some(&Bson::I64(resolved_timestamp))) = (flag.state == "resolved", document.get("resolved_timestamp")) {
+            durations.resolve.push((resolved_timestamp - created_timestamp).max(0) as f64);
+        }
+    }
+
+    let mut documents = Vec::new();
+    for (group, mut durations) in groups.into_iter() {
+        let mut document = doc!(
+            "group" => group,
+            "flags" => (durations.detect.len() as i64),
+            "resolved" => (durations.resolve.len() as i64)
+        );
+
+        document.insert("mttd", summarize(&mut durations.detect));
+        document.insert("mttr", summarize(&mut durations.resolve));
+        documents.push(document);
+    }
+
+    Ok(documents)
+}
+
+fn summarize(values: &mut Vec<f64>) -> Bson {
+    //mean and percentiles in seconds, null without samples
+    if values.len() == 0 {
+        return Bson::Null;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    Bson::Document(doc!(
+        "mean" => mean,
+        "p50" => (percentile(values, 50.0)),
+        "p90" => (percentile(values, 90.0)),
+        "p99" => (percentile(values, 99.0)),
+        "max" => (values[values.len() - 1])
+    ))
+}
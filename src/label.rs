@@ -0,0 +1,144 @@
+use error::TipupError;
+
+use std::collections::HashMap;
+
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    NotExists(String),
+    AtLeast(String, String),
+    AtMost(String, String),
+}
+
+impl Requirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match *self {
+            Requirement::Equals(ref key, ref value) => labels.get(key).map_or(false, |x| x == value),
+            Requirement::NotEquals(ref key, ref value) => labels.get(key).map_or(true, |x| x != value),
+            Requirement::In(ref key, ref values) => labels.get(key).map_or(false, |x| values.contains(x)),
+            Requirement::NotIn(ref key, ref values) => labels.get(key).map_or(true, |x| !values.contains(x)),
+            Requirement::Exists(ref key) => labels.contains_key(key),
+            Requirement::NotExists(ref key) => !labels.contains_key(key),
+            //ordered requirements match only once expanded against the key's levels
+            Requirement::AtLeast(..) | Requirement::AtMost(..) => false,
+        }
+    }
+}
+
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    //parses kubernetes style selectors, e.g. "env=prod,tier!=lab,region in (us,eu),!canary", and
+    //ordered requirements like "status>=warning" on keys with levels
+    pub fn parse(selector: &str) -> Result<LabelSelector, TipupError> {
+        let mut requirements = Vec::new();
+        for expression in split_expressions(selector) {
+            let expression = expression.trim();
+            if expression.len() == 0 {
+                continue;
+            }
+
+            let requirement = if let Some(index) = expression.find("!=") {
+                Requirement::NotEquals(try!(parse_key(&expression[..index])), expression[index + 2..].trim().to_owned())
+            } else if let Some(index) = expression.find("==") {
+                Requirement::Equals(try!(parse_key(&expression[..index])), expression[index + 2..].trim().to_owned())
+            } else if let Some(index) = expression.find(">=") {
+                Requirement::AtLeast(try!(parse_key(&expression[..index])), expression[index + 2..].trim().to_owned())
+            } else if let Some(index) = expression.find("<=") {
+                Requirement::AtMost(try!(parse_key(&expression[..index])), expression[index + 2..].trim().to_owned())
+            } else if let Some(index) = expression.find('=') {
+                Requirement::Equals(try!(parse_key(&expression[..index])), expression[index + 1..].trim().to_owned())
+            } else if let Some(index) = expression.find(" notin ") {
+                Requirement::NotIn(try!(parse_key(&expression[..index])), try!(parse_set(&expression[index + 7..])))
+            } else if let Some(index) = expression.find(" in ") {
+                Requirement::In(try!(parse_key(&expression[..index])), try!(parse_set(&expression[index + 4..])))
+            } else if expression.starts_with('!') {
+                Requirement::NotExists(try!(parse_key(&expression[1..])))
+            } else {
+                Requirement::Exists(try!(parse_key(expression)))
+            };
+
+            requirements.push(requirement);
+        }
+
+        Ok(
+            LabelSelector {
+                requirements: requirements,
+            }
+        )
+    }
+
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements.iter().all(|x| x.matches(labels))
+    }
+
+    //expands ordered requirements on a key into the sets of levels, ordered from lowest to highest,
+    //they admit, e.g. "status>=error" into "status in (error,critical)"
+    pub fn with_levels(mut self, key: &str, levels: &Vec<String>) -> Result<LabelSelector, TipupError> {
+        for requirement in self.requirements.iter_mut() {
+            let expanded = match *requirement {
+                Requirement::AtLeast(ref requirement_key, ref value) | Requirement::AtMost(ref requirement_key, ref value) if requirement_key == key => {
+                    let index = match levels.iter().position(|x| x == value) {
+                        Some(index) => index,
+                        None => return Err(TipupError::from(format!("unknown {} level '{}' in selector", key, value))),
+                    };
+
+                    let admitted = match *requirement {
+                        Requirement::AtLeast(..) => levels[index..].to_vec(),
+                        _ => levels[..index + 1].to_vec(),
+                    };
+
+                    Requirement::In(key.to_owned(), admitted)
+                },
+                _ => continue,
+            };
+
+            *requirement = expanded;
+        }
+
+        Ok(self)
+    }
+}
+
+fn split_expressions(selector: &str) -> Vec<&str> {
+    //split on commas outside of parenthesized sets
+    let mut expressions = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                expressions.push(&selector[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+
+    expressions.push(&selector[start..]);
+    expressions
+}
+
+fn parse_key(key: &str) -> Result<String, TipupError> {
+    let key = key.trim();
+    if key.len() == 0 || key.contains(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '!' || c == '<' || c == '>') {
+        return Err(TipupError::from(format!("invalid label key '{}' in selector", key)));
+    }
+
+    Ok(key.to_owned())
+}
+
+fn parse_set(set: &str) -> Result<Vec<String>, TipupError> {
+    let set = set.trim();
+    if !set.starts_with('(') || !set.ends_with(')') {
+        return Err(TipupError::from(format!("invalid label set '{}' in selector", set)));
+    }
+
+    Ok(set[1..set.len() - 1].split(',').map(|x| x.trim().to_owned()).filter(|x| x.len() > 0).collect())
+}
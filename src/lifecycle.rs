@@ -0,0 +1,168 @@
+use bson::{self, Bson, Document};
+use hyper::header::ContentType;
+use mongodb::db::{Database, ThreadedDatabase};
+use serde_json;
+
+use error::TipupError;
+use flag_manager::Flag;
+use http;
+use label::LabelSelector;
+
+pub const TRANSITIONS: [&'static str; 4] = ["opened", "acknowledged", "escalated", "resolved"];
+
+//webhook notified as flags move through the lifecycle transitions it subscribes to
+pub struct LifecycleWebhook {
+    name: String,
+    url: String,
+    transitions: Vec<String>,
+    selector: LabelSelector,
+    template: Option<String>,
+}
+
+impl LifecycleWebhook {
+    pub fn parse(document: &Document) -> Result<LifecycleWebhook, TipupError> {
+        let name = match document.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return Err(TipupError::from("failed to parse lifecycle webhook name")),
+        };
+
+        let url = match document.get("url") {
+            Some(&Bson::String(ref url)) => url.to_owned(),
+            _ => return Err(TipupError::from(format!("failed to parse url of lifecycle webhook '{}'", name))),
+        };
+
+        let mut transitions = Vec::new();
+        match document.get("transitions") {
+            Some(&Bson::Array(ref transition_array)) => {
+                for transition in transition_array.iter() {
+                    match transition {
+                        &Bson::String(ref transition) if TRANSITIONS.contains(&transition.as_str()) => transitions.push(transition.to_owned()),
+                        _ => return Err(TipupError::from(format!("lifecycle webhook '{}' transitions must be one of {}", name, TRANSITIONS.join(", ")))),
+                    }
+                }
+            },
+            _ => return Err(TipupError::from(format!("failed to parse transitions of lifecycle webhook '{}'", name))),
+        }
+
+        let selector = match document.get("selector") {
+            Some(&Bson::String(ref selector)) => try!(LabelSelector::parse(selector)),
+            None => try!(LabelSelector::parse("")),
+            _ => return Err(TipupError::from(format!("failed to parse selector of lifecycle webhook '{}'", name))),
+        };
+
+        let template = match document.get("template") {
+            Some(&Bson::String(ref template)) => Some(template.to_owned()),
+            None => None,
+            _ => return Err(TipupError::from(format!("failed to parse template of lifecycle webhook '{}'", name))),
+        };
+
+        Ok(
+            LifecycleWebhook {
+                name: name,
+                url: url,
+                transitions: transitions,
+                selector: selector,
+                template: template,
+            }
+        )
+    }
+
+    pub fn load(tipup_db: &Database) -> Result<Vec<LifecycleWebhook>, TipupError> {
+        let mut webhooks = Vec::new();
+        for document in try!(tipup_db.collection("lifecycle_webhooks").find(None, None)) {
+            webhooks.push(try!(LifecycleWebhook::parse(&try!(document))));
+        }
+
+        Ok(webhooks)
+    }
+
+    fn payload(&self, transition: &str, flag: &Flag) -> Result<String, TipupError> {
+        let flag_json = match bson::to_bson(flag) {
+            Ok(bson) => bson.to_json().to_string(),
+            Err(_) => return Err(TipupError::from("failed to serialize flag for lifecycle webhook")),
+        };
+
+        let template = match self.template {
+            Some(ref template) => template,
+            None => return Ok(format!("{{\"transition\":\"{}\",\"flag\":{}}}", transition, flag_json)),
+        };
+
+        //substitute '{{key}}' placeholders with json escaped flag values, '{{flag}}' with the whole flag
+        let mut payload = String::new();
+        let mut remaining = template.as_str();
+        while let Some(start) = remaining.find("{{") {
+            let end = match remaining[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+
+            payload.push_str(&remaining[..start]);
+            let key = remaining[start + 2..end].trim();
+            match key {
+                "flag" => payload.push_str(&flag_json),
+                "transition" => payload.push_str(&escape(transition)),
+                _ => payload.push_str(&escape(&lookup(flag, key).unwrap_or(String::new()))),
+            }
+
+            remaining = &remaining[end + 2..];
+        }
+
+        payload.push_str(remaining);
+        Ok(payload)
+    }
+}
+
+pub fn notify(webhooks: &Vec<LifecycleWebhook>, transition: &str, flag: &Flag) {
+    //silenced flags never notify, keeping external systems from tracking flags they never saw open
+    if flag.silenced {
+        return;
+    }
+
+    let selector_labels = flag.selector_labels();
+    for webhook in webhooks.iter() {
+        if !webhook.transitions.iter().any(|x| x == transition) || !webhook.selector.matches(&selector_labels) {
+            continue;
+        }
+
+        if let Err(e) = send(webhook, transition, flag) {
+            error!("failed to notify lifecycle webhook '{}' of flag {} {}: {}", webhook.name, flag.id, transition, e);
+        }
+    }
+}
+
+fn send(webhook: &LifecycleWebhook, transition: &str, flag: &Flag) -> Result<(), TipupError> {
+    let body = try!(webhook.payload(transition, flag));
+    let response = try!(try!(http::client()).post(&webhook.url).header(ContentType::json()).body(&body).send());
+    if !response.status.is_success() {
+        return Err(TipupError::from(format!("webhook '{}' responded with status {}", webhook.url, response.status)));
+    }
+
+    Ok(())
+}
+
+pub fn lookup(flag: &Flag, key: &str) -> Option<String> {
+    match key {
+        "id" => Some(flag.id.to_hex()),
+        "timestamp" => Some(flag.timestamp.to_string()),
+        "last_timestamp" => Some(flag.last_timestamp.to_string()),
+        "hostname" => flag.hostname.clone(),
+        "domain" => Some(flag.domain.clone()),
+        "status" => Some(flag.status.clone()),
+        "analyzer" => Some(flag.analyzer.clone()),
+        "state" => Some(flag.state.clone()),
+        "count" => Some(flag.count.to_string()),
+        "url" => flag.url.clone(),
+        _ if key.starts_with("labels.") => flag.labels.get(&key[7..]).cloned(),
+        _ if key.starts_with("evidence.") => flag.evidence.get(&key[9..]).cloned(),
+        _ => None,
+    }
+}
+
+fn escape(value: &str) -> String {
+    //json string contents without the surrounding quotes
+    let quoted = serde_json::to_string(value).unwrap_or(String::new());
+    match quoted.len() >= 2 {
+        true => quoted[1..quoted.len() - 1].to_owned(),
+        false => String::new(),
+    }
+}
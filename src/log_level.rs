@@ -0,0 +1,47 @@
+use slog::{Drain, OwnedKeyValueList, Record};
+
+use error::TipupError;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+//most verbose level logged, shared by every logger so a reload takes effect immediately
+static LEVEL: AtomicUsize = AtomicUsize::new(4);
+
+pub const LEVELS: [&'static str; 6] = ["critical", "error", "warning", "info", "debug", "trace"];
+
+//drops records more verbose than the current level
+pub struct LevelFilter<D: Drain> {
+    drain: D,
+}
+
+impl<D: Drain> LevelFilter<D> {
+    pub fn new(drain: D) -> LevelFilter<D> {
+        LevelFilter {
+            drain: drain,
+        }
+    }
+}
+
+impl<D: Drain> Drain for LevelFilter<D> {
+    type Error = D::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKeyValueList) -> Result<(), D::Error> {
+        match record.level().as_usize() <= LEVEL.load(Ordering::Relaxed) {
+            true => self.drain.log(record, values),
+            false => Ok(()),
+        }
+    }
+}
+
+//levels are ordered as slog orders them, critical being 1
+pub fn parse(level: &str) -> Result<usize, TipupError> {
+    match LEVELS.iter().position(|x| x == &level) {
+        Some(index) => Ok(index + 1),
+        None => Err(TipupError::from(format!("unknown log level '{}', expected one of {}", level, LEVELS.join(", ")))),
+    }
+}
+
+pub fn set(level: &str) -> Result<(), TipupError> {
+    LEVEL.store(try!(parse(level)), Ordering::Relaxed);
+    Ok(())
+}
@@ -2,88 +2,310 @@
 extern crate bson;
 #[macro_use]
 extern crate chan;
+extern crate chan_signal;
 #[macro_use]
 extern crate clap;
 extern crate dbscan;
+extern crate flate2;
+extern crate glob;
+extern crate hyper;
+extern crate hyper_openssl;
 extern crate mongodb;
+extern crate regex;
 extern crate rustc_serialize;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 #[macro_use]
 extern crate slog;
 #[macro_use]
 extern crate slog_scope;
 extern crate slog_term;
 extern crate time;
+extern crate tiny_http;
 
 use bson::Bson;
-use chan::Sender;
-use clap::{App, ArgMatches};
+use bson::ordered::OrderedDocument;
+use chan::{Receiver, Sender};
+use chan_signal::Signal;
+use clap::App;
 use mongodb::{Client, ClientInner, ClientOptions, ThreadedClient};
-use mongodb::coll::options::{CursorType, FindOneAndUpdateOptions, FindOptions};
+use mongodb::coll::options::FindOneAndUpdateOptions;
 use mongodb::db::{Database, ThreadedDatabase};
 use slog::{DrainExt, Logger};
 
+mod aggregator;
 mod analyzer;
+mod analyzer_health;
+mod analyzer_revision;
+mod api;
+mod atlas_source;
+mod availability_manager;
+mod canary;
+mod classifier;
+mod clock;
+mod command;
+mod config;
+mod crash_report;
+mod dependency;
+mod derivation;
+mod dry_run;
 mod error;
-mod event_manager;
+mod event;
+mod feed;
+mod fetch_scheduler;
+mod filter;
 mod flag_manager;
+mod flag_store;
+mod flag_url;
+mod http;
+mod incident_manager;
+mod kpi;
+mod label;
+mod lifecycle;
+mod log_level;
+mod maintenance_manager;
+mod metrics;
+mod parameter_monitor;
+mod partition;
+mod noise_report;
 mod pipe;
+mod provider_manager;
+mod quality_gate;
+mod resources;
+mod result_source;
 mod result_window;
+mod retention_manager;
+mod sanitizer;
+mod self_healing;
+mod severity;
+mod sink;
+mod sla_manager;
+mod snapshot;
+mod spill_queue;
+mod supervisor;
+mod target_stats;
+mod time_window;
+mod timeline;
+mod topology;
+mod verification;
 
-use analyzer::{Analyzer, ErrorAnalyzer, StdDevAnalyzer};
+use aggregator::Aggregator;
+use analyzer::{Analyzer, BurstAnalyzer, ChangePointAnalyzer, ConsensusAnalyzer, ContentChangeAnalyzer, CorrelationAnalyzer, CusumAnalyzer, DistributionDriftAnalyzer, DivergenceAnalyzer, DnsFailureAnalyzer, EntropyAnalyzer, ErrorAnalyzer, EsdAnalyzer, EwmaAnalyzer, FlapAnalyzer, GeoDnsAnalyzer, HoltWintersAnalyzer, HttpStatusAnalyzer, IsolationForestAnalyzer, JitterAnalyzer, KMeansAnalyzer, KeyedAnalyzer, LatencyPathAnalyzer, MarkovAnalyzer, MovingAverageAnalyzer, PacketLossAnalyzer, PercentileAnalyzer, RateOfChangeAnalyzer, StdDevAnalyzer, ThresholdAnalyzer, TlsExpiryAnalyzer, TrendAnalyzer, UptimeAnalyzer, WindowedAnalyzer};
+use analyzer_health::HealthManager;
+use api::Api;
+use availability_manager::AvailabilityManager;
+use canary::Canary;
+use classifier::ErrorClassifier;
+use clock::Clock;
+use config::{Config, Validation};
+use crash_report::{CrashContext, CrashReporter};
+use dependency::Dependency;
+use derivation::Derivation;
 use error::TipupError;
-use event_manager::EventManager;
+use fetch_scheduler::FetchScheduler;
+use filter::ResultFilter;
 use flag_manager::{Flag, FlagManager};
+use flag_store::MongoFlagStore;
+use incident_manager::IncidentManager;
+use label::LabelSelector;
+use maintenance_manager::MaintenanceManager;
+use metrics::Metrics;
+use parameter_monitor::ParameterMonitor;
+use noise_report::NoiseReporter;
 use pipe::Pipe;
+use quality_gate::QualityGate;
+use result_source::ResultSource;
 use result_window::ResultWindow;
+use sanitizer::Sanitizer;
+use severity::SeverityTaxonomy;
+use sink::{JiraSink, LogSink, NdjsonSink, Sink, WebhookSink};
+use provider_manager::ProviderManager;
+use sla_manager::SlaManager;
+use snapshot::SnapshotManager;
+use spill_queue::SpillQueue;
+use supervisor::Supervisor;
+use target_stats::TargetStats;
 
-use std::sync::{Arc, RwLock};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::sync::{Arc, Mutex, RwLock};
 
-fn parse_args(matches: &ArgMatches) -> Result<(String, u16, String, String, String, String, String, u32, u32), TipupError> {
-    let mongodb_ip_address = try!(value_t!(matches, "MONGODB_IP_ADDRESS", String));
-    let mongodb_port = try!(value_t!(matches.value_of("MONGODB_PORT"), u16));
-    let ca_file = try!(value_t!(matches.value_of("CA_FILE"), String));
-    let certificate_file = try!(value_t!(matches.value_of("CERTIFICATE_FILE"), String));
-    let key_file = try!(value_t!(matches.value_of("KEY_FILE"), String));
-    let username = try!(value_t!(matches.value_of("USERNAME"), String));
-    let password = try!(value_t!(matches.value_of("PASSWORD"), String));
-    let update_flags_interval = try!(value_t!(matches.value_of("UPDATE_FLAGS_INTERVAL"), u32));
-    let update_events_interval = try!(value_t!(matches.value_of("UPDATE_EVENTS_INTERVAL"), u32));
+//resolution of the per host fetch schedule
+const FETCH_TICK_MILLISECONDS: u32 = 1000;
 
-    Ok((mongodb_ip_address, mongodb_port, ca_file, certificate_file, key_file, username, password, update_flags_interval, update_events_interval))
-}
+//resolution of the daily retention schedule
+const RETENTION_TICK_MILLISECONDS: u32 = 60 * 1000;
 
 fn main() {
-    slog_scope::set_global_logger(Logger::root(slog_term::streamer().build().fuse(), o![]));
+    slog_scope::set_global_logger(Logger::root(log_level::LevelFilter::new(slog_term::streamer().build()).fuse(), o![]));
 
-    //parse arguments
+    //parse arguments, including those of any config file
+    let args: Vec<String> = env::args().collect();
     let yaml = load_yaml!("args.yaml");
-    let matches = App::from_yaml(yaml).get_matches();
+    let matches = match config::arguments(args.clone()) {
+        Ok(config_args) => App::from_yaml(yaml).get_matches_from(config_args),
+        Err(e) => panic!("{}", e),
+    };
+
+    let mut validation = Validation::new();
+    let mut config = Config::parse(&matches, &mut validation);
+
+    //validate configuration reporting every problem at once
+    if let ("config", Some(sub_matches)) = matches.subcommand() {
+        if let Err(e) = command::config::execute(sub_matches, &config, validation) {
+            panic!("{}", e);
+        }
+
+        return;
+    }
+
+    if let Err(e) = validation.check() {
+        panic!("{}", e);
+    }
+
+    if let Err(e) = log_level::set(&config.log_level) {
+        panic!("{}", e);
+    }
+
+    //reload configuration on SIGHUP, blocked before any thread is spawned so every thread inherits the mask
+    let signal_rx = chan_signal::notify(&[Signal::HUP]);
 
-    let (mongodb_ip_address, mongodb_port, ca_file, certificate_file, key_file, username, password, update_flags_interval, update_events_interval) = match parse_args(&matches) {
-        Ok(args) => args,
+    let mut result_filter = match config.result_filter() {
+        Ok(result_filter) => result_filter,
+        Err(e) => panic!("{}", e),
+    };
+
+    let result_source = match config.result_source() {
+        Ok(result_source) => result_source,
+        Err(e) => panic!("{}", e),
+    };
+
+    let taxonomy = match config.severity_taxonomy() {
+        Ok(taxonomy) => taxonomy,
+        Err(e) => panic!("{}", e),
+    };
+
+    let flag_url_template = match config.flag_url_template() {
+        Ok(flag_url_template) => flag_url_template,
         Err(e) => panic!("{}", e),
     };
 
     //connect to mongodb
-    let client = match initialize_mongodb_client(&mongodb_ip_address, mongodb_port, &ca_file, &certificate_file, &key_file) {
+    let client = match initialize_mongodb_client(&config) {
         Ok(client) => client,
         Err(e) => panic!("{}", e),
     };
     
-    //create pipe and result_window
+    //execute command if one was provided
+    if let (name, Some(sub_matches)) = matches.subcommand() {
+        let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+            Ok(db) => db,
+            Err(e) => panic!("{}", e),
+        };
+
+        //commands act on flags persisted by running instances
+        let flag_store = MongoFlagStore::new(client.clone(), &config.username, &config.password);
+        if let Err(e) = command::execute(name, sub_matches, &db, &flag_store) {
+            panic!("{}", e);
+        }
+
+        return;
+    }
+
+    //write crash reports when any thread panics
+    let crash_context = CrashContext::new();
+    CrashReporter::new(&config.crash_report_directory, &config.crash_report_collection,
+        client.clone(), &config.username, &config.password, crash_context.clone()).install();
+
+    //create pipe, result_window, sanitizer, and aggregator
     let result_window = Arc::new(RwLock::new(ResultWindow::new()));
-    let (flag_tx, flag_rx) = chan::sync(50);
-    let mut pipe = Pipe::new();
+    let (flag_tx, analyzer_flag_rx) = chan::sync(50);
+    let (manager_flag_tx, flag_rx) = chan::sync(50);
+    let clock = clock::system();
+    let metrics = Metrics::new();
+    let mut pipe = Pipe::new(metrics.clone(), clock.clone());
+    let mut sanitizer = Sanitizer::new(flag_tx.clone());
+    let mut aggregator = Aggregator::new();
+    let mut target_stats = TargetStats::new(&config.target_stats_field, config.target_stats_window);
+    let mut quality_gate = QualityGate::new();
+    let mut classifier = ErrorClassifier::new();
+    let mut parameter_monitor = ParameterMonitor::new(flag_tx.clone());
+    let mut snapshot_manager = SnapshotManager::new(config.full_snapshot_interval, clock.clone());
+    let sla_manager = SlaManager::new(flag_tx.clone(), clock.clone());
+    let atlas_source = match config.atlas_source(clock.clone()) {
+        Ok(atlas_source) => atlas_source,
+        Err(e) => panic!("{}", e),
+    };
+
+    let flag_store = match flag_store::create(&config.flag_store, client.clone(), &config.username, &config.password) {
+        Ok(flag_store) => flag_store,
+        Err(e) => panic!("{}", e),
+    };
+
+    let provider_manager = ProviderManager::new(config.provider_window, config.provider_min_targets as usize, flag_store.clone(), flag_tx.clone(), clock.clone());
+    let health_manager = HealthManager::new(pipe.health(), config.analyzer_health_min_score, config.analyzer_health_min_evaluations as u64,
+        config.analyzer_health_max_latency, flag_store.clone(), flag_tx.clone(), metrics.clone(), clock.clone());
+    let mut flag_manager = FlagManager::new(config.flag_resolve_timeout, config.flag_merge, config.flag_id_window as i64, config.self_healing_window, config.flag_notification_rate, taxonomy.clone(), flag_url_template, flag_store.clone(), metrics.clone(), clock.clone());
     {
-        let db = match initialize_db(&client, "proddle", &username, &password) {
+        let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
             Ok(db) => db,
             Err(e) => panic!("{}", e),
         };
 
-        if let Err(e) = load_analyzers(&db, &mut pipe, flag_tx, result_window.clone()) {
+        let mut validation = Validation::new();
+        if let Err(e) = config::validate_definitions(&db, &taxonomy, &mut validation) {
+            panic!("{}", e);
+        }
+
+        if let Err(e) = validation.check() {
+            panic!("{}", e);
+        }
+
+        if let Err(e) = load_analyzers(&db, &mut pipe, flag_tx, result_window.clone(), clock.clone()) {
+            panic!("{}", e);
+        }
+
+        if let Err(e) = pipe.publish_routing_table(&db) {
+            panic!("{}", e);
+        }
+
+        //skip analyzers disabled for poor health before this start
+        if let Err(e) = health_manager.execute(&db) {
+            panic!("{}", e);
+        }
+
+        if let Err(e) = load_sinks(&db, &mut flag_manager) {
+            panic!("{}", e);
+        }
+
+        if let Err(e) = flag_manager.refresh(&db) {
+            panic!("{}", e);
+        }
+
+        info!("initializing sanitizer");
+        if let Err(e) = sanitizer.initialize(&db) {
+            panic!("{}", e);
+        }
+
+        info!("initializing error classifier");
+        if let Err(e) = classifier.initialize(&db) {
+            panic!("{}", e);
+        }
+
+        info!("initializing parameter monitor");
+        if let Err(e) = parameter_monitor.initialize(&db) {
+            panic!("{}", e);
+        }
+
+        info!("initializing aggregator");
+        if let Err(e) = aggregator.initialize(&db) {
+            panic!("{}", e);
+        }
+
+        info!("initializing quality gates");
+        if let Err(e) = quality_gate.initialize(&db) {
             panic!("{}", e);
         }
 
@@ -92,64 +314,458 @@ fn main() {
         if let Err(e) = result_window.initialize(&db) {
            panic!("{}", e);
         }
+
+        info!("initializing snapshot manager");
+        if let Err(e) = snapshot_manager.initialize(&db) {
+            panic!("{}", e);
+        }
+    }
+
+    //relay flags to the flag manager through an on-disk spill queue
+    let spill_queue = match SpillQueue::open(&config.flag_spill_file) {
+        Ok(spill_queue) => Arc::new(Mutex::new(spill_queue)),
+        Err(e) => panic!("{}", e),
+    };
+
+    //supervise internal threads, restarting them after panics
+    let supervisor = Supervisor::new(config.max_thread_restarts as usize, metrics.clone(), crash_context.clone(), clock.clone());
+    let (relay_spill_queue, relay_metrics, relay_crash_context) = (spill_queue.clone(), metrics.clone(), crash_context.clone());
+    let relay_thread = supervisor.spawn("flag_relay", move || {
+        spill_queue::relay(analyzer_flag_rx.clone(), manager_flag_tx.clone(), relay_spill_queue.clone(), relay_metrics.clone(), relay_crash_context.clone());
+    });
+
+    if let Err(e) = relay_thread {
+        panic!("{}", e);
     }
 
     //create flag manager and start
     info!("initializing flag manager");
-    let (thread_username, thread_password) = (username.clone(), password.clone());
-    std::thread::spawn(move || {
-        let mut flag_buffer = Vec::new();
-        let mut flag_manager = FlagManager::new();
-        let process_flag_tick = chan::tick_ms(5 * 1000);
-
-        let client = match initialize_mongodb_client(&mongodb_ip_address, mongodb_port, &ca_file, &certificate_file, &key_file) {
-            Ok(client) => client,
+    let thread_config = config.clone();
+    let thread_crash_context = crash_context.clone();
+    let thread_metrics = metrics.clone();
+    let thread_clock = clock.clone();
+    let flag_manager = Mutex::new(flag_manager);
+    let (sink_reload_tx, sink_reload_rx) = chan::async();
+    let flag_thread = supervisor.spawn("flag_manager", move || {
+        run_flag_manager(&thread_config, &flag_manager, &flag_rx, &sink_reload_rx, &spill_queue, &thread_metrics, &thread_clock, &thread_crash_context);
+    });
+
+    if let Err(e) = flag_thread {
+        panic!("{}", e);
+    }
+
+    //start api
+    let (reload_tx, reload_rx) = chan::async();
+    if config.api_address.len() > 0 {
+        info!("starting api on {} with {} worker(s)", config.api_address, config.workers());
+        if let Err(e) = Api::new(client.clone(), &config.username, &config.password, &config.api_token, flag_store.clone(), metrics.clone(), pipe.statistics(), quality_gate.statuses(), reload_tx).start(&config.api_address, config.workers(), &supervisor) {
+            panic!("{}", e);
+        }
+    }
+
+    //create availability manager
+    let availability_manager = AvailabilityManager::new(clock.clone());
+
+    //create retention manager
+    let mut retention_manager = match config.retention_manager(clock.clone()) {
+        Ok(retention_manager) => retention_manager,
+        Err(e) => panic!("{}", e),
+    };
+
+    //create maintenance manager
+    let maintenance_manager = MaintenanceManager::new(config.maintenance_interval, clock.clone());
+
+    //create noise reporter
+    info!("initializing noise reporter");
+    let mut noise_reporter = match NoiseReporter::new(config.noise_report_size, &config.noise_digest_url, clock.clone()) {
+        Ok(noise_reporter) => noise_reporter,
+        Err(e) => panic!("{}", e),
+    };
+
+    {
+        let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+            Ok(db) => db,
             Err(e) => panic!("{}", e),
         };
 
-        loop {
-            chan_select! {
-                flag_rx.recv() -> flag => {
-                    if let Some(flag) = flag {
-                        flag_buffer.push(flag);
-                    }
-                },
-                process_flag_tick.recv() => {
-                    if flag_buffer.len() > 0 {
-                        let db = match initialize_db(&client, "proddle", &thread_username, &thread_password) {
-                            Ok(db) => db,
-                            Err(e) => {
-                                error!("{}", e);
-                                continue;
-                            },
-                        };
+        if let Err(e) = noise_reporter.initialize(&db) {
+            panic!("{}", e);
+        }
+    }
 
-                        for flag in flag_buffer.iter() {
-                            if let Err(e) = flag_manager.process_flag(flag, &db) {
-                                error!("{}", e);
+    //create incident manager
+    info!("initializing incident manager");
+    let incident_manager = IncidentManager::new(604800, config.incident_window as i64, clock.clone()); //7 days = 604800 seconds
+
+    //create fetch scheduler
+    let mut fetch_scheduler = FetchScheduler::new(config.update_flags_interval, config.min_poll_interval, config.max_poll_interval, config.fetch_jitter, clock.clone());
+
+    //start command loop
+    info!("TIPUP STARTED");
+    let update_flags_tick = chan::tick_ms(config.update_flags_interval * 1000);
+    let fetch_tick = chan::tick_ms(FETCH_TICK_MILLISECONDS);
+    let update_incidents_tick = chan::tick_ms(config.update_incidents_interval * 1000);
+    let snapshot_tick = chan::tick_ms(config.snapshot_interval * 1000);
+    let sla_tick = chan::tick_ms(config.sla_interval * 1000);
+    let availability_tick = chan::tick_ms(config.availability_interval * 1000);
+    let provider_tick = chan::tick_ms(config.provider_interval * 1000);
+    let analyzer_health_tick = chan::tick_ms(config.analyzer_health_interval * 1000);
+    let atlas_tick = chan::tick_ms(config.atlas_interval * 1000);
+    let retention_tick = chan::tick_ms(RETENTION_TICK_MILLISECONDS);
+    let maintenance_tick = chan::tick_ms(config.maintenance_interval * 1000);
+    let noise_report_tick = chan::tick_ms(config.noise_report_interval * 1000);
+    let target_stats_tick = chan::tick_ms(config.target_stats_interval * 1000);
+    loop {
+        chan_select! {
+            signal_rx.recv() => {
+                info!("received SIGHUP, reloading configuration");
+                if let Err(e) = reload(&args, &mut config, &mut result_filter, &mut fetch_scheduler, &client, &taxonomy, &sink_reload_tx) {
+                    error!("rejected configuration reload: {}", e);
+                }
+            },
+            reload_rx.recv() -> reply_tx => {
+                let reply_tx: Sender<Result<Vec<String>, String>> = match reply_tx {
+                    Some(reply_tx) => reply_tx,
+                    None => continue,
+                };
+
+                info!("received reload request, reloading configuration");
+                match reload(&args, &mut config, &mut result_filter, &mut fetch_scheduler, &client, &taxonomy, &sink_reload_tx) {
+                    Ok(changes) => reply_tx.send(Ok(changes)),
+                    Err(e) => {
+                        error!("rejected configuration reload: {}", e);
+                        reply_tx.send(Err(e.to_string()));
+                    },
+                }
+            },
+            update_flags_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = pipe.refresh(&db) {
+                    error!("{}", e);
+                }
+
+                //track measurements to poll, newly discovered ones spread over the interval
+                match fetch_keys(&db, &result_source, &result_filter) {
+                    Ok(keys) => fetch_scheduler.schedule(keys),
+                    Err(e) => error!("{}", e),
+                }
+
+                //evict cached state beyond the memory limit, half to the result window and a quarter to quality gates
+                if let Some(limit) = config.state_memory_limit() {
+                    resources::enforce_limit("result window", &mut *result_window.write().unwrap(), limit / 2);
+                    resources::enforce_limit("quality gate", &mut quality_gate, limit / 4);
+                }
+            },
+            fetch_tick.recv() => {
+                let keys = fetch_scheduler.due();
+                if keys.len() == 0 {
+                    continue;
+                }
+
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                let mut count = 0;
+                for key in keys.iter() {
+                    match fetch_results(&db, &result_source, &key.0, &key.1, config.fetch_limit, &pipe, &result_filter, &sanitizer, &classifier, &mut parameter_monitor, &mut aggregator, &mut quality_gate, &mut target_stats, result_window.clone(), &crash_context) {
+                        Ok((key_count, sampling_interval, backlogged)) => {
+                            count += key_count;
+                            fetch_scheduler.fetched(key, sampling_interval);
+                            if backlogged {
+                                fetch_scheduler.expedite(key);
                             }
+                        },
+                        Err(e) => {
+                            error!("{}", e);
+                            fetch_scheduler.fetched(key, None);
+                        },
+                    }
+                }
+
+                if count > 0 {
+                    info!("fetched {} new measurements(s) from {} vantage host measurement(s)", count, keys.len());
+                }
+
+                //summarize aggregation windows left open by hosts which stopped reporting, then
+                //advance their stored timestamps past the summarized results
+                let mut flushed_keys = BTreeSet::new();
+                for document in aggregator.flush_expired(clock.now()) {
+                    if let (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref measurement_class))) = (document.get("vantage_hostname"), document.get("measurement_class")) {
+                        flushed_keys.insert((hostname.to_owned(), measurement_class.to_owned()));
+                    }
+
+                    if let Err(e) = analyze_result(&pipe, &quality_gate, &mut target_stats, &result_window, document) {
+                        error!("{}", e);
+                    }
+                }
+
+                for &(ref hostname, ref measurement_class) in flushed_keys.iter() {
+                    if let Some(fetched_timestamp) = aggregator.fetched_timestamp(hostname, measurement_class) {
+                        if let Err(e) = update_watermark(&db, hostname, measurement_class, aggregator.watermark(hostname, measurement_class, fetched_timestamp)) {
+                            error!("{}", e);
                         }
-                        
-                        info!("wrote {} new flag(s)", flag_buffer.len());
-                        flag_buffer.clear();
                     }
-                },
-            }
+                }
+
+                if let Err(e) = pipe.flush_traces(&db) {
+                    error!("{}", e);
+                }
+
+                if let Err(e) = pipe.flush_derived_results(&db) {
+                    error!("{}", e);
+                }
+            },
+            update_incidents_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = incident_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            snapshot_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                let result_window = result_window.read().unwrap();
+                if let Err(e) = snapshot_manager.execute(&db, &result_window) {
+                    error!("{}", e);
+                }
+            },
+            sla_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = sla_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            availability_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = availability_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            provider_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = provider_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            analyzer_health_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = health_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            atlas_tick.recv() => {
+                let atlas_source = match atlas_source {
+                    Some(ref atlas_source) => atlas_source,
+                    None => continue,
+                };
+
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = atlas_source.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            retention_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = retention_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            maintenance_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = maintenance_manager.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            noise_report_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = noise_reporter.execute(&db) {
+                    error!("{}", e);
+                }
+            },
+            target_stats_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = target_stats.flush(&db) {
+                    error!("{}", e);
+                }
+            },
         }
-    });
+    }
+}
 
-    //create event manager
-    info!("initializing event manager");
-    let event_manager = EventManager::new(604800); //7 days = 604800 seconds
+fn run_flag_manager(config: &Config, flag_manager: &Mutex<FlagManager>, flag_rx: &Receiver<Flag>, sink_reload_rx: &Receiver<()>, spill_queue: &Mutex<SpillQueue>, metrics: &Metrics, clock: &Arc<Clock>, crash_context: &CrashContext) {
+    //recover flag manager state left by a panicked run
+    let mut flag_manager = match flag_manager.lock() {
+        Ok(flag_manager) => flag_manager,
+        Err(e) => e.into_inner(),
+    };
+
+    let mut flag_buffer = Vec::new();
+    let process_flag_tick = chan::tick_ms(5 * 1000);
+    let escalation_tick = chan::tick_ms(config.escalation_interval * 1000);
+
+    let client = match initialize_mongodb_client(config) {
+        Ok(client) => client,
+        Err(e) => panic!("{}", e),
+    };
 
-    //start command loop
-    info!("TIPUP STARTED");
-    let update_flags_tick = chan::tick_ms(update_flags_interval * 1000);
-    let update_events_tick = chan::tick_ms(update_events_interval * 1000);
     loop {
         chan_select! {
-            update_flags_tick.recv() => {
-                let db = match initialize_db(&client, "proddle", &username, &password) {
+            flag_rx.recv() -> flag => {
+                let mut flag = match flag {
+                    Some(flag) => flag,
+                    None => continue,
+                };
+
+                if flag_manager.seen(&flag) {
+                    flag_buffer.push(flag);
+                    continue;
+                }
+
+                //process first occurrences immediately rather than waiting for the next tick
+                match flag_manager.first_occurrence(&flag) {
+                    Ok(true) => match flag_manager.process_flag(&mut flag) {
+                        Ok(_) => info!("wrote first occurrence flag for analyzer '{}' on domain '{}'", flag.analyzer, flag.domain),
+                        Err(e) => error!("{}", e),
+                    },
+                    Ok(false) => flag_buffer.push(flag),
+                    Err(e) => {
+                        error!("{}", e);
+                        flag_buffer.push(flag);
+                    },
+                }
+            },
+            process_flag_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    },
+                };
+
+                if flag_buffer.len() > 0 {
+                    if let Err(e) = flag_manager.refresh(&db) {
+                        error!("{}", e);
+                    }
+
+                    //spill flags which failed to process so they are retried rather than lost
+                    let mut count = 0;
+                    for mut flag in flag_buffer.drain(..) {
+                        match flag_manager.process_flag(&mut flag) {
+                            Ok(true) => count += 1,
+                            Ok(false) => {},
+                            Err(e) => {
+                                error!("{}", e);
+                                if let Err(e) = spill_queue.lock().unwrap().push(flag) {
+                                    error!("{}", e);
+                                }
+                            },
+                        }
+                    }
+
+                    info!("wrote {} new flag(s)", count);
+                }
+
+                flag_manager.flush_deferred();
+                flag_manager.flush_incident_updates(&db);
+                crash_context.set_queue_depth("flag_buffer", flag_buffer.len());
+                crash_context.set_queue_depth("deferred_flags", flag_manager.deferred_count());
+                metrics.set("tipup_flag_queue_depth", &[("queue", "flag_buffer")], flag_buffer.len() as f64);
+                metrics.set("tipup_flag_queue_depth", &[("queue", "deferred_flags")], flag_manager.deferred_count() as f64);
+                if let Some(limit) = config.state_memory_limit() {
+                    resources::enforce_limit("flag cache", &mut *flag_manager, limit / 4);
+                }
+            },
+            sink_reload_rx.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
                     Ok(db) => db,
                     Err(e) => {
                         error!("{}", e);
@@ -157,12 +773,14 @@ fn main() {
                     },
                 };
 
-                if let Err(e) = fetch_results(&db, &pipe, result_window.clone()) {
+                //sinks were validated with the reload, pending incident updates of replaced sinks are dropped
+                flag_manager.clear_sinks();
+                if let Err(e) = load_sinks(&db, &mut flag_manager) {
                     error!("{}", e);
                 }
             },
-            update_events_tick.recv() => {
-                /*let db = match initialize_db(&client, "proddle", &username, &password) {
+            escalation_tick.recv() => {
+                let db = match initialize_db(&client, "proddle", &config.username, &config.password) {
                     Ok(db) => db,
                     Err(e) => {
                         error!("{}", e);
@@ -170,20 +788,77 @@ fn main() {
                     },
                 };
 
-                if let Err(e) = event_manager.execute(&db) {
+                if let Err(e) = flag_manager.refresh(&db) {
                     error!("{}", e);
-                }*/
+                }
+
+                match flag_manager.escalate() {
+                    Ok(0) => {},
+                    Ok(count) => info!("escalated {} flag(s)", count),
+                    Err(e) => error!("{}", e),
+                }
+
+                //raise confirmation durations of analyzers producing mostly self healing flags
+                if config.self_healing_adjust > 0.0 {
+                    if let Err(e) = self_healing::adjust(&db, config.self_healing_adjust, config.self_healing_window, clock.now()) {
+                        error!("{}", e);
+                    }
+                }
             },
         }
     }
 }
 
-fn initialize_mongodb_client(mongodb_ip_address: &str, mongodb_port: u16, ca_file: &str, certificate_file: &str, key_file: &str) -> Result<Arc<ClientInner>, mongodb::Error> {
-    if ca_file.eq("") && certificate_file.eq("") && key_file.eq("") {
-        Client::connect(mongodb_ip_address, mongodb_port)
+//rereads the configuration, applying it only once arguments and stored definitions validate, and
+//logs every changed setting, noting those which take effect on restart
+fn reload(args: &Vec<String>, config: &mut Config, result_filter: &mut ResultFilter, fetch_scheduler: &mut FetchScheduler,
+        client: &Client, taxonomy: &SeverityTaxonomy, sink_reload_tx: &Sender<()>) -> Result<Vec<String>, TipupError> {
+    let reloaded = try!(config::reload(args));
+    let reloaded_filter = try!(reloaded.result_filter());
+    {
+        let db = try!(initialize_db(client, "proddle", &config.username, &config.password));
+        let mut validation = Validation::new();
+        try!(config::validate_definitions(&db, taxonomy, &mut validation));
+        try!(validation.check());
+    }
+
+    try!(log_level::set(&reloaded.log_level));
+    fetch_scheduler.configure(reloaded.min_poll_interval, reloaded.max_poll_interval, reloaded.fetch_jitter);
+    *result_filter = reloaded_filter;
+    sink_reload_tx.send(());
+
+    let (applied, restart) = config.changes(&reloaded);
+    config.fetch_jitter = reloaded.fetch_jitter;
+    config.min_poll_interval = reloaded.min_poll_interval;
+    config.max_poll_interval = reloaded.max_poll_interval;
+    config.fetch_limit = reloaded.fetch_limit;
+    config.hostname_allow = reloaded.hostname_allow;
+    config.hostname_deny = reloaded.hostname_deny;
+    config.measurement_allow = reloaded.measurement_allow;
+    config.measurement_deny = reloaded.measurement_deny;
+    config.log_level = reloaded.log_level;
+
+    let mut changes = Vec::new();
+    for change in applied {
+        info!("reload applied {}", change);
+        changes.push(change);
+    }
+
+    for change in restart {
+        warn!("reload ignored {}, which takes effect on restart", change);
+        changes.push(format!("{} (requires restart)", change));
+    }
+
+    info!("reloaded configuration and sinks with {} changed setting(s)", changes.len());
+    Ok(changes)
+}
+
+fn initialize_mongodb_client(config: &Config) -> Result<Arc<ClientInner>, mongodb::Error> {
+    if config.ca_file.eq("") && config.certificate_file.eq("") && config.key_file.eq("") {
+        Client::connect(&config.mongodb_ip_address, config.mongodb_port)
     } else {
-        let client_options = ClientOptions::with_ssl(ca_file, certificate_file, key_file, true);
-        Client::connect_with_options(mongodb_ip_address, mongodb_port, client_options)
+        let client_options = ClientOptions::with_ssl(&config.ca_file, &config.certificate_file, &config.key_file, true);
+        Client::connect_with_options(&config.mongodb_ip_address, config.mongodb_port, client_options)
     }
 }
 
@@ -193,57 +868,62 @@ fn initialize_db(client: &Client, db_name: &str, username: &str, password: &str)
     Ok(db)
 }
 
-fn load_analyzers(db: &Database, pipe: &mut Pipe, flag_tx: Sender<Flag>, result_window: Arc<RwLock<ResultWindow>>) -> Result<(), TipupError> {
+fn load_analyzers(db: &Database, pipe: &mut Pipe, flag_tx: Sender<Flag>, result_window: Arc<RwLock<ResultWindow>>, clock: Arc<Clock>) -> Result<(), TipupError> {
     //query mongodb for analyzer definitions
     let mut count = 0;
+    let mut classes = HashMap::new();
+    let mut canaries = Vec::new();
+    let mut derivations = Vec::new();
+    let mut dependencies = Vec::new();
     let cursor = try!(db.collection("analyzers").find(None, None));
     for document in cursor {
         //parse document
         let document = try!(document);
         info!("loading analyzer: {:?}", document);
 
-        let name = match document.get("name") {
-            Some(&Bson::String(ref name)) => name,
-            _ => return Err(TipupError::from("failed to parse analyzer name")),
-        };
+        //create analyzer and add to pipe
+        let (name, measurement_class, analyzer) = try!(create_analyzer(&document, flag_tx.clone(), result_window.clone(), clock.clone()));
+        if let Some(&Bson::String(ref class)) = document.get("class") {
+            classes.insert(name.clone(), class.to_owned());
+        }
 
-        let class = match document.get("class") {
-            Some(&Bson::String(ref class)) => class,
-            _ => return Err(TipupError::from("failed to parse analyzer class")),
-        };
+        if let Some(canary) = try!(Canary::parse(&document)) {
+            canaries.push(canary);
+        }
 
-        let status = match document.get("status") {
-            Some(&Bson::String(ref status)) => status,
-            _ => return Err(TipupError::from("failed to parse analyzer status")),
-        };
+        if let Some(derivation) = try!(Derivation::parse(&document)) {
+            derivations.push(derivation);
+        }
 
-        let measurement_class = match document.get("measurement_class") {
-            Some(&Bson::String(ref measurement_class)) => measurement_class,
-            _ => return Err(TipupError::from("failed to parse analyzer measurement_class")),
-        };
+        if let Some(dependency) = try!(Dependency::parse(&document)) {
+            dependencies.push(dependency);
+        }
 
-        let fields: Vec<String> = match document.get("fields") {
-            Some(&Bson::Array(ref fields)) => fields.iter().map(|x| x.to_string().replace("\"", "")).collect(),
-            _ => return Err(TipupError::from("failed to parse analyzer fields")),
-        };
+        try!(pipe.add_analyzer(name, measurement_class, analyzer));
+        count += 1;
 
-        let parameters = match document.get("parameters") {
-            Some(&Bson::Array(ref parameters)) => parameters,
-            _ => return Err(TipupError::from("failed to parse analyzer parameters")),
-        };
+        //track definition changes so tuning can be rolled back
+        if let Err(e) = analyzer_revision::record(db, &document, clock.now()) {
+            error!("failed to record analyzer revision: {}", e);
+        }
+    }
 
-        //create analyzer
-        let analyzer = match class.as_ref() {
-            "ErrorAnalyzer" => Box::new(try!(ErrorAnalyzer::new(name, status, fields, flag_tx.clone()))) as Box<Analyzer>,
-            //"StdDevAnalyzer" => Box::new(try!(StdDevAnalyzer::new(name, parameters, result_window.clone(), flag_tx.clone()))) as Box<Analyzer>,
-            _ => return Err(TipupError::from("unknown analyzer class")),
-        };
+    //split keys between canary analyzers and the baseline version they evaluate
+    for canary in canaries {
+        if classes.get(&canary.name) != classes.get(&canary.baseline) {
+            return Err(TipupError::from(format!("canary '{}' must be of the same class as baseline analyzer '{}'", canary.name, canary.baseline)));
+        }
 
-        //add analyzer to pipe
-        try!(pipe.add_analyzer(name.to_owned(), measurement_class.to_owned(), analyzer));
-        count += 1;
+        try!(pipe.add_canary(canary));
     }
 
+    for derivation in derivations {
+        try!(pipe.add_derivation(derivation));
+    }
+
+    //order dispatch by dependencies once every analyzer and derivation is known
+    try!(pipe.add_dependencies(dependencies));
+
     if count > 0 {
         info!("loaded {} analyzer(s)", count);
     }
@@ -251,99 +931,420 @@ fn load_analyzers(db: &Database, pipe: &mut Pipe, flag_tx: Sender<Flag>, result_
     Ok(())
 }
 
-fn fetch_results(db: &Database, pipe: &Pipe, result_window: Arc<RwLock<ResultWindow>>) -> Result<(), TipupError> {
-    //iterate over distinct hostnames for measurements
+fn load_sinks(db: &Database, flag_manager: &mut FlagManager) -> Result<(), TipupError> {
+    //query mongodb for sink definitions
     let mut count = 0;
-    let hostname_cursor = try!(db.collection("measurements").distinct("vantage_hostname", None, None));
-    for hostname_document in hostname_cursor {
-        let hostname = match hostname_document {
-            Bson::String(ref hostname) => hostname,
-            _ => continue,
+    let cursor = try!(db.collection("sinks").find(None, None));
+    for document in cursor {
+        //parse document
+        let document = try!(document);
+        info!("loading sink: {:?}", document);
+
+        //create sink and add to flag manager
+        let (name, selector, sink, incident_update_interval) = try!(create_sink(&document));
+        try!(flag_manager.add_sink(name, selector, sink, incident_update_interval));
+        count += 1;
+    }
+
+    if count > 0 {
+        info!("loaded {} sink(s)", count);
+    }
+
+    Ok(())
+}
+
+fn create_analyzer(document: &OrderedDocument, flag_tx: Sender<Flag>, result_window: Arc<RwLock<ResultWindow>>, clock: Arc<Clock>) -> Result<(String, String, Box<Analyzer>), TipupError> {
+    let name = match document.get("name") {
+        Some(&Bson::String(ref name)) => name,
+        _ => return Err(TipupError::from("failed to parse analyzer name")),
+    };
+
+    let class = match document.get("class") {
+        Some(&Bson::String(ref class)) => class,
+        _ => return Err(TipupError::from("failed to parse analyzer class")),
+    };
+
+    let status = match document.get("status") {
+        Some(&Bson::String(ref status)) => status,
+        _ => return Err(TipupError::from("failed to parse analyzer status")),
+    };
+
+    let measurement_class = match document.get("measurement_class") {
+        Some(&Bson::String(ref measurement_class)) => measurement_class,
+        _ => return Err(TipupError::from("failed to parse analyzer measurement_class")),
+    };
+
+    //fields are optional, analyzers checking error fields default to the measurement error message
+    let fields: Vec<String> = match document.get("fields") {
+        Some(&Bson::Array(ref fields)) => fields.iter().map(|x| x.to_string().replace("\"", "")).collect(),
+        None => Vec::new(),
+        _ => return Err(TipupError::from("failed to parse analyzer fields")),
+    };
+
+    let parameters = &try!(parse_parameters(name, document));
+
+    //analyzers partitioned by key fields send flags through the keyed analyzer to restore their domain
+    let key_fields = try!(partition::parse_key_fields(document));
+    let (analyzer_flag_tx, keyed_flag_rx) = match key_fields.len() {
+        0 => (flag_tx.clone(), None),
+        _ => {
+            let (analyzer_flag_tx, keyed_flag_rx) = chan::async();
+            (analyzer_flag_tx, Some(keyed_flag_rx))
+        },
+    };
+
+    //create analyzer, evaluating a variant per time window override if any
+    let analyzer = match document.get("overrides") {
+        Some(&Bson::Array(ref overrides)) => try!(build_windowed_analyzer(class, name, status, fields, parameters, &key_fields, overrides, analyzer_flag_tx, result_window, clock)),
+        None => try!(build_analyzer(class, name, status, fields, parameters, &key_fields, analyzer_flag_tx, result_window, clock)),
+        _ => return Err(TipupError::from("failed to parse analyzer overrides")),
+    };
+
+    let analyzer = match keyed_flag_rx {
+        Some(keyed_flag_rx) => Box::new(KeyedAnalyzer::new(analyzer, keyed_flag_rx, key_fields, flag_tx)) as Box<Analyzer>,
+        None => analyzer,
+    };
+
+    Ok((name.to_owned(), measurement_class.to_owned(), analyzer))
+}
+
+fn parse_parameters(name: &str, document: &OrderedDocument) -> Result<OrderedDocument, TipupError> {
+    match document.get("parameters") {
+        Some(&Bson::Document(ref parameters)) => Ok(parameters.clone()),
+        //legacy definitions hold an array, whose documents are merged in order
+        Some(&Bson::Array(ref parameter_array)) => {
+            let mut parameters = doc!();
+            for parameter in parameter_array.iter() {
+                match parameter {
+                    &Bson::Document(ref parameter) => {
+                        for (key, value) in parameter.iter() {
+                            parameters.insert(key.to_owned(), value.clone());
+                        }
+                    },
+                    _ => warn!("ignoring non-document parameter {} of analyzer '{}'", parameter, name),
+                }
+            }
+
+            Ok(parameters)
+        },
+        None => Ok(doc!()),
+        _ => Err(TipupError::from("failed to parse analyzer parameters")),
+    }
+}
+
+fn build_windowed_analyzer(class: &str, name: &str, status: &str, fields: Vec<String>, parameters: &OrderedDocument, key_fields: &Vec<Vec<String>>, overrides: &Vec<Bson>, flag_tx: Sender<Flag>, result_window: Arc<RwLock<ResultWindow>>, clock: Arc<Clock>) -> Result<Box<Analyzer>, TipupError> {
+    let (default_flag_tx, default_flag_rx) = chan::async();
+    let default = try!(build_analyzer(class, name, status, fields.clone(), parameters, key_fields, default_flag_tx, result_window.clone(), clock.clone()));
+    let mut variants = Vec::new();
+    for window_override in overrides.iter() {
+        let window_override = match window_override {
+            &Bson::Document(ref window_override) => window_override,
+            _ => return Err(TipupError::from("failed to parse analyzer override")),
+        };
+
+        let window = match window_override.get("window") {
+            Some(&Bson::String(ref window)) => window.to_owned(),
+            _ => return Err(TipupError::from("failed to parse analyzer override window")),
         };
 
-        //query db for timestamp of last seen result
-        let search_document = Some(doc!("vantage_hostname" => hostname));
-        let document = try!(db.collection("analyzed_measurements").find_one(search_document, None));
-        let timestamp = match document {
-            Some(document) => {
-                match document.get("timestamp") {
-                    Some(&Bson::I64(timestamp)) => timestamp,
-                    _ => return Err(TipupError::from(format!("failed to parse 'timestamp' value in analyzed_measurements for host '{}'", hostname))),
+        let status = match window_override.get("status") {
+            Some(&Bson::String(ref status)) => status,
+            None => status,
+            _ => return Err(TipupError::from("failed to parse analyzer override status")),
+        };
+
+        //override parameters replace the equally named analyzer parameters
+        let mut window_parameters = parameters.clone();
+        match window_override.get("parameters") {
+            Some(&Bson::Document(ref override_parameters)) => {
+                for (key, value) in override_parameters.iter() {
+                    window_parameters.insert(key.to_owned(), value.clone());
                 }
             },
-            None => 0,
-        };
+            None => {},
+            _ => return Err(TipupError::from("failed to parse analyzer override parameters")),
+        }
 
-        //iterate over newest measurements
-        let gt = doc!("$gt" => timestamp);
-        let search_document = Some(doc!(
-            "vantage_hostname" => hostname,
-            "timestamp" => gt
-        ));
-
-        //create find options
-        let negative_one = -1;
-        let sort_document = Some(doc!("timestamp" => negative_one));
-        let find_options = Some(FindOptions {
-            allow_partial_results: false,
-            no_cursor_timeout: false,
-            oplog_replay: false,
-            skip: None,
-            limit: None,
-            cursor_type: CursorType::NonTailable,
-            batch_size: None,
-            comment: None,
-            max_time_ms: None,
-            modifiers: None,
-            projection: None,
-            sort: sort_document,
-            read_preference: None,
-        });
-
-        //iterate over new measurements
-        let cursor = try!(db.collection("measurements").find(search_document, find_options));
-        let mut max_timestamp = -1;
-        for document in cursor {
-            let document = try!(document);
-            if let Err(e) = pipe.send_measurement(&document) {
-                panic!("document:{:?} err:{}", document, e);
-            }
+        let (variant_flag_tx, variant_flag_rx) = chan::async();
+        let variant = try!(build_analyzer(class, name, status, fields.clone(), &window_parameters, key_fields, variant_flag_tx, result_window.clone(), clock.clone()));
+        variants.push((window, variant, variant_flag_rx));
+    }
+
+    Ok(Box::new(WindowedAnalyzer::new((default, default_flag_rx), variants, flag_tx)) as Box<Analyzer>)
+}
+
+fn build_analyzer(class: &str, name: &str, status: &str, fields: Vec<String>, parameters: &OrderedDocument, key_fields: &Vec<Vec<String>>, flag_tx: Sender<Flag>, result_window: Arc<RwLock<ResultWindow>>, clock: Arc<Clock>) -> Result<Box<Analyzer>, TipupError> {
+    let analyzer = match class {
+        "BurstAnalyzer" => Box::new(try!(BurstAnalyzer::new(name, status, fields, parameters, flag_tx))) as Box<Analyzer>,
+        "ChangePointAnalyzer" => Box::new(try!(ChangePointAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "ConsensusAnalyzer" => Box::new(try!(ConsensusAnalyzer::new(name, status, fields, parameters, flag_tx))) as Box<Analyzer>,
+        "ContentChangeAnalyzer" => Box::new(try!(ContentChangeAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "CorrelationAnalyzer" => Box::new(try!(CorrelationAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "CusumAnalyzer" => Box::new(try!(CusumAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "DistributionDriftAnalyzer" => Box::new(try!(DistributionDriftAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "DivergenceAnalyzer" => Box::new(try!(DivergenceAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "DnsFailureAnalyzer" => Box::new(try!(DnsFailureAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "EntropyAnalyzer" => Box::new(try!(EntropyAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "ErrorAnalyzer" => Box::new(try!(ErrorAnalyzer::new(name, status, fields, flag_tx))) as Box<Analyzer>,
+        "EsdAnalyzer" => Box::new(try!(EsdAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "EwmaAnalyzer" => Box::new(try!(EwmaAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "FlapAnalyzer" => Box::new(try!(FlapAnalyzer::new(name, status, fields, parameters, flag_tx))) as Box<Analyzer>,
+        "GeoDnsAnalyzer" => Box::new(try!(GeoDnsAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "HoltWintersAnalyzer" => Box::new(try!(HoltWintersAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "HttpStatusAnalyzer" => Box::new(try!(HttpStatusAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "IsolationForestAnalyzer" => Box::new(try!(IsolationForestAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "JitterAnalyzer" => Box::new(try!(JitterAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "KMeansAnalyzer" => Box::new(try!(KMeansAnalyzer::new(name, status, parameters, flag_tx, clock))) as Box<Analyzer>,
+        "LatencyPathAnalyzer" => Box::new(try!(LatencyPathAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "MarkovAnalyzer" => Box::new(try!(MarkovAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "MovingAverageAnalyzer" => Box::new(try!(MovingAverageAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "PacketLossAnalyzer" => Box::new(try!(PacketLossAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "PercentileAnalyzer" => Box::new(try!(PercentileAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "RateOfChangeAnalyzer" => Box::new(try!(RateOfChangeAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "StdDevAnalyzer" => Box::new(try!(StdDevAnalyzer::new(name, status, parameters, key_fields, result_window, flag_tx))) as Box<Analyzer>,
+        "ThresholdAnalyzer" => Box::new(try!(ThresholdAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "TlsExpiryAnalyzer" => Box::new(try!(TlsExpiryAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "TrendAnalyzer" => Box::new(try!(TrendAnalyzer::new(name, status, parameters, flag_tx))) as Box<Analyzer>,
+        "UptimeAnalyzer" => Box::new(try!(UptimeAnalyzer::new(name, status, fields, parameters, flag_tx))) as Box<Analyzer>,
+        _ => return Err(TipupError::from(format!("unknown analyzer class '{}'", class))),
+    };
+
+    Ok(analyzer)
+}
+
+fn create_sink(document: &OrderedDocument) -> Result<(String, LabelSelector, Box<Sink + Send>, Option<i64>), TipupError> {
+    let name = match document.get("name") {
+        Some(&Bson::String(ref name)) => name,
+        _ => return Err(TipupError::from("failed to parse sink name")),
+    };
+
+    let class = match document.get("class") {
+        Some(&Bson::String(ref class)) => class,
+        _ => return Err(TipupError::from("failed to parse sink class")),
+    };
+
+    let selector = match document.get("selector") {
+        Some(&Bson::String(ref selector)) => try!(LabelSelector::parse(selector)),
+        None => try!(LabelSelector::parse("")),
+        _ => return Err(TipupError::from("failed to parse sink selector")),
+    };
 
+    let empty_parameters = doc!();
+    let parameters = match document.get("parameters") {
+        Some(&Bson::Document(ref parameters)) => parameters,
+        None => &empty_parameters,
+        _ => return Err(TipupError::from("failed to parse sink parameters")),
+    };
+
+    //seconds between notifications of an incident, coalescing its flags, none notifies each flag
+    let incident_update_interval = match document.get("incident_update_interval") {
+        Some(&Bson::I32(interval)) if interval > 0 => Some(interval as i64),
+        Some(&Bson::I64(interval)) if interval > 0 => Some(interval),
+        None => None,
+        _ => return Err(TipupError::from("failed to parse sink incident_update_interval as positive seconds")),
+    };
+
+    //create sink
+    let sink = match class.as_ref() {
+        "JiraSink" => Box::new(try!(JiraSink::new(parameters))) as Box<Sink + Send>,
+        "LogSink" => Box::new(try!(LogSink::new(name))) as Box<Sink + Send>,
+        "NdjsonSink" => Box::new(try!(NdjsonSink::new(parameters))) as Box<Sink + Send>,
+        "WebhookSink" => Box::new(try!(WebhookSink::new(parameters))) as Box<Sink + Send>,
+        _ => return Err(TipupError::from(format!("unknown sink class '{}'", class))),
+    };
+
+    Ok((name.to_owned(), selector, sink, incident_update_interval))
+}
+
+fn fetch_keys(db: &Database, result_source: &ResultSource, result_filter: &ResultFilter) -> Result<Vec<(String, String)>, TipupError> {
+    //distinct (hostname, measurement_class) pairs of measurements, denied classes included so
+    //their results are still marked as analyzed
+    let mut keys = Vec::new();
+    let id_document = doc!("vantage_hostname" => "$vantage_hostname", "measurement_class" => "$measurement_class");
+    let stages = vec!(doc!("$group" => { "_id" => id_document }));
+    for document in try!(result_source.aggregate(db, stages)) {
+        match document.get("_id") {
+            Some(&Bson::Document(ref id_document)) => match (id_document.get("vantage_hostname"), id_document.get("measurement_class")) {
+                (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref measurement_class))) => if result_filter.hostname_allowed(hostname) {
+                    keys.push((hostname.to_owned(), measurement_class.to_owned()));
+                },
+                _ => continue,
+            },
+            _ => continue,
+        }
+    }
+
+    Ok(keys)
+}
+
+fn fetch_results(db: &Database, result_source: &ResultSource, hostname: &str, measurement_class: &str, fetch_limit: u32, pipe: &Pipe, result_filter: &ResultFilter, sanitizer: &Sanitizer, classifier: &ErrorClassifier, parameter_monitor: &mut ParameterMonitor, aggregator: &mut Aggregator, quality_gate: &mut QualityGate, target_stats: &mut TargetStats, result_window: Arc<RwLock<ResultWindow>>, crash_context: &CrashContext) -> Result<(usize, Option<f64>, bool), TipupError> {
+    //query db for timestamp of last seen result, falling back to the host wide timestamp recorded
+    //before results were fetched per measurement class
+    let search_document = Some(doc!("vantage_hostname" => hostname, "measurement_class" => measurement_class));
+    let mut document = try!(db.collection("analyzed_measurements").find_one(search_document, None));
+    if document.is_none() {
+        let exists = doc!("$exists" => false);
+        let search_document = Some(doc!("vantage_hostname" => hostname, "measurement_class" => exists));
+        document = try!(db.collection("analyzed_measurements").find_one(search_document, None));
+    }
+
+    let timestamp = match document {
+        Some(document) => {
             match document.get("timestamp") {
-                Some(&Bson::I64(result_timestamp)) => max_timestamp = std::cmp::max(max_timestamp, result_timestamp),
-                _ => return Err(TipupError::from("failed to parse 'timestamp' value in result")),
+                Some(&Bson::I64(timestamp)) => timestamp,
+                _ => return Err(TipupError::from(format!("failed to parse 'timestamp' value in analyzed_measurements for host '{}'", hostname))),
             }
+        },
+        None => 0,
+    };
 
-            //add result to result window
-            {
-                let mut result_window = result_window.write().unwrap();
-                try!(result_window.add_result(document))
-            }
+    //continue past results buffered in open aggregation windows, held behind the stored timestamp
+    let timestamp = match aggregator.fetched_timestamp(hostname, measurement_class) {
+        Some(fetched_timestamp) => std::cmp::max(timestamp, fetched_timestamp),
+        None => timestamp,
+    };
+
+    //iterate over measurements newer than the last seen result
+    let gt = doc!("$gt" => timestamp);
+    let search_document = doc!(
+        "vantage_hostname" => hostname,
+        "measurement_class" => measurement_class,
+        "timestamp" => gt
+    );
+
+    //oldest first so a limited fetch continues where it stopped
+    let positive_one = 1;
+    let sort_document = doc!("timestamp" => positive_one);
+    let limit = match fetch_limit {
+        0 => None,
+        fetch_limit => Some(fetch_limit as i64),
+    };
+
+    let mut documents = try!(result_source.find(db, search_document, sort_document, limit));
+
+    //a full batch leaves a backlog, whose results sharing the batch's last timestamp are deferred
+    //to the next fetch so the timestamp only advances past completely processed results
+    let backlogged = fetch_limit > 0 && documents.len() == fetch_limit as usize;
+    if backlogged {
+        let last_timestamp = documents.last().and_then(|x| x.get("timestamp").cloned());
+        if documents.iter().any(|x| x.get("timestamp") != last_timestamp.as_ref()) {
+            documents.retain(|x| x.get("timestamp") != last_timestamp.as_ref());
+        } else {
+            warn!("more than {} results of '{}' measurements on host '{}' share a timestamp, the remainder are skipped", fetch_limit, measurement_class, hostname);
+        }
+    }
+
+    //iterate over new measurements
+    let (mut count, mut max_timestamp) = (0, -1);
+    let (mut results, mut domains) = (0, HashSet::new());
+    for document in documents {
+        crash_context.set_document(&document);
+        match document.get("timestamp") {
+            Some(&Bson::I64(result_timestamp)) => max_timestamp = std::cmp::max(max_timestamp, result_timestamp),
+            _ => return Err(TipupError::from("failed to parse 'timestamp' value in result")),
+        }
 
-            count += 1;
+        results += 1;
+        if let Some(&Bson::String(ref domain)) = document.get("measurement_domain") {
+            domains.insert(domain.to_owned());
         }
 
-        //update db with most recenlty analyzed result timestamp
-        if max_timestamp != -1 {
-            let search_document = doc!("vantage_hostname" => hostname);
-            let update_timestamp_document = doc!("timestamp" => max_timestamp);
-            let update_document = doc!("$set" => update_timestamp_document);
-            let update_options = Some(FindOneAndUpdateOptions {
-                return_document: None,
-                max_time_ms: None,
-                projection: None,
-                sort: None,
-                upsert: Some(true),
-                write_concern: None,
-            });
+        //skip excluded measurements after advancing the timestamp past them
+        match document.get("measurement_class") {
+            Some(&Bson::String(ref measurement_class)) if !result_filter.measurement_allowed(measurement_class) => continue,
+            _ => {},
+        }
+
+        //sanitize invalid values before analyzers see the result, tracking rejections for quality gates
+        let sample = quality_gate.sample(&document);
+        let mut document = match try!(sanitizer.sanitize(document)) {
+            Some(document) => document,
+            None => {
+                if let Some(sample) = sample {
+                    quality_gate.record(sample, false);
+                }
+
+                continue;
+            },
+        };
 
-            try!(db.collection("analyzed_measurements").find_one_and_update(search_document, update_document, update_options));
+        if let Some(sample) = sample {
+            quality_gate.record(sample, true);
         }
+
+        //categorize free-text measurement errors
+        classifier.classify(&mut document);
+
+        //reset learned state when probes change measurement parameters
+        if let Some((measurement_class, hostname, domain)) = try!(parameter_monitor.check(&document)) {
+            pipe.reset(&measurement_class, &hostname, &domain);
+            result_window.write().unwrap().reset(&hostname, &domain);
+        }
+
+        //buffer high-frequency results into window summaries
+        let document = match aggregator.aggregate(document) {
+            Ok(Some(document)) => document,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("skipping unaggregatable '{}' result on host '{}': {}", measurement_class, hostname, e);
+                continue;
+            },
+        };
+
+        try!(analyze_result(pipe, quality_gate, target_stats, &result_window, document));
+        count += 1;
     }
 
-    if count > 0 {
-        info!("fetched {} new measurements(s)", count);
+    //analyze summaries of completed aggregation windows
+    for document in aggregator.flush(hostname, measurement_class, max_timestamp) {
+        try!(analyze_result(pipe, quality_gate, target_stats, &result_window, document));
+        count += 1;
+    }
+
+    //update db with most recenlty analyzed result timestamp
+    if max_timestamp != -1 {
+        aggregator.fetched(hostname, measurement_class, max_timestamp);
+        try!(update_watermark(db, hostname, measurement_class, aggregator.watermark(hostname, measurement_class, max_timestamp)));
     }
 
+    //each domain is sampled once per interval, unknown until a previous fetch bounds the results
+    let sampling_interval = match (timestamp > 0 && results > 0, max_timestamp - timestamp) {
+        (true, elapsed) if elapsed > 0 => Some(elapsed as f64 * domains.len().max(1) as f64 / results as f64),
+        _ => None,
+    };
+
+    Ok((count, sampling_interval, backlogged))
+}
+
+fn update_watermark(db: &Database, hostname: &str, measurement_class: &str, timestamp: i64) -> Result<(), TipupError> {
+    let search_document = doc!("vantage_hostname" => hostname, "measurement_class" => measurement_class);
+    let update_timestamp_document = doc!("timestamp" => timestamp);
+    let update_document = doc!("$set" => update_timestamp_document);
+    let update_options = Some(FindOneAndUpdateOptions {
+        return_document: None,
+        max_time_ms: None,
+        projection: None,
+        sort: None,
+        upsert: Some(true),
+        write_concern: None,
+    });
+
+    try!(db.collection("analyzed_measurements").find_one_and_update(search_document, update_document, update_options));
     Ok(())
 }
+
+fn analyze_result(pipe: &Pipe, quality_gate: &QualityGate, target_stats: &mut TargetStats, result_window: &Arc<RwLock<ResultWindow>>, document: OrderedDocument) -> Result<(), TipupError> {
+    //keys failing their quality gate still accrue results but are not analyzed
+    if quality_gate.passes(&document) {
+        if let Err(e) = pipe.send_measurement(&document) {
+            panic!("document:{:?} err:{}", document, e);
+        }
+    }
+
+    //summarize latency of every result, including those of gated keys
+    target_stats.record(&document);
+
+    //add result to result window
+    let mut result_window = result_window.write().unwrap();
+    result_window.add_result(document)
+}
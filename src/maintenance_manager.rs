@@ -0,0 +1,235 @@
+use bson::{Bson, Document};
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use time::{self, Timespec};
+
+use clock::Clock;
+use error::TipupError;
+use label::LabelSelector;
+
+use std::sync::Arc;
+
+const DAYS: [&'static str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+//(name, minimum, maximum) of the minute, hour, day of month, month, and day of week cron fields
+const FIELDS: [(&'static str, u32, u32); 5] = [("minute", 0, 59), ("hour", 0, 23), ("day of month", 1, 31), ("month", 1, 12), ("day of week", 0, 7)];
+
+//a recurring maintenance window of a target or vantage host, e.g. weekly reboots every sunday at
+//03:00 lasting half an hour, silenced ahead of each occurrence
+pub struct MaintenanceSchedule {
+    pub name: String,
+    selector: String,
+    fields: Vec<Vec<bool>>,
+    duration: i64,
+    utc_offset: i64,
+    comment: String,
+}
+
+impl MaintenanceSchedule {
+    pub fn parse(document: &Document) -> Result<MaintenanceSchedule, TipupError> {
+        let name = match document.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return Err(TipupError::from("failed to parse maintenance schedule name")),
+        };
+
+        let fields = match document.get("schedule") {
+            Some(&Bson::String(ref schedule)) => try!(parse_schedule(schedule)),
+            _ => return Err(TipupError::from(format!("failed to parse schedule of maintenance schedule '{}'", name))),
+        };
+
+        let duration = match document.get("duration") {
+            Some(&Bson::I32(duration)) if duration > 0 => duration as i64,
+            Some(&Bson::I64(duration)) if duration > 0 => duration,
+            _ => return Err(TipupError::from(format!("failed to parse duration of maintenance schedule '{}' as positive seconds", name))),
+        };
+
+        let utc_offset = match document.get("utc_offset") {
+            Some(&Bson::I32(utc_offset)) => utc_offset as i64 * 60,
+            None => 0,
+            _ => return Err(TipupError::from(format!("failed to parse utc_offset of maintenance schedule '{}' as minutes", name))),
+        };
+
+        //silence flags of the hostname and domain, narrowed by any further selector
+        let mut expressions = Vec::new();
+        for &(key, label) in [("hostname", "hostname"), ("domain", "domain"), ("selector", "")].iter() {
+            match document.get(key) {
+                Some(&Bson::String(ref value)) if label.len() > 0 => expressions.push(format!("{}={}", label, value)),
+                Some(&Bson::String(ref value)) => expressions.push(value.to_owned()),
+                None => {},
+                _ => return Err(TipupError::from(format!("failed to parse {} of maintenance schedule '{}'", key, name))),
+            }
+        }
+
+        if expressions.len() == 0 {
+            return Err(TipupError::from(format!("maintenance schedule '{}' requires a hostname, domain, or selector", name)));
+        }
+
+        let selector = expressions.join(",");
+        try!(LabelSelector::parse(&selector));
+
+        let comment = match document.get("comment") {
+            Some(&Bson::String(ref comment)) => comment.to_owned(),
+            _ => format!("maintenance schedule '{}'", name),
+        };
+
+        Ok(
+            MaintenanceSchedule {
+                name: name,
+                selector: selector,
+                fields: fields,
+                duration: duration,
+                utc_offset: utc_offset,
+                comment: comment,
+            }
+        )
+    }
+
+    //start timestamps of occurrences starting within [start, end]
+    fn occurrences(&self, start: i64, end: i64) -> Vec<i64> {
+        let mut occurrences = Vec::new();
+        let mut timestamp = ((start + 59) / 60) * 60;
+        while timestamp <= end {
+            let tm = time::at_utc(Timespec::new(timestamp + self.utc_offset, 0));
+            let (minute, hour, day, month, weekday) = (tm.tm_min as usize, tm.tm_hour as usize, tm.tm_mday as usize, (tm.tm_mon + 1) as usize, tm.tm_wday as usize);
+
+            //skip the rest of days and hours which cannot match rather than every minute of them
+            if !self.fields[3][month] || !self.day_matches(day, weekday) {
+                timestamp += 86400 - (((hour * 60) + minute) * 60) as i64;
+            } else if !self.fields[1][hour] {
+                timestamp += 3600 - (minute * 60) as i64;
+            } else {
+                if self.fields[0][minute] {
+                    occurrences.push(timestamp);
+                }
+
+                timestamp += 60;
+            }
+        }
+
+        occurrences
+    }
+
+    //as in cron, restricting both day fields matches days satisfying either
+    fn day_matches(&self, day: usize, weekday: usize) -> bool {
+        let day_of_month_restricted = self.fields[2][1..].iter().any(|x| !x);
+        let day_of_week_restricted = self.fields[4].iter().any(|x| !x);
+        match (day_of_month_restricted, day_of_week_restricted) {
+            (true, true) => self.fields[2][day] || self.fields[4][weekday],
+            _ => self.fields[2][day] && self.fields[4][weekday],
+        }
+    }
+}
+
+pub struct MaintenanceManager {
+    interval: i64,
+    clock: Arc<Clock>,
+}
+
+impl MaintenanceManager {
+    pub fn new(interval: u32, clock: Arc<Clock>) -> MaintenanceManager {
+        MaintenanceManager {
+            interval: interval as i64,
+            clock: clock,
+        }
+    }
+
+    pub fn execute(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let mut schedules = Vec::new();
+        for document in try!(proddle_db.collection("maintenance_schedules").find(None, None)) {
+            schedules.push(try!(MaintenanceSchedule::parse(&try!(document))));
+        }
+
+        //silence occurrences still in progress or starting before the next execution, inserting
+        //each once so silences edited to end early are kept, though deleted ones are recreated
+        let now = self.clock.now();
+        let mut count = 0;
+        for schedule in schedules.iter() {
+            for start_timestamp in schedule.occurrences(now - schedule.duration + 1, now + self.interval) {
+                let search_document = doc!("schedule" => (schedule.name.clone()), "start_timestamp" => start_timestamp);
+                let update_document = doc!("$setOnInsert" => {
+                    "selector" => (schedule.selector.clone()),
+                    "end_timestamp" => (start_timestamp + schedule.duration),
+                    "comment" => (schedule.comment.clone()),
+                    "created_by" => "maintenance_schedule"
+                });
+
+                let mut update_options = UpdateOptions::new();
+                update_options.upsert = Some(true);
+                let result = try!(proddle_db.collection("silences").update_one(search_document, update_document, Some(update_options)));
+                if result.upserted_id.is_some() {
+                    count += 1;
+                }
+            }
+        }
+
+        if count > 0 {
+            info!("created {} maintenance silence(s)", count);
+        }
+
+        Ok(())
+    }
+}
+
+//parses 'minute hour day-of-month month day-of-week' with '*', lists, ranges, and '/' steps into
+//the values each field matches, sunday being 0 or 7 and days of week also named like 'mon'
+fn parse_schedule(schedule: &str) -> Result<Vec<Vec<bool>>, TipupError> {
+    let expressions: Vec<&str> = schedule.split_whitespace().collect();
+    if expressions.len() != FIELDS.len() {
+        return Err(TipupError::from(format!("failed to parse schedule '{}', expected 'minute hour day-of-month month day-of-week'", schedule)));
+    }
+
+    let mut fields = Vec::new();
+    for (expression, &(name, minimum, maximum)) in expressions.iter().zip(FIELDS.iter()) {
+        let mut values = vec![false; maximum as usize + 1];
+        for term in expression.split(',') {
+            let (range, step) = match term.find('/') {
+                Some(index) => match term[index + 1..].parse::<u32>() {
+                    Ok(step) if step > 0 => (&term[..index], step),
+                    _ => return Err(TipupError::from(format!("failed to parse step of {} field '{}' in schedule '{}'", name, term, schedule))),
+                },
+                None => (term, 1),
+            };
+
+            let (first, last) = match range.find('-') {
+                _ if range == "*" => (minimum, maximum),
+                Some(index) => (try!(parse_field_value(&range[..index], name, minimum, maximum)), try!(parse_field_value(&range[index + 1..], name, minimum, maximum))),
+                None => {
+                    let value = try!(parse_field_value(range, name, minimum, maximum));
+                    (value, if step > 1 { maximum } else { value })
+                },
+            };
+
+            if first > last {
+                return Err(TipupError::from(format!("{} range '{}' in schedule '{}' is reversed", name, range, schedule)));
+            }
+
+            let mut value = first;
+            while value <= last {
+                values[value as usize] = true;
+                value += step;
+            }
+        }
+
+        fields.push(values);
+    }
+
+    //sunday may be written as 7
+    if fields[4][7] {
+        fields[4][0] = true;
+    }
+
+    Ok(fields)
+}
+
+fn parse_field_value(value: &str, name: &str, minimum: u32, maximum: u32) -> Result<u32, TipupError> {
+    if name == "day of week" {
+        if let Some(index) = DAYS.iter().position(|x| *x == value.to_lowercase()) {
+            return Ok(index as u32);
+        }
+    }
+
+    match value.parse::<u32>() {
+        Ok(value) if value >= minimum && value <= maximum => Ok(value),
+        _ => Err(TipupError::from(format!("failed to parse {} value '{}', expected {} through {}", name, value, minimum, maximum))),
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+struct Registry {
+    counters: BTreeMap<(String, String), f64>,
+    gauges: BTreeMap<(String, String), f64>,
+}
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            registry: Arc::new(Mutex::new(
+                Registry {
+                    counters: BTreeMap::new(),
+                    gauges: BTreeMap::new(),
+                }
+            )),
+        }
+    }
+
+    pub fn increment(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let mut registry = self.registry.lock().unwrap();
+        *registry.counters.entry((name.to_owned(), format_labels(labels))).or_insert(0.0) += value;
+    }
+
+    pub fn set(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.gauges.insert((name.to_owned(), format_labels(labels)), value);
+    }
+
+    //records an observation as prometheus style '_sum' and '_count' counters
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.increment(&format!("{}_sum", name), labels, value);
+        self.increment(&format!("{}_count", name), labels, 1.0);
+    }
+
+    //renders metrics in the prometheus text exposition format
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut output = String::new();
+        for (metrics, kind) in [(&registry.counters, "counter"), (&registry.gauges, "gauge")].iter() {
+            let mut previous_name: Option<&String> = None;
+            for (&(ref name, ref labels), value) in metrics.iter() {
+                if previous_name != Some(name) {
+                    output.push_str(&format!("# TYPE {} {}\n", name, kind));
+                    previous_name = Some(name);
+                }
+
+                output.push_str(&format!("{}{} {}\n", name, labels, value));
+            }
+        }
+
+        output
+    }
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.len() == 0 {
+        return String::new();
+    }
+
+    let labels: Vec<String> = labels.iter().map(|&(key, value)| format!("{}=\"{}\"", key, value.replace("\\", "\\\\").replace("\"", "\\\""))).collect();
+    format!("{{{}}}", labels.join(","))
+}
@@ -0,0 +1,154 @@
+use bson::{Bson, Document};
+use hyper::Client;
+use hyper::header::ContentType;
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+use http;
+use kpi;
+
+use std::sync::Arc;
+
+const SECONDS_PER_DAY: i64 = 86400;
+const SECONDS_PER_WEEK: i64 = 604800;
+
+pub struct NoiseReporter {
+    size: i32,
+    digest_url: Option<String>,
+    client: Option<Client>,
+    last_digest_day: Option<i64>,
+    last_kpi_week: Option<i64>,
+    clock: Arc<Clock>,
+}
+
+impl NoiseReporter {
+    pub fn new(size: u32, digest_url: &str, clock: Arc<Clock>) -> Result<NoiseReporter, TipupError> {
+        let (digest_url, client) = match digest_url.len() {
+            0 => (None, None),
+            _ => (Some(digest_url.to_owned()), Some(try!(http::client()))),
+        };
+
+        Ok(
+            NoiseReporter {
+                size: size as i32,
+                digest_url: digest_url,
+                client: client,
+                last_digest_day: None,
+                last_kpi_week: None,
+                clock: clock,
+            }
+        )
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //continue daily digests from the most recently sent report
+        let negative_one = -1;
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("timestamp" => negative_one));
+        if let Some(document) = try!(proddle_db.collection("noise_report").find_one(Some(doc!("digest" => true)), Some(find_options))) {
+            if let Some(&Bson::I64(timestamp)) = document.get("timestamp") {
+                self.last_digest_day = Some(timestamp / SECONDS_PER_DAY);
+            }
+        }
+
+        //continue weekly kpis from the most recent report including them
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("timestamp" => negative_one));
+        let kpi_exists = doc!("$exists" => true);
+        if let Some(document) = try!(proddle_db.collection("noise_report").find_one(Some(doc!("kpi" => kpi_exists)), Some(find_options))) {
+            if let Some(&Bson::I64(timestamp)) = document.get("timestamp") {
+                self.last_kpi_week = Some(timestamp / SECONDS_PER_WEEK);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn execute(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //rank targets and vantage points by flags over the last day
+        let now = self.clock.now();
+        let targets = try!(self.top_flag_producers(proddle_db, "$domain", now - SECONDS_PER_DAY));
+        let vantage_points = try!(self.top_flag_producers(proddle_db, "$hostname", now - SECONDS_PER_DAY));
+
+        let mut report = doc!(
+            "timestamp" => now,
+            "window" => "24h",
+            "targets" => (targets.into_iter().map(|(domain, flags)| Bson::Document(doc!("domain" => domain, "flags" => flags))).collect::<Vec<Bson>>()),
+            "vantage_points" => (vantage_points.into_iter().map(|(hostname, flags)| Bson::Document(doc!("hostname" => hostname, "flags" => flags))).collect::<Vec<Bson>>())
+        );
+
+        //include detection and resolution kpis over the past week once per week
+        let week = now / SECONDS_PER_WEEK;
+        let kpi_due = self.last_kpi_week.map_or(true, |x| x < week);
+        if kpi_due {
+            let analyzers = try!(kpi::statistics(proddle_db, "analyzer", now - SECONDS_PER_WEEK));
+            let targets = try!(kpi::statistics(proddle_db, "domain", now - SECONDS_PER_WEEK));
+            report.insert("kpi", doc!(
+                "window" => "7d",
+                "analyzers" => (analyzers.into_iter().map(|x| Bson::Document(x)).collect::<Vec<Bson>>()),
+                "targets" => (targets.into_iter().map(|x| Bson::Document(x)).collect::<Vec<Bson>>())
+            ));
+            self.last_kpi_week = Some(week);
+        }
+
+        //send at most one digest per utc day, besides those carrying weekly kpis
+        let day = now / SECONDS_PER_DAY;
+        let digest = self.digest_url.is_some() && (kpi_due || self.last_digest_day.map_or(true, |x| x < day));
+        if digest {
+            try!(self.send_digest(&report));
+            self.last_digest_day = Some(day);
+        }
+
+        report.insert("digest", digest);
+        try!(proddle_db.collection("noise_report").insert_one(report, None));
+        Ok(())
+    }
+
+    fn top_flag_producers(&self, proddle_db: &Database, group_field: &str, timestamp: i64) -> Result<Vec<(String, i64)>, TipupError> {
+        //merged flags count every occurrence
+        let timestamp_gte = doc!("$gte" => timestamp);
+        let null_ne = doc!("$ne" => (Bson::Null));
+        let match_doc = doc!("timestamp" => timestamp_gte, (&group_field[1..]) => null_ne);
+        let negative_one = -1;
+        let aggregate_doc = vec!(
+            doc!("$match" => match_doc),
+            doc!("$group" => { "_id" => group_field, "flags" => { "$sum" => { "$ifNull" => ["$count", 1] } } }),
+            doc!("$sort" => { "flags" => negative_one }),
+            doc!("$limit" => (self.size)),
+        );
+
+        let mut producers = Vec::new();
+        for document in try!(proddle_db.collection("flags").aggregate(aggregate_doc, None)) {
+            let document = try!(document);
+            let flags = match document.get("flags") {
+                Some(&Bson::I32(flags)) => flags as i64,
+                Some(&Bson::I64(flags)) => flags,
+                _ => continue,
+            };
+
+            if let Some(&Bson::String(ref id)) = document.get("_id") {
+                producers.push((id.to_owned(), flags));
+            }
+        }
+
+        Ok(producers)
+    }
+
+    fn send_digest(&self, report: &Document) -> Result<(), TipupError> {
+        let (url, client) = match (&self.digest_url, &self.client) {
+            (&Some(ref url), &Some(ref client)) => (url, client),
+            _ => return Ok(()),
+        };
+
+        let body = Bson::Document(report.clone()).to_json().to_string();
+        let response = try!(client.post(url).header(ContentType::json()).body(&body).send());
+        if !response.status.is_success() {
+            return Err(TipupError::from(format!("noise digest '{}' responded with status {}", url, response.status)));
+        }
+
+        info!("sent daily noise digest to '{}'", url);
+        Ok(())
+    }
+}
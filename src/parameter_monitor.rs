@@ -0,0 +1,108 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+pub struct ParameterMonitor {
+    fields: HashMap<String, Vec<Vec<String>>>,
+    parameters: HashMap<(String, String, String), Vec<Option<String>>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl ParameterMonitor {
+    pub fn new(flag_tx: Sender<Flag>) -> ParameterMonitor {
+        ParameterMonitor {
+            fields: HashMap::new(),
+            parameters: HashMap::new(),
+            flag_tx: flag_tx,
+        }
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //query mongodb for the metadata fields describing how each measurement class is probed
+        for document in try!(proddle_db.collection("measurement_parameters").find(None, None)) {
+            let document = try!(document);
+
+            let measurement_class = match document.get("measurement_class") {
+                Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+                _ => return Err(TipupError::from("failed to parse measurement parameters measurement_class")),
+            };
+
+            let mut fields = Vec::new();
+            match document.get("fields") {
+                Some(&Bson::Array(ref field_array)) => {
+                    for field in field_array.iter() {
+                        match field {
+                            &Bson::Array(ref field) => fields.push(field.iter().map(|x| x.to_string().replace("\"", "")).collect()),
+                            _ => return Err(TipupError::from(format!("failed to parse measurement parameter fields for '{}'", measurement_class))),
+                        }
+                    }
+                },
+                _ => return Err(TipupError::from(format!("failed to parse measurement parameter fields for '{}'", measurement_class))),
+            }
+
+            self.fields.insert(measurement_class, fields);
+        }
+
+        Ok(())
+    }
+
+    pub fn check(&mut self, document: &OrderedDocument) -> Result<Option<(String, String, String)>, TipupError> {
+        //returns the (measurement_class, hostname, domain) key of results whose parameters changed
+        let key = match (document.get("measurement_class"), document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(ref measurement_class)), Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) =>
+                (measurement_class.to_owned(), hostname.to_owned(), domain.to_owned()),
+            _ => return Ok(None),
+        };
+
+        let fields = match self.fields.get(&key.0) {
+            Some(fields) => fields,
+            None => return Ok(None),
+        };
+
+        let parameters: Vec<Option<String>> = fields.iter().map(|x| get_string(x, document)).collect();
+        let previous_parameters = match self.parameters.insert(key.clone(), parameters.clone()) {
+            Some(previous_parameters) => previous_parameters,
+            None => return Ok(None),
+        };
+
+        if previous_parameters == parameters {
+            return Ok(None);
+        }
+
+        //note the change with an informational flag listing changed parameters
+        let mut flag = try!(Flag::new(document, "info", "parameter_monitor"));
+        for (i, field) in fields.iter().enumerate() {
+            if previous_parameters[i] != parameters[i] {
+                flag.evidence.insert(field.join("."), format!("{} -> {}",
+                    previous_parameters[i].as_ref().map_or("none", |x| x.as_str()), parameters[i].as_ref().map_or("none", |x| x.as_str())));
+            }
+        }
+
+        info!("measurement parameters changed"; "measurement_class" => key.0.to_owned(), "hostname" => key.1.to_owned(), "domain" => key.2.to_owned());
+        self.flag_tx.send(flag);
+        Ok(Some(key))
+    }
+}
+
+fn get_string(variable_name: &Vec<String>, document: &OrderedDocument) -> Option<String> {
+    let mut index_document = document;
+    for variable in variable_name {
+        match index_document.get(variable) {
+            Some(&Bson::Document(ref document)) => index_document = document,
+            Some(&Bson::String(ref s)) => return Some(s.to_owned()),
+            Some(&Bson::FloatingPoint(f)) => return Some(f.to_string()),
+            Some(&Bson::I32(i)) => return Some(i.to_string()),
+            Some(&Bson::I64(i)) => return Some(i.to_string()),
+            Some(&Bson::Boolean(b)) => return Some(b.to_string()),
+            _ => return None,
+        }
+    }
+
+    None
+}
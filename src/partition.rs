@@ -0,0 +1,63 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+
+use analyzer::get_bson;
+use error::TipupError;
+
+//separates the measured domain from custom key field values in a partition key
+const SEPARATOR: char = '|';
+
+pub fn parse_key_fields(document: &OrderedDocument) -> Result<Vec<Vec<String>>, TipupError> {
+    //dot separated result fields which further partition analyzer state, e.g. ["url.path", "record_type"]
+    match document.get("key_fields") {
+        Some(&Bson::Array(ref key_field_array)) => {
+            let mut key_fields = Vec::new();
+            for key_field in key_field_array.iter() {
+                match key_field {
+                    &Bson::String(ref key_field) if key_field.len() > 0 => key_fields.push(key_field.split('.').map(|x| x.to_owned()).collect()),
+                    _ => return Err(TipupError::from("failed to parse analyzer key_fields, expected dot separated field names")),
+                }
+            }
+
+            Ok(key_fields)
+        },
+        None => Ok(Vec::new()),
+        _ => Err(TipupError::from("failed to parse analyzer key_fields")),
+    }
+}
+
+//the domain a result is keyed by, extended with the values of any key fields, e.g. 'example.com|url.path=/login'
+pub fn partition_domain(domain: &str, key_fields: &Vec<Vec<String>>, document: &OrderedDocument) -> String {
+    let mut partition = domain.to_owned();
+    for key_field in key_fields.iter() {
+        partition.push(SEPARATOR);
+        partition.push_str(&format!("{}={}", key_field.join("."), format_value(get_bson(key_field, document))));
+    }
+
+    partition
+}
+
+//true if the partition belongs to the domain
+pub fn contains(partition: &str, domain: &str) -> bool {
+    partition == domain || (partition.starts_with(domain) && partition[domain.len()..].starts_with(SEPARATOR))
+}
+
+//the key field values of a partition without its domain
+pub fn suffix<'a>(partition: &'a str, domain: &str) -> &'a str {
+    match contains(partition, domain) && partition.len() > domain.len() {
+        true => &partition[domain.len() + 1..],
+        false => "",
+    }
+}
+
+fn format_value(value: Option<&Bson>) -> String {
+    match value {
+        Some(&Bson::String(ref value)) => value.to_owned(),
+        Some(&Bson::I32(value)) => value.to_string(),
+        Some(&Bson::I64(value)) => value.to_string(),
+        Some(&Bson::FloatingPoint(value)) => value.to_string(),
+        Some(&Bson::Boolean(value)) => value.to_string(),
+        Some(value) => value.to_json().to_string(),
+        None => "-".to_owned(),
+    }
+}
@@ -1,34 +1,402 @@
-use bson::Bson;
+use bson::{Bson, Document};
 use bson::ordered::OrderedDocument;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
 
-use analyzer::Analyzer;
+use analyzer::{Analyzer, Trace};
+use analyzer_health::{AnalyzerHealth, Outcome};
+use canary::Canary;
+use clock::Clock;
+use crash_report;
+use dependency::{self, Dependency};
+use derivation::Derivation;
 use error::TipupError;
+use metrics::Metrics;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+//minimum number of seconds over which results per second are computed
+const THROUGHPUT_WINDOW: f64 = 10.0;
+
+struct Throughput {
+    results: u64,
+    lag_sum: f64,
+    last_lag: f64,
+    window_start: f64,
+    window_results: u64,
+    results_per_second: f64,
+}
+
+#[derive(Clone)]
+pub struct PipeStatistics {
+    throughputs: Arc<Mutex<BTreeMap<String, Throughput>>>,
+}
+
+impl PipeStatistics {
+    fn record(&self, measurement_class: &str, lag: f64, now: f64) -> f64 {
+        let mut throughputs = self.throughputs.lock().unwrap();
+        let throughput = throughputs.entry(measurement_class.to_owned()).or_insert(
+            Throughput {
+                results: 0,
+                lag_sum: 0.0,
+                last_lag: 0.0,
+                window_start: now,
+                window_results: 0,
+                results_per_second: 0.0,
+            }
+        );
+
+        throughput.results += 1;
+        throughput.lag_sum += lag;
+        throughput.last_lag = lag;
+        throughput.window_results += 1;
+
+        let elapsed = now - throughput.window_start;
+        if elapsed >= THROUGHPUT_WINDOW {
+            throughput.results_per_second = throughput.window_results as f64 / elapsed;
+            throughput.window_start = now;
+            throughput.window_results = 0;
+        }
+
+        throughput.results_per_second
+    }
+
+    pub fn to_documents(&self) -> Vec<Document> {
+        let throughputs = self.throughputs.lock().unwrap();
+        throughputs.iter().map(|(measurement_class, throughput)| {
+            doc!(
+                "measurement_class" => (measurement_class.to_owned()),
+                "results" => (throughput.results as i64),
+                "results_per_second" => (throughput.results_per_second),
+                "last_lag" => (throughput.last_lag),
+                "mean_lag" => (throughput.lag_sum / throughput.results as f64)
+            )
+        }).collect()
+    }
+}
+
+struct TraceSubscription {
+    analyzer: String,
+    hostname: Option<String>,
+    domain: Option<String>,
+}
+
+impl TraceSubscription {
+    fn matches(&self, analyzer: &str, document: &OrderedDocument) -> bool {
+        self.analyzer == analyzer
+            && self.hostname.as_ref().map_or(true, |x| document.get("vantage_hostname") == Some(&Bson::String(x.to_owned())))
+            && self.domain.as_ref().map_or(true, |x| document.get("measurement_domain") == Some(&Bson::String(x.to_owned())))
+    }
+}
 
 pub struct Pipe {
     analyzers: Arc<Mutex<HashMap<String, HashMap<String, Box<Analyzer>>>>>,
+    canaries: Vec<Canary>,
+    derivations: HashMap<String, Derivation>,
+    dependencies: HashMap<String, Vec<String>>,
+    dependents: HashSet<String>,
+    order: HashMap<String, Vec<String>>,
+    subscriptions: HashMap<String, Vec<(String, String)>>,
+    trace_subscriptions: Mutex<Vec<TraceSubscription>>,
+    traces: Mutex<Vec<Document>>,
+    derived_results: Mutex<Vec<Document>>,
+    statistics: PipeStatistics,
+    health: AnalyzerHealth,
+    metrics: Metrics,
+    clock: Arc<Clock>,
 }
 
 impl Pipe {
-    pub fn new() -> Pipe {
+    pub fn new(metrics: Metrics, clock: Arc<Clock>) -> Pipe {
         Pipe {
             analyzers: Arc::new(Mutex::new(HashMap::new())),
+            canaries: Vec::new(),
+            derivations: HashMap::new(),
+            dependencies: HashMap::new(),
+            dependents: HashSet::new(),
+            order: HashMap::new(),
+            subscriptions: HashMap::new(),
+            trace_subscriptions: Mutex::new(Vec::new()),
+            traces: Mutex::new(Vec::new()),
+            derived_results: Mutex::new(Vec::new()),
+            statistics: PipeStatistics {
+                throughputs: Arc::new(Mutex::new(BTreeMap::new())),
+            },
+            health: AnalyzerHealth::new(),
+            metrics: metrics,
+            clock: clock,
         }
     }
 
+    pub fn statistics(&self) -> PipeStatistics {
+        self.statistics.clone()
+    }
+
+    pub fn health(&self) -> AnalyzerHealth {
+        self.health.clone()
+    }
+
     pub fn add_analyzer(&mut self, name: String, measurement_class: String, analyzer: Box<Analyzer>) -> Result<(), TipupError> {
         let mut analyzers = self.analyzers.lock().unwrap();
-        let mut analyzers = analyzers.entry(measurement_class).or_insert(HashMap::new());
+        let mut analyzers = analyzers.entry(measurement_class.clone()).or_insert(HashMap::new());
         if analyzers.contains_key(&name) {
             return Err(TipupError::from("analyzer name already exists"));
         }
 
+        //analyzers subscribing to further measurement classes also receive their results
+        for subscribed_class in analyzer.measurement_classes() {
+            if subscribed_class != measurement_class {
+                self.subscriptions.entry(subscribed_class).or_insert(Vec::new()).push((measurement_class.clone(), name.clone()));
+            }
+        }
+
+        self.order.entry(measurement_class).or_insert(Vec::new()).push(name.clone());
         analyzers.insert(name, analyzer);
         Ok(())
     }
 
+    pub fn add_canary(&mut self, canary: Canary) -> Result<(), TipupError> {
+        //canaries split keys with a baseline analyzer of the same measurement class
+        {
+            let analyzers = self.analyzers.lock().unwrap();
+            if !analyzers.values().any(|x| x.contains_key(&canary.name) && x.contains_key(&canary.baseline)) {
+                return Err(TipupError::from(format!("canary '{}' requires baseline analyzer '{}' of the same measurement class", canary.name, canary.baseline)));
+            }
+        }
+
+        if self.canaries.iter().any(|x| x.baseline == canary.baseline || x.name == canary.baseline || x.baseline == canary.name) {
+            return Err(TipupError::from(format!("baseline analyzer '{}' may only have one canary and canaries may not be chained", canary.baseline)));
+        }
+
+        info!("routing {}% of '{}' keys to canary '{}'", canary.percentage, canary.baseline, canary.name);
+        self.canaries.push(canary);
+        Ok(())
+    }
+
+    pub fn add_derivation(&mut self, derivation: Derivation) -> Result<(), TipupError> {
+        {
+            let analyzers = self.analyzers.lock().unwrap();
+            if !analyzers.values().any(|x| x.contains_key(&derivation.name)) {
+                return Err(TipupError::from(format!("derived results require analyzer '{}'", derivation.name)));
+            }
+        }
+
+        info!("deriving '{}' results from analyzer '{}'", derivation.measurement_class, derivation.name);
+        self.derivations.insert(derivation.name.clone(), derivation);
+        Ok(())
+    }
+
+    pub fn add_dependencies(&mut self, dependencies: Vec<Dependency>) -> Result<(), TipupError> {
+        let classes: HashMap<String, String> = {
+            let analyzers = self.analyzers.lock().unwrap();
+            analyzers.iter().flat_map(|(measurement_class, analyzers)| analyzers.keys().map(move |x| (x.to_owned(), measurement_class.to_owned()))).collect()
+        };
+
+        //dependencies analyze the same results or derive the results their dependents analyze
+        let mut dependents = HashSet::new();
+        for dependency in dependencies.iter() {
+            let measurement_class = match classes.get(&dependency.name) {
+                Some(measurement_class) => measurement_class,
+                None => return Err(TipupError::from(format!("dependencies require analyzer '{}'", dependency.name))),
+            };
+
+            for name in dependency.depends_on.iter() {
+                match classes.get(name) {
+                    Some(x) if x == measurement_class => { dependents.insert(name.to_owned()); },
+                    Some(_) if self.derivations.get(name).map_or(false, |x| &x.measurement_class == measurement_class) => {},
+                    Some(_) => return Err(TipupError::from(format!("analyzer '{}' depends on '{}' which neither analyzes nor derives '{}' results", dependency.name, name, measurement_class))),
+                    None => return Err(TipupError::from(format!("analyzer '{}' depends on unknown analyzer '{}'", dependency.name, name))),
+                }
+            }
+        }
+
+        let dependencies: HashMap<String, Vec<String>> = dependencies.into_iter().map(|x| (x.name, x.depends_on)).collect();
+        let mut names: Vec<String> = classes.keys().cloned().collect();
+        names.sort();
+        let order = try!(dependency::topological_order(&names, &dependencies));
+
+        //dispatch each measurement class's analyzers after those they depend on
+        self.order.clear();
+        for name in order {
+            self.order.entry(classes[&name].clone()).or_insert(Vec::new()).push(name);
+        }
+
+        for (name, depends_on) in dependencies.iter() {
+            info!("dispatching '{}' results after analyzer(s) {}", name, depends_on.join(", "));
+        }
+
+        self.dependencies = dependencies;
+        self.dependents = dependents;
+        Ok(())
+    }
+
+    //false if the key is assigned to the other side of a canary split the analyzer belongs to,
+    //analyzers routed by target splitting whole targets so they see each of its vantage points
+    fn routed(&self, name: &str, by_target: bool, document: &OrderedDocument) -> bool {
+        let (hostname, domain) = match (document.get("vantage_hostname"), document.get("measurement_domain")) {
+            (Some(&Bson::String(_)), Some(&Bson::String(ref domain))) if by_target => ("", domain.as_str()),
+            (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.as_str(), domain.as_str()),
+            _ => return true,
+        };
+
+        for canary in self.canaries.iter() {
+            if canary.name == name {
+                return canary.contains(hostname, domain);
+            } else if canary.baseline == name {
+                return !canary.contains(hostname, domain);
+            }
+        }
+
+        true
+    }
+
+    pub fn refresh(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let mut analyzers = self.analyzers.lock().unwrap();
+        for analyzers in analyzers.values_mut() {
+            for analyzer in analyzers.values_mut() {
+                try!(analyzer.refresh(proddle_db));
+            }
+        }
+
+        //load unexpired decision trace subscriptions so tracing toggles at runtime
+        let mut trace_subscriptions = Vec::new();
+        let now = self.clock.now();
+        for document in try!(proddle_db.collection("trace_subscriptions").find(None, None)) {
+            let document = try!(document);
+            if let Some(&Bson::I64(until_timestamp)) = document.get("until_timestamp") {
+                if until_timestamp <= now {
+                    continue;
+                }
+            }
+
+            let analyzer = match document.get("analyzer") {
+                Some(&Bson::String(ref analyzer)) => analyzer.to_owned(),
+                _ => return Err(TipupError::from("failed to parse trace subscription analyzer")),
+            };
+
+            trace_subscriptions.push(
+                TraceSubscription {
+                    analyzer: analyzer,
+                    hostname: match document.get("hostname") {
+                        Some(&Bson::String(ref hostname)) => Some(hostname.to_owned()),
+                        _ => None,
+                    },
+                    domain: match document.get("domain") {
+                        Some(&Bson::String(ref domain)) => Some(domain.to_owned()),
+                        _ => None,
+                    },
+                }
+            );
+        }
+
+        *self.trace_subscriptions.lock().unwrap() = trace_subscriptions;
+        Ok(())
+    }
+
+    pub fn flush_traces(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let traces: Vec<Document> = self.traces.lock().unwrap().drain(..).collect();
+        if traces.len() > 0 {
+            try!(proddle_db.collection("analyzer_traces").insert_many(traces, None));
+        }
+
+        Ok(())
+    }
+
+    pub fn flush_derived_results(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let derived_results: Vec<Document> = self.derived_results.lock().unwrap().drain(..).collect();
+        if derived_results.len() > 0 {
+            try!(proddle_db.collection("derived_results").insert_many(derived_results, None));
+        }
+
+        Ok(())
+    }
+
+    pub fn reset(&self, measurement_class: &str, hostname: &str, domain: &str) {
+        let mut analyzers = self.analyzers.lock().unwrap();
+        if let Some(analyzers) = analyzers.get_mut(measurement_class) {
+            for analyzer in analyzers.values_mut() {
+                analyzer.reset(hostname, domain);
+            }
+        }
+
+        for &(ref analyzer_class, ref name) in self.subscriptions.get(measurement_class).map_or(&[][..], |x| &x[..]) {
+            if let Some(analyzer) = analyzers.get_mut(analyzer_class).and_then(|x| x.get_mut(name)) {
+                analyzer.reset(hostname, domain);
+            }
+        }
+    }
+
+    pub fn routing_table(&self) -> BTreeMap<String, Vec<String>> {
+        //map of measurement class to the names of analyzers it is routed to
+        let analyzers = self.analyzers.lock().unwrap();
+        let mut routing_table = BTreeMap::new();
+        for (measurement_class, analyzers) in analyzers.iter() {
+            let mut names: Vec<String> = analyzers.keys().cloned().collect();
+            names.sort();
+            routing_table.insert(measurement_class.to_owned(), names);
+        }
+
+        for (measurement_class, subscriptions) in self.subscriptions.iter() {
+            let names = routing_table.entry(measurement_class.to_owned()).or_insert(Vec::new());
+            names.extend(subscriptions.iter().map(|&(_, ref x)| x.to_owned()));
+            names.sort();
+        }
+
+        routing_table
+    }
+
+    pub fn publish_routing_table(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        //load previously persisted routing table
+        let mut previous_table = BTreeMap::new();
+        if let Some(document) = try!(proddle_db.collection("routing_table").find_one(Some(doc!("_id" => "pipe")), None)) {
+            if let Some(&Bson::Array(ref routes)) = document.get("routes") {
+                for route in routes.iter() {
+                    if let &Bson::Document(ref route) = route {
+                        if let (Some(&Bson::String(ref measurement_class)), Some(&Bson::Array(ref analyzers))) = (route.get("measurement_class"), route.get("analyzers")) {
+                            previous_table.insert(measurement_class.to_owned(), analyzers.iter().map(|x| x.to_string().replace("\"", "")).collect());
+                        }
+                    }
+                }
+            }
+        }
+
+        //log differences per measurement class
+        let routing_table = self.routing_table();
+        let empty = Vec::new();
+        let measurement_classes: Vec<&String> = {
+            let mut measurement_classes: Vec<&String> = previous_table.keys().chain(routing_table.keys()).collect();
+            measurement_classes.sort();
+            measurement_classes.dedup();
+            measurement_classes
+        };
+
+        for measurement_class in measurement_classes {
+            let previous_analyzers: &Vec<String> = previous_table.get(measurement_class).unwrap_or(&empty);
+            let analyzers = routing_table.get(measurement_class).unwrap_or(&empty);
+            let added: Vec<String> = analyzers.iter().filter(|x| !previous_analyzers.contains(x)).cloned().collect();
+            let removed: Vec<String> = previous_analyzers.iter().filter(|x| !analyzers.contains(x)).cloned().collect();
+            if added.len() > 0 || removed.len() > 0 {
+                info!("routing table changed"; "measurement_class" => measurement_class.to_owned(),
+                    "added" => added.join(","), "removed" => removed.join(","), "analyzers" => analyzers.join(","));
+            }
+        }
+
+        //persist current routing table
+        let routes: Vec<Bson> = routing_table.iter().map(|(measurement_class, analyzers)| {
+            let analyzers: Vec<Bson> = analyzers.iter().map(|x| Bson::String(x.to_owned())).collect();
+            Bson::Document(doc!("measurement_class" => (measurement_class.to_owned()), "analyzers" => analyzers))
+        }).collect();
+
+        let timestamp = self.clock.now();
+        let update_document = doc!("$set" => { "timestamp" => timestamp, "routes" => routes });
+        let mut update_options = UpdateOptions::new();
+        update_options.upsert = Some(true);
+        try!(proddle_db.collection("routing_table").update_one(doc!("_id" => "pipe"), update_document, Some(update_options)));
+        Ok(())
+    }
+
     pub fn send_measurement(&self, document: &OrderedDocument) -> Result<(), TipupError> {
         //get measurement name
         let measurement_class = match document.get("measurement_class") {
@@ -36,14 +404,99 @@ impl Pipe {
             _ => return Err(TipupError::from("failed to parse result measurement_class")),
         };
 
+        //track detection lag between result timestamp and processing along with throughput
+        if let Some(&Bson::I64(timestamp)) = document.get("timestamp") {
+            let lag = (self.clock.now() - timestamp) as f64;
+            let results_per_second = self.statistics.record(measurement_class, lag, self.clock.precise_now());
+            let labels = [("measurement_class", measurement_class.as_str())];
+            self.metrics.increment("tipup_pipe_results_total", &labels, 1.0);
+            self.metrics.observe("tipup_pipe_detection_lag_seconds", &labels, lag);
+            self.metrics.set("tipup_pipe_results_per_second", &labels, results_per_second);
+        }
+
         //send to analyzers registered to that measurement
-        let mut analyzers = self.analyzers.lock().unwrap();
-        if analyzers.contains_key(measurement_class) {
-            for analyzer in analyzers.get_mut(measurement_class).unwrap().values_mut() {
-                try!(analyzer.process_measurement(document));
+        let mut derived_results = Vec::new();
+        {
+            let mut analyzers = self.analyzers.lock().unwrap();
+            let (empty_order, empty_subscriptions) = (Vec::new(), Vec::new());
+            let routes: Vec<(&String, &String)> = self.order.get(measurement_class).unwrap_or(&empty_order).iter()
+                .map(|x| (measurement_class, x))
+                .chain(self.subscriptions.get(measurement_class).unwrap_or(&empty_subscriptions).iter().map(|&(ref x, ref y)| (x, y)))
+                .collect();
+
+            if routes.len() > 0 {
+                let trace_subscriptions = self.trace_subscriptions.lock().unwrap();
+                let mut outputs = Document::new();
+                for (analyzer_class, name) in routes {
+                    let analyzer = match analyzers.get_mut(analyzer_class).and_then(|x| x.get_mut(name)) {
+                        Some(analyzer) => analyzer,
+                        None => continue,
+                    };
+
+                    if self.health.is_disabled(name) || !self.routed(name, analyzer.routes_by_target(), document) {
+                        continue;
+                    }
+
+                    let mut trace = Trace::new(trace_subscriptions.iter().any(|x| x.matches(name, document)));
+                    let derivation = self.derivations.get(name);
+                    if derivation.is_some() || self.dependents.contains(name) {
+                        trace.collect_statistics();
+                    }
+
+                    //dependents read the statistics of their dependencies under 'dependencies'
+                    let dependent_document = self.dependencies.get(name).map(|depends_on| {
+                        let mut dependent_document = document.clone();
+                        let mut dependencies = Document::new();
+                        for dependency in depends_on.iter() {
+                            if let Some(statistics) = outputs.get(dependency) {
+                                dependencies.insert(dependency.to_owned(), statistics.clone());
+                            }
+                        }
+
+                        dependent_document.insert("dependencies", dependencies);
+                        dependent_document
+                    });
+
+                    let input = dependent_document.as_ref().unwrap_or(document);
+                    //failing analyzers are scored by their health rather than halting analysis
+                    let start = Instant::now();
+                    let result = crash_report::contain(|| analyzer.process_measurement(input, &mut trace));
+                    let latency = start.elapsed().as_secs_f64();
+                    match result {
+                        Ok(Ok(())) => self.health.record(name, latency, Outcome::Ok),
+                        Ok(Err(e)) => {
+                            error!("analyzer '{}' failed: {}", name, e);
+                            self.health.record(name, latency, Outcome::Error);
+                            continue;
+                        },
+                        Err(_) => {
+                            error!("analyzer '{}' panicked", name);
+                            self.health.record(name, latency, Outcome::Panic);
+                            continue;
+                        },
+                    }
+
+                    if self.dependents.contains(name) {
+                        outputs.insert(name.to_owned(), trace.statistics().clone());
+                    }
+
+                    if let Some(derived_result) = derivation.and_then(|x| x.derive(input, trace.statistics())) {
+                        derived_results.push(derived_result);
+                    }
+
+                    if let Some(trace_document) = trace.to_document(name, input, self.clock.now()) {
+                        self.traces.lock().unwrap().push(trace_document);
+                    }
+                }
             }
         }
 
+        //analyzers of derived measurement classes consume derived results as they are produced
+        for derived_result in derived_results {
+            try!(self.send_measurement(&derived_result));
+            self.derived_results.lock().unwrap().push(derived_result);
+        }
+
         Ok(())
     }
 }
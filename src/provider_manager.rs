@@ -0,0 +1,299 @@
+use bson::Bson;
+use chan::Sender;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+use flag_manager::Flag;
+use flag_store::FlagStore;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::Arc;
+
+//built-in status of provider-level flags, mapped onto the configured severity taxonomy
+const PROVIDER_STATUS: &'static str = "error";
+//targets listed in a provider flag's evidence
+const EVIDENCE_TARGETS: usize = 20;
+
+struct Provider {
+    name: Option<String>,
+    domains: BTreeSet<String>,
+    hostnames: BTreeSet<String>,
+    flags: usize,
+}
+
+//aggregates unresolved flags whose targets share an origin as, raising one provider-level flag
+//once enough of its targets are degraded rather than leaving operators to correlate target flags
+pub struct ProviderManager {
+    window: i64,
+    minimum_targets: usize,
+    flag_store: Arc<FlagStore>,
+    flag_tx: Sender<Flag>,
+    clock: Arc<Clock>,
+}
+
+impl ProviderManager {
+    pub fn new(window: i64, minimum_targets: usize, flag_store: Arc<FlagStore>, flag_tx: Sender<Flag>, clock: Arc<Clock>) -> ProviderManager {
+        ProviderManager {
+            window: window,
+            minimum_targets: minimum_targets,
+            flag_store: flag_store,
+            flag_tx: flag_tx,
+            clock: clock,
+        }
+    }
+
+    pub fn execute(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        let mut target_asns = HashMap::new();
+        for document in try!(proddle_db.collection("target_asns").find(None, None)) {
+            let document = try!(document);
+            let domain = match document.get("domain") {
+                Some(&Bson::String(ref domain)) => domain.to_owned(),
+                _ => return Err(TipupError::from("failed to parse target asn domain")),
+            };
+
+            let name = match document.get("name") {
+                Some(&Bson::String(ref name)) if name.len() > 0 => Some(name.to_owned()),
+                _ => None,
+            };
+
+            target_asns.insert(domain, (try!(parse_asn(document.get("asn"))), name));
+        }
+
+        if target_asns.len() == 0 {
+            return Ok(());
+        }
+
+        //group recently active target flags by the origin as of their domain
+        let now = self.clock.now();
+        let mut providers: HashMap<u32, Provider> = HashMap::new();
+        for flag in try!(self.flag_store.find_by_states(&["open", "acknowledged"])) {
+            if flag.last_timestamp < now - self.window || flag.analyzer == "provider" {
+                continue;
+            }
+
+            let &(asn, ref name) = match target_asns.get(&flag.domain) {
+                Some(target_asn) => target_asn,
+                None => continue,
+            };
+
+            let provider = providers.entry(asn).or_insert(
+                Provider {
+                    name: name.clone(),
+                    domains: BTreeSet::new(),
+                    hostnames: BTreeSet::new(),
+                    flags: 0,
+                }
+            );
+
+            provider.domains.insert(flag.domain.clone());
+            if let Some(hostname) = flag.hostname {
+                provider.hostnames.insert(hostname);
+            }
+
+            provider.flags += 1;
+        }
+
+        //repeated provider flags merge into the unresolved flag of the as
+        let mut count = 0;
+        for (asn, provider) in providers.iter().filter(|&(_, x)| x.domains.len() >= self.minimum_targets) {
+            let provider_name = format!("AS{}", asn);
+            let summary = format!("{} targets degraded from {} vantage points", provider_name, provider.hostnames.len());
+            let mut flag = Flag::for_domain(&provider_name, now, PROVIDER_STATUS, "provider");
+            flag.labels.insert("asn".to_owned(), asn.to_string());
+            flag.evidence.insert("summary".to_owned(), summary);
+            flag.evidence.insert("targets".to_owned(), provider.domains.len().to_string());
+            flag.evidence.insert("vantage_points".to_owned(), provider.hostnames.len().to_string());
+            flag.evidence.insert("flags".to_owned(), provider.flags.to_string());
+            flag.evidence.insert("domains".to_owned(), provider.domains.iter().take(EVIDENCE_TARGETS).cloned().collect::<Vec<String>>().join(","));
+            if let Some(ref name) = provider.name {
+                flag.evidence.insert("as_name".to_owned(), name.to_owned());
+            }
+
+            self.flag_tx.send(flag);
+            count += 1;
+        }
+
+        if count > 0 {
+            info!("raised provider flags for {} as(es)", count);
+        }
+
+        Ok(())
+    }
+}
+
+//imports a csv enrichment table with a header row of domain, asn, and optionally name columns
+pub fn import_table(proddle_db: &Database, filename: &str) -> Result<usize, TipupError> {
+    let file = match File::open(filename) {
+        Ok(file) => file,
+        Err(e) => return Err(TipupError::from(format!("failed to open '{}': {}", filename, e))),
+    };
+
+    let mut lines = BufReader::new(file).lines();
+    let header: Vec<String> = match lines.next() {
+        Some(Ok(header)) => header.split(',').map(|x| x.trim().to_lowercase()).collect(),
+        _ => return Err(TipupError::from(format!("'{}' is missing a csv header", filename))),
+    };
+
+    let (domain_index, asn_index) = match (header.iter().position(|x| x == "domain"), header.iter().position(|x| x == "asn")) {
+        (Some(domain_index), Some(asn_index)) => (domain_index, asn_index),
+        _ => return Err(TipupError::from(format!("'{}' requires 'domain' and 'asn' columns", filename))),
+    };
+
+    let name_index = header.iter().position(|x| x == "name");
+    let mut count = 0;
+    for (i, line) in lines.enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Err(TipupError::from(format!("failed to read '{}': {}", filename, e))),
+        };
+
+        if line.trim().len() == 0 {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+        let (domain, asn) = match (fields.get(domain_index), fields.get(asn_index).map(|x| x.trim_start_matches("AS").parse::<u32>())) {
+            (Some(domain), Some(Ok(asn))) if domain.len() > 0 => (domain.to_string(), asn),
+            _ => return Err(TipupError::from(format!("failed to parse domain and asn on line {} of '{}'", i + 2, filename))),
+        };
+
+        let name = name_index.and_then(|x| fields.get(x)).map_or("", |x| *x);
+        try!(upsert(proddle_db, &domain, asn, name, "table"));
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+//imports origin ases of domains by resolving them against a bgp prefix-to-as dump, lines of
+//'prefix length asn' (caida pfx2as) or 'prefix/length asn'
+pub fn import_bgp(proddle_db: &Database, filename: &str, domains: &Vec<String>) -> Result<(usize, usize), TipupError> {
+    let file = match File::open(filename) {
+        Ok(file) => file,
+        Err(e) => return Err(TipupError::from(format!("failed to open '{}': {}", filename, e))),
+    };
+
+    let mut prefixes = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Err(TipupError::from(format!("failed to read '{}': {}", filename, e))),
+        };
+
+        let fields: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == '|').filter(|x| x.len() > 0).collect();
+        let (prefix, asn) = match fields.len() {
+            0 => continue,
+            2 => (fields[0].to_owned(), fields[1]),
+            3 => (format!("{}/{}", fields[0], fields[1]), fields[2]),
+            _ => return Err(TipupError::from(format!("failed to parse prefix on line {} of '{}'", i + 1, filename))),
+        };
+
+        //multi-origin prefixes list ases separated by '_' or ',', the first is used
+        let asn = match asn.split(|c| c == '_' || c == ',').next().map(|x| x.parse::<u32>()) {
+            Some(Ok(asn)) => asn,
+            _ => return Err(TipupError::from(format!("failed to parse asn on line {} of '{}'", i + 1, filename))),
+        };
+
+        prefixes.push((try!(Prefix::parse(&prefix)), asn));
+    }
+
+    //longest prefix match of the first resolved address of each domain
+    let (mut count, mut unmatched) = (0, 0);
+    for domain in domains.iter() {
+        let address = match (domain.as_str(), 0).to_socket_addrs() {
+            Ok(mut addresses) => addresses.next().map(|x| x.ip()),
+            Err(_) => None,
+        };
+
+        let asn = address.and_then(|address| prefixes.iter()
+            .filter(|&&(ref prefix, _)| prefix.contains(&address))
+            .max_by_key(|&&(ref prefix, _)| prefix.length)
+            .map(|&(_, asn)| asn));
+
+        match asn {
+            Some(asn) => {
+                try!(upsert(proddle_db, domain, asn, "", "bgp"));
+                count += 1;
+            },
+            None => unmatched += 1,
+        }
+    }
+
+    Ok((count, unmatched))
+}
+
+fn upsert(proddle_db: &Database, domain: &str, asn: u32, name: &str, source: &str) -> Result<(), TipupError> {
+    let mut set_document = doc!("domain" => domain, "asn" => (asn as i64), "source" => source);
+    if name.len() > 0 {
+        set_document.insert("name", name);
+    }
+
+    let mut update_options = UpdateOptions::new();
+    update_options.upsert = Some(true);
+    try!(proddle_db.collection("target_asns").update_one(doc!("domain" => domain), doc!("$set" => set_document), Some(update_options)));
+    Ok(())
+}
+
+fn parse_asn(asn: Option<&Bson>) -> Result<u32, TipupError> {
+    match asn {
+        Some(&Bson::I32(asn)) if asn >= 0 => Ok(asn as u32),
+        Some(&Bson::I64(asn)) if asn >= 0 && asn <= u32::max_value() as i64 => Ok(asn as u32),
+        Some(&Bson::String(ref asn)) => match asn.trim_start_matches("AS").parse::<u32>() {
+            Ok(asn) => Ok(asn),
+            Err(_) => Err(TipupError::from(format!("failed to parse asn '{}'", asn))),
+        },
+        _ => Err(TipupError::from("failed to parse target asn")),
+    }
+}
+
+struct Prefix {
+    network: Vec<u8>,
+    length: usize,
+}
+
+impl Prefix {
+    fn parse(prefix: &str) -> Result<Prefix, TipupError> {
+        let mut fields = prefix.trim().splitn(2, '/');
+        let address = match fields.next().map(|x| x.parse::<IpAddr>()) {
+            Some(Ok(address)) => address,
+            _ => return Err(TipupError::from(format!("failed to parse address of prefix '{}'", prefix))),
+        };
+
+        let network = octets(&address);
+        let length = match fields.next().map(|x| x.parse::<usize>()) {
+            Some(Ok(length)) if length <= network.len() * 8 => length,
+            _ => return Err(TipupError::from(format!("failed to parse length of prefix '{}'", prefix))),
+        };
+
+        Ok(
+            Prefix {
+                network: network,
+                length: length,
+            }
+        )
+    }
+
+    fn contains(&self, address: &IpAddr) -> bool {
+        let address = octets(address);
+        if address.len() != self.network.len() {
+            return false;
+        }
+
+        (0..self.length).all(|i| {
+            let mask = 0x80 >> (i % 8);
+            (address[i / 8] & mask) == (self.network[i / 8] & mask)
+        })
+    }
+}
+
+fn octets(address: &IpAddr) -> Vec<u8> {
+    match *address {
+        IpAddr::V4(ref address) => address.octets().to_vec(),
+        IpAddr::V6(ref address) => address.octets().to_vec(),
+    }
+}
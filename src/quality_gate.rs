@@ -0,0 +1,222 @@
+use bson::{Bson, Document};
+use bson::ordered::OrderedDocument;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+use resources::{self, Evictable};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+type Key = (String, String, String);
+
+struct QualityPolicy {
+    window: i64,
+    minimum_samples: usize,
+    maximum_failure_rate: f64,
+}
+
+struct KeyQuality {
+    timestamp: i64,
+    samples: usize,
+    failure_rate: f64,
+    reason: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct QualityStatuses {
+    statuses: Arc<Mutex<BTreeMap<Key, KeyQuality>>>,
+}
+
+impl QualityStatuses {
+    pub fn to_documents(&self, insufficient_only: bool) -> Vec<Document> {
+        let statuses = self.statuses.lock().unwrap();
+        statuses.iter().filter(|&(_, quality)| !insufficient_only || quality.reason.is_some()).map(|(&(ref measurement_class, ref hostname, ref domain), quality)| {
+            let mut document = doc!(
+                "measurement_class" => (measurement_class.to_owned()),
+                "vantage_hostname" => (hostname.to_owned()),
+                "domain" => (domain.to_owned()),
+                "timestamp" => (quality.timestamp),
+                "samples" => (quality.samples as i64),
+                "failure_rate" => (quality.failure_rate),
+                "status" => (if quality.reason.is_some() { "insufficient_data" } else { "ok" })
+            );
+
+            if let Some(ref reason) = quality.reason {
+                document.insert("reason", reason.to_owned());
+            }
+
+            document
+        }).collect()
+    }
+}
+
+pub struct QualityGate {
+    policies: HashMap<String, QualityPolicy>,
+    samples: HashMap<Key, VecDeque<(i64, bool)>>,
+    statuses: QualityStatuses,
+}
+
+impl QualityGate {
+    pub fn new() -> QualityGate {
+        QualityGate {
+            policies: HashMap::new(),
+            samples: HashMap::new(),
+            statuses: QualityStatuses {
+                statuses: Arc::new(Mutex::new(BTreeMap::new())),
+            },
+        }
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //query mongodb for quality gates, measurement classes without one are always analyzed
+        let mut count = 0;
+        for document in try!(proddle_db.collection("quality_gates").find(None, None)) {
+            let document = try!(document);
+
+            let measurement_class = match document.get("measurement_class") {
+                Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+                _ => return Err(TipupError::from("failed to parse quality gate measurement_class")),
+            };
+
+            let window = match document.get("window") {
+                Some(&Bson::I32(window)) if window > 0 => window as i64,
+                Some(&Bson::I64(window)) if window > 0 => window,
+                None => 3600,
+                _ => return Err(TipupError::from(format!("failed to parse quality gate window for '{}' as positive seconds", measurement_class))),
+            };
+
+            let minimum_samples = match document.get("minimum_samples") {
+                Some(&Bson::I32(minimum_samples)) if minimum_samples >= 0 => minimum_samples as usize,
+                None => 5,
+                _ => return Err(TipupError::from(format!("failed to parse quality gate minimum_samples for '{}'", measurement_class))),
+            };
+
+            let maximum_failure_rate = match document.get("maximum_failure_rate") {
+                Some(&Bson::FloatingPoint(rate)) if rate >= 0.0 && rate <= 1.0 => rate,
+                None => 0.5,
+                _ => return Err(TipupError::from(format!("quality gate maximum_failure_rate for '{}' must be between 0 and 1", measurement_class))),
+            };
+
+            self.policies.insert(measurement_class,
+                QualityPolicy {
+                    window: window,
+                    minimum_samples: minimum_samples,
+                    maximum_failure_rate: maximum_failure_rate,
+                }
+            );
+
+            count += 1;
+        }
+
+        if count > 0 {
+            info!("loaded {} quality gate(s)", count);
+        }
+
+        Ok(())
+    }
+
+    pub fn statuses(&self) -> QualityStatuses {
+        self.statuses.clone()
+    }
+
+    pub fn sample(&self, document: &OrderedDocument) -> Option<(Key, i64)> {
+        //identify gated results before sanitization consumes them
+        match (document.get("measurement_class"), document.get("vantage_hostname"), document.get("measurement_domain"), document.get("timestamp")) {
+            (Some(&Bson::String(ref measurement_class)), Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain)), Some(&Bson::I64(timestamp)))
+                    if self.policies.contains_key(measurement_class) =>
+                Some(((measurement_class.to_owned(), hostname.to_owned(), domain.to_owned()), timestamp)),
+            _ => None,
+        }
+    }
+
+    pub fn record(&mut self, sample: (Key, i64), parsed: bool) {
+        let (key, timestamp) = sample;
+        let window = match self.policies.get(&key.0) {
+            Some(policy) => policy.window,
+            None => return,
+        };
+
+        let samples = self.samples.entry(key).or_insert(VecDeque::new());
+        samples.push_back((timestamp, parsed));
+        while samples.front().map_or(false, |&(x, _)| x <= timestamp - window) {
+            samples.pop_front();
+        }
+    }
+
+    pub fn passes(&self, document: &OrderedDocument) -> bool {
+        let (key, timestamp) = match self.sample(document) {
+            Some(sample) => sample,
+            None => return true,
+        };
+
+        let policy = &self.policies[&key.0];
+        let (samples, failures) = match self.samples.get(&key) {
+            Some(samples) => {
+                let recent: Vec<&(i64, bool)> = samples.iter().filter(|&&(x, _)| x > timestamp - policy.window).collect();
+                (recent.len(), recent.iter().filter(|&&&(_, parsed)| !parsed).count())
+            },
+            None => (0, 0),
+        };
+
+        let failure_rate = match samples {
+            0 => 0.0,
+            _ => failures as f64 / samples as f64,
+        };
+
+        //record an insufficient data status rather than evaluating unreliable keys
+        let reason = if samples < policy.minimum_samples {
+            Some(format!("{} samples within {}s, requires {}", samples, policy.window, policy.minimum_samples))
+        } else if failure_rate > policy.maximum_failure_rate {
+            Some(format!("parse failure rate {:.2} exceeds {:.2}", failure_rate, policy.maximum_failure_rate))
+        } else {
+            None
+        };
+
+        let passes = reason.is_none();
+        let mut statuses = self.statuses.statuses.lock().unwrap();
+        statuses.insert(key,
+            KeyQuality {
+                timestamp: timestamp,
+                samples: samples,
+                failure_rate: failure_rate,
+                reason: reason,
+            }
+        );
+
+        passes
+    }
+}
+
+impl Evictable for QualityGate {
+    fn memory_usage(&self) -> usize {
+        self.samples.iter().map(|(&(ref measurement_class, ref hostname, ref domain), samples)| {
+            resources::key_size(&[measurement_class, hostname, domain]) + (samples.len() * 16)
+        }).sum()
+    }
+
+    fn evict(&mut self, bytes: usize) -> usize {
+        //evict keys whose most recent sample is oldest first
+        let mut keys: Vec<(i64, Key)> = self.samples.iter()
+            .map(|(key, samples)| (samples.back().map_or(0, |&(x, _)| x), key.clone())).collect();
+        keys.sort();
+
+        let mut usage = self.memory_usage();
+        let mut statuses = self.statuses.statuses.lock().unwrap();
+        let mut count = 0;
+        for (_, key) in keys {
+            if usage <= bytes {
+                break;
+            }
+
+            if let Some(samples) = self.samples.remove(&key) {
+                usage = usage.saturating_sub(resources::key_size(&[&key.0, &key.1, &key.2]) + (samples.len() * 16));
+            }
+
+            statuses.remove(&key);
+            count += 1;
+        }
+
+        count
+    }
+}
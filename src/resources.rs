@@ -0,0 +1,78 @@
+use std;
+use std::fs::File;
+use std::io::Read;
+
+//cgroup v1 reports an unlimited memory limit as a very large value
+const UNLIMITED_MEMORY: u64 = 1 << 60;
+
+pub trait Evictable {
+    //approximate bytes held by cached state
+    fn memory_usage(&self) -> usize;
+
+    //evict least recently updated state until usage is at most bytes, returning entries evicted
+    fn evict(&mut self, bytes: usize) -> usize;
+}
+
+pub fn enforce_limit(name: &str, evictable: &mut Evictable, bytes: usize) {
+    if evictable.memory_usage() > bytes {
+        let count = evictable.evict(bytes);
+        info!("evicted {} {} entry(s) to stay within {} byte(s)", count, name, bytes);
+    }
+}
+
+pub fn key_size(fields: &[&str]) -> usize {
+    //string contents plus allocation overhead of each field
+    fields.iter().map(|x| x.len() + 24).sum()
+}
+
+//cpus available to tipup, honoring cgroup v2 and v1 cpu quotas
+pub fn available_cpus() -> usize {
+    let cpus = match read_file("/proc/cpuinfo") {
+        Some(cpuinfo) => cpuinfo.lines().filter(|x| x.starts_with("processor")).count(),
+        None => 1,
+    };
+
+    match cpu_quota() {
+        Some(quota) => std::cmp::min(cpus, quota.ceil().max(1.0) as usize).max(1),
+        None => cpus.max(1),
+    }
+}
+
+fn cpu_quota() -> Option<f64> {
+    //cgroup v2 'cpu.max' contains '<quota> <period>' or 'max <period>'
+    if let Some(cpu_max) = read_file("/sys/fs/cgroup/cpu.max") {
+        let mut fields = cpu_max.split_whitespace();
+        return match (fields.next().map(|x| x.parse::<f64>()), fields.next().map(|x| x.parse::<f64>())) {
+            (Some(Ok(quota)), Some(Ok(period))) if period > 0.0 => Some(quota / period),
+            _ => None,
+        };
+    }
+
+    //cgroup v1 reports a quota of -1 when unlimited
+    match (read_file("/sys/fs/cgroup/cpu/cpu.cfs_quota_us"), read_file("/sys/fs/cgroup/cpu/cpu.cfs_period_us")) {
+        (Some(quota), Some(period)) => match (quota.trim().parse::<f64>(), period.trim().parse::<f64>()) {
+            (Ok(quota), Ok(period)) if quota > 0.0 && period > 0.0 => Some(quota / period),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+//memory limit in bytes imposed by cgroup v2 or v1, if any
+pub fn memory_limit() -> Option<u64> {
+    let limit = read_file("/sys/fs/cgroup/memory.max")
+        .or(read_file("/sys/fs/cgroup/memory/memory.limit_in_bytes"));
+
+    match limit.map(|x| x.trim().parse::<u64>()) {
+        Some(Ok(limit)) if limit < UNLIMITED_MEMORY => Some(limit),
+        _ => None,
+    }
+}
+
+fn read_file(path: &str) -> Option<String> {
+    let mut contents = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) => Some(contents),
+        Err(_) => None,
+    }
+}
@@ -0,0 +1,124 @@
+use bson::{Bson, Document};
+use mongodb::coll::options::{CursorType, FindOptions};
+use mongodb::cursor::Cursor;
+use mongodb::db::{Database, ThreadedDatabase};
+use serde_json;
+
+use error::TipupError;
+
+//the collection results are read from, which may be a view, optionally transformed by an
+//aggregation pipeline whose output documents are treated as results, alongside collections of
+//results adapted from other platforms (e.g. ripe atlas) which are read as they are
+pub struct ResultSource {
+    collection: String,
+    pipeline: Vec<Document>,
+    adapted_collections: Vec<String>,
+}
+
+impl ResultSource {
+    pub fn new(collection: &str, pipeline: &str, adapted_collections: Vec<String>) -> Result<ResultSource, TipupError> {
+        let mut stages = Vec::new();
+        if pipeline.len() > 0 {
+            let value = match serde_json::from_str(pipeline) {
+                Ok(value) => value,
+                Err(e) => return Err(TipupError::from(format!("failed to parse results pipeline as json: {}", e))),
+            };
+
+            match Bson::from_json(&value) {
+                Bson::Array(array) => for stage in array {
+                    match stage {
+                        Bson::Document(stage) => stages.push(stage),
+                        _ => return Err(TipupError::from("results pipeline stages must be json objects")),
+                    }
+                },
+                _ => return Err(TipupError::from("results pipeline must be a json array of stages")),
+            }
+        }
+
+        Ok(
+            ResultSource {
+                collection: collection.to_owned(),
+                pipeline: stages,
+                adapted_collections: adapted_collections,
+            }
+        )
+    }
+
+    //results of every collection merged oldest first, limited as a whole
+    pub fn find(&self, db: &Database, filter: Document, sort: Document, limit: Option<i64>) -> Result<Vec<Document>, TipupError> {
+        let mut documents = try!(collect(try!(self.find_collection(db, filter.clone(), sort.clone(), limit))));
+        if self.adapted_collections.len() == 0 {
+            return Ok(documents);
+        }
+
+        for collection in self.adapted_collections.iter() {
+            let find_options = find_options(sort.clone(), limit);
+            documents.extend(try!(collect(try!(db.collection(collection).find(Some(filter.clone()), Some(find_options))))));
+        }
+
+        documents.sort_by_key(|x| match x.get("timestamp") {
+            Some(&Bson::I64(timestamp)) => timestamp,
+            _ => 0,
+        });
+
+        if let Some(limit) = limit {
+            documents.truncate(limit as usize);
+        }
+
+        Ok(documents)
+    }
+
+    pub fn aggregate(&self, db: &Database, stages: Vec<Document>) -> Result<Vec<Document>, TipupError> {
+        let mut pipeline = self.pipeline.clone();
+        pipeline.extend(stages.clone());
+        let mut documents = try!(collect(try!(db.collection(&self.collection).aggregate(pipeline, None))));
+        for collection in self.adapted_collections.iter() {
+            documents.extend(try!(collect(try!(db.collection(collection).aggregate(stages.clone(), None)))));
+        }
+
+        Ok(documents)
+    }
+
+    fn find_collection(&self, db: &Database, filter: Document, sort: Document, limit: Option<i64>) -> Result<Cursor, TipupError> {
+        //pipelines filter, sort, and limit their output
+        if self.pipeline.len() > 0 {
+            let mut stages = self.pipeline.clone();
+            stages.push(doc!("$match" => filter));
+            stages.push(doc!("$sort" => sort));
+            if let Some(limit) = limit {
+                stages.push(doc!("$limit" => limit));
+            }
+
+            return Ok(try!(db.collection(&self.collection).aggregate(stages, None)));
+        }
+
+        Ok(try!(db.collection(&self.collection).find(Some(filter), Some(find_options(sort, limit)))))
+    }
+}
+
+fn find_options(sort: Document, limit: Option<i64>) -> FindOptions {
+    FindOptions {
+        allow_partial_results: false,
+        no_cursor_timeout: false,
+        oplog_replay: false,
+        skip: None,
+        limit: limit,
+        cursor_type: CursorType::NonTailable,
+        batch_size: None,
+        comment: None,
+        max_time_ms: None,
+        modifiers: None,
+        projection: None,
+        sort: Some(sort),
+        read_preference: None,
+    }
+}
+
+fn collect(cursor: Cursor) -> Result<Vec<Document>, TipupError> {
+    let mut documents = Vec::new();
+    for document in cursor {
+        documents.push(try!(document));
+    }
+
+    Ok(documents)
+}
@@ -1,11 +1,16 @@
-use bson::Bson;
+use bson::{Bson, Document};
 use bson::ordered::OrderedDocument;
+use mongodb::coll::options::FindOptions;
 use mongodb::db::{Database, ThreadedDatabase};
 use time;
 
+use analyzer::get_value;
 use error::TipupError;
+use partition;
+use resources::{self, Evictable};
 
-use std::collections::HashMap;
+use std;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 pub struct ResultWindow {
@@ -19,19 +24,19 @@ impl ResultWindow {
         }
     }
 
-    pub fn register_variable(&mut self, variable_name: &Vec<String>) -> Result<Arc<RwLock<VariableWindow>>, TipupError> {
-        //check if variable_name already exists
+    pub fn register_variable(&mut self, variable_name: &Vec<String>, key_fields: &Vec<Vec<String>>) -> Result<Arc<RwLock<VariableWindow>>, TipupError> {
+        //check if variable_name already exists with the same key fields
         for variable_window in self.variable_windows.iter() {
             {
                 let variable_window_clone = variable_window.read().unwrap();
-                if variable_window_clone.variable_name_equals(&variable_name) {
+                if variable_window_clone.variable_name_equals(&variable_name) && &variable_window_clone.key_fields == key_fields {
                     return Ok(variable_window.clone());
                 }
             }
         }
 
         //create new variable window
-        let variable_window = Arc::new(RwLock::new(VariableWindow::new(variable_name.to_owned())));
+        let variable_window = Arc::new(RwLock::new(VariableWindow::new(variable_name.to_owned(), key_fields.to_owned())));
         self.variable_windows.push(variable_window.clone());
         Ok(variable_window)
     }
@@ -47,6 +52,17 @@ impl ResultWindow {
         Ok(())
     }
 
+    pub fn snapshot(&self, full: bool) -> Vec<(Vec<String>, Vec<Document>)> {
+        let mut snapshots = Vec::new();
+        for variable_window in self.variable_windows.iter() {
+            let mut variable_window = variable_window.write().unwrap();
+            let entries = variable_window.snapshot_entries(full);
+            snapshots.push((variable_window.snapshot_name(), entries));
+        }
+
+        snapshots
+    }
+
     pub fn add_result(&mut self, document: OrderedDocument) -> Result<(), TipupError> {
         //parse hostname and domain
         let (hostname, domain);
@@ -72,27 +88,64 @@ impl ResultWindow {
 
         Ok(())
     }
+
+    pub fn reset(&mut self, hostname: &str, domain: &str) {
+        for variable_window in self.variable_windows.iter() {
+            let mut variable_window = variable_window.write().unwrap();
+            variable_window.reset(hostname, domain);
+        }
+    }
+}
+
+impl Evictable for ResultWindow {
+    fn memory_usage(&self) -> usize {
+        self.variable_windows.iter().map(|x| x.read().unwrap().memory_usage()).sum()
+    }
+
+    fn evict(&mut self, bytes: usize) -> usize {
+        //share the limit evenly between variables
+        let bytes = bytes / std::cmp::max(self.variable_windows.len(), 1);
+        self.variable_windows.iter().map(|x| x.write().unwrap().evict(bytes)).sum()
+    }
 }
 
 pub struct VariableWindow {
     variable_name: Vec<String>,
+    key_fields: Vec<Vec<String>>,
     values: HashMap<String, HashMap<String, Vec<f64>>>,
+    dirty: HashSet<(String, String)>,
+    sequence: u64,
+    updated: HashMap<(String, String), u64>,
 }
 
 impl VariableWindow {
-    fn new(variable_name: Vec<String>) -> VariableWindow {
+    fn new(variable_name: Vec<String>, key_fields: Vec<Vec<String>>) -> VariableWindow {
         VariableWindow {
             variable_name: variable_name,
+            key_fields: key_fields,
             values: HashMap::new(),
+            dirty: HashSet::new(),
+            sequence: 0,
+            updated: HashMap::new(),
         }
     }
 
     fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //prefer restoring learned values from persisted snapshots
+        if try!(self.restore(proddle_db)) {
+            return Ok(());
+        }
+
+        //windows partitioned by key fields learn from live results
+        if self.key_fields.len() > 0 {
+            return Ok(());
+        }
+
         let start_time = time::now_utc().to_timespec().sec - (60 * 60 * 24 * 5);
         let timestamp_gte = doc!("$gte" => start_time);
         let match_doc = doc!("measurement_class" => "HttpGet", "timestamp" => timestamp_gte);
-        let id_doc = doc!("vantage_hostname" => "$hostname", "domain" => "$domain");
-        let values_doc = doc!("$push" => "$total_time");
+        let id_doc = doc!("vantage_hostname" => "$vantage_hostname", "domain" => "$measurement_domain");
+        let values_doc = doc!("$push" => (format!("${}", self.variable_name.join("."))));
         let group_doc = doc!("_id" => id_doc, "values" => values_doc);
         let aggregate_doc = vec!(
             doc!("$match" => match_doc),
@@ -142,18 +195,174 @@ impl VariableWindow {
         Ok(())
     }
 
+    fn restore(&mut self, proddle_db: &Database) -> Result<bool, TipupError> {
+        //find most recent full snapshot
+        let variable_name: Vec<Bson> = self.snapshot_name().into_iter().map(|x| Bson::String(x)).collect();
+        let negative_one = -1;
+        let sort_document = doc!("sequence" => negative_one);
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(sort_document);
+        let search_document = Some(doc!("variable_name" => (variable_name.clone()), "kind" => "full"));
+        let full_sequence = match try!(proddle_db.collection("state_snapshots").find_one(search_document, Some(find_options))) {
+            Some(document) => match document.get("sequence") {
+                Some(&Bson::I64(sequence)) => sequence,
+                _ => return Err(TipupError::from("failed to parse state snapshot sequence")),
+            },
+            None => return Ok(false),
+        };
+
+        //apply full snapshot followed by subsequent deltas in order
+        let sequence_gte = doc!("$gte" => full_sequence);
+        let search_document = Some(doc!("variable_name" => variable_name, "sequence" => sequence_gte));
+        let one = 1;
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("sequence" => one));
+        let mut count = 0;
+        for document in try!(proddle_db.collection("state_snapshots").find(search_document, Some(find_options))) {
+            let document = try!(document);
+            let entries = match document.get("entries") {
+                Some(&Bson::Array(ref entries)) => entries,
+                _ => return Err(TipupError::from("failed to parse state snapshot entries")),
+            };
+
+            for entry in entries {
+                let entry = match entry {
+                    &Bson::Document(ref entry) => entry,
+                    _ => continue,
+                };
+
+                let (hostname, domain) = match (entry.get("vantage_hostname"), entry.get("domain")) {
+                    (Some(&Bson::String(ref hostname)), Some(&Bson::String(ref domain))) => (hostname.to_owned(), domain.to_owned()),
+                    _ => continue,
+                };
+
+                let values = match entry.get("values") {
+                    Some(&Bson::Array(ref array)) => array.iter().filter_map(|x| match x {
+                        &Bson::FloatingPoint(f) => Some(f),
+                        _ => None,
+                    }).collect(),
+                    _ => continue,
+                };
+
+                self.values.entry(hostname).or_insert(HashMap::new()).insert(domain, values);
+                count += 1;
+            }
+        }
+
+        info!("restored {} state entry(s) for variable {:?} from snapshot {}", count, self.variable_name, full_sequence);
+        Ok(true)
+    }
+
+    fn snapshot_entries(&mut self, full: bool) -> Vec<Document> {
+        let mut entries = Vec::new();
+        for (hostname, domain_map) in self.values.iter() {
+            for (domain, values) in domain_map.iter() {
+                if !full && !self.dirty.contains(&(hostname.to_owned(), domain.to_owned())) {
+                    continue;
+                }
+
+                let values: Vec<Bson> = values.iter().map(|x| Bson::FloatingPoint(*x)).collect();
+                entries.push(doc!(
+                    "vantage_hostname" => hostname,
+                    "domain" => domain,
+                    "values" => values
+                ));
+            }
+        }
+
+        self.dirty.clear();
+        entries
+    }
+
     fn add_result(&mut self, hostname: &str, domain: &str, document: &OrderedDocument) -> Result<(), TipupError> {
         if let Some(value) = get_value(&self.variable_name, document) {
+            let domain = &partition::partition_domain(domain, &self.key_fields, document);
             let values = self.values.entry(hostname.to_owned()).or_insert(HashMap::new()).entry(domain.to_owned()).or_insert(Vec::new());
             values.push(value);
             if values.len() > 10 {
                 values.remove(0);
             }
+
+            self.dirty.insert((hostname.to_owned(), domain.to_owned()));
+            self.sequence += 1;
+            self.updated.insert((hostname.to_owned(), domain.to_owned()), self.sequence);
         }
 
         Ok(())
     }
 
+    fn reset(&mut self, hostname: &str, domain: &str) {
+        //retain empty entries so the next delta snapshot records the reset
+        if let Some(domain_map) = self.values.get_mut(hostname) {
+            for (partition_domain, values) in domain_map.iter_mut().filter(|&(ref x, _)| partition::contains(x, domain)) {
+                values.clear();
+                self.dirty.insert((hostname.to_owned(), partition_domain.to_owned()));
+            }
+        }
+    }
+
+    //snapshots of partitioned windows are distinguished by their key fields
+    fn snapshot_name(&self) -> Vec<String> {
+        let mut snapshot_name = self.variable_name.clone();
+        if self.key_fields.len() > 0 {
+            let key_fields: Vec<String> = self.key_fields.iter().map(|x| x.join(".")).collect();
+            snapshot_name.push(format!("[{}]", key_fields.join(",")));
+        }
+
+        snapshot_name
+    }
+
+    fn memory_usage(&self) -> usize {
+        let mut usage = 0;
+        for (hostname, domain_map) in self.values.iter() {
+            for (domain, values) in domain_map.iter() {
+                usage += resources::key_size(&[hostname, domain]) + (values.len() * 8);
+            }
+        }
+
+        usage
+    }
+
+    fn evict(&mut self, bytes: usize) -> usize {
+        //evict least recently updated keys first
+        let mut keys: Vec<(u64, String, String)> = Vec::new();
+        for (hostname, domain_map) in self.values.iter() {
+            for domain in domain_map.keys() {
+                let sequence = *self.updated.get(&(hostname.to_owned(), domain.to_owned())).unwrap_or(&0);
+                keys.push((sequence, hostname.to_owned(), domain.to_owned()));
+            }
+        }
+        keys.sort();
+
+        let mut usage = self.memory_usage();
+        let mut count = 0;
+        for (_, hostname, domain) in keys {
+            if usage <= bytes {
+                break;
+            }
+
+            let mut empty = false;
+            if let Some(domain_map) = self.values.get_mut(&hostname) {
+                if let Some(values) = domain_map.remove(&domain) {
+                    usage = usage.saturating_sub(resources::key_size(&[&hostname, &domain]) + (values.len() * 8));
+                }
+
+                empty = domain_map.len() == 0;
+            }
+
+            if empty {
+                self.values.remove(&hostname);
+            }
+
+            let key = (hostname, domain);
+            self.dirty.remove(&key);
+            self.updated.remove(&key);
+            count += 1;
+        }
+
+        count
+    }
+
     pub fn get_values(&self, hostname: &str, domain: &str) -> Option<&Vec<f64>> {
         if let Some(domain_map) = self.values.get(hostname) {
             if let Some(results) = domain_map.get(domain) {
@@ -180,21 +389,6 @@ impl VariableWindow {
         true
     }
 }
-
-fn get_value(variable_name: &Vec<String>, document: &OrderedDocument) -> Option<f64> {
-    let mut index_document = document;
-    for variable in variable_name {
-        match index_document.get(variable) {
-            Some(&Bson::Document(ref document)) => index_document = document,
-            Some(&Bson::FloatingPoint(f)) => return Some(f),
-            Some(&Bson::I32(i)) => return Some(i as f64),
-            Some(&Bson::I64(i)) => return Some(i as f64),
-            _ => return None,
-        }
-    }
-
-    None
-}
 /*pub struct ResultWindow {
     results: HashMap<String, HashMap<String, Vec<OrderedDocument>>>,
 }
@@ -0,0 +1,155 @@
+use bson::Bson;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+//max age seconds of crash reports written to the configured crash report collection
+const CRASH_REPORT_MAX_AGE: i64 = 7776000;
+
+//default (collection, timestamp field, max age seconds) policies for tipup-owned collections,
+//where flags only carry a resolved_timestamp once resolved so open flags are never pruned
+const DEFAULT_POLICIES: [(&'static str, &'static str, i64); 16] = [
+    ("analyzer_revisions", "timestamp", 31536000),
+    ("analyzer_traces", "timestamp", 604800),
+    ("availability", "timestamp", 604800),
+    ("dead_letters", "timestamp", 2592000),
+    ("derived_results", "timestamp", 604800),
+    ("events", "end_timestamp", 7776000),
+    ("flags", "resolved_timestamp", 7776000),
+    ("incident_timelines", "updated_timestamp", 7776000),
+    ("incidents", "maximum_timestamp", 7776000),
+    ("noise_report", "timestamp", 2592000),
+    ("self_healing", "timestamp", 7776000),
+    ("silences", "end_timestamp", 2592000),
+    ("sla_compliance", "timestamp", 34214400),
+    ("state_snapshots", "timestamp", 2592000),
+    ("target_stats", "timestamp", 604800),
+    ("trace_subscriptions", "until_timestamp", 86400),
+];
+
+pub struct RetentionManager {
+    time_of_day: i64,
+    crash_report_collection: String,
+    last_day: Option<i64>,
+    clock: Arc<Clock>,
+}
+
+impl RetentionManager {
+    pub fn new(time_of_day: i64, crash_report_collection: &str, clock: Arc<Clock>) -> RetentionManager {
+        RetentionManager {
+            time_of_day: time_of_day,
+            crash_report_collection: crash_report_collection.to_owned(),
+            last_day: None,
+            clock: clock,
+        }
+    }
+
+    //pruning runs once per utc day, at the first execution past the configured time of day
+    fn due(&self, now: i64) -> bool {
+        let day = now / SECONDS_PER_DAY;
+        now % SECONDS_PER_DAY >= self.time_of_day && self.last_day.map_or(true, |x| x < day)
+    }
+
+    pub fn execute(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        let now = self.clock.now();
+        if !self.due(now) {
+            return Ok(());
+        }
+
+        self.last_day = Some(now / SECONDS_PER_DAY);
+
+        //merge configured retention policies over defaults
+        let mut policies: HashMap<String, (String, i64)> = HashMap::new();
+        for &(collection, timestamp_field, max_age) in DEFAULT_POLICIES.iter() {
+            policies.insert(collection.to_owned(), (timestamp_field.to_owned(), max_age));
+        }
+
+        if self.crash_report_collection.len() > 0 {
+            policies.insert(self.crash_report_collection.clone(), ("timestamp".to_owned(), CRASH_REPORT_MAX_AGE));
+        }
+
+        for document in try!(proddle_db.collection("retention_policies").find(None, None)) {
+            let document = try!(document);
+
+            let collection = match document.get("collection") {
+                Some(&Bson::String(ref collection)) => collection.to_owned(),
+                _ => return Err(TipupError::from("failed to parse retention policy collection")),
+            };
+
+            let max_age = match document.get("max_age") {
+                Some(&Bson::I32(max_age)) => max_age as i64,
+                Some(&Bson::I64(max_age)) => max_age,
+                _ => return Err(TipupError::from(format!("failed to parse retention policy max_age for collection '{}'", collection))),
+            };
+
+            let timestamp_field = match document.get("timestamp_field") {
+                Some(&Bson::String(ref timestamp_field)) => timestamp_field.to_owned(),
+                _ => "timestamp".to_owned(),
+            };
+
+            policies.insert(collection, (timestamp_field, max_age));
+        }
+
+        //prune documents older than each collection's max age
+        for (collection, &(ref timestamp_field, max_age)) in policies.iter() {
+            if max_age <= 0 {
+                continue;
+            }
+
+            let timestamp_lt = doc!("$lt" => (now - max_age));
+            let search_document = doc!(timestamp_field => timestamp_lt);
+            let result = try!(proddle_db.collection(collection).delete_many(search_document, None));
+            if result.deleted_count > 0 {
+                info!("pruned {} document(s) from '{}'", result.deleted_count, collection);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//parses an 'HH:MM' utc time of day into seconds past midnight
+pub fn parse_time_of_day(time_of_day: &str) -> Result<i64, TipupError> {
+    let mut fields = time_of_day.splitn(2, ':');
+    match (fields.next().map(|x| x.parse::<i64>()), fields.next().map(|x| x.parse::<i64>())) {
+        (Some(Ok(hour)), Some(Ok(minute))) if hour >= 0 && hour < 24 && minute >= 0 && minute < 60 => Ok(hour * 3600 + minute * 60),
+        _ => Err(TipupError::from(format!("failed to parse time of day '{}' as HH:MM", time_of_day))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clock::MockClock;
+
+    use super::{parse_time_of_day, RetentionManager};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn parses_time_of_day() {
+        assert_eq!(parse_time_of_day("00:00").unwrap(), 0);
+        assert_eq!(parse_time_of_day("03:30").unwrap(), 12600);
+        assert_eq!(parse_time_of_day("23:59").unwrap(), 86340);
+        assert!(parse_time_of_day("24:00").is_err());
+        assert!(parse_time_of_day("3").is_err());
+        assert!(parse_time_of_day("03:60").is_err());
+    }
+
+    #[test]
+    fn runs_once_a_day_past_time_of_day() {
+        let mut retention_manager = RetentionManager::new(10800, "", Arc::new(MockClock::new(0)));
+        assert!(!retention_manager.due(10799));
+        assert!(retention_manager.due(10800));
+
+        retention_manager.last_day = Some(0);
+        assert!(!retention_manager.due(86399));
+        assert!(!retention_manager.due(86400 + 10799));
+        assert!(retention_manager.due(86400 + 10800));
+    }
+}
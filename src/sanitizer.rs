@@ -0,0 +1,184 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use chan::Sender;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use analyzer::get_bson;
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::collections::HashMap;
+
+pub enum Policy {
+    Drop,
+    Clamp,
+    Flag,
+}
+
+struct FieldPolicy {
+    field: Vec<String>,
+    policy: Policy,
+    status: String,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+}
+
+pub struct Sanitizer {
+    policies: HashMap<String, Vec<FieldPolicy>>,
+    flag_tx: Sender<Flag>,
+}
+
+impl Sanitizer {
+    pub fn new(flag_tx: Sender<Flag>) -> Sanitizer {
+        Sanitizer {
+            policies: HashMap::new(),
+            flag_tx: flag_tx,
+        }
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //query mongodb for sanitization policies
+        let mut count = 0;
+        let cursor = try!(proddle_db.collection("sanitization_policies").find(None, None));
+        for document in cursor {
+            let document = try!(document);
+
+            let measurement_class = match document.get("measurement_class") {
+                Some(&Bson::String(ref measurement_class)) => measurement_class.to_owned(),
+                _ => return Err(TipupError::from("failed to parse sanitization policy measurement_class")),
+            };
+
+            let field: Vec<String> = match document.get("field") {
+                Some(&Bson::Array(ref field)) => field.iter().map(|x| x.to_string().replace("\"", "")).collect(),
+                _ => return Err(TipupError::from("failed to parse sanitization policy field")),
+            };
+
+            let policy = match document.get("policy") {
+                Some(&Bson::String(ref policy)) => {
+                    match policy.as_ref() {
+                        "drop" => Policy::Drop,
+                        "clamp" => Policy::Clamp,
+                        "flag" => Policy::Flag,
+                        _ => return Err(TipupError::from(format!("unknown sanitization policy '{}'", policy))),
+                    }
+                },
+                _ => return Err(TipupError::from("failed to parse sanitization policy")),
+            };
+
+            let status = match document.get("status") {
+                Some(&Bson::String(ref status)) => status.to_owned(),
+                _ => "invalid".to_owned(),
+            };
+
+            let (minimum, maximum) = (get_bound(&document, "minimum"), get_bound(&document, "maximum"));
+            if let Policy::Clamp = policy {
+                if minimum.is_none() && maximum.is_none() {
+                    return Err(TipupError::from("clamp sanitization policy requires a minimum or maximum"));
+                }
+            }
+
+            self.policies.entry(measurement_class).or_insert(Vec::new()).push(
+                FieldPolicy {
+                    field: field,
+                    policy: policy,
+                    status: status,
+                    minimum: minimum,
+                    maximum: maximum,
+                }
+            );
+
+            count += 1;
+        }
+
+        if count > 0 {
+            info!("loaded {} sanitization policy(s)", count);
+        }
+
+        Ok(())
+    }
+
+    pub fn sanitize(&self, mut document: OrderedDocument) -> Result<Option<OrderedDocument>, TipupError> {
+        let policies = match document.get("measurement_class") {
+            Some(&Bson::String(ref measurement_class)) => match self.policies.get(measurement_class) {
+                Some(policies) => policies,
+                None => return Ok(Some(document)),
+            },
+            _ => return Ok(Some(document)),
+        };
+
+        for field_policy in policies.iter() {
+            //missing fields are left for analyzers to handle
+            let value = match get_bson(&field_policy.field, &document) {
+                Some(&Bson::FloatingPoint(f)) => Some(f),
+                Some(&Bson::I32(i)) => Some(i as f64),
+                Some(&Bson::I64(i)) => Some(i as f64),
+                Some(_) => None,
+                None => continue,
+            };
+
+            //check if value is valid
+            let valid = match value {
+                Some(value) => value.is_finite()
+                    && field_policy.minimum.map_or(true, |minimum| value >= minimum)
+                    && field_policy.maximum.map_or(true, |maximum| value <= maximum),
+                None => false,
+            };
+
+            if valid {
+                continue;
+            }
+
+            match field_policy.policy {
+                Policy::Drop => return Ok(None),
+                Policy::Clamp => {
+                    let value = match value {
+                        Some(value) if !value.is_nan() => value,
+                        _ => return Ok(None),
+                    };
+
+                    let mut clamped = value;
+                    if let Some(minimum) = field_policy.minimum {
+                        clamped = clamped.max(minimum);
+                    }
+
+                    if let Some(maximum) = field_policy.maximum {
+                        clamped = clamped.min(maximum);
+                    }
+
+                    set_value(&field_policy.field, &mut document, clamped);
+                },
+                Policy::Flag => {
+                    let flag = try!(Flag::new(&document, &field_policy.status, "sanitizer"));
+                    self.flag_tx.send(flag);
+                    return Ok(None);
+                },
+            }
+        }
+
+        Ok(Some(document))
+    }
+}
+
+fn get_bound(document: &OrderedDocument, key: &str) -> Option<f64> {
+    match document.get(key) {
+        Some(&Bson::FloatingPoint(f)) => Some(f),
+        Some(&Bson::I32(i)) => Some(i as f64),
+        Some(&Bson::I64(i)) => Some(i as f64),
+        _ => None,
+    }
+}
+
+fn set_value(field: &[String], document: &mut OrderedDocument, value: f64) {
+    if field.len() == 1 {
+        //preserve integer types where the original value was integral
+        let bson = match document.get(&field[0]) {
+            Some(&Bson::I32(_)) => Bson::I32(value.round() as i32),
+            Some(&Bson::I64(_)) => Bson::I64(value.round() as i64),
+            _ => Bson::FloatingPoint(value),
+        };
+
+        document.insert(field[0].to_owned(), bson);
+    } else if let Some(&mut Bson::Document(ref mut document)) = document.get_mut(&field[0]) {
+        set_value(&field[1..], document, value);
+    }
+}
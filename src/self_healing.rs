@@ -0,0 +1,142 @@
+use bson::{Bson, Document};
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+use flag_manager::Flag;
+
+use std;
+use std::collections::HashMap;
+
+//analyzers require this many auto-resolved flags before their confirmation is adjusted
+const MINIMUM_RESOLVED: i64 = 10;
+
+//confirmation duration assigned on the first adjustment of an analyzer
+const MINIMUM_CONFIRMATION: i64 = 60;
+
+pub fn record(proddle_db: &Database, flags: &[Flag], window: i64, timestamp: i64) -> Result<(), TipupError> {
+    //flags resolved while still open and lasting at most window seconds healed on their own
+    let mut counts: HashMap<&str, (i64, i64)> = HashMap::new();
+    for flag in flags.iter() {
+        let count = counts.entry(&flag.analyzer).or_insert((0, 0));
+        count.0 += 1;
+        if is_self_healed(flag, window) {
+            count.1 += 1;
+        }
+    }
+
+    for (analyzer, (resolved, self_healed)) in counts {
+        let search_document = doc!("analyzer" => analyzer);
+        let update_document = doc!(
+            "$inc" => { "resolved" => resolved, "self_healed" => self_healed },
+            "$set" => { "timestamp" => timestamp }
+        );
+
+        let mut update_options = UpdateOptions::new();
+        update_options.upsert = Some(true);
+        try!(proddle_db.collection("self_healing").update_one(search_document, update_document, Some(update_options)));
+    }
+
+    Ok(())
+}
+
+pub fn is_self_healed(flag: &Flag, window: i64) -> bool {
+    flag.state == "open" && flag.last_timestamp - flag.timestamp <= window
+}
+
+pub fn statistics(proddle_db: &Database) -> Result<Vec<Document>, TipupError> {
+    let confirmations = try!(load_confirmations(proddle_db));
+    let mut documents = Vec::new();
+    for document in try!(proddle_db.collection("self_healing").find(None, None)) {
+        let mut document = try!(document);
+        document.remove("_id");
+
+        let (resolved, self_healed) = try!(parse_counts(&document));
+        let ratio = match resolved {
+            0 => 0.0,
+            _ => self_healed as f64 / resolved as f64,
+        };
+
+        let confirmation = match document.get("analyzer") {
+            Some(&Bson::String(ref analyzer)) => confirmations.get(analyzer).cloned().unwrap_or(0),
+            _ => 0,
+        };
+
+        document.insert("ratio", ratio);
+        document.insert("confirmation", confirmation);
+        documents.push(document);
+    }
+
+    Ok(documents)
+}
+
+pub fn adjust(proddle_db: &Database, threshold: f64, window: i64, timestamp: i64) -> Result<usize, TipupError> {
+    //raise confirmation durations of analyzers whose flags mostly heal on their own
+    let confirmations = try!(load_confirmations(proddle_db));
+    let mut count = 0;
+    for document in try!(proddle_db.collection("self_healing").find(None, None)) {
+        let document = try!(document);
+        let analyzer = match document.get("analyzer") {
+            Some(&Bson::String(ref analyzer)) => analyzer.to_owned(),
+            _ => return Err(TipupError::from("failed to parse self healing analyzer")),
+        };
+
+        let (resolved, self_healed) = try!(parse_counts(&document));
+        if resolved < MINIMUM_RESOLVED || (self_healed as f64 / resolved as f64) < threshold {
+            continue;
+        }
+
+        //double the confirmation duration, flags healing within window need wait no longer than it
+        let confirmation = match confirmations.get(&analyzer) {
+            Some(&confirmation) => confirmation,
+            None => continue,
+        };
+
+        let adjusted_confirmation = std::cmp::min(std::cmp::max(confirmation * 2, MINIMUM_CONFIRMATION), window);
+        if adjusted_confirmation <= confirmation {
+            continue;
+        }
+
+        let update_document = doc!("$set" => { "confirmation" => adjusted_confirmation, "modified_by" => "self_healing" });
+        try!(proddle_db.collection("analyzers").update_one(doc!("name" => (analyzer.clone())), update_document, None));
+
+        //restart statistics so the next adjustment reflects the new confirmation duration
+        let update_document = doc!("$set" => { "resolved" => 0i64, "self_healed" => 0i64, "adjusted_timestamp" => timestamp });
+        try!(proddle_db.collection("self_healing").update_one(doc!("analyzer" => (analyzer.clone())), update_document, None));
+
+        info!("raised confirmation of analyzer '{}' from {}s to {}s after {} of {} flag(s) self healed",
+            analyzer, confirmation, adjusted_confirmation, self_healed, resolved);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+pub fn load_confirmations(proddle_db: &Database) -> Result<HashMap<String, i64>, TipupError> {
+    let mut confirmations = HashMap::new();
+    for document in try!(proddle_db.collection("analyzers").find(None, None)) {
+        let document = try!(document);
+        let name = match document.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => continue,
+        };
+
+        let confirmation = match document.get("confirmation") {
+            Some(&Bson::I32(confirmation)) if confirmation >= 0 => confirmation as i64,
+            Some(&Bson::I64(confirmation)) if confirmation >= 0 => confirmation,
+            None => 0,
+            _ => return Err(TipupError::from(format!("failed to parse confirmation of analyzer '{}' as non-negative seconds", name))),
+        };
+
+        confirmations.insert(name, confirmation);
+    }
+
+    Ok(confirmations)
+}
+
+fn parse_counts(document: &Document) -> Result<(i64, i64), TipupError> {
+    match (document.get("resolved"), document.get("self_healed")) {
+        (Some(&Bson::I64(resolved)), Some(&Bson::I64(self_healed))) => Ok((resolved, self_healed)),
+        _ => Err(TipupError::from("failed to parse self healing 'resolved' and 'self_healed' counts")),
+    }
+}
@@ -0,0 +1,78 @@
+use error::TipupError;
+
+use std::collections::HashMap;
+
+//statuses raised by tipup itself (parameter monitor, slas, sanitizer) and assumed by default
+//definitions, ordered from least to most severe
+pub const BUILTIN_LEVELS: [&'static str; 4] = ["info", "warning", "error", "critical"];
+
+//the ordered severity levels of a deployment, e.g. P5 through P1, with mappings of the built-in
+//statuses onto them so every flag carries a configured level
+#[derive(Clone)]
+pub struct SeverityTaxonomy {
+    levels: Vec<String>,
+    mappings: HashMap<String, String>,
+}
+
+impl SeverityTaxonomy {
+    //levels are ordered from least to most severe, mappings of the form 'status=level'
+    pub fn new(levels: &Vec<String>, mappings: &Vec<String>) -> Result<SeverityTaxonomy, TipupError> {
+        let levels: Vec<String> = match levels.len() {
+            0 => BUILTIN_LEVELS.iter().map(|x| x.to_string()).collect(),
+            _ => levels.clone(),
+        };
+
+        for (i, level) in levels.iter().enumerate() {
+            if level.len() == 0 || level.contains(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')') {
+                return Err(TipupError::from(format!("invalid severity level '{}'", level)));
+            }
+
+            if levels[..i].contains(level) {
+                return Err(TipupError::from(format!("duplicate severity level '{}'", level)));
+            }
+        }
+
+        let mut taxonomy_mappings = HashMap::new();
+        for mapping in mappings.iter() {
+            let (status, level) = match mapping.find('=') {
+                Some(index) => (mapping[..index].trim(), mapping[index + 1..].trim()),
+                None => return Err(TipupError::from(format!("failed to parse severity mapping '{}', expected 'status=level'", mapping))),
+            };
+
+            if !levels.iter().any(|x| x == level) {
+                return Err(TipupError::from(format!("severity mapping '{}' maps to unknown level '{}'", mapping, level)));
+            }
+
+            if levels.iter().any(|x| x == status) {
+                return Err(TipupError::from(format!("severity mapping '{}' remaps the configured level '{}'", mapping, status)));
+            }
+
+            taxonomy_mappings.insert(status.to_owned(), level.to_owned());
+        }
+
+        for status in BUILTIN_LEVELS.iter() {
+            if !levels.iter().any(|x| x == status) && !taxonomy_mappings.contains_key(*status) {
+                return Err(TipupError::from(format!("built-in status '{}' is neither a severity level nor mapped to one", status)));
+            }
+        }
+
+        Ok(
+            SeverityTaxonomy {
+                levels: levels,
+                mappings: taxonomy_mappings,
+            }
+        )
+    }
+
+    pub fn levels(&self) -> &Vec<String> {
+        &self.levels
+    }
+
+    //the configured level of a status, either a level itself or mapped to one
+    pub fn resolve(&self, status: &str) -> Option<String> {
+        match self.levels.iter().any(|x| x == status) {
+            true => Some(status.to_owned()),
+            false => self.mappings.get(status).cloned(),
+        }
+    }
+}
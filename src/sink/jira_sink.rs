@@ -0,0 +1,276 @@
+use bson::{Bson, Document};
+use bson::oid::ObjectId;
+use bson::ordered::OrderedDocument;
+use hyper::Client;
+use hyper::header::{Authorization, Basic, ContentType};
+use serde_json::{self, Value};
+
+use error::TipupError;
+use flag_manager::Flag;
+use http;
+use sink::{IncidentUpdate, Sink};
+
+use std::collections::{HashMap, HashSet};
+
+//an open ticket tracking the unresolved flags of a domain incident
+struct Ticket {
+    key: String,
+    flag_ids: HashSet<ObjectId>,
+}
+
+//opens a jira ticket per domain incident for flags at or above a minimum status, commenting as
+//flags accumulate and transitioning the ticket once every flag has resolved
+pub struct JiraSink {
+    url: String,
+    project: String,
+    issue_type: String,
+    labels: Vec<String>,
+    username: String,
+    token: String,
+    statuses: Vec<String>,
+    minimum_status: usize,
+    resolve_transition: Option<String>,
+    tickets: HashMap<String, Ticket>,
+    references: HashMap<ObjectId, String>,
+    client: Client,
+}
+
+impl JiraSink {
+    pub fn new(parameters: &OrderedDocument) -> Result<JiraSink, TipupError> {
+        let mut strings = HashMap::new();
+        for key in ["url", "project", "username", "token"].iter() {
+            match parameters.get(key) {
+                Some(&Bson::String(ref value)) => strings.insert(*key, value.to_owned()),
+                _ => return Err(TipupError::from(format!("failed to parse {} parameter in JiraSink", key))),
+            };
+        }
+
+        let issue_type = match parameters.get("issue_type") {
+            Some(&Bson::String(ref issue_type)) => issue_type.to_owned(),
+            None => "Bug".to_owned(),
+            _ => return Err(TipupError::from("failed to parse issue_type parameter in JiraSink")),
+        };
+
+        let labels = try!(parse_strings(parameters, "labels", Vec::new()));
+
+        //statuses are ordered from least to most severe
+        let statuses = try!(parse_strings(parameters, "statuses", ["info", "warning", "error", "critical"].iter().map(|x| x.to_string()).collect()));
+        let minimum_status = match parameters.get("minimum_status") {
+            Some(&Bson::String(ref minimum_status)) => match statuses.iter().position(|x| x == minimum_status) {
+                Some(minimum_status) => minimum_status,
+                None => return Err(TipupError::from(format!("minimum_status '{}' is not one of the JiraSink statuses", minimum_status))),
+            },
+            None => statuses.iter().position(|x| x == "error").unwrap_or(0),
+            _ => return Err(TipupError::from("failed to parse minimum_status parameter in JiraSink")),
+        };
+
+        let resolve_transition = match parameters.get("resolve_transition") {
+            Some(&Bson::String(ref resolve_transition)) => Some(resolve_transition.to_owned()),
+            Some(&Bson::I32(resolve_transition)) => Some(resolve_transition.to_string()),
+            None => None,
+            _ => return Err(TipupError::from("failed to parse resolve_transition parameter in JiraSink")),
+        };
+
+        Ok(
+            JiraSink {
+                url: strings.remove("url").unwrap().trim_end_matches('/').to_owned(),
+                project: strings.remove("project").unwrap(),
+                issue_type: issue_type,
+                labels: labels,
+                username: strings.remove("username").unwrap(),
+                token: strings.remove("token").unwrap(),
+                statuses: statuses,
+                minimum_status: minimum_status,
+                resolve_transition: resolve_transition,
+                tickets: HashMap::new(),
+                references: HashMap::new(),
+                client: try!(http::client()),
+            }
+        )
+    }
+
+    fn create_ticket(&self, summary: String, description: String) -> Result<String, TipupError> {
+        let labels: Vec<Bson> = self.labels.iter().map(|x| Bson::String(x.to_owned())).collect();
+        let fields = doc!(
+            "project" => { "key" => (self.project.clone()) },
+            "issuetype" => { "name" => (self.issue_type.clone()) },
+            "summary" => summary,
+            "description" => description,
+            "labels" => labels
+        );
+
+        let response = try!(self.post("issue", doc!("fields" => fields)));
+        match response.get("key").and_then(|x| x.as_str()) {
+            Some(key) => Ok(key.to_owned()),
+            None => Err(TipupError::from("jira responded without an issue key")),
+        }
+    }
+
+    fn comment(&self, key: &str, body: String) -> Result<(), TipupError> {
+        try!(self.post(&format!("issue/{}/comment", key), doc!("body" => body)));
+        Ok(())
+    }
+
+    fn post(&self, path: &str, document: Document) -> Result<Value, TipupError> {
+        let url = format!("{}/rest/api/2/{}", self.url, path);
+        let authorization = Authorization(Basic { username: self.username.clone(), password: Some(self.token.clone()) });
+        let body = Bson::Document(document).to_json().to_string();
+        let response = try!(self.client.post(&url).header(authorization).header(ContentType::json()).body(&body).send());
+        if !response.status.is_success() {
+            return Err(TipupError::from(format!("jira '{}' responded with status {}", url, response.status)));
+        }
+
+        //transitions and some comments respond without content
+        match serde_json::from_reader(response) {
+            Ok(value) => Ok(value),
+            Err(_) => Ok(Value::Null),
+        }
+    }
+}
+
+impl Sink for JiraSink {
+    fn send_flag(&mut self, flag: &Flag) -> Result<(), TipupError> {
+        //flags already tracked by a ticket are being re-notified, e.g. after escalation
+        if let Some(key) = self.references.get(&flag.id) {
+            return self.comment(key, format!("flag {} is now '{}'", flag.id, flag.status));
+        }
+
+        match self.statuses.iter().position(|x| x == &flag.status) {
+            Some(status) if status >= self.minimum_status => {},
+            _ => return Ok(()),
+        }
+
+        //add flags to the open ticket of their domain incident
+        let key = match self.tickets.get(&flag.domain).map(|x| x.key.clone()) {
+            Some(key) => {
+                try!(self.comment(&key, describe(flag)));
+                key
+            },
+            None => {
+                let key = try!(self.create_ticket(format!("[tipup] {} incident on {}", flag.status, flag.domain), describe(flag)));
+                info!("opened jira ticket {} for domain '{}'", key, flag.domain);
+                key
+            },
+        };
+
+        self.tickets.entry(flag.domain.clone()).or_insert(Ticket { key: key.clone(), flag_ids: HashSet::new() }).flag_ids.insert(flag.id.clone());
+        self.references.insert(flag.id.clone(), key);
+        Ok(())
+    }
+
+    fn resolve_flag(&mut self, flag: &Flag) -> Result<(), TipupError> {
+        let key = match self.references.remove(&flag.id) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let remaining = match self.tickets.get_mut(&flag.domain) {
+            Some(ticket) => {
+                ticket.flag_ids.remove(&flag.id);
+                ticket.flag_ids.len()
+            },
+            None => 0,
+        };
+
+        try!(self.comment(&key, format!("flag {} resolved, {} flag(s) remain unresolved", flag.id, remaining)));
+        if remaining > 0 {
+            return Ok(());
+        }
+
+        //close the ticket once its incident has no unresolved flags
+        self.tickets.remove(&flag.domain);
+        if let Some(ref resolve_transition) = self.resolve_transition {
+            try!(self.post(&format!("issue/{}/transitions", key), doc!("transition" => { "id" => (resolve_transition.clone()) })));
+            info!("resolved jira ticket {} for domain '{}'", key, flag.domain);
+        }
+
+        Ok(())
+    }
+
+    fn send_incident(&mut self, update: &IncidentUpdate) -> Result<(), TipupError> {
+        let status = update.statuses.keys().filter_map(|x| self.statuses.iter().position(|y| y == x)).max();
+
+        //incident updates share the domain ticket of individually sent flags
+        let key = match self.tickets.get(&update.domain).map(|x| x.key.clone()) {
+            Some(key) => {
+                try!(self.comment(&key, describe_incident(update)));
+                key
+            },
+            None => match status {
+                Some(status) if status >= self.minimum_status && update.unresolved_flags > 0 => {
+                    let summary = format!("[tipup] {} incident on {}", self.statuses[status], update.domain);
+                    let key = try!(self.create_ticket(summary, describe_incident(update)));
+                    info!("opened jira ticket {} for incident {} on domain '{}'", key, update.id, update.domain);
+                    self.tickets.insert(update.domain.clone(), Ticket { key: key.clone(), flag_ids: HashSet::new() });
+                    key
+                },
+                _ => return Ok(()),
+            },
+        };
+
+        //close the ticket once neither the incident nor individually sent flags remain unresolved
+        let tracked_flags = self.tickets.get(&update.domain).map_or(0, |x| x.flag_ids.len());
+        if update.unresolved_flags > 0 || tracked_flags > 0 {
+            return Ok(());
+        }
+
+        self.tickets.remove(&update.domain);
+        if let Some(ref resolve_transition) = self.resolve_transition {
+            try!(self.post(&format!("issue/{}/transitions", key), doc!("transition" => { "id" => (resolve_transition.clone()) })));
+            info!("resolved jira ticket {} for domain '{}'", key, update.domain);
+        }
+
+        Ok(())
+    }
+
+    fn reference(&self, flag: &Flag) -> Option<String> {
+        self.references.get(&flag.id).cloned()
+    }
+
+    fn restore(&mut self, flag: &Flag, reference: &str) {
+        self.tickets.entry(flag.domain.clone()).or_insert(Ticket { key: reference.to_owned(), flag_ids: HashSet::new() }).flag_ids.insert(flag.id.clone());
+        self.references.insert(flag.id.clone(), reference.to_owned());
+    }
+}
+
+fn describe(flag: &Flag) -> String {
+    let mut description = format!("flag {} analyzer:{} status:{} domain:{} hostname:{}", flag.id, flag.analyzer,
+        flag.status, flag.domain, flag.hostname.as_ref().map_or("-", |x| x.as_str()));
+    for (key, value) in flag.evidence.iter() {
+        description.push_str(&format!("\n{}: {}", key, value));
+    }
+
+    if let Some(ref url) = flag.url {
+        description.push_str(&format!("\n{}", url));
+    }
+
+    description
+}
+
+fn describe_incident(update: &IncidentUpdate) -> String {
+    let mut description = format!("incident {} domain:{} flags:{} unresolved:{} new:{} hostnames:{}", update.id, update.domain,
+        update.flags, update.unresolved_flags, update.new_flags, update.hostnames.join(","));
+    for (status, count) in update.statuses.iter() {
+        description.push_str(&format!("\n{}: {}", status, count));
+    }
+
+    description
+}
+
+fn parse_strings(parameters: &OrderedDocument, key: &str, default: Vec<String>) -> Result<Vec<String>, TipupError> {
+    match parameters.get(key) {
+        Some(&Bson::Array(ref array)) => {
+            let mut strings = Vec::new();
+            for value in array.iter() {
+                match value {
+                    &Bson::String(ref value) => strings.push(value.to_owned()),
+                    _ => return Err(TipupError::from(format!("failed to parse {} parameter in JiraSink", key))),
+                }
+            }
+
+            Ok(strings)
+        },
+        None => Ok(default),
+        _ => Err(TipupError::from(format!("failed to parse {} parameter in JiraSink", key))),
+    }
+}
@@ -0,0 +1,31 @@
+use error::TipupError;
+use flag_manager::Flag;
+use sink::{IncidentUpdate, Sink};
+
+pub struct LogSink {
+    name: String,
+}
+
+impl LogSink {
+    pub fn new(name: &str) -> Result<LogSink, TipupError> {
+        Ok(
+            LogSink {
+                name: name.to_owned(),
+            }
+        )
+    }
+}
+
+impl Sink for LogSink {
+    fn send_flag(&mut self, flag: &Flag) -> Result<(), TipupError> {
+        info!("[{}] flag analyzer:{} status:{} domain:{} hostname:{} labels:{:?}", self.name, flag.analyzer,
+            flag.status, flag.domain, flag.hostname.as_ref().map_or("-", |x| x.as_str()), flag.labels);
+        Ok(())
+    }
+
+    fn send_incident(&mut self, update: &IncidentUpdate) -> Result<(), TipupError> {
+        info!("[{}] incident id:{} domain:{} hostnames:{} flags:{} unresolved:{} new:{} statuses:{:?}", self.name, update.id,
+            update.domain, update.hostnames.len(), update.flags, update.unresolved_flags, update.new_flags, update.statuses);
+        Ok(())
+    }
+}
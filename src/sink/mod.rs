@@ -0,0 +1,121 @@
+pub mod jira_sink;
+pub mod log_sink;
+pub mod ndjson_sink;
+pub mod webhook_sink;
+
+pub use sink::jira_sink::JiraSink;
+pub use sink::log_sink::LogSink;
+pub use sink::ndjson_sink::NdjsonSink;
+pub use sink::webhook_sink::WebhookSink;
+
+use bson::{self, Bson, Document};
+use bson::oid::ObjectId;
+use mongodb::db::{Database, ThreadedDatabase};
+use time;
+
+use error::TipupError;
+use flag_manager::Flag;
+use metrics::Metrics;
+
+use std;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+//number of delivery attempts before a flag is dead-lettered
+pub const DELIVERY_ATTEMPTS: u32 = 3;
+
+//current state of an incident sent in place of its flags by sinks coalescing notifications by
+//incident, new_flags counting the flags raised since the previous update
+#[derive(Debug, Serialize)]
+pub struct IncidentUpdate {
+    pub id: ObjectId,
+    pub domain: String,
+    pub minimum_timestamp: i64,
+    pub maximum_timestamp: i64,
+    pub hostnames: Vec<String>,
+    pub flags: i64,
+    pub unresolved_flags: i64,
+    pub new_flags: i64,
+    pub statuses: BTreeMap<String, i64>,
+}
+
+pub trait Sink {
+    fn send_flag(&mut self, flag: &Flag) -> Result<(), TipupError>;
+
+    fn send_incident(&mut self, update: &IncidentUpdate) -> Result<(), TipupError>;
+
+    //notified when a flag previously sent to the sink resolves
+    fn resolve_flag(&mut self, _flag: &Flag) -> Result<(), TipupError> {
+        Ok(())
+    }
+
+    //external reference (e.g. a ticket key) the sink created for a flag, recorded on the flag document
+    fn reference(&self, _flag: &Flag) -> Option<String> {
+        None
+    }
+
+    //restore a reference recorded on an unresolved flag after a restart
+    fn restore(&mut self, _flag: &Flag, _reference: &str) {}
+}
+
+pub fn deliver(name: &str, sink: &mut Sink, flag: &Flag, metrics: &Metrics) -> Result<(), TipupError> {
+    //send flag with linear backoff between attempts, recording delivery metrics
+    let mut attempt = 1;
+    loop {
+        let start = time::precise_time_s();
+        let result = sink.send_flag(flag);
+        metrics.observe("tipup_sink_delivery_seconds", &[("sink", name)], time::precise_time_s() - start);
+
+        match result {
+            Ok(()) => {
+                metrics.increment("tipup_sink_deliveries_total", &[("sink", name), ("result", "success")], 1.0);
+                return Ok(());
+            },
+            Err(e) => {
+                metrics.increment("tipup_sink_deliveries_total", &[("sink", name), ("result", "failure")], 1.0);
+                if attempt >= DELIVERY_ATTEMPTS {
+                    return Err(e);
+                }
+
+                error!("failed to send flag to sink '{}' (attempt {}/{}): {}", name, attempt, DELIVERY_ATTEMPTS, e);
+                std::thread::sleep(Duration::from_millis(500 * attempt as u64));
+                attempt += 1;
+            },
+        }
+    }
+}
+
+pub fn dead_letter(tipup_db: &Database, name: &str, flag: &Flag, error: &TipupError) -> Result<(), TipupError> {
+    let flag_document = match bson::to_bson(flag) {
+        Ok(flag_document) => flag_document,
+        Err(_) => return Err(TipupError::from("failed to parse flag as Bson")),
+    };
+
+    let document = doc!(
+        "sink" => name,
+        "flag" => flag_document,
+        "error" => (error.to_string()),
+        "attempts" => (DELIVERY_ATTEMPTS as i32),
+        "timestamp" => (time::now_utc().to_timespec().sec)
+    );
+
+    try!(tipup_db.collection("dead_letters").insert_one(document, None));
+    Ok(())
+}
+
+pub fn parse_dead_letter(document: &Document) -> Result<(String, Flag), TipupError> {
+    let name = match document.get("sink") {
+        Some(&Bson::String(ref name)) => name.to_owned(),
+        _ => return Err(TipupError::from("failed to parse dead letter sink")),
+    };
+
+    let flag = match document.get("flag") {
+        Some(&Bson::Document(ref flag)) => match bson::from_bson(Bson::Document(flag.clone())) {
+            Ok(flag) => flag,
+            Err(_) => return Err(TipupError::from("failed to parse dead letter flag")),
+        },
+        _ => return Err(TipupError::from("failed to parse dead letter flag")),
+    };
+
+    Ok((name, flag))
+}
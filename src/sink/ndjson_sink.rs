@@ -0,0 +1,209 @@
+use bson::{self, Bson};
+use bson::ordered::OrderedDocument;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use time::{self, Timespec};
+
+use error::TipupError;
+use flag_manager::Flag;
+use sink::{IncidentUpdate, Sink};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+//appends every flag event as a line of extended json to a local file, rotating it by size and
+//age into timestamped (optionally gzipped) files for log shippers to pick up
+pub struct NdjsonSink {
+    path: String,
+    max_bytes: u64,
+    max_age: i64,
+    compress: bool,
+    max_files: usize,
+    file: Option<File>,
+    bytes: u64,
+    opened_timestamp: i64,
+}
+
+impl NdjsonSink {
+    pub fn new(parameters: &OrderedDocument) -> Result<NdjsonSink, TipupError> {
+        let path = match parameters.get("path") {
+            Some(&Bson::String(ref path)) if path.len() > 0 => path.to_owned(),
+            _ => return Err(TipupError::from("failed to parse path parameter in NdjsonSink")),
+        };
+
+        let max_bytes = match parameters.get("max_bytes") {
+            Some(&Bson::I32(max_bytes)) if max_bytes > 0 => max_bytes as u64,
+            Some(&Bson::I64(max_bytes)) if max_bytes > 0 => max_bytes as u64,
+            None => 104857600,
+            _ => return Err(TipupError::from("failed to parse max_bytes parameter in NdjsonSink as positive integer")),
+        };
+
+        let max_age = match parameters.get("max_age") {
+            Some(&Bson::I32(max_age)) if max_age > 0 => max_age as i64,
+            Some(&Bson::I64(max_age)) if max_age > 0 => max_age,
+            None => 86400,
+            _ => return Err(TipupError::from("failed to parse max_age parameter in NdjsonSink as positive seconds")),
+        };
+
+        let compress = match parameters.get("compress") {
+            Some(&Bson::Boolean(compress)) => compress,
+            None => true,
+            _ => return Err(TipupError::from("failed to parse compress parameter in NdjsonSink as boolean")),
+        };
+
+        //number of rotated files retained
+        let max_files = match parameters.get("max_files") {
+            Some(&Bson::I32(max_files)) if max_files >= 0 => max_files as usize,
+            None => 7,
+            _ => return Err(TipupError::from("failed to parse max_files parameter in NdjsonSink as non-negative integer")),
+        };
+
+        let mut ndjson_sink = NdjsonSink {
+            path: path,
+            max_bytes: max_bytes,
+            max_age: max_age,
+            compress: compress,
+            max_files: max_files,
+            file: None,
+            bytes: 0,
+            opened_timestamp: 0,
+        };
+
+        try!(ndjson_sink.open());
+        Ok(ndjson_sink)
+    }
+
+    fn open(&mut self) -> Result<(), TipupError> {
+        //continue appending to an existing file, its age measured from when tipup opened it
+        let file = match OpenOptions::new().create(true).append(true).open(&self.path) {
+            Ok(file) => file,
+            Err(e) => return Err(TipupError::from(format!("failed to open ndjson file '{}': {}", self.path, e))),
+        };
+
+        self.bytes = file.metadata().map(|x| x.len()).unwrap_or(0);
+        self.opened_timestamp = time::now_utc().to_timespec().sec;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn write_event(&mut self, event: &str, flag: &Flag) -> Result<(), TipupError> {
+        match bson::to_bson(flag) {
+            Ok(flag_document) => self.write_line(event, "flag", flag_document),
+            Err(_) => Err(TipupError::from("failed to parse flag as Bson")),
+        }
+    }
+
+    fn write_line(&mut self, event: &str, key: &str, value: Bson) -> Result<(), TipupError> {
+        let now = time::now_utc().to_timespec().sec;
+        let mut document = doc!(
+            "event" => event,
+            "timestamp" => now
+        );
+        document.insert(key, value);
+
+        let line = format!("{}\n", Bson::Document(document).to_json());
+        if self.bytes > 0 && (self.bytes + line.len() as u64 > self.max_bytes || now - self.opened_timestamp >= self.max_age) {
+            try!(self.rotate(now));
+        }
+
+        if self.file.is_none() {
+            try!(self.open());
+        }
+
+        let result = self.file.as_mut().unwrap().write_all(line.as_bytes());
+        match result {
+            Ok(_) => {
+                self.bytes += line.len() as u64;
+                Ok(())
+            },
+            Err(e) => {
+                //reopen on the next event in case the file was removed or replaced
+                self.file = None;
+                Err(TipupError::from(format!("failed to write ndjson file '{}': {}", self.path, e)))
+            },
+        }
+    }
+
+    fn rotate(&mut self, now: i64) -> Result<(), TipupError> {
+        self.file = None;
+        let rotated_path = format!("{}.{}", self.path, format_timestamp(now));
+        if let Err(e) = fs::rename(&self.path, &rotated_path) {
+            return Err(TipupError::from(format!("failed to rotate ndjson file '{}': {}", self.path, e)));
+        }
+
+        if self.compress {
+            if let Err(e) = compress(&rotated_path) {
+                error!("failed to compress rotated ndjson file '{}': {}", rotated_path, e);
+            }
+        }
+
+        if let Err(e) = self.prune() {
+            error!("failed to remove expired ndjson files of '{}': {}", self.path, e);
+        }
+
+        info!("rotated ndjson file '{}' to '{}'", self.path, rotated_path);
+        self.open()
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        //rotated file names sort by their timestamp suffix
+        let path = Path::new(&self.path);
+        let directory = match path.parent() {
+            Some(directory) if directory.as_os_str().len() > 0 => directory,
+            _ => Path::new("."),
+        };
+
+        let prefix = match path.file_name() {
+            Some(file_name) => format!("{}.", file_name.to_string_lossy()),
+            None => return Ok(()),
+        };
+
+        let mut rotated_paths = Vec::new();
+        for entry in try!(fs::read_dir(directory)) {
+            let entry = try!(entry);
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                rotated_paths.push(entry.path());
+            }
+        }
+
+        rotated_paths.sort();
+        let expired = rotated_paths.len().saturating_sub(self.max_files);
+        for rotated_path in rotated_paths.iter().take(expired) {
+            try!(fs::remove_file(rotated_path));
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for NdjsonSink {
+    fn send_flag(&mut self, flag: &Flag) -> Result<(), TipupError> {
+        self.write_event("flag", flag)
+    }
+
+    fn resolve_flag(&mut self, flag: &Flag) -> Result<(), TipupError> {
+        self.write_event("resolved", flag)
+    }
+
+    fn send_incident(&mut self, update: &IncidentUpdate) -> Result<(), TipupError> {
+        match bson::to_bson(update) {
+            Ok(update_document) => self.write_line("incident", "incident", update_document),
+            Err(_) => Err(TipupError::from("failed to parse incident update as Bson")),
+        }
+    }
+}
+
+fn compress(path: &str) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(try!(File::create(format!("{}.gz", path))), Compression::Default);
+    try!(io::copy(&mut try!(File::open(path)), &mut encoder));
+    try!(encoder.finish().and_then(|file| file.sync_all()));
+    fs::remove_file(path)
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    match time::at_utc(Timespec::new(timestamp, 0)).strftime("%Y%m%dT%H%M%SZ") {
+        Ok(formatted) => formatted.to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
@@ -0,0 +1,60 @@
+use bson::{self, Bson};
+use bson::ordered::OrderedDocument;
+use hyper::Client;
+use hyper::header::ContentType;
+
+use error::TipupError;
+use flag_manager::Flag;
+use http;
+use sink::{IncidentUpdate, Sink};
+
+pub struct WebhookSink {
+    url: String,
+    client: Client,
+}
+
+impl WebhookSink {
+    pub fn new(parameters: &OrderedDocument) -> Result<WebhookSink, TipupError> {
+        let url = match parameters.get("url") {
+            Some(&Bson::String(ref url)) => url.to_owned(),
+            _ => return Err(TipupError::from("failed to parse url parameter in WebhookSink")),
+        };
+
+        Ok(
+            WebhookSink {
+                url: url,
+                client: try!(http::client()),
+            }
+        )
+    }
+
+    fn post(&self, body: &str) -> Result<(), TipupError> {
+        let response = try!(self.client.post(&self.url).header(ContentType::json()).body(body).send());
+        if !response.status.is_success() {
+            return Err(TipupError::from(format!("webhook '{}' responded with status {}", self.url, response.status)));
+        }
+
+        Ok(())
+    }
+}
+
+impl Sink for WebhookSink {
+    fn send_flag(&mut self, flag: &Flag) -> Result<(), TipupError> {
+        let body = match bson::to_bson(flag) {
+            Ok(bson) => bson.to_json().to_string(),
+            Err(_) => return Err(TipupError::from("failed to serialize flag for WebhookSink")),
+        };
+
+        self.post(&body)
+    }
+
+    fn send_incident(&mut self, update: &IncidentUpdate) -> Result<(), TipupError> {
+        //wrapped so receivers can tell incident updates from flags
+        let body = match bson::to_bson(update) {
+            Ok(bson) => Bson::Document(doc!("incident" => bson)).to_json().to_string(),
+            Err(_) => return Err(TipupError::from("failed to serialize incident update for WebhookSink")),
+        };
+
+        self.post(&body)
+    }
+}
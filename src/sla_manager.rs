@@ -0,0 +1,198 @@
+use bson::Bson;
+use chan::Sender;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+use time::{self, Timespec};
+
+use analyzer::{get_f64, get_value};
+use clock::Clock;
+use error::TipupError;
+use flag_manager::Flag;
+
+use std::sync::Arc;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+struct Sla {
+    domain: String,
+    measurement_class: Option<String>,
+    latency_field: Vec<String>,
+    max_p95_latency: Option<f64>,
+    min_availability: Option<f64>,
+    status: String,
+}
+
+pub struct SlaManager {
+    flag_tx: Sender<Flag>,
+    clock: Arc<Clock>,
+}
+
+impl SlaManager {
+    pub fn new(flag_tx: Sender<Flag>, clock: Arc<Clock>) -> SlaManager {
+        SlaManager {
+            flag_tx: flag_tx,
+            clock: clock,
+        }
+    }
+
+    pub fn execute(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        //evaluate the most recent complete utc day
+        let now = self.clock.now();
+        let day_end = now - (now % SECONDS_PER_DAY);
+        let day_start = day_end - SECONDS_PER_DAY;
+        let (day, month) = (format_timestamp(day_start, "%Y-%m-%d"), format_timestamp(day_start, "%Y-%m"));
+
+        let mut count = 0;
+        for sla in try!(load_slas(proddle_db)) {
+            //skip slas already evaluated for this day
+            let search_document = Some(doc!("domain" => (sla.domain.clone()), "month" => (month.clone()), "days.day" => (day.clone())));
+            if try!(proddle_db.collection("sla_compliance").find_one(search_document, None)).is_some() {
+                continue;
+            }
+
+            let (availability, p95_latency) = try!(compute_compliance(proddle_db, &sla, day_start, day_end));
+            let availability = match availability {
+                Some(availability) => availability,
+                None => continue, //no measurements for this target
+            };
+
+            let availability_breached = sla.min_availability.map_or(false, |x| availability < x);
+            let latency_breached = match (sla.max_p95_latency, p95_latency) {
+                (Some(max_p95_latency), Some(p95_latency)) => p95_latency > max_p95_latency,
+                _ => false,
+            };
+
+            if availability_breached || latency_breached {
+                info!("sla breached for domain '{}' on {} (availability:{} p95_latency:{:?})", sla.domain, day, availability, p95_latency);
+                self.flag_tx.send(Flag::for_domain(&sla.domain, day_end, &sla.status, "sla"));
+            }
+
+            //record day in monthly compliance summary
+            let compliant = !availability_breached && !latency_breached;
+            let compliant_increment = if compliant { 1 } else { 0 };
+            let mut day_document = doc!(
+                "day" => (day.clone()),
+                "availability" => availability,
+                "compliant" => compliant
+            );
+
+            if let Some(p95_latency) = p95_latency {
+                day_document.insert("p95_latency", p95_latency);
+            }
+
+            let search_document = doc!("domain" => (sla.domain.clone()), "month" => (month.clone()));
+            let increment_document = doc!("days_evaluated" => 1, "days_compliant" => compliant_increment);
+            //timestamp of the latest evaluated day, by which retention prunes monthly summaries
+            let update_document = doc!(
+                "$inc" => increment_document,
+                "$push" => { "days" => day_document },
+                "$max" => { "timestamp" => day_end }
+            );
+
+            let mut update_options = UpdateOptions::new();
+            update_options.upsert = Some(true);
+            try!(proddle_db.collection("sla_compliance").update_one(search_document, update_document, Some(update_options)));
+            count += 1;
+        }
+
+        if count > 0 {
+            info!("evaluated {} sla(s) for {}", count, day);
+        }
+
+        Ok(())
+    }
+}
+
+fn load_slas(proddle_db: &Database) -> Result<Vec<Sla>, TipupError> {
+    let mut slas = Vec::new();
+    let cursor = try!(proddle_db.collection("slas").find(None, None));
+    for document in cursor {
+        let document = try!(document);
+
+        let domain = match document.get("domain") {
+            Some(&Bson::String(ref domain)) => domain.to_owned(),
+            _ => return Err(TipupError::from("failed to parse sla domain")),
+        };
+
+        let measurement_class = match document.get("measurement_class") {
+            Some(&Bson::String(ref measurement_class)) => Some(measurement_class.to_owned()),
+            _ => None,
+        };
+
+        let latency_field: Vec<String> = match document.get("latency_field") {
+            Some(&Bson::Array(ref latency_field)) => latency_field.iter().map(|x| x.to_string().replace("\"", "")).collect(),
+            _ => Vec::new(),
+        };
+
+        let max_p95_latency = get_f64(&document, "max_p95_latency");
+        if max_p95_latency.is_some() && latency_field.len() == 0 {
+            return Err(TipupError::from(format!("sla for domain '{}' defines max_p95_latency without a latency_field", domain)));
+        }
+
+        let status = match document.get("status") {
+            Some(&Bson::String(ref status)) => status.to_owned(),
+            _ => "error".to_owned(),
+        };
+
+        slas.push(
+            Sla {
+                domain: domain,
+                measurement_class: measurement_class,
+                latency_field: latency_field,
+                max_p95_latency: max_p95_latency,
+                min_availability: get_f64(&document, "min_availability"),
+                status: status,
+            }
+        );
+    }
+
+    Ok(slas)
+}
+
+fn compute_compliance(proddle_db: &Database, sla: &Sla, start_timestamp: i64, end_timestamp: i64) -> Result<(Option<f64>, Option<f64>), TipupError> {
+    let timestamp_document = doc!("$gte" => start_timestamp, "$lt" => end_timestamp);
+    let mut search_document = doc!("measurement_domain" => (sla.domain.clone()), "timestamp" => timestamp_document);
+    if let Some(ref measurement_class) = sla.measurement_class {
+        search_document.insert("measurement_class", measurement_class.to_owned());
+    }
+
+    //count successful measurements and collect their latencies
+    let (mut total, mut successful) = (0, 0);
+    let mut latencies = Vec::new();
+    for document in try!(proddle_db.collection("measurements").find(Some(search_document), None)) {
+        let document = try!(document);
+        total += 1;
+        if document.contains_key("measurement_error_message") {
+            continue;
+        }
+
+        successful += 1;
+        match get_value(&sla.latency_field, &document) {
+            Some(latency) if latency.is_finite() => latencies.push(latency),
+            _ => {},
+        }
+    }
+
+    if total == 0 {
+        return Ok((None, None));
+    }
+
+    let availability = (successful as f64 / total as f64) * 100.0;
+    let p95_latency = match latencies.len() {
+        0 => None,
+        _ => {
+            latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let index = ((latencies.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+            Some(latencies[index])
+        },
+    };
+
+    Ok((Some(availability), p95_latency))
+}
+
+fn format_timestamp(timestamp: i64, format: &str) -> String {
+    match time::at_utc(Timespec::new(timestamp, 0)).strftime(format) {
+        Ok(formatted) => formatted.to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
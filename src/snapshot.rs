@@ -0,0 +1,117 @@
+use bson::{Bson, Document};
+use mongodb::coll::options::FindOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use clock::Clock;
+use error::TipupError;
+use result_window::ResultWindow;
+
+use std::sync::Arc;
+
+//maximum number of entries written per snapshot document
+const SNAPSHOT_CHUNK_SIZE: usize = 1000;
+
+pub struct SnapshotManager {
+    full_snapshot_interval: u32,
+    deltas_since_full: u32,
+    sequence: i64,
+    clock: Arc<Clock>,
+}
+
+impl SnapshotManager {
+    pub fn new(full_snapshot_interval: u32, clock: Arc<Clock>) -> SnapshotManager {
+        SnapshotManager {
+            full_snapshot_interval: full_snapshot_interval,
+            deltas_since_full: 0,
+            sequence: 0,
+            clock: clock,
+        }
+    }
+
+    pub fn initialize(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        //continue sequence numbering from the most recent snapshot
+        let negative_one = -1;
+        let mut find_options = FindOptions::new();
+        find_options.sort = Some(doc!("sequence" => negative_one));
+        if let Some(document) = try!(proddle_db.collection("state_snapshots").find_one(None, Some(find_options))) {
+            match document.get("sequence") {
+                Some(&Bson::I64(sequence)) => self.sequence = sequence,
+                _ => return Err(TipupError::from("failed to parse state snapshot sequence")),
+            }
+        }
+
+        //always begin with a full snapshot so restores never depend on deltas from a previous run
+        self.deltas_since_full = self.full_snapshot_interval;
+        Ok(())
+    }
+
+    pub fn execute(&mut self, proddle_db: &Database, result_window: &ResultWindow) -> Result<(), TipupError> {
+        let full = self.deltas_since_full >= self.full_snapshot_interval;
+        let kind = if full { "full" } else { "delta" };
+        self.sequence += 1;
+
+        //write snapshot documents in chunks to stay under document size limits
+        let (timestamp, sequence) = (self.clock.now(), self.sequence);
+        let mut count = 0;
+        for (variable_name, entries) in result_window.snapshot(full) {
+            if !full && entries.len() == 0 {
+                continue;
+            }
+
+            let variable_name: Vec<Bson> = variable_name.into_iter().map(|x| Bson::String(x)).collect();
+            let mut documents: Vec<Document> = Vec::new();
+            for (i, chunk) in entries.chunks(SNAPSHOT_CHUNK_SIZE).enumerate() {
+                let chunk_index = i as i32;
+                let chunk_entries: Vec<Bson> = chunk.iter().map(|x| Bson::Document(x.clone())).collect();
+                documents.push(doc!(
+                    "variable_name" => (variable_name.clone()),
+                    "sequence" => sequence,
+                    "kind" => kind,
+                    "chunk" => chunk_index,
+                    "timestamp" => timestamp,
+                    "entries" => chunk_entries
+                ));
+            }
+
+            //full snapshots of empty windows still mark a restore point
+            if documents.len() == 0 {
+                let empty_entries: Vec<Bson> = Vec::new();
+                documents.push(doc!(
+                    "variable_name" => (variable_name.clone()),
+                    "sequence" => sequence,
+                    "kind" => kind,
+                    "chunk" => 0,
+                    "timestamp" => timestamp,
+                    "entries" => empty_entries
+                ));
+            }
+
+            count += entries.len();
+            try!(proddle_db.collection("state_snapshots").insert_many(documents, None));
+        }
+
+        if full {
+            self.deltas_since_full = 0;
+            try!(self.compact(proddle_db));
+        } else {
+            self.deltas_since_full += 1;
+        }
+
+        if count > 0 {
+            info!("wrote {} snapshot {} with {} state entry(s)", kind, sequence, count);
+        }
+
+        Ok(())
+    }
+
+    fn compact(&self, proddle_db: &Database) -> Result<(), TipupError> {
+        //snapshots preceding the latest full snapshot are no longer needed for restores
+        let sequence_lt = doc!("$lt" => (self.sequence));
+        let result = try!(proddle_db.collection("state_snapshots").delete_many(doc!("sequence" => sequence_lt), None));
+        if result.deleted_count > 0 {
+            info!("compacted {} state snapshot document(s)", result.deleted_count);
+        }
+
+        Ok(())
+    }
+}
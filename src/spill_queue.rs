@@ -0,0 +1,180 @@
+use bson::{self, Bson};
+use chan::{self, Receiver, Sender};
+use serde_json;
+
+use crash_report::CrashContext;
+use error::TipupError;
+use flag_manager::Flag;
+use metrics::Metrics;
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+
+//on-disk buffer of flags the flag manager could not accept, one extended json flag per line
+pub struct SpillQueue {
+    path: String,
+    flags: VecDeque<Flag>,
+}
+
+impl SpillQueue {
+    pub fn open(path: &str) -> Result<SpillQueue, TipupError> {
+        //recover flags spilled before a restart
+        let mut flags = VecDeque::new();
+        if let Ok(file) = File::open(path) {
+            for line in BufReader::new(file).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => return Err(TipupError::from(format!("failed to read spill queue '{}': {}", path, e))),
+                };
+
+                match parse_flag(&line) {
+                    Some(flag) => flags.push_back(flag),
+                    None => error!("skipping unparseable flag in spill queue '{}'", path),
+                }
+            }
+        }
+
+        if flags.len() > 0 {
+            info!("recovered {} spilled flag(s) from '{}'", flags.len(), path);
+        }
+
+        Ok(
+            SpillQueue {
+                path: path.to_owned(),
+                flags: flags,
+            }
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    pub fn push(&mut self, flag: Flag) -> Result<(), TipupError> {
+        let line = try!(format_flag(&flag));
+        self.flags.push_back(flag);
+
+        let mut file = match OpenOptions::new().create(true).append(true).open(&self.path) {
+            Ok(file) => file,
+            Err(e) => return Err(TipupError::from(format!("failed to open spill queue '{}': {}", self.path, e))),
+        };
+
+        match writeln!(file, "{}", line).and_then(|_| file.sync_data()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(TipupError::from(format!("failed to write spill queue '{}': {}", self.path, e))),
+        }
+    }
+
+    //forward spilled flags in order while the flag manager accepts them without blocking
+    pub fn forward(&mut self, flag_tx: &Sender<Flag>) -> Result<usize, TipupError> {
+        let mut count = 0;
+        while let Some(flag) = self.flags.pop_front() {
+            if let Err(flag) = try_send(flag_tx, flag) {
+                self.flags.push_front(flag);
+                break;
+            }
+
+            count += 1;
+        }
+
+        if count > 0 {
+            try!(self.persist());
+        }
+
+        Ok(count)
+    }
+
+    fn persist(&self) -> Result<(), TipupError> {
+        //rewrite remaining flags to a temporary file and rename it over the queue
+        let temporary_path = format!("{}.tmp", self.path);
+        let mut contents = String::new();
+        for flag in self.flags.iter() {
+            contents.push_str(&try!(format_flag(flag)));
+            contents.push('\n');
+        }
+
+        let result = File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_data()))
+            .and_then(|_| fs::rename(&temporary_path, &self.path));
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(TipupError::from(format!("failed to rewrite spill queue '{}': {}", self.path, e))),
+        }
+    }
+}
+
+//relay flags from analyzers to the flag manager, spilling them to disk while it is unavailable
+pub fn relay(flag_rx: Receiver<Flag>, flag_tx: Sender<Flag>, spill_queue: Arc<Mutex<SpillQueue>>, metrics: Metrics, crash_context: CrashContext) {
+    let retry_tick = chan::tick_ms(1000);
+    loop {
+        chan_select! {
+            flag_rx.recv() -> flag => {
+                let flag = match flag {
+                    Some(flag) => flag,
+                    None => return,
+                };
+
+                //preserve ordering by queueing behind previously spilled flags
+                let mut spill_queue = spill_queue.lock().unwrap();
+                let spill_flag = match spill_queue.len() {
+                    0 => try_send(&flag_tx, flag).err(),
+                    _ => Some(flag),
+                };
+
+                if let Some(flag) = spill_flag {
+                    if spill_queue.len() == 0 {
+                        info!("flag manager unavailable, spilling flags to disk");
+                    }
+
+                    if let Err(e) = spill_queue.push(flag) {
+                        error!("{}", e);
+                    }
+                }
+            },
+            retry_tick.recv() => {},
+        }
+
+        let mut spill_queue = spill_queue.lock().unwrap();
+        if spill_queue.len() > 0 {
+            match spill_queue.forward(&flag_tx) {
+                Ok(0) => {},
+                Ok(count) => info!("drained {} spilled flag(s), {} remaining", count, spill_queue.len()),
+                Err(e) => error!("{}", e),
+            }
+        }
+
+        metrics.set("tipup_flag_spill_queue_length", &[], spill_queue.len() as f64);
+        crash_context.set_queue_depth("flag_spill_queue", spill_queue.len());
+    }
+}
+
+fn try_send(flag_tx: &Sender<Flag>, flag: Flag) -> Result<(), Flag> {
+    //chan consumes the value of a failed select send, so offer a clone
+    let mut sent = false;
+    chan_select! {
+        default => {},
+        flag_tx.send(flag.clone()) => sent = true,
+    }
+
+    match sent {
+        true => Ok(()),
+        false => Err(flag),
+    }
+}
+
+fn format_flag(flag: &Flag) -> Result<String, TipupError> {
+    match bson::to_bson(flag) {
+        Ok(bson) => Ok(bson.to_json().to_string()),
+        Err(_) => Err(TipupError::from("failed to parse flag json as Bson::Document")),
+    }
+}
+
+fn parse_flag(line: &str) -> Option<Flag> {
+    match serde_json::from_str(line) {
+        Ok(value) => bson::from_bson(Bson::from_json(&value)).ok(),
+        Err(_) => None,
+    }
+}
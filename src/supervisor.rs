@@ -0,0 +1,120 @@
+use clock::Clock;
+use crash_report::CrashContext;
+use error::TipupError;
+use metrics::Metrics;
+
+use std;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+//seconds over which thread failures count towards the restart limit
+const FAILURE_WINDOW: i64 = 3600;
+
+//maximum seconds to wait before restarting a failed thread
+const MAX_BACKOFF: u64 = 60;
+
+//restarts internal threads which panic, exiting the process once a thread fails too often
+#[derive(Clone)]
+pub struct Supervisor {
+    max_restarts: usize,
+    metrics: Metrics,
+    crash_context: CrashContext,
+    clock: Arc<Clock>,
+}
+
+impl Supervisor {
+    pub fn new(max_restarts: usize, metrics: Metrics, crash_context: CrashContext, clock: Arc<Clock>) -> Supervisor {
+        Supervisor {
+            max_restarts: max_restarts,
+            metrics: metrics,
+            crash_context: crash_context,
+            clock: clock,
+        }
+    }
+
+    pub fn spawn<F>(&self, name: &str, task: F) -> Result<(), TipupError> where F: Fn() + Send + Sync + 'static {
+        //panics on supervised threads are reported without exiting the process
+        self.crash_context.supervise(name);
+
+        let (supervisor, thread_name, task) = (self.clone(), name.to_owned(), Arc::new(task));
+        let monitor = thread::Builder::new().name(format!("{}_supervisor", name)).spawn(move || {
+            supervisor.monitor(&thread_name, task);
+        });
+
+        match monitor {
+            Ok(_) => Ok(()),
+            Err(e) => Err(TipupError::from(format!("failed to start supervisor for thread '{}': {}", name, e))),
+        }
+    }
+
+    fn monitor<F>(&self, name: &str, task: Arc<F>) where F: Fn() + Send + Sync + 'static {
+        let mut failures = VecDeque::new();
+        loop {
+            let thread_task = task.clone();
+            let result = thread::Builder::new().name(name.to_owned()).spawn(move || thread_task());
+            match result.map(|handle| handle.join()) {
+                Ok(Ok(_)) => {
+                    info!("thread '{}' exited", name);
+                    return;
+                },
+                Ok(Err(_)) => error!("thread '{}' panicked", name),
+                Err(e) => error!("failed to start thread '{}': {}", name, e),
+            }
+
+            self.record_failure(&mut failures);
+            self.metrics.increment("tipup_thread_restarts_total", &[("thread", name)], 1.0);
+            if failures.len() > self.max_restarts {
+                error!("thread '{}' failed {} time(s) within {}s, exiting", name, failures.len(), FAILURE_WINDOW);
+                std::process::exit(1);
+            }
+
+            //back off exponentially with recent failures
+            let backoff = std::cmp::min(1 << std::cmp::min(failures.len() - 1, 6), MAX_BACKOFF);
+            info!("restarting thread '{}' in {}s", name, backoff);
+            thread::sleep(Duration::from_secs(backoff));
+        }
+    }
+
+    //records a failure now, dropping those outside the failure window
+    fn record_failure(&self, failures: &mut VecDeque<i64>) {
+        let now = self.clock.now();
+        failures.push_back(now);
+        while failures.front().map_or(false, |x| *x <= now - FAILURE_WINDOW) {
+            failures.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clock::MockClock;
+    use crash_report::CrashContext;
+    use metrics::Metrics;
+
+    use super::Supervisor;
+
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    #[test]
+    fn failures_expire_after_window() {
+        let clock = Arc::new(MockClock::new(10000));
+        let supervisor = Supervisor::new(3, Metrics::new(), CrashContext::new(), clock.clone());
+        let mut failures = VecDeque::new();
+        supervisor.record_failure(&mut failures);
+
+        clock.step(3599.0);
+        supervisor.record_failure(&mut failures);
+        assert_eq!(failures.len(), 2);
+
+        clock.step(1.0);
+        supervisor.record_failure(&mut failures);
+        assert_eq!(failures.len(), 2);
+
+        clock.step(3600.0);
+        supervisor.record_failure(&mut failures);
+        assert_eq!(failures.len(), 1);
+    }
+}
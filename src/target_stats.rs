@@ -0,0 +1,101 @@
+use bson::Bson;
+use bson::ordered::OrderedDocument;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use analyzer::get_value;
+use error::TipupError;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+//most recent values retained per target regardless of the window, bounding memory of targets
+//measured at high frequency
+const MAX_SAMPLES: usize = 10000;
+
+//rolling latency percentiles per (domain, measurement_class) across vantage points, maintained
+//from the ingest stream and written to the target_stats collection so dashboards query summaries
+//rather than aggregating raw results
+pub struct TargetStats {
+    field: Vec<String>,
+    window: i64,
+    samples: HashMap<(String, String), VecDeque<(i64, f64)>>,
+    dirty: HashSet<(String, String)>,
+}
+
+impl TargetStats {
+    pub fn new(field: &str, window: u32) -> TargetStats {
+        TargetStats {
+            field: field.split('.').map(|x| x.to_owned()).collect(),
+            window: window as i64,
+            samples: HashMap::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn record(&mut self, document: &OrderedDocument) {
+        let (domain, measurement_class, timestamp) = match (document.get("measurement_domain"), document.get("measurement_class"), document.get("timestamp")) {
+            (Some(&Bson::String(ref domain)), Some(&Bson::String(ref measurement_class)), Some(&Bson::I64(timestamp))) => (domain.to_owned(), measurement_class.to_owned(), timestamp),
+            _ => return,
+        };
+
+        let value = match get_value(&self.field, document) {
+            Some(value) if value.is_finite() => value,
+            _ => return,
+        };
+
+        let key = (domain, measurement_class);
+        let samples = self.samples.entry(key.clone()).or_insert(VecDeque::new());
+        samples.push_back((timestamp, value));
+        if samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+
+        self.dirty.insert(key);
+    }
+
+    //writes summaries of targets with new results, evicting values older than the window before
+    //the latest result of each
+    pub fn flush(&mut self, proddle_db: &Database) -> Result<(), TipupError> {
+        let (window, mut count) = (self.window, 0);
+        for key in self.dirty.drain() {
+            let samples = match self.samples.get_mut(&key) {
+                Some(samples) => samples,
+                None => continue,
+            };
+
+            let latest_timestamp = samples.iter().map(|x| x.0).max().unwrap_or(0);
+            samples.retain(|x| x.0 > latest_timestamp - window);
+
+            let mut values: Vec<f64> = samples.iter().map(|x| x.1).collect();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let search_document = doc!("domain" => (key.0.clone()), "measurement_class" => (key.1.clone()));
+            let update_document = doc!("$set" => {
+                "timestamp" => latest_timestamp,
+                "window" => window,
+                "field" => (self.field.join(".")),
+                "samples" => (values.len() as i64),
+                "p50" => (percentile(&values, 50.0)),
+                "p95" => (percentile(&values, 95.0)),
+                "p99" => (percentile(&values, 99.0))
+            });
+
+            let mut update_options = UpdateOptions::new();
+            update_options.upsert = Some(true);
+            try!(proddle_db.collection("target_stats").update_one(search_document, update_document, Some(update_options)));
+            count += 1;
+        }
+
+        if count > 0 {
+            info!("updated statistics of {} target(s)", count);
+        }
+
+        Ok(())
+    }
+}
+
+fn percentile(sorted_values: &Vec<f64>, percentile: f64) -> f64 {
+    //nearest-rank percentile
+    let rank = ((percentile / 100.0) * sorted_values.len() as f64).ceil() as usize;
+    sorted_values[rank.saturating_sub(1).min(sorted_values.len() - 1)]
+}
@@ -0,0 +1,93 @@
+use bson::Bson;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+
+use std::collections::HashMap;
+
+const DAYS: [&'static str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+//a named recurring window, e.g. business hours from 09:00 to 17:00 on weekdays
+pub struct TimeWindow {
+    days: Vec<i64>,
+    start: i64,
+    end: i64,
+    utc_offset: i64,
+}
+
+impl TimeWindow {
+    pub fn load(proddle_db: &Database) -> Result<HashMap<String, TimeWindow>, TipupError> {
+        //query mongodb for named time windows
+        let mut time_windows = HashMap::new();
+        for document in try!(proddle_db.collection("time_windows").find(None, None)) {
+            let document = try!(document);
+
+            let name = match document.get("name") {
+                Some(&Bson::String(ref name)) => name.to_owned(),
+                _ => return Err(TipupError::from("failed to parse time window name")),
+            };
+
+            let (start, end) = match (document.get("start"), document.get("end")) {
+                (Some(&Bson::String(ref start)), Some(&Bson::String(ref end))) => (try!(parse_time_of_day(start)), try!(parse_time_of_day(end))),
+                _ => return Err(TipupError::from(format!("time window '{}' requires 'start' and 'end' times", name))),
+            };
+
+            //days default to every day of the week
+            let days = match document.get("days") {
+                Some(&Bson::Array(ref day_array)) => {
+                    let mut days = Vec::new();
+                    for day in day_array.iter() {
+                        match day {
+                            &Bson::String(ref day) if DAYS.contains(&day.to_lowercase().as_str()) =>
+                                days.push(DAYS.iter().position(|x| *x == day.to_lowercase()).unwrap() as i64),
+                            _ => return Err(TipupError::from(format!("failed to parse days of time window '{}', expected values like 'mon'", name))),
+                        }
+                    }
+
+                    days
+                },
+                None => (0..7).collect(),
+                _ => return Err(TipupError::from(format!("failed to parse days of time window '{}'", name))),
+            };
+
+            let utc_offset = match document.get("utc_offset") {
+                Some(&Bson::I32(utc_offset)) => utc_offset as i64 * 60,
+                None => 0,
+                _ => return Err(TipupError::from(format!("failed to parse utc_offset of time window '{}' as minutes", name))),
+            };
+
+            time_windows.insert(name,
+                TimeWindow {
+                    days: days,
+                    start: start,
+                    end: end,
+                    utc_offset: utc_offset,
+                }
+            );
+        }
+
+        Ok(time_windows)
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        let local_timestamp = timestamp + self.utc_offset;
+        let seconds = ((local_timestamp % 86400) + 86400) % 86400;
+        let day = ((((local_timestamp - seconds) / 86400 + 4) % 7) + 7) % 7; //1970-01-01 was a thursday
+
+        //windows wrapping past midnight belong to the day they start on
+        match self.start <= self.end {
+            true => self.days.contains(&day) && seconds >= self.start && seconds < self.end,
+            false => (self.days.contains(&day) && seconds >= self.start)
+                || (self.days.contains(&((day + 6) % 7)) && seconds < self.end),
+        }
+    }
+}
+
+fn parse_time_of_day(time: &str) -> Result<i64, TipupError> {
+    //parse 'HH:MM' into seconds past midnight
+    let mut fields = time.splitn(2, ':');
+    match (fields.next().map(|x| x.parse::<i64>()), fields.next().map(|x| x.parse::<i64>())) {
+        (Some(Ok(hour)), Some(Ok(minute))) if hour >= 0 && hour < 24 && minute >= 0 && minute < 60 => Ok((hour * 3600) + (minute * 60)),
+        _ => Err(TipupError::from(format!("failed to parse time of day '{}' in time window, expected 'HH:MM'", time))),
+    }
+}
@@ -0,0 +1,228 @@
+use bson::{self, Bson, Document};
+use bson::oid::ObjectId;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+use event::Event;
+use flag_manager::Flag;
+use incident_manager::Incident;
+use label::LabelSelector;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TimelineEntry {
+    pub timestamp: i64,
+    pub kind: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+//the story of an incident from its first flag through escalations, silences, acknowledgements,
+//related external events, and resolution
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Timeline {
+    #[serde(rename = "_id")]
+    pub incident_id: ObjectId,
+    pub domain: String,
+    pub start_timestamp: i64,
+    pub resolved_timestamp: Option<i64>,
+    pub updated_timestamp: i64,
+    pub entries: Vec<TimelineEntry>,
+}
+
+impl Timeline {
+    pub fn build(incident: &Incident, tipup_db: &Database, now: i64) -> Result<Timeline, TipupError> {
+        let mut entries = Vec::new();
+
+        //flags along with their escalation, acknowledgement, and resolution history
+        let mut flags = Vec::new();
+        let id_in = doc!("$in" => (incident.flag_ids.iter().map(|x| Bson::ObjectId(x.clone())).collect::<Vec<Bson>>()));
+        for document in try!(tipup_db.collection("flags").find(Some(doc!("_id" => id_in)), None)) {
+            let document = try!(document);
+            match bson::from_bson::<Flag>(Bson::Document(document.clone())) {
+                Ok(flag) => flags.push((flag, document)),
+                Err(_) => return Err(TipupError::from("failed to parse bson document into flag")),
+            }
+        }
+
+        flags.sort_by(|a, b| a.0.timestamp.cmp(&b.0.timestamp));
+        let mut resolved_timestamp = match flags.len() {
+            0 => None,
+            _ => Some(i64::min_value()),
+        };
+
+        for (i, &(ref flag, ref document)) in flags.iter().enumerate() {
+            let reference = Some(flag.id.to_hex());
+            entries.push(
+                TimelineEntry {
+                    timestamp: flag.timestamp,
+                    kind: match i { 0 => "first_flag", _ => "flag" }.to_owned(),
+                    description: format!("{} raised '{}' flag", flag.analyzer, flag.status),
+                    hostname: flag.hostname.clone(),
+                    reference: reference.clone(),
+                }
+            );
+
+            if let Some(&Bson::Array(ref escalations)) = document.get("escalations") {
+                for escalation in escalations.iter() {
+                    match escalation {
+                        &Bson::Document(ref escalation) => match (escalation.get("timestamp"), escalation.get("previous_status"), escalation.get("status")) {
+                            (Some(&Bson::I64(timestamp)), Some(&Bson::String(ref previous_status)), Some(&Bson::String(ref status))) => entries.push(
+                                TimelineEntry {
+                                    timestamp: timestamp,
+                                    kind: "escalation".to_owned(),
+                                    description: format!("{} flag escalated from '{}' to '{}'", flag.analyzer, previous_status, status),
+                                    hostname: flag.hostname.clone(),
+                                    reference: reference.clone(),
+                                }
+                            ),
+                            _ => return Err(TipupError::from("failed to parse flag escalation")),
+                        },
+                        _ => return Err(TipupError::from("failed to parse flag escalation as document")),
+                    }
+                }
+            }
+
+            if let Some(&Bson::I64(acknowledged_timestamp)) = document.get("acknowledged_timestamp") {
+                entries.push(
+                    TimelineEntry {
+                        timestamp: acknowledged_timestamp,
+                        kind: "acknowledgement".to_owned(),
+                        description: format!("{} flag acknowledged", flag.analyzer),
+                        hostname: flag.hostname.clone(),
+                        reference: reference.clone(),
+                    }
+                );
+            }
+
+            //the incident resolves with the last of its flags
+            match (flag.state.as_str(), document.get("resolved_timestamp")) {
+                ("resolved", Some(&Bson::I64(timestamp))) => {
+                    entries.push(
+                        TimelineEntry {
+                            timestamp: timestamp,
+                            kind: "flag_resolved".to_owned(),
+                            description: format!("{} flag resolved", flag.analyzer),
+                            hostname: flag.hostname.clone(),
+                            reference: reference.clone(),
+                        }
+                    );
+
+                    resolved_timestamp = resolved_timestamp.map(|x| x.max(timestamp));
+                },
+                _ => resolved_timestamp = None,
+            }
+        }
+
+        //silences in effect during the incident which matched any of its flags
+        let end_timestamp = resolved_timestamp.unwrap_or(now);
+        let (start_lte, end_gte) = (doc!("$lte" => end_timestamp), doc!("$gte" => (incident.minimum_timestamp)));
+        let search_document = Some(doc!("start_timestamp" => start_lte, "end_timestamp" => end_gte));
+        for document in try!(tipup_db.collection("silences").find(search_document, None)) {
+            let document = try!(document);
+            let (selector, start_timestamp, silence_end_timestamp) = match (document.get("selector"), document.get("start_timestamp"), document.get("end_timestamp")) {
+                (Some(&Bson::String(ref selector)), Some(&Bson::I64(start_timestamp)), Some(&Bson::I64(end_timestamp))) => (selector.to_owned(), start_timestamp, end_timestamp),
+                _ => return Err(TipupError::from("failed to parse silence")),
+            };
+
+            let label_selector = try!(LabelSelector::parse(&selector));
+            if !flags.iter().any(|x| label_selector.matches(&x.0.selector_labels())) {
+                continue;
+            }
+
+            let mut description = format!("silence '{}' applied for {}s", selector, silence_end_timestamp - start_timestamp);
+            if let Some(&Bson::String(ref created_by)) = document.get("created_by") {
+                description.push_str(&format!(" by {}", created_by));
+            }
+
+            if let Some(&Bson::String(ref comment)) = document.get("comment") {
+                description.push_str(&format!(": {}", comment));
+            }
+
+            entries.push(
+                TimelineEntry {
+                    timestamp: start_timestamp,
+                    kind: "silence".to_owned(),
+                    description: description,
+                    hostname: None,
+                    reference: match document.get("_id") {
+                        Some(&Bson::ObjectId(ref id)) => Some(id.to_hex()),
+                        _ => None,
+                    },
+                }
+            );
+        }
+
+        //related external events, e.g. deployments and maintenance
+        if incident.related_event_ids.len() > 0 {
+            let id_in = doc!("$in" => (incident.related_event_ids.iter().map(|x| Bson::ObjectId(x.clone())).collect::<Vec<Bson>>()));
+            for document in try!(tipup_db.collection("events").find(Some(doc!("_id" => id_in)), None)) {
+                let document = try!(document);
+                let event: Event = match bson::from_bson(Bson::Document(document)) {
+                    Ok(event) => event,
+                    Err(_) => return Err(TipupError::from("failed to parse bson document into event")),
+                };
+
+                entries.push(
+                    TimelineEntry {
+                        timestamp: event.start_timestamp,
+                        kind: "event".to_owned(),
+                        description: format!("{}: {}", event.kind, event.description),
+                        hostname: event.hostname.clone(),
+                        reference: Some(event.id.to_hex()),
+                    }
+                );
+            }
+        }
+
+        if let Some(timestamp) = resolved_timestamp {
+            entries.push(
+                TimelineEntry {
+                    timestamp: timestamp,
+                    kind: "resolution".to_owned(),
+                    description: format!("incident resolved with all {} flag(s)", flags.len()),
+                    hostname: None,
+                    reference: None,
+                }
+            );
+        }
+
+        //entries sharing a timestamp keep their causal order
+        entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(
+            Timeline {
+                incident_id: incident.id.clone(),
+                domain: incident.domain.clone(),
+                start_timestamp: incident.minimum_timestamp,
+                resolved_timestamp: resolved_timestamp,
+                updated_timestamp: now,
+                entries: entries,
+            }
+        )
+    }
+
+    pub fn load(tipup_db: &Database, incident_id: &ObjectId) -> Result<Option<Timeline>, TipupError> {
+        match try!(tipup_db.collection("incident_timelines").find_one(Some(doc!("_id" => (incident_id.clone()))), None)) {
+            Some(document) => match bson::from_bson(Bson::Document(document)) {
+                Ok(timeline) => Ok(Some(timeline)),
+                Err(_) => Err(TipupError::from("failed to parse bson document into incident timeline")),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn persist(&self, tipup_db: &Database) -> Result<(), TipupError> {
+        let document: Document = match bson::to_bson(self) {
+            Ok(Bson::Document(document)) => document,
+            _ => return Err(TipupError::from("failed to parse incident timeline as Bson::Document")),
+        };
+
+        let mut update_options = UpdateOptions::new();
+        update_options.upsert = Some(true);
+        try!(tipup_db.collection("incident_timelines").replace_one(doc!("_id" => (self.incident_id.clone())), document, Some(update_options)));
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use bson::Bson;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::{Database, ThreadedDatabase};
+
+use error::TipupError;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+//topology attributes of a vantage hostname
+const ATTRIBUTES: [&'static str; 3] = ["region", "site", "asn"];
+
+pub struct Topology {
+    hosts: HashMap<String, HashMap<String, String>>,
+}
+
+impl Topology {
+    pub fn load(proddle_db: &Database) -> Result<Topology, TipupError> {
+        let mut hosts = HashMap::new();
+        for document in try!(proddle_db.collection("topology").find(None, None)) {
+            let document = try!(document);
+            let hostname = match document.get("hostname") {
+                Some(&Bson::String(ref hostname)) => hostname.to_owned(),
+                _ => return Err(TipupError::from("failed to parse topology hostname")),
+            };
+
+            let mut attributes = HashMap::new();
+            for attribute in ATTRIBUTES.iter() {
+                match document.get(attribute) {
+                    Some(&Bson::String(ref value)) => { attributes.insert(attribute.to_string(), value.to_owned()); },
+                    Some(&Bson::I32(value)) => { attributes.insert(attribute.to_string(), value.to_string()); },
+                    Some(&Bson::I64(value)) => { attributes.insert(attribute.to_string(), value.to_string()); },
+                    _ => {},
+                }
+            }
+
+            hosts.insert(hostname, attributes);
+        }
+
+        Ok(
+            Topology {
+                hosts: hosts,
+            }
+        )
+    }
+
+    //region, site, and asn labels of the hostname
+    pub fn labels(&self, hostname: &str) -> Option<&HashMap<String, String>> {
+        self.hosts.get(hostname)
+    }
+
+    pub fn get(&self, hostname: &str, attribute: &str) -> Option<&String> {
+        self.hosts.get(hostname).and_then(|x| x.get(attribute))
+    }
+}
+
+pub fn import_csv(proddle_db: &Database, filename: &str) -> Result<usize, TipupError> {
+    let file = match File::open(filename) {
+        Ok(file) => file,
+        Err(e) => return Err(TipupError::from(format!("failed to open '{}': {}", filename, e))),
+    };
+
+    //map header columns to topology attributes
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next() {
+        Some(Ok(header)) => split_csv(&header),
+        _ => return Err(TipupError::from(format!("'{}' is missing a csv header", filename))),
+    };
+
+    let hostname_index = match header.iter().position(|x| x.to_lowercase() == "hostname") {
+        Some(index) => index,
+        None => return Err(TipupError::from(format!("'{}' is missing a 'hostname' column", filename))),
+    };
+
+    let attribute_indices: Vec<(&str, usize)> = ATTRIBUTES.iter()
+        .filter_map(|attribute| header.iter().position(|x| x.to_lowercase() == *attribute).map(|index| (*attribute, index)))
+        .collect();
+
+    let mut count = 0;
+    for (i, line) in lines.enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Err(TipupError::from(format!("failed to read '{}': {}", filename, e))),
+        };
+
+        if line.trim().len() == 0 {
+            continue;
+        }
+
+        let fields = split_csv(&line);
+        let hostname = match fields.get(hostname_index) {
+            Some(hostname) if hostname.len() > 0 => hostname.to_owned(),
+            _ => return Err(TipupError::from(format!("missing hostname on line {} of '{}'", i + 2, filename))),
+        };
+
+        //upsert non-empty attributes of the hostname
+        let mut set_document = doc!("hostname" => (hostname.clone()));
+        for &(attribute, index) in attribute_indices.iter() {
+            match fields.get(index) {
+                Some(value) if value.len() > 0 => { set_document.insert(attribute, value.to_owned()); },
+                _ => {},
+            }
+        }
+
+        let mut update_options = UpdateOptions::new();
+        update_options.upsert = Some(true);
+        try!(proddle_db.collection("topology").update_one(doc!("hostname" => hostname), doc!("$set" => set_document), Some(update_options)));
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn split_csv(line: &str) -> Vec<String> {
+    //split on commas outside of double quoted fields
+    let mut fields = Vec::new();
+    let (mut field, mut quoted) = (String::new(), false);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                fields.push(field.trim().to_owned());
+                field = String::new();
+            },
+            c => field.push(c),
+        }
+    }
+
+    fields.push(field.trim().to_owned());
+    fields
+}
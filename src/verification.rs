@@ -0,0 +1,62 @@
+use hyper::status::StatusClass;
+
+use error::TipupError;
+use http;
+
+use std::process::Command;
+
+//seconds a verification ping waits for its reply
+const PING_TIMEOUT_SECONDS: u32 = 2;
+
+pub const METHODS: [&'static str; 2] = ["ping", "http"];
+
+//outcome of probing a flagged target from the tipup host, a reachable target suggesting the
+//flag is an artifact of the measurement pipeline rather than an outage
+pub struct Verification {
+    pub method: String,
+    pub reachable: bool,
+    pub detail: String,
+}
+
+pub fn verify(method: &str, domain: &str) -> Result<Verification, TipupError> {
+    let (reachable, detail) = match method {
+        "ping" => ping(domain),
+        "http" => try!(head(domain)),
+        _ => return Err(TipupError::from(format!("unknown verification method '{}', expected one of {}", method, METHODS.join(", ")))),
+    };
+
+    Ok(
+        Verification {
+            method: method.to_owned(),
+            reachable: reachable,
+            detail: detail,
+        }
+    )
+}
+
+//a single icmp echo through the system ping, which holds the privileges raw sockets require
+fn ping(domain: &str) -> (bool, String) {
+    let output = match Command::new("ping").arg("-c").arg("1").arg("-W").arg(PING_TIMEOUT_SECONDS.to_string()).arg(domain).output() {
+        Ok(output) => output,
+        Err(e) => return (false, format!("failed to execute ping: {}", e)),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match output.status.success() {
+        true => {
+            let rtt = stdout.split_whitespace().find(|x| x.starts_with("time=")).map_or("", |x| &x[5..]);
+            (true, format!("echo reply in {}ms", rtt))
+        },
+        false => (false, format!("no echo reply within {}s", PING_TIMEOUT_SECONDS)),
+    }
+}
+
+//any response short of a server error shows the target serving
+fn head(domain: &str) -> Result<(bool, String), TipupError> {
+    let client = try!(http::client());
+    let url = format!("https://{}/", domain);
+    match client.head(&url).send() {
+        Ok(response) => Ok((response.status.class() != StatusClass::ServerError, format!("HEAD {} responded with status {}", url, response.status))),
+        Err(e) => Ok((false, format!("HEAD {} failed: {}", url, e))),
+    }
+}
@@ -0,0 +1,195 @@
+#[macro_use(bson, doc)]
+extern crate bson;
+extern crate mongodb;
+extern crate time;
+
+use bson::Bson;
+use bson::oid::ObjectId;
+use mongodb::{Client, ThreadedClient};
+use mongodb::db::{Database, ThreadedDatabase};
+
+use std::env;
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const USERNAME: &'static str = "tipup";
+const PASSWORD: &'static str = "tipup";
+
+//ephemeral mongod and tipup processes which are killed when dropped
+struct Environment {
+    port: u16,
+    dbpath: PathBuf,
+    mongod: Child,
+    tipup: Option<Child>,
+}
+
+impl Environment {
+    fn start(name: &str) -> Option<Environment> {
+        //integration tests require a local mongod binary, skip when unavailable
+        let mongod_path = match find_executable("mongod") {
+            Some(mongod_path) => mongod_path,
+            None => {
+                println!("skipping integration test '{}': mongod not found on PATH", name);
+                return None;
+            },
+        };
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let dbpath = env::temp_dir().join(format!("tipup-{}-{}", name, port));
+        fs::create_dir_all(&dbpath).unwrap();
+
+        let mongod = Command::new(mongod_path)
+            .args(&["--dbpath", dbpath.to_str().unwrap(), "--port", &port.to_string(), "--bind_ip", "127.0.0.1", "--nounixsocket"])
+            .stdout(Stdio::null())
+            .spawn().unwrap();
+
+        let environment = Environment {
+            port: port,
+            dbpath: dbpath,
+            mongod: mongod,
+            tipup: None,
+        };
+
+        //wait for mongod to accept connections
+        for _ in 0..60 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                let db = environment.db();
+                db.create_user(USERNAME, PASSWORD, None).unwrap();
+                return Some(environment);
+            }
+
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        panic!("mongod failed to start on port {}", port);
+    }
+
+    fn db(&self) -> Database {
+        let client = Client::connect("127.0.0.1", self.port).unwrap();
+        client.db("proddle")
+    }
+
+    fn run_tipup(&mut self, args: &[&str]) {
+        //tipup is built alongside the test executable in target/<profile>
+        let mut tipup_path = env::current_exe().unwrap();
+        tipup_path.pop();
+        if tipup_path.ends_with("deps") {
+            tipup_path.pop();
+        }
+        tipup_path.push("tipup");
+
+        let port = self.port.to_string();
+        let mut command = Command::new(tipup_path);
+        command.args(&["-I", "127.0.0.1", "-P", &port, "-u", USERNAME, "-p", PASSWORD, "--update_flags_interval", "1"])
+            .args(args)
+            .stdout(Stdio::null());
+        self.tipup = Some(command.spawn().unwrap());
+    }
+
+    fn wait_for_flags(&self, analyzer: &str, seconds: u64) -> Vec<bson::Document> {
+        let db = self.db();
+        let mut flags = Vec::new();
+        for _ in 0..(seconds * 2) {
+            flags = db.collection("flags").find(Some(doc!("analyzer" => analyzer)), None).unwrap()
+                .map(|x| x.unwrap()).collect();
+            if flags.len() > 0 {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        flags
+    }
+}
+
+impl Drop for Environment {
+    fn drop(&mut self) {
+        if let Some(ref mut tipup) = self.tipup {
+            let _ = tipup.kill();
+            let _ = tipup.wait();
+        }
+
+        let _ = self.mongod.kill();
+        let _ = self.mongod.wait();
+        let _ = fs::remove_dir_all(&self.dbpath);
+    }
+}
+
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let paths = match env::var_os("PATH") {
+        Some(paths) => paths,
+        None => return None,
+    };
+
+    env::split_paths(&paths).map(|x| x.join(name)).find(|x| x.is_file())
+}
+
+fn seed(db: &Database) {
+    //an error analyzer for http measurements along with one failed and one successful result
+    db.collection("analyzers").insert_one(doc!(
+        "name" => "http-errors",
+        "class" => "ErrorAnalyzer",
+        "status" => "warning",
+        "measurement_class" => "HttpGet",
+        "fields" => ["measurement_error_message"]
+    ), None).unwrap();
+
+    let timestamp = time::now_utc().to_timespec().sec;
+    db.collection("measurements").insert_one(doc!(
+        "_id" => (ObjectId::new().unwrap()),
+        "timestamp" => timestamp,
+        "vantage_hostname" => "vantage-a",
+        "measurement_domain" => "example.com",
+        "measurement_class" => "HttpGet",
+        "measurement_error_message" => "connection timed out"
+    ), None).unwrap();
+
+    db.collection("measurements").insert_one(doc!(
+        "_id" => (ObjectId::new().unwrap()),
+        "timestamp" => timestamp,
+        "vantage_hostname" => "vantage-b",
+        "measurement_domain" => "example.com",
+        "measurement_class" => "HttpGet"
+    ), None).unwrap();
+}
+
+#[test]
+fn failed_results_produce_flags() {
+    let mut environment = match Environment::start("flags") {
+        Some(environment) => environment,
+        None => return,
+    };
+
+    seed(&environment.db());
+    environment.run_tipup(&[]);
+
+    let flags = environment.wait_for_flags("http-errors", 30);
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].get("hostname"), Some(&Bson::String("vantage-a".to_owned())));
+    assert_eq!(flags[0].get("domain"), Some(&Bson::String("example.com".to_owned())));
+    assert_eq!(flags[0].get("status"), Some(&Bson::String("warning".to_owned())));
+    assert_eq!(flags[0].get_document("labels").unwrap().get("error_category"), Some(&Bson::String("timeout".to_owned())));
+}
+
+#[test]
+fn denied_measurements_are_not_analyzed() {
+    let mut environment = match Environment::start("deny") {
+        Some(environment) => environment,
+        None => return,
+    };
+
+    seed(&environment.db());
+    environment.run_tipup(&["--measurement_deny", "Http*"]);
+
+    assert_eq!(environment.wait_for_flags("http-errors", 10).len(), 0);
+
+    //results are still marked as analyzed so they are not revisited
+    let db = environment.db();
+    let analyzed = db.collection("analyzed_measurements").find_one(Some(doc!("vantage_hostname" => "vantage-a")), None).unwrap();
+    assert!(analyzed.is_some());
+}